@@ -0,0 +1,290 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, Write},
+    str::FromStr,
+};
+
+use chip8::{disasm::disassemble_at, machine::Machine};
+use chip8_frontend::dump;
+
+pub const HELP: &str = "step [n], next, continue [frames], break ADDR, \
+                        delete [ADDR], regs, mem ADDR [len], \
+                        disasm [ADDR] [n], screen, key K, reset, quit";
+
+/// Frames run by `continue` and `next` at most, a minute
+const RUN_FRAMES: u64 = 3600;
+const MEMORY_ROW: usize = 16;
+const DISASM_LINES: usize = 8;
+
+/// A command of the prompt, addresses and keys are hexadecimal
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Command {
+    /// Execute n instructions
+    Step(u64),
+    /// Execute one instruction, running subroutine calls to their return
+    Next,
+    /// Run until a breakpoint, for n frames at most
+    Continue(u64),
+    Break(u16),
+    /// Remove one breakpoint, or all of them
+    Delete(Option<u16>),
+    Regs,
+    /// Print n bytes of the memory
+    Mem(u16, usize),
+    /// Print n instructions, from the pc by default
+    Disasm(Option<u16>, usize),
+    Screen,
+    /// Hold a key, or release it
+    Key(u8),
+    Reset,
+    Help,
+    Quit,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+        let arg = |n: usize| args.get(n).copied();
+
+        let max_args = match name {
+            "m" | "mem" | "disasm" => 2,
+            _ => 1,
+        };
+        if let Some(extra) = arg(max_args) {
+            return Err(format!("unexpected argument: {}", extra));
+        }
+
+        match name {
+            "s" | "step" => Ok(Command::Step(parse_count(arg(0), 1)?)),
+            "n" | "next" => Ok(Command::Next),
+            "c" | "continue" => {
+                Ok(Command::Continue(parse_count(arg(0), RUN_FRAMES)?))
+            }
+            "b" | "break" => Ok(Command::Break(parse_addr(arg(0))?)),
+            "d" | "delete" => match arg(0) {
+                Some(_) => Ok(Command::Delete(Some(parse_addr(arg(0))?))),
+                None => Ok(Command::Delete(None)),
+            },
+            "r" | "regs" => Ok(Command::Regs),
+            "m" | "mem" => Ok(Command::Mem(
+                parse_addr(arg(0))?,
+                parse_count(arg(1), MEMORY_ROW as u64)? as usize,
+            )),
+            "disasm" => Ok(Command::Disasm(
+                arg(0).map(|_| parse_addr(arg(0))).transpose()?,
+                parse_count(arg(1), DISASM_LINES as u64)? as usize,
+            )),
+            "screen" => Ok(Command::Screen),
+            "k" | "key" => match parse_addr(arg(0))? {
+                key @ 0..=0xF => Ok(Command::Key(key as u8)),
+                _ => Err(format!("invalid key: {}", arg(0).unwrap_or(""))),
+            },
+            "reset" => Ok(Command::Reset),
+            "h" | "help" => Ok(Command::Help),
+            "q" | "quit" => Ok(Command::Quit),
+            _ => Err(format!("unknown command: {}", name)),
+        }
+    }
+}
+
+fn parse_count(arg: Option<&str>, default: u64) -> Result<u64, String> {
+    match arg {
+        Some(arg) => arg.parse().map_err(|_| format!("invalid count: {}", arg)),
+        None => Ok(default),
+    }
+}
+
+fn parse_addr(arg: Option<&str>) -> Result<u16, String> {
+    let arg = arg.ok_or("missing address")?;
+    let digits = arg
+        .strip_prefix("0x")
+        .or_else(|| arg.strip_prefix('$'))
+        .unwrap_or(arg);
+
+    u16::from_str_radix(digits, 16)
+        .map_err(|_| format!("invalid address: {}", arg))
+}
+
+/// Read commands from `input` until it ends or `quit`, an empty line runs
+/// the last command again
+pub fn run(
+    machine: &mut Machine,
+    mut input: impl BufRead,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let mut breakpoints = BTreeSet::new();
+    let mut last: Option<Command> = None;
+
+    writeln!(out, "{}", HELP)?;
+    loop {
+        write!(out, "(chip8 0x{:03x}) ", machine.cpu().pc())?;
+        out.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+
+        let command = match line.trim() {
+            "" => match &last {
+                Some(command) => command.clone(),
+                None => continue,
+            },
+            line => match line.parse::<Command>() {
+                Ok(command) => command,
+                Err(e) => {
+                    writeln!(out, "{}", e)?;
+                    continue;
+                }
+            },
+        };
+        if command == Command::Quit {
+            return Ok(());
+        }
+
+        execute(machine, &mut breakpoints, &command, &mut out)?;
+        last = Some(command);
+    }
+}
+
+fn execute(
+    machine: &mut Machine,
+    breakpoints: &mut BTreeSet<u16>,
+    command: &Command,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    match *command {
+        Command::Step(count) => {
+            for _ in 0..count {
+                machine.step();
+            }
+            disasm(machine, breakpoints, machine.cpu().pc(), 1, out)?;
+        }
+        Command::Next => {
+            if !machine.step_over(RUN_FRAMES as u32) {
+                writeln!(out, "the subroutine didn't return")?;
+            }
+            disasm(machine, breakpoints, machine.cpu().pc(), 1, out)?;
+        }
+        Command::Continue(frames) => {
+            // the first instruction is the breakpoint we stopped on
+            let mut resuming = true;
+            let mut ran = 0;
+            let stopped = loop {
+                if ran == frames {
+                    break false;
+                }
+                if !machine.run_frame_until(|cpu| {
+                    !std::mem::take(&mut resuming)
+                        && breakpoints.contains(&cpu.pc())
+                }) {
+                    break true;
+                }
+                ran += 1;
+            };
+
+            match stopped {
+                true => writeln!(out, "breakpoint after {} frames", ran)?,
+                false => writeln!(out, "ran {} frames", ran)?,
+            }
+            disasm(machine, breakpoints, machine.cpu().pc(), 1, out)?;
+        }
+        Command::Break(addr) => {
+            breakpoints.insert(addr);
+            writeln!(out, "breakpoint at 0x{:03x}", addr)?;
+        }
+        Command::Delete(Some(addr)) => match breakpoints.remove(&addr) {
+            true => writeln!(out, "deleted breakpoint at 0x{:03x}", addr)?,
+            false => writeln!(out, "no breakpoint at 0x{:03x}", addr)?,
+        },
+        Command::Delete(None) => {
+            breakpoints.clear();
+            writeln!(out, "deleted all breakpoints")?;
+        }
+        Command::Regs => {
+            let bus = machine.bus();
+            write!(out, "{}", dump::cpu(machine.cpu()))?;
+            writeln!(out, "dt: {:02x}  st: {:02x}", bus.delay, bus.beep)?;
+            if let Some(x) = machine.cpu().key_await() {
+                writeln!(out, "waiting for a key in v{:x}", x)?;
+            }
+        }
+        Command::Mem(addr, len) => {
+            let memory = machine.bus().memory();
+            let start = (addr as usize).min(memory.len());
+            let end = start.saturating_add(len).min(memory.len());
+            for (row, bytes) in
+                memory[start..end].chunks(MEMORY_ROW).enumerate()
+            {
+                let hex: Vec<String> =
+                    bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                writeln!(
+                    out,
+                    "{:#06x}  {}",
+                    start + row * MEMORY_ROW,
+                    hex.join(" ")
+                )?;
+            }
+        }
+        Command::Disasm(addr, count) => {
+            let addr = addr.unwrap_or(machine.cpu().pc());
+            disasm(machine, breakpoints, addr, count, out)?;
+        }
+        Command::Screen => {
+            write!(out, "{}", dump::text(&machine.bus().vram()))?
+        }
+        Command::Key(key) => {
+            let held = &mut machine.bus_mut().keys[key as usize];
+            *held = !*held;
+            match *held {
+                true => writeln!(out, "key {:x} held", key)?,
+                false => writeln!(out, "key {:x} released", key)?,
+            }
+        }
+        Command::Reset => {
+            machine.reset();
+            writeln!(out, "reset")?;
+        }
+        Command::Help => writeln!(out, "{}", HELP)?,
+        Command::Quit => {}
+    }
+
+    Ok(())
+}
+
+/// `count` instructions from `addr`, the pc marked by `>` and the
+/// breakpoints by `*`
+fn disasm(
+    machine: &Machine,
+    breakpoints: &BTreeSet<u16>,
+    addr: u16,
+    count: usize,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let memory = machine.bus().memory();
+    let pc = machine.cpu().pc();
+
+    for addr in (addr as usize..memory.len()).step_by(2).take(count) {
+        let addr = addr as u16;
+        let (opcode, text) = disassemble_at(memory, addr);
+        writeln!(
+            out,
+            "{}{} {:#05x}  {:04x}  {}",
+            if addr == pc { '>' } else { ' ' },
+            if breakpoints.contains(&addr) {
+                '*'
+            } else {
+                ' '
+            },
+            addr,
+            opcode,
+            text
+        )?;
+    }
+
+    Ok(())
+}
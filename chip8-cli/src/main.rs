@@ -0,0 +1,1268 @@
+mod allocations;
+mod debug;
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Instant,
+};
+
+use chip8::{
+    asm::assemble,
+    cpu::KeyWaitPolicy,
+    disasm::disassemble_at,
+    machine::{Machine, CPU_FREQUENCY, FRAME_RATE},
+    rom::Rom,
+};
+#[cfg(feature = "rhai")]
+use chip8_frontend::rhai_script::RhaiScript;
+use chip8_frontend::{
+    config::{ConfigError, KEY_WAIT_POLICIES},
+    corpus::{self, Manifest, Outcome},
+    coverage::Coverage,
+    database::{Database, COMMUNITY_URL},
+    determinism, dump, listing,
+    rom_info::RomInfo,
+    script::{self, Script, Stop},
+    slots, sprites, trace,
+};
+use clap::{Args, Parser, Subcommand};
+use log::debug;
+
+#[global_allocator]
+static ALLOCATOR: allocations::Counter = allocations::Counter;
+
+/// Extensions of the files looked up in the rom database
+const ROM_EXTENSIONS: [&str; 2] = ["ch8", "c8"];
+
+/// Programs are loaded there
+const PROGRAM_START: u16 = 0x200;
+
+/// Tools for chip8 roms, without window
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a rom, then dump the screen and the cpu state
+    Run {
+        rom: String,
+        /// Number of 60Hz frames to run
+        #[arg(short, long, default_value_t = 600)]
+        frames: u64,
+        /// Number of instructions to run in place of the frames, rounded up
+        /// to whole frames
+        #[arg(long, conflicts_with = "frames")]
+        cycles: Option<u64>,
+        /// Stop early once the rom jumps to itself or waits for a key that
+        /// the script will never press
+        #[arg(long)]
+        until_halt: bool,
+        /// Instructions per second
+        #[arg(long, default_value_t = CPU_FREQUENCY)]
+        speed: f64,
+        /// Keypad script, one `FRAME KEYS` line per change, `KEYS` being
+        /// hex keys separated by commas or `-` for none
+        #[arg(short, long)]
+        input: Option<String>,
+        /// Print the screen as text
+        #[arg(long)]
+        text: bool,
+        /// Print a hash of the screen
+        #[arg(long)]
+        hash: bool,
+        /// Write the screen to a png file
+        #[arg(long)]
+        png: Option<String>,
+        /// Size of a chip8 pixel in the png
+        #[arg(long, default_value_t = 8)]
+        scale: u32,
+        /// Write the pc, opcode, I and registers before each instruction
+        /// to this file, `-` for the standard output
+        #[arg(long, conflicts_with = "rhai")]
+        trace: Option<String>,
+        /// Rhai script run along the rom, its `on_frame` function being
+        /// called before each frame
+        #[arg(long)]
+        rhai: Option<String>,
+    },
+    /// Compare two traces, from `run --trace` or another emulator, and
+    /// print the first instruction where they differ
+    TraceDiff { left: String, right: String },
+    /// Run a rom with two settings, or against the screen hashes of an
+    /// earlier run, and print how similar their screens are each frame
+    Compare(CompareArgs),
+    /// Print the instructions of a rom
+    Disasm { rom: String },
+    /// Debug a rom from a prompt on the standard input, to step it, break
+    /// on addresses and print its registers, memory and screen
+    Debug {
+        rom: String,
+        /// Instructions per second
+        #[arg(long, default_value_t = CPU_FREQUENCY)]
+        speed: f64,
+    },
+    /// Assemble a source in the syntax of `disasm` into a rom
+    Asm {
+        source: String,
+        /// Rom to write, the source with a `ch8` extension by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Write an HTML listing of a rom, its code and data told apart by
+    /// running it
+    Html {
+        rom: String,
+        /// Page to write, the rom with a `html` extension by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Number of 60Hz frames to run, more of the rom is reached with
+        /// more frames and keys
+        #[arg(short, long, default_value_t = 600)]
+        frames: u64,
+        /// Instructions per second
+        #[arg(long, default_value_t = CPU_FREQUENCY)]
+        speed: f64,
+        /// Keypad script, see `run`
+        #[arg(short, long)]
+        input: Option<String>,
+    },
+    /// Find the likely sprites of a rom and draw them on a png sheet
+    Sprites {
+        rom: String,
+        /// Sheet to write, the rom with a `png` extension by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Number of 60Hz frames to run to also find the sprites drawn
+        /// through a computed address
+        #[arg(short, long, default_value_t = 0)]
+        frames: u64,
+        /// Keypad script, see `run`
+        #[arg(short, long)]
+        input: Option<String>,
+        /// Size of a chip8 pixel in the sheet
+        #[arg(long, default_value_t = 4)]
+        scale: u32,
+    },
+    /// Print the size, hashes, platform and instructions of a rom, and what
+    /// the rom database knows of it
+    Info {
+        rom: String,
+        /// Print a JSON object instead
+        #[arg(long)]
+        json: bool,
+        /// Rom database to look the rom up in, `chip8/roms.toml` in the
+        /// user configuration directory by default
+        #[arg(long)]
+        database: Option<PathBuf>,
+    },
+    /// Run, print or compare saved states
+    State {
+        #[command(subcommand)]
+        command: StateCommand,
+    },
+    /// Write the screen to a png file each time it changes
+    Record {
+        rom: String,
+        /// Directory of the pngs, named after their frame
+        output: PathBuf,
+        /// Number of 60Hz frames to run
+        #[arg(short, long, default_value_t = 600)]
+        frames: u64,
+        /// Instructions per second
+        #[arg(long, default_value_t = CPU_FREQUENCY)]
+        speed: f64,
+        /// Keypad script, see `run`
+        #[arg(short, long)]
+        input: Option<String>,
+        /// Size of a chip8 pixel in the pngs
+        #[arg(long, default_value_t = 8)]
+        scale: u32,
+    },
+    /// Update the rom database, or look roms up in it
+    Database {
+        #[command(subcommand)]
+        command: DatabaseCommand,
+    },
+    /// Run a rom as fast as possible without display, and print the
+    /// instructions per second, the frame times and the allocations
+    Bench {
+        rom: String,
+        /// Number of 60Hz frames to run
+        #[arg(short, long, default_value_t = 60_000)]
+        frames: u64,
+        /// Instructions per second
+        #[arg(long, default_value_t = CPU_FREQUENCY)]
+        speed: f64,
+    },
+    /// Run a rom twice at once with the same seed and keys, and check that
+    /// their states are the same after each frame
+    Determinism {
+        rom: String,
+        /// Number of 60Hz frames to run
+        #[arg(short, long, default_value_t = 600)]
+        frames: u64,
+        /// Instructions per second
+        #[arg(long, default_value_t = CPU_FREQUENCY)]
+        speed: f64,
+        /// Keypad script, see `run`
+        #[arg(short, long)]
+        input: Option<String>,
+    },
+    /// Run the test roms of a directory until they halt, and check their
+    /// screens against the hashes of a manifest
+    Corpus {
+        dir: PathBuf,
+        /// Expected results, `manifest.toml` in the directory by default
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+        /// Frames given to each rom to halt, unless the manifest says
+        /// otherwise
+        #[arg(short, long, default_value_t = 600)]
+        frames: u64,
+        /// Add the screens of the roms without a hash to the manifest
+        #[arg(long)]
+        update: bool,
+    },
+}
+
+#[derive(Args)]
+struct CompareArgs {
+    rom: String,
+    /// Number of 60Hz frames to run
+    #[arg(short, long, default_value_t = 600)]
+    frames: u64,
+    /// Keypad script, see `run`, played in both runs
+    #[arg(short, long)]
+    input: Option<String>,
+    /// Instructions per second of the first and second runs
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["A", "B"],
+        default_values_t = [CPU_FREQUENCY, CPU_FREQUENCY]
+    )]
+    speed: Vec<f64>,
+    /// FX0A key wait policy of the first and second runs
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["A", "B"],
+        value_parser = parse_policy,
+        default_values = ["lowest", "lowest"]
+    )]
+    policy: Vec<KeyWaitPolicy>,
+    /// Seed of the random numbers of both runs
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+    /// Compare the first run with the hashes of this file instead of a
+    /// second run, one `FRAME HASH` line per frame
+    #[arg(long)]
+    golden: Option<PathBuf>,
+    /// Write the screen hashes of the first run to this file
+    #[arg(long)]
+    save_golden: Option<PathBuf>,
+    /// Directory where the screens of the first different frame are
+    /// written
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Size of a chip8 pixel in the pngs
+    #[arg(long, default_value_t = 8)]
+    scale: u32,
+}
+
+#[derive(Subcommand)]
+enum StateCommand {
+    /// Load a saved state of a rom, run it and print the cpu state
+    Run {
+        rom: String,
+        state: String,
+        /// Number of 60Hz frames to run after loading
+        #[arg(short, long, default_value_t = 0)]
+        frames: u64,
+        /// Keypad script, see `run`, its frames start at the state
+        #[arg(short, long)]
+        input: Option<String>,
+        /// Print the screen as text
+        #[arg(long)]
+        text: bool,
+        /// Save the state reached to this file
+        #[arg(long)]
+        save: Option<String>,
+    },
+    /// Print the registers, stack, timers, keys, screen and memory of a
+    /// state or a slot file
+    Dump { state: String },
+    /// Print what differs from a state or slot file to another
+    Diff { a: String, b: String },
+}
+
+#[derive(Subcommand)]
+enum DatabaseCommand {
+    /// Download the community database to the data directory, then print
+    /// the roms of `dirs` it knows
+    Update {
+        /// Directories searched for `ch8` and `c8` roms
+        dirs: Vec<PathBuf>,
+        #[arg(long, default_value = COMMUNITY_URL)]
+        url: String,
+        /// Import this `programs.json` instead of downloading it
+        #[arg(long, conflicts_with = "url")]
+        file: Option<PathBuf>,
+    },
+    /// Print the roms of `dirs` the database knows
+    Scan {
+        /// Directories searched for `ch8` and `c8` roms
+        dirs: Vec<PathBuf>,
+    },
+}
+
+fn main() -> ExitCode {
+    dotenv::dotenv().ok();
+    env_logger::builder().format_timestamp_nanos().init();
+
+    let cli = Cli::parse();
+
+    // their own exit code, 1 when what they compare differs
+    match &cli.command {
+        Command::TraceDiff { left, right } => return trace_diff(left, right),
+        Command::Compare(args) => return compare(args),
+        Command::Corpus {
+            dir,
+            manifest,
+            frames,
+            update,
+        } => return corpus(dir, manifest.as_deref(), *frames, *update),
+        Command::Determinism {
+            rom,
+            frames,
+            speed,
+            input,
+        } => return determinism(rom, *frames, *speed, input.as_deref()),
+        _ => {}
+    }
+
+    let until_halt = matches!(
+        cli.command,
+        Command::Run {
+            until_halt: true,
+            ..
+        }
+    );
+    match run(cli.command) {
+        Ok(Stop::FrameLimit) if until_halt => ExitCode::from(1),
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Returns why the rom stopped, the frame limit for the commands that don't
+/// run it
+fn run(command: Command) -> Result<Stop, String> {
+    match command {
+        Command::Run {
+            rom,
+            frames,
+            cycles,
+            until_halt,
+            speed,
+            input,
+            text,
+            hash,
+            png,
+            scale,
+            trace,
+            rhai,
+        } => {
+            let frames = match cycles {
+                Some(_) if speed <= 0.0 => {
+                    return Err("--cycles needs a speed above 0".to_string())
+                }
+                Some(cycles) => {
+                    (cycles as f64 * FRAME_RATE / speed).ceil() as u64
+                }
+                None => frames,
+            };
+            let mut machine = load_machine(&rom, speed)?;
+            let mut script = load_script(input.as_deref())?;
+            let (frames, stop) = match (&trace, &rhai) {
+                (_, Some(path)) => run_rhai(
+                    path,
+                    &mut machine,
+                    &mut script,
+                    frames,
+                    until_halt,
+                )?,
+                (Some(path), None) => {
+                    let mut out = create(path)?;
+                    trace::run(
+                        &mut machine,
+                        &mut script,
+                        frames,
+                        until_halt,
+                        &mut out,
+                    )
+                    .map_err(|e| format!("{}: {}", path, e))?
+                }
+                (None, None) => {
+                    script::run(&mut machine, &mut script, frames, until_halt)
+                }
+            };
+
+            let vram = &machine.bus().vram();
+            if text {
+                print!("{}", dump::text(vram));
+            }
+            if hash {
+                println!("hash: {:016x}", machine.bus().frame_hash());
+            }
+            if let Some(path) = &png {
+                dump::png(vram, path, scale)?;
+            }
+
+            println!("stop: {}", stop);
+            println!("frames: {}", frames);
+            print!("{}", dump::cpu(machine.cpu()));
+
+            Ok(stop)
+        }
+
+        Command::TraceDiff { .. }
+        | Command::Compare(_)
+        | Command::Corpus { .. }
+        | Command::Determinism { .. } => {
+            unreachable!("run with their own exit code")
+        }
+
+        Command::Disasm { rom } => {
+            let data = read(&rom)?;
+            for offset in (0..data.len()).step_by(2) {
+                let (opcode, text) = disassemble_at(&data, offset as u16);
+                println!(
+                    "{:#05x}  {:04x}  {}",
+                    PROGRAM_START as usize + offset,
+                    opcode,
+                    text
+                );
+            }
+
+            Ok(Stop::FrameLimit)
+        }
+
+        Command::Debug { rom, speed } => {
+            let mut machine = load_machine(&rom, speed)?;
+            debug::run(&mut machine, io::stdin().lock(), io::stdout().lock())
+                .map_err(|e| e.to_string())?;
+
+            Ok(Stop::FrameLimit)
+        }
+
+        Command::Asm { source, output } => {
+            let text = fs::read_to_string(&source)
+                .map_err(|e| format!("{}: {}", source, e))?;
+            let program = assemble(&text).map_err(|e| {
+                format!("{}: {}\n{}", source, e, e.annotate(&text))
+            })?;
+
+            let output = output
+                .unwrap_or_else(|| Path::new(&source).with_extension("ch8"));
+            fs::write(&output, &program)
+                .map_err(|e| format!("{}: {}", output.display(), e))?;
+            println!("{}: {} bytes", output.display(), program.len());
+
+            Ok(Stop::FrameLimit)
+        }
+
+        Command::Html {
+            rom,
+            output,
+            frames,
+            speed,
+            input,
+        } => {
+            let data = read(&rom)?;
+            let mut machine = load_machine(&rom, speed)?;
+            let mut script = load_script(input.as_deref())?;
+
+            let coverage = cover(&mut machine, &mut script, frames);
+
+            let title = Path::new(&rom)
+                .file_name()
+                .map_or(rom.clone(), |name| name.to_string_lossy().into());
+            let page = listing::html(&title, &data, &coverage);
+            let output = output
+                .unwrap_or_else(|| Path::new(&rom).with_extension("html"));
+            fs::write(&output, page)
+                .map_err(|e| format!("{}: {}", output.display(), e))?;
+            println!("{}", output.display());
+
+            Ok(Stop::FrameLimit)
+        }
+
+        Command::Sprites {
+            rom,
+            output,
+            frames,
+            input,
+            scale,
+        } => {
+            let mut machine = load_machine(&rom, CPU_FREQUENCY)?;
+            let mut script = load_script(input.as_deref())?;
+            // as loaded, before the rom writes to it
+            let memory = machine.bus().memory().to_vec();
+
+            let coverage = cover(&mut machine, &mut script, frames);
+            let found = sprites::find(&memory, Some(&coverage));
+            for sprite in &found {
+                println!("{:#05x}  {} rows", sprite.addr, sprite.rows);
+            }
+
+            if !found.is_empty() {
+                let output = output
+                    .unwrap_or_else(|| Path::new(&rom).with_extension("png"));
+                sprites::sheet(
+                    &memory,
+                    &found,
+                    &output.to_string_lossy(),
+                    scale,
+                )?;
+                println!(
+                    "{} sprites drawn to {}",
+                    found.len(),
+                    output.display()
+                );
+            }
+
+            Ok(Stop::FrameLimit)
+        }
+
+        Command::Info {
+            rom,
+            json,
+            database,
+        } => {
+            let info = RomInfo::new(&read(&rom)?);
+            let database = match &database {
+                Some(path) => Database::load_from(path)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?,
+                None => Database::load(),
+            };
+            let entry = database.get(&info.sha1);
+
+            if json {
+                let value = serde_json::json!({
+                    "size": info.size,
+                    "checksum": format!("{:08x}", info.checksum),
+                    "sha1": info.sha1,
+                    "platform": info.platform.to_string(),
+                    "too_big": info.too_big(),
+                    "extensions": info
+                        .extensions
+                        .iter()
+                        .map(|&(addr, opcode)| serde_json::json!({
+                            "addr": addr,
+                            "opcode": format!("{:04x}", opcode),
+                        }))
+                        .collect::<Vec<_>>(),
+                    "features": info.features,
+                    "histogram": info
+                        .histogram
+                        .iter()
+                        .map(|(instruction, count)| serde_json::json!({
+                            "instruction": instruction,
+                            "count": count,
+                        }))
+                        .collect::<Vec<_>>(),
+                    "entry": info
+                        .entry
+                        .iter()
+                        .map(|(addr, opcode, text)| serde_json::json!({
+                            "addr": addr,
+                            "opcode": format!("{:04x}", opcode),
+                            "text": text,
+                        }))
+                        .collect::<Vec<_>>(),
+                    "database": entry.map(|entry| serde_json::json!({
+                        "title": entry.title,
+                        "authors": entry.authors,
+                        "year": entry.year,
+                        "platform": entry.platform,
+                        "speed": entry.speed,
+                        "description": entry.description,
+                    })),
+                });
+                println!("{:#}", value);
+
+                return Ok(Stop::FrameLimit);
+            }
+
+            println!("size: {} bytes", info.size);
+            if info.too_big() {
+                println!("too big for the memory");
+            }
+            println!("checksum: {:08x}", info.checksum);
+            println!("sha1: {}", info.sha1);
+            println!("platform: {}", info.platform);
+            // the emulator runs them as chip8 instructions
+            for (addr, opcode) in &info.extensions {
+                println!("  {:#05x}  {:04x}", addr, opcode);
+            }
+            println!("uses: {}", info.features.join(", "));
+
+            println!("instructions:");
+            for (instruction, count) in &info.histogram {
+                println!("  {:<4}  {}", instruction, count);
+            }
+            println!("entry:");
+            for (addr, opcode, text) in &info.entry {
+                println!("  {:#05x}  {:04x}  {}", addr, opcode, text);
+            }
+
+            match entry {
+                Some(entry) => {
+                    println!("title: {}", entry.title);
+                    if !entry.authors.is_empty() {
+                        println!("authors: {}", entry.authors.join(", "));
+                    }
+                    if let Some(year) = entry.year {
+                        println!("year: {}", year);
+                    }
+                    if let Some(platform) = &entry.platform {
+                        println!("meant for: {}", platform);
+                    }
+                    if let Some(speed) = entry.speed {
+                        println!("speed: {} instructions/s", speed);
+                    }
+                    if let Some(description) = &entry.description {
+                        println!("description: {}", description);
+                    }
+                }
+                None => println!("not in the rom database"),
+            }
+
+            Ok(Stop::FrameLimit)
+        }
+
+        Command::State { command } => run_state(command),
+
+        Command::Database { command } => {
+            if let DatabaseCommand::Update { url, file, .. } = &command {
+                let json = match file {
+                    Some(path) => fs::read_to_string(path)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?,
+                    None => download(url)?,
+                };
+                let community = Database::from_community(&json)
+                    .map_err(|e| format!("community database: {}", e))?;
+
+                let path = Database::community_path()
+                    .ok_or("no data directory for the database")?;
+                community
+                    .save_to(&path)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                println!(
+                    "{} roms written to {}",
+                    community.len(),
+                    path.display()
+                );
+            }
+
+            let (DatabaseCommand::Update { dirs, .. }
+            | DatabaseCommand::Scan { dirs }) = command;
+            let database = Database::load();
+            let mut roms = vec![];
+            for dir in &dirs {
+                find_roms(dir, &mut roms)
+                    .map_err(|e| format!("{}: {}", dir.display(), e))?;
+            }
+            roms.sort();
+
+            let mut known = 0;
+            for path in &roms {
+                let info = RomInfo::new(&read(&path.to_string_lossy())?);
+                if let Some(entry) = database.get(&info.sha1) {
+                    println!("{}: {}", path.display(), entry.title);
+                    known += 1;
+                }
+            }
+            if !dirs.is_empty() {
+                println!("{} of {} roms known", known, roms.len());
+            }
+
+            Ok(Stop::FrameLimit)
+        }
+
+        Command::Record {
+            rom,
+            output,
+            frames,
+            speed,
+            input,
+            scale,
+        } => {
+            let mut machine = load_machine(&rom, speed)?;
+            let mut script = load_script(input.as_deref())?;
+            fs::create_dir_all(&output)
+                .map_err(|e| format!("{}: {}", output.display(), e))?;
+
+            // the first screen is always written
+            let mut last = None;
+            let mut written = 0;
+            for frame in 0..frames {
+                if let Some(keys) = script.keys_at(frame) {
+                    machine.bus_mut().keys = keys;
+                }
+                machine.run_frame();
+
+                let vram = machine.bus().vram();
+                if last != Some(vram) {
+                    let path = output.join(format!("{:06}.png", frame));
+                    dump::png(&vram, &path.to_string_lossy(), scale)?;
+                    last = Some(vram);
+                    written += 1;
+                }
+            }
+            println!("{} screens written to {}", written, output.display());
+
+            Ok(Stop::FrameLimit)
+        }
+
+        Command::Bench { rom, frames, speed } => {
+            let mut machine = load_machine(&rom, speed)?;
+            // allocated before the allocations are counted
+            let mut times = Vec::with_capacity(frames as usize);
+            let mut instructions: u64 = 0;
+
+            let (count, bytes) = allocations::snapshot();
+            let start = Instant::now();
+            for _ in 0..frames {
+                let frame = Instant::now();
+                machine.run_frame_until(|_| {
+                    instructions += 1;
+                    false
+                });
+                times.push(frame.elapsed());
+            }
+            let elapsed = start.elapsed().as_secs_f64().max(f64::EPSILON);
+            let (count, bytes) = {
+                let (total_count, total_bytes) = allocations::snapshot();
+                (total_count - count, total_bytes - bytes)
+            };
+
+            let fps = frames as f64 / elapsed;
+            println!("frames: {} in {:.3}s", frames, elapsed);
+            println!("speed: {:.0} fps, {:.1}x", fps, fps / FRAME_RATE);
+            println!(
+                "cpu: {} instructions, {:.0} instructions/s",
+                instructions,
+                instructions as f64 / elapsed
+            );
+
+            times.sort();
+            if let (Some(min), Some(max)) = (times.first(), times.last()) {
+                let percentile =
+                    |p: f64| times[((times.len() - 1) as f64 * p) as usize];
+                println!(
+                    "frame time: min {:?}, median {:?}, 99% {:?}, max {:?}",
+                    min,
+                    percentile(0.5),
+                    percentile(0.99),
+                    max
+                );
+            }
+            println!("allocations: {} of {} bytes", count, bytes);
+
+            Ok(Stop::FrameLimit)
+        }
+    }
+}
+
+fn run_state(command: StateCommand) -> Result<Stop, String> {
+    match command {
+        StateCommand::Run {
+            rom,
+            state,
+            frames,
+            input,
+            text,
+            save,
+        } => {
+            let mut machine = load_machine(&rom, CPU_FREQUENCY)?;
+            let data = read(&state)?;
+            machine
+                .load_state(&data)
+                .map_err(|e| format!("{}: {}", state, e))?;
+
+            let mut script = load_script(input.as_deref())?;
+            let (frames, stop) =
+                script::run(&mut machine, &mut script, frames, false);
+
+            if text {
+                print!("{}", dump::text(&machine.bus().vram()));
+            }
+            if let Some(path) = &save {
+                fs::write(path, machine.save_state())
+                    .map_err(|e| format!("{}: {}", path, e))?;
+            }
+
+            let bus = machine.bus();
+            println!("frames: {}", frames);
+            print!("{}", dump::cpu(machine.cpu()));
+            println!("delay: {:#04x}  sound: {:#04x}", bus.delay, bus.beep);
+
+            Ok(stop)
+        }
+
+        StateCommand::Dump { state } => {
+            let machine = load_state(&state)?;
+            print!("{}", dump::state(&machine));
+
+            Ok(Stop::FrameLimit)
+        }
+
+        StateCommand::Diff { a, b } => {
+            let lines = dump::state_diff(&load_state(&a)?, &load_state(&b)?);
+            for line in &lines {
+                println!("{}", line);
+            }
+            if lines.is_empty() {
+                println!("the states are the same");
+            }
+
+            Ok(Stop::FrameLimit)
+        }
+    }
+}
+
+/// Exit code 1 when a rom fails
+fn corpus(
+    dir: &Path,
+    manifest: Option<&Path>,
+    frames: u64,
+    update: bool,
+) -> ExitCode {
+    match run_corpus(dir, manifest, frames, update) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn determinism(
+    path: &str,
+    frames: u64,
+    speed: f64,
+    input: Option<&str>,
+) -> ExitCode {
+    let result = read(path).and_then(|data| {
+        let script = load_script(input)?;
+        let rom = Rom::from_bytes(data);
+        Ok(determinism::verify(&rom, speed, &script, frames))
+    });
+
+    match result {
+        Ok(None) => {
+            println!("the runs are the same over {} frames", frames);
+            ExitCode::SUCCESS
+        }
+        Ok(Some(divergence)) => {
+            println!("{}", divergence);
+            ExitCode::from(1)
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Prints the outcome of each rom then their counts, returns true when
+/// none failed
+fn run_corpus(
+    dir: &Path,
+    manifest: Option<&Path>,
+    frames: u64,
+    update: bool,
+) -> Result<bool, String> {
+    let path =
+        manifest.map_or_else(|| dir.join("manifest.toml"), Path::to_path_buf);
+    let mut manifest = match Manifest::load_from(&path) {
+        Ok(manifest) => manifest,
+        Err(ConfigError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+            Manifest::default()
+        }
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+
+    let mut roms = vec![];
+    find_roms(dir, &mut roms)
+        .map_err(|e| format!("{}: {}", dir.display(), e))?;
+    roms.sort();
+
+    // by their path in the directory, as in the manifest
+    let names: Vec<String> = roms
+        .iter()
+        .map(|rom| {
+            let relative = rom.strip_prefix(dir).unwrap_or(rom);
+            let parts: Vec<_> = relative
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect();
+            parts.join("/")
+        })
+        .collect();
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+
+    let (mut passed, mut failed, mut unknown, mut added) = (0, 0, 0, 0);
+    for (rom, name) in roms.iter().zip(&names) {
+        let data =
+            fs::read(rom).map_err(|e| format!("{}: {}", rom.display(), e))?;
+        let expected = manifest.roms.get(name).cloned().unwrap_or_default();
+        let (outcome, ran) = corpus::run(data, &expected, frames)
+            .map_err(|e| format!("{}: input: {}", name, e))?;
+        println!("{:width$}  {} after {} frames", name, outcome, ran);
+
+        match outcome {
+            Outcome::Pass => passed += 1,
+            Outcome::Unknown(hash) => {
+                unknown += 1;
+                if update {
+                    let entry = manifest.roms.entry(name.clone()).or_default();
+                    entry.hash = Some(format!("{:016x}", hash));
+                    added += 1;
+                }
+            }
+            _ => failed += 1,
+        }
+    }
+
+    let missing = manifest
+        .roms
+        .keys()
+        .filter(|&name| !names.contains(name))
+        .count();
+    println!(
+        "{} roms: {} passed, {} failed, {} unknown, {} missing from the \
+         directory",
+        roms.len(),
+        passed,
+        failed,
+        unknown,
+        missing
+    );
+
+    if added > 0 {
+        manifest
+            .save_to(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        println!("{}: {} hashes added", path.display(), added);
+    }
+
+    Ok(failed == 0)
+}
+
+fn compare(args: &CompareArgs) -> ExitCode {
+    match run_comparison(args) {
+        Ok(None) => ExitCode::SUCCESS,
+        Ok(Some(_)) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Returns the first frame where the screens differ
+fn run_comparison(args: &CompareArgs) -> Result<Option<u64>, String> {
+    let machine = |run: usize| {
+        load_machine(&args.rom, args.speed[run]).map(|mut machine| {
+            machine.cpu_mut().set_seed(args.seed);
+            machine.cpu_mut().set_key_wait_policy(args.policy[run]);
+            machine
+        })
+    };
+    let mut a = machine(0)?;
+    let (mut b, golden) = match &args.golden {
+        Some(path) => (None, Some(read_golden(path, args.frames)?)),
+        None => (Some(machine(1)?), None),
+    };
+    let mut script = load_script(args.input.as_deref())?;
+
+    let mut hashes = vec![];
+    let mut first_difference = None;
+    let mut total = 0.0;
+    for frame in 0..args.frames {
+        if let Some(keys) = script.keys_at(frame) {
+            a.bus_mut().keys = keys;
+            if let Some(b) = &mut b {
+                b.bus_mut().keys = keys;
+            }
+        }
+        a.run_frame();
+        let hash = a.bus().frame_hash();
+        hashes.push(hash);
+
+        // a golden hash is the same screen or not at all
+        let similarity = match (&mut b, &golden) {
+            (Some(b), _) => {
+                b.run_frame();
+                dump::similarity(&a.bus().vram(), &b.bus().vram())
+            }
+            (None, Some(golden)) if golden[frame as usize] == hash => 1.0,
+            (None, _) => 0.0,
+        };
+        println!("{} {:.4}", frame, similarity);
+        total += similarity;
+
+        if similarity < 1.0 && first_difference.is_none() {
+            first_difference = Some(frame);
+            if let Some(dir) = &args.output {
+                fs::create_dir_all(dir)
+                    .map_err(|e| format!("{}: {}", dir.display(), e))?;
+                let mut screens = vec![("a", &a)];
+                screens.extend(b.as_ref().map(|b| ("b", b)));
+                for (run, machine) in screens {
+                    let path = dir.join(format!("{:06}-{}.png", frame, run));
+                    dump::png(
+                        &machine.bus().vram(),
+                        &path.to_string_lossy(),
+                        args.scale,
+                    )?;
+                }
+            }
+        }
+    }
+
+    if let Some(path) = &args.save_golden {
+        let text: String = hashes
+            .iter()
+            .enumerate()
+            .map(|(frame, hash)| format!("{} {:016x}\n", frame, hash))
+            .collect();
+        fs::write(path, text)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+
+    let mean = total / args.frames.max(1) as f64;
+    match first_difference {
+        Some(frame) => println!(
+            "first difference: frame {}, mean similarity {:.4}",
+            frame, mean
+        ),
+        None => println!("no difference"),
+    }
+
+    Ok(first_difference)
+}
+
+/// The hashes of `save_golden`, at least `frames` of them
+fn read_golden(path: &Path, frames: u64) -> Result<Vec<u64>, String> {
+    let error = |e: String| format!("{}: {}", path.display(), e);
+    let text = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+
+    let mut hashes = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let hash = match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [frame, hash] if frame.parse() == Ok(hashes.len()) => {
+                u64::from_str_radix(hash, 16).ok()
+            }
+            _ => None,
+        };
+        match hash {
+            Some(hash) => hashes.push(hash),
+            None => return Err(error(format!("{}: invalid line", number + 1))),
+        }
+    }
+
+    if (hashes.len() as u64) < frames {
+        return Err(error(format!("only {} frames", hashes.len())));
+    }
+
+    Ok(hashes)
+}
+
+fn parse_policy(name: &str) -> Result<KeyWaitPolicy, String> {
+    KEY_WAIT_POLICIES
+        .iter()
+        .find(|(_, known)| *known == name)
+        .map(|&(policy, _)| policy)
+        .ok_or_else(|| format!("unknown policy {}", name))
+}
+
+fn trace_diff(left: &str, right: &str) -> ExitCode {
+    let open = |path: &str| {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| format!("{}: {}", path, e))
+    };
+    let result = open(left)
+        .and_then(|left| Ok((left, open(right)?)))
+        .and_then(|(left, right)| trace::diff(left, right));
+
+    match result {
+        Ok(None) => {
+            println!("the traces are the same");
+            ExitCode::SUCCESS
+        }
+        Ok(Some(divergence)) => {
+            println!("{}", divergence);
+            if let Some(previous) = divergence.previous {
+                println!("  both   {}", previous);
+            }
+            let entry = |entry: Option<trace::Entry>| {
+                entry.map_or("(end)".to_string(), |entry| entry.to_string())
+            };
+            println!("  left   {}", entry(divergence.left));
+            println!("  right  {}", entry(divergence.right));
+            ExitCode::from(1)
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn download(url: &str) -> Result<String, String> {
+    debug!("download: {}", url);
+
+    ureq::get(url)
+        .call()
+        .map_err(|e| e.to_string())
+        .and_then(|mut response| {
+            response
+                .body_mut()
+                .read_to_string()
+                .map_err(|e| e.to_string())
+        })
+        .map_err(|e| format!("{}: {}", url, e))
+}
+
+/// Add the roms in `dir` and its subdirectories to `roms`
+fn find_roms(dir: &Path, roms: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_roms(&path, roms)?;
+        } else if path.extension().is_some_and(|extension| {
+            ROM_EXTENSIONS
+                .iter()
+                .any(|known| extension.eq_ignore_ascii_case(known))
+        }) {
+            roms.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn load_machine(path: &str, speed: f64) -> Result<Machine, String> {
+    let rom = Rom::from_bytes(read(path)?);
+    debug!("loaded: {}", rom);
+
+    let mut machine = Machine::new(rom);
+    machine.set_cpu_frequency(speed);
+
+    Ok(machine)
+}
+
+/// The machine of a state or a slot file, without its rom
+fn load_state(path: &str) -> Result<Machine, String> {
+    slots::saved_machine(&read(path)?).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Run `frames` frames of `machine` with the keys of `script`, recording
+/// what its memory is used for
+fn cover(machine: &mut Machine, script: &mut Script, frames: u64) -> Coverage {
+    let mut coverage = Coverage::new(machine);
+    for frame in 0..frames {
+        if let Some(keys) = script.keys_at(frame) {
+            machine.bus_mut().keys = keys;
+        }
+        coverage.run_frame(machine);
+    }
+
+    coverage
+}
+
+/// A buffered writer to the file at `path`, or to the standard output for
+/// `-`
+fn create(path: &str) -> Result<Box<dyn Write>, String> {
+    match path {
+        "-" => Ok(Box::new(io::stdout().lock())),
+        path => File::create(path)
+            .map(|file| Box::new(BufWriter::new(file)) as Box<dyn Write>)
+            .map_err(|e| format!("{}: {}", path, e)),
+    }
+}
+
+/// `script::run` with the rhai script at `path`, printing the lines it
+/// last gave to `hud`
+#[cfg(feature = "rhai")]
+fn run_rhai(
+    path: &str,
+    machine: &mut Machine,
+    script: &mut Script,
+    frames: u64,
+    until_halt: bool,
+) -> Result<(u64, Stop), String> {
+    let mut rhai = RhaiScript::new_from(path)?;
+
+    // no frame runs after the first error
+    let mut result = Ok(());
+    let ran =
+        script::run_with(machine, script, frames, until_halt, |machine| {
+            if result.is_ok() {
+                result = rhai.run_frame(machine);
+                for line in rhai.take_output() {
+                    println!("{}", line);
+                }
+            }
+        });
+    result.map_err(|e| format!("{}: {}", path, e))?;
+
+    for line in rhai.hud() {
+        println!("hud: {}", line);
+    }
+
+    Ok(ran)
+}
+
+#[cfg(not(feature = "rhai"))]
+fn run_rhai(
+    _path: &str,
+    _machine: &mut Machine,
+    _script: &mut Script,
+    _frames: u64,
+    _until_halt: bool,
+) -> Result<(u64, Stop), String> {
+    Err("built without the rhai feature".to_string())
+}
+
+fn load_script(path: Option<&str>) -> Result<Script, String> {
+    match path {
+        Some(path) => Script::new_from(path),
+        None => Ok(Script::default()),
+    }
+}
@@ -0,0 +1,83 @@
+use std::str::FromStr;
+
+pub const HELP: &str = "step [n], next, continue, frame [n], break ADDR, \
+                        delete [ADDR], mem ADDR, sprite [ADDR], reset, \
+                        quit";
+
+/// A command typed in the command line, addresses are hexadecimal
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Execute n instructions
+    Step(usize),
+    /// Execute one instruction, running subroutine calls to their return
+    Next,
+    /// Run until a breakpoint or the user pauses
+    Continue,
+    /// Run n frames
+    Frame(usize),
+    Break(u16),
+    /// Remove one breakpoint, or all of them
+    Delete(Option<u16>),
+    /// Move the memory view
+    Memory(u16),
+    /// Preview the sprite at an address, or the next likely sprite
+    Sprite(Option<u16>),
+    Reset,
+    Help,
+    Quit,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let arg = words.next();
+
+        if let Some(extra) = words.next() {
+            return Err(format!("unexpected argument: {}", extra));
+        }
+
+        match name {
+            "s" | "step" => Ok(Command::Step(parse_count(arg)?)),
+            "n" | "next" => Ok(Command::Next),
+            "c" | "continue" => Ok(Command::Continue),
+            "f" | "frame" => Ok(Command::Frame(parse_count(arg)?)),
+            "b" | "break" => Ok(Command::Break(parse_addr(arg)?)),
+            "d" | "delete" => match arg {
+                Some(_) => Ok(Command::Delete(Some(parse_addr(arg)?))),
+                None => Ok(Command::Delete(None)),
+            },
+            "m" | "mem" => Ok(Command::Memory(parse_addr(arg)?)),
+            "sprite" => match arg {
+                Some(_) => Ok(Command::Sprite(Some(parse_addr(arg)?))),
+                None => Ok(Command::Sprite(None)),
+            },
+            "reset" => Ok(Command::Reset),
+            "h" | "help" => Ok(Command::Help),
+            "q" | "quit" => Ok(Command::Quit),
+            _ => Err(format!("unknown command: {}", name)),
+        }
+    }
+}
+
+fn parse_count(arg: Option<&str>) -> Result<usize, String> {
+    match arg {
+        Some(arg) => arg.parse().map_err(|_| format!("invalid count: {}", arg)),
+        None => Ok(1),
+    }
+}
+
+fn parse_addr(arg: Option<&str>) -> Result<u16, String> {
+    let arg = arg.ok_or("missing address")?;
+    let digits = arg
+        .strip_prefix("0x")
+        .or_else(|| arg.strip_prefix('$'))
+        .unwrap_or(arg);
+
+    match u16::from_str_radix(digits, 16) {
+        Ok(addr) if addr < 0x1000 => Ok(addr),
+        _ => Err(format!("invalid address: {}", arg)),
+    }
+}
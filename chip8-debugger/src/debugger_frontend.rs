@@ -0,0 +1,642 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    io,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use chip8::{
+    bus::{DISPLAY_HEIGHT, DISPLAY_WIDTH, KEYPAD_SIZE},
+    disasm::disassemble_at,
+    keypad::Keypad,
+    machine::Machine,
+};
+use chip8_frontend::sprites::{self, Sprite, MAX_ROWS};
+use log::warn;
+use ratatui::{
+    crossterm::{
+        event::{
+            self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+            KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+            PushKeyboardEnhancementFlags,
+        },
+        execute, terminal,
+    },
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+    DefaultTerminal, Frame,
+};
+
+use crate::command::{Command, HELP};
+
+const FOREGROUND: Color = Color::Rgb(69, 115, 13);
+const BACKGROUND: Color = Color::Rgb(124, 209, 21);
+
+// most terminals only report key presses, so a key is held for this long
+// after its last press (or auto-repeat) event
+const KEY_HOLD: Duration = Duration::from_millis(150);
+
+const MEMORY_SIZE: u16 = 0x1000;
+const MEMORY_ROW: u16 = 16;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Paused,
+    /// Running, until the pc returns to an address with the given stack
+    /// depth if any
+    Running(Option<(u16, usize)>),
+}
+
+pub struct DebuggerFrontend {
+    // chip8
+    machine: Machine,
+    // terminal
+    terminal: DefaultTerminal,
+    key_release_events: bool,
+    key_expiry: [Option<Instant>; KEYPAD_SIZE],
+    key_map: HashMap<KeyCode, Keypad>,
+    // debugger
+    mode: Mode,
+    breakpoints: BTreeSet<u16>,
+    memory_addr: u16,
+    sprite: Option<Sprite>,
+    input: String,
+    last_command: Option<Command>,
+    message: String,
+    resuming: bool,
+    // loop
+    running: bool,
+}
+
+impl DebuggerFrontend {
+    pub fn new(machine: Machine) -> Self {
+        let terminal = ratatui::init();
+
+        let key_release_events = terminal::supports_keyboard_enhancement()
+            .unwrap_or(false)
+            && execute!(
+                io::stdout(),
+                PushKeyboardEnhancementFlags(
+                    KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                )
+            )
+            .is_ok();
+
+        let mut key_map = HashMap::new();
+        key_map.insert(KeyCode::Char('1'), Keypad::Key1);
+        key_map.insert(KeyCode::Char('2'), Keypad::Key2);
+        key_map.insert(KeyCode::Char('3'), Keypad::Key3);
+        key_map.insert(KeyCode::Char('4'), Keypad::KeyC);
+        key_map.insert(KeyCode::Char('a'), Keypad::Key4);
+        key_map.insert(KeyCode::Char('z'), Keypad::Key5);
+        key_map.insert(KeyCode::Char('e'), Keypad::Key6);
+        key_map.insert(KeyCode::Char('r'), Keypad::KeyD);
+        key_map.insert(KeyCode::Char('q'), Keypad::Key7);
+        key_map.insert(KeyCode::Char('s'), Keypad::Key8);
+        key_map.insert(KeyCode::Char('d'), Keypad::Key9);
+        key_map.insert(KeyCode::Char('f'), Keypad::KeyE);
+        key_map.insert(KeyCode::Char('w'), Keypad::KeyA);
+        key_map.insert(KeyCode::Char('x'), Keypad::Key0);
+        key_map.insert(KeyCode::Char('c'), Keypad::KeyB);
+        key_map.insert(KeyCode::Char('v'), Keypad::KeyF);
+
+        Self {
+            // chip8
+            machine,
+            // terminal
+            terminal,
+            key_release_events,
+            key_expiry: [None; KEYPAD_SIZE],
+            key_map,
+            // debugger
+            mode: Mode::Paused,
+            breakpoints: BTreeSet::new(),
+            memory_addr: 0x200,
+            sprite: None,
+            input: String::new(),
+            last_command: None,
+            message: String::from(HELP),
+            resuming: false,
+            // loop
+            running: true,
+        }
+    }
+
+    pub fn run(&mut self) {
+        let mut loop_time = Instant::now();
+
+        while self.running {
+            self.read_events();
+
+            let delta = loop_time.elapsed();
+            loop_time = Instant::now();
+
+            if let Mode::Running(until) = self.mode {
+                // a slow draw isn't caught up
+                for _ in 0..self.machine.frames_due(delta).run {
+                    if !self.run_frame(until) {
+                        self.pause();
+                        break;
+                    }
+                }
+            }
+
+            self.draw();
+
+            sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Run a frame, returns false when it stopped on a breakpoint
+    fn run_frame(&mut self, until: Option<(u16, usize)>) -> bool {
+        let breakpoints = &self.breakpoints;
+        let mut resuming = self.resuming;
+
+        let complete = self.machine.run_frame_until(|cpu| {
+            // the first instruction is the breakpoint we stopped on
+            !std::mem::take(&mut resuming)
+                && (breakpoints.contains(&cpu.pc())
+                    || until == Some((cpu.pc(), cpu.call_stack().len())))
+        });
+        self.resuming = resuming;
+
+        complete
+    }
+
+    fn pause(&mut self) {
+        self.mode = Mode::Paused;
+        self.machine.pause(true);
+        self.message = format!("stopped at 0x{:03X}", self.machine.cpu().pc());
+
+        // keys can't be released while the command line has the keyboard
+        self.key_expiry = [None; KEYPAD_SIZE];
+        self.machine.bus_mut().keys = [false; KEYPAD_SIZE];
+    }
+
+    fn read_events(&mut self) {
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            let event = match event::read() {
+                Ok(event) => event,
+                Err(err) => {
+                    warn!("terminal event: {}", err);
+                    break;
+                }
+            };
+
+            if let Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind,
+                ..
+            }) = event
+            {
+                if code == KeyCode::Char('c')
+                    && modifiers.contains(KeyModifiers::CONTROL)
+                {
+                    self.running = false;
+                } else if self.mode == Mode::Paused {
+                    if kind != KeyEventKind::Release {
+                        self.edit_input(code);
+                    }
+                } else if code == KeyCode::Esc {
+                    self.pause();
+                } else {
+                    let code = match code {
+                        KeyCode::Char(c) => {
+                            KeyCode::Char(c.to_ascii_lowercase())
+                        }
+                        code => code,
+                    };
+
+                    if let Some(&key) = self.key_map.get(&code) {
+                        self.keyboard_input(key, kind);
+                    }
+                }
+            }
+        }
+
+        // release keys the terminal won't tell us about
+        let now = Instant::now();
+        for key in 0..KEYPAD_SIZE {
+            if self.key_expiry[key].is_some_and(|expiry| expiry <= now) {
+                self.key_expiry[key] = None;
+                self.machine.bus_mut().keys[key] = false;
+            }
+        }
+    }
+
+    fn keyboard_input(&mut self, key: Keypad, kind: KeyEventKind) {
+        match kind {
+            KeyEventKind::Release => self.machine.set_key(key, false),
+            _ => {
+                self.machine.set_key(key, true);
+                if !self.key_release_events {
+                    self.key_expiry[key as usize] =
+                        Some(Instant::now() + KEY_HOLD);
+                }
+            }
+        }
+    }
+
+    fn edit_input(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Esc => self.input.clear(),
+            KeyCode::Enter => {
+                let input = std::mem::take(&mut self.input);
+
+                // an empty line repeats the last command, like gdb
+                let command = match input.trim() {
+                    "" => self.last_command.clone(),
+                    input => match input.parse::<Command>() {
+                        Ok(command) => Some(command),
+                        Err(err) => {
+                            self.message = err;
+                            None
+                        }
+                    },
+                };
+
+                if let Some(command) = command {
+                    self.execute(command.clone());
+                    self.last_command = Some(command);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn execute(&mut self, command: Command) {
+        let pc = self.machine.cpu().pc();
+
+        match command {
+            Command::Step(count) => {
+                for _ in 0..count {
+                    self.machine.step();
+                }
+                self.message =
+                    format!("stepped to 0x{:03X}", self.machine.cpu().pc());
+            }
+            Command::Next => {
+                let (opcode, _) =
+                    disassemble_at(self.machine.bus().memory(), pc);
+                if opcode & 0xF000 == 0x2000 {
+                    let depth = self.machine.cpu().call_stack().len();
+                    self.resume(Some((pc.wrapping_add(2), depth)));
+                } else {
+                    self.execute(Command::Step(1));
+                }
+            }
+            Command::Continue => self.resume(None),
+            Command::Frame(count) => {
+                self.resuming = true;
+                for _ in 0..count {
+                    if !self.run_frame(None) {
+                        break;
+                    }
+                }
+                self.message =
+                    format!("stopped at 0x{:03X}", self.machine.cpu().pc());
+            }
+            Command::Break(addr) => {
+                self.breakpoints.insert(addr);
+                self.message = format!("breakpoint at 0x{:03X}", addr);
+            }
+            Command::Delete(Some(addr)) => {
+                self.message = match self.breakpoints.remove(&addr) {
+                    true => format!("deleted breakpoint at 0x{:03X}", addr),
+                    false => format!("no breakpoint at 0x{:03X}", addr),
+                };
+            }
+            Command::Delete(None) => {
+                self.breakpoints.clear();
+                self.message = String::from("deleted all breakpoints");
+            }
+            Command::Memory(addr) => {
+                self.memory_addr = addr - addr % MEMORY_ROW;
+                self.message.clear();
+            }
+            Command::Sprite(addr) => {
+                let found = sprites::find(self.machine.bus().memory(), None);
+                self.sprite = match addr {
+                    // as many rows as it is drawn with, if it is found
+                    Some(addr) => Some(
+                        found
+                            .into_iter()
+                            .find(|sprite| sprite.addr == addr)
+                            .unwrap_or(Sprite {
+                                addr,
+                                rows: MAX_ROWS,
+                            }),
+                    ),
+                    None => {
+                        let after = self.sprite.map(|sprite| sprite.addr);
+                        found
+                            .iter()
+                            .find(|sprite| Some(sprite.addr) > after)
+                            .or(found.first())
+                            .copied()
+                    }
+                };
+                self.message = match self.sprite {
+                    Some(sprite) => format!(
+                        "sprite at 0x{:03X}, {} rows",
+                        sprite.addr, sprite.rows
+                    ),
+                    None => String::from("no sprite found"),
+                };
+            }
+            Command::Reset => {
+                self.machine.reset();
+                self.message = String::from("reset");
+            }
+            Command::Help => self.message = String::from(HELP),
+            Command::Quit => self.running = false,
+        }
+    }
+
+    fn resume(&mut self, until: Option<(u16, usize)>) {
+        self.resuming = true;
+        self.mode = Mode::Running(until);
+        self.machine.pause(false);
+        self.message = String::from("running, Esc to pause");
+    }
+
+    fn draw(&mut self) {
+        let res = self.terminal.draw(|frame| {
+            let [top, middle, bottom] = Layout::vertical([
+                Constraint::Length(DISPLAY_HEIGHT as u16 / 2 + 2),
+                Constraint::Min(0),
+                Constraint::Length(3),
+            ])
+            .areas(frame.area());
+            let [display, registers, stack, breakpoints, sprite] =
+                Layout::horizontal([
+                    Constraint::Length(DISPLAY_WIDTH as u16 + 2),
+                    Constraint::Length(16),
+                    Constraint::Length(10),
+                    Constraint::Length(14),
+                    Constraint::Min(0),
+                ])
+                .areas(top);
+            let [disassembly, memory] = Layout::horizontal([
+                Constraint::Length(DISPLAY_WIDTH as u16 + 2),
+                Constraint::Min(0),
+            ])
+            .areas(middle);
+
+            draw_display(frame, display, &self.machine);
+            draw_registers(frame, registers, &self.machine);
+            draw_stack(frame, stack, &self.machine);
+            draw_breakpoints(frame, breakpoints, &self.breakpoints);
+            draw_sprite(frame, sprite, &self.machine, self.sprite);
+            draw_disassembly(
+                frame,
+                disassembly,
+                &self.machine,
+                &self.breakpoints,
+            );
+            draw_memory(frame, memory, &self.machine, self.memory_addr);
+
+            let title = match self.mode {
+                Mode::Paused => "paused",
+                Mode::Running(_) => "running",
+            };
+            let block = Block::bordered()
+                .title(title)
+                .title_bottom(self.message.as_str());
+            frame.render_widget(
+                Paragraph::new(format!("> {}", self.input)).block(block),
+                bottom,
+            );
+            if self.mode == Mode::Paused {
+                let x = bottom.x + 3 + self.input.chars().count() as u16;
+                frame.set_cursor_position((x, bottom.y + 1));
+            }
+        });
+
+        if let Err(err) = res {
+            warn!("terminal draw: {}", err);
+        }
+    }
+}
+
+impl Drop for DebuggerFrontend {
+    fn drop(&mut self) {
+        if self.key_release_events {
+            execute!(io::stdout(), PopKeyboardEnhancementFlags).ok();
+        }
+
+        ratatui::restore();
+    }
+}
+
+fn draw_display(frame: &mut Frame, area: Rect, machine: &Machine) {
+    let vram = &machine.bus().vram();
+
+    // one character cell holds two pixels stacked vertically
+    let lines: Vec<Line> = (0..DISPLAY_HEIGHT)
+        .step_by(2)
+        .map(|h| {
+            (0..DISPLAY_WIDTH)
+                .map(|w| {
+                    Span::styled(
+                        "▀",
+                        Style::new()
+                            .fg(pixel_color(vram[w][h]))
+                            .bg(pixel_color(vram[w][h + 1])),
+                    )
+                })
+                .collect::<Line>()
+        })
+        .collect();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("display")),
+        area,
+    );
+}
+
+fn draw_registers(frame: &mut Frame, area: Rect, machine: &Machine) {
+    let cpu = machine.cpu();
+    let v = cpu.registers();
+
+    let mut lines = vec![
+        Line::from(format!("PC 0x{:03X}", cpu.pc())),
+        Line::from(format!("I  0x{:03X}", cpu.index())),
+    ];
+    lines.extend((0..8).map(|x| {
+        Line::from(format!(
+            "V{:X} {:02X}  V{:X} {:02X}",
+            x,
+            v[x],
+            x + 8,
+            v[x + 8]
+        ))
+    }));
+    lines.push(Line::from(format!(
+        "DT {:02X}  ST {:02X}",
+        machine.bus().delay,
+        machine.bus().beep
+    )));
+    if let Some(x) = cpu.key_await() {
+        lines.push(Line::from(format!("wait key V{:X}", x)));
+    }
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("registers")),
+        area,
+    );
+}
+
+fn draw_stack(frame: &mut Frame, area: Rect, machine: &Machine) {
+    // innermost call first
+    let lines: Vec<Line> = machine
+        .cpu()
+        .call_stack()
+        .iter()
+        .rev()
+        .map(|addr| Line::from(format!("0x{:03X}", addr)))
+        .collect();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("stack")),
+        area,
+    );
+}
+
+fn draw_breakpoints(
+    frame: &mut Frame,
+    area: Rect,
+    breakpoints: &BTreeSet<u16>,
+) {
+    let lines: Vec<Line> = breakpoints
+        .iter()
+        .map(|addr| Line::from(format!("0x{:03X}", addr)))
+        .collect();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("breakpoints")),
+        area,
+    );
+}
+
+fn draw_sprite(
+    frame: &mut Frame,
+    area: Rect,
+    machine: &Machine,
+    sprite: Option<Sprite>,
+) {
+    let (title, lines) = match sprite {
+        Some(sprite) => {
+            let pixels = sprite.pixels(machine.bus().memory());
+            // the same two pixels per character cell as the display
+            let lines: Vec<Line> = pixels
+                .chunks(2)
+                .map(|rows| {
+                    (0..8)
+                        .map(|x| {
+                            let bottom = rows.get(1).is_some_and(|row| row[x]);
+                            Span::styled(
+                                "▀",
+                                Style::new()
+                                    .fg(pixel_color(rows[0][x]))
+                                    .bg(pixel_color(bottom)),
+                            )
+                        })
+                        .collect::<Line>()
+                })
+                .collect();
+            (format!("sprite 0x{:03X}", sprite.addr), lines)
+        }
+        None => (String::from("sprite"), vec![]),
+    };
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(title)),
+        area,
+    );
+}
+
+fn draw_disassembly(
+    frame: &mut Frame,
+    area: Rect,
+    machine: &Machine,
+    breakpoints: &BTreeSet<u16>,
+) {
+    let pc = machine.cpu().pc();
+    let rows = area.height.saturating_sub(2);
+
+    // keep the pc on the first third of the pane
+    let start = pc.saturating_sub(rows / 3 * 2);
+    let lines: Vec<Line> = (0..rows)
+        .map(|row| start + row * 2)
+        .take_while(|&addr| addr < MEMORY_SIZE)
+        .map(|addr| {
+            let (opcode, text) = disassemble_at(machine.bus().memory(), addr);
+            let marker = match breakpoints.contains(&addr) {
+                true => Span::styled("* ", Style::new().fg(Color::Red)),
+                false => Span::raw("  "),
+            };
+            let line = Line::from(vec![
+                marker,
+                Span::raw(format!("0x{:03X}  {:04X}  {}", addr, opcode, text)),
+            ]);
+
+            match addr == pc {
+                true => {
+                    line.style(Style::new().add_modifier(Modifier::REVERSED))
+                }
+                false => line,
+            }
+        })
+        .collect();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("disassembly")),
+        area,
+    );
+}
+
+fn draw_memory(frame: &mut Frame, area: Rect, machine: &Machine, start: u16) {
+    let memory = machine.bus().memory();
+    let i = machine.cpu().index();
+    let rows = area.height.saturating_sub(2);
+
+    let lines: Vec<Line> = (0..rows)
+        .map(|row| start + row * MEMORY_ROW)
+        .take_while(|&addr| addr < MEMORY_SIZE)
+        .map(|addr| {
+            let mut spans = vec![Span::raw(format!("0x{:03X} ", addr))];
+            spans.extend((addr..addr + MEMORY_ROW).map(|addr| {
+                let byte = Span::raw(format!(" {:02X}", memory[addr as usize]));
+                match addr == i {
+                    true => byte
+                        .style(Style::new().add_modifier(Modifier::REVERSED)),
+                    false => byte,
+                }
+            }));
+
+            Line::from(spans)
+        })
+        .collect();
+
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(Block::bordered().title(format!("memory, I = 0x{:03X}", i))),
+        area,
+    );
+}
+
+fn pixel_color(pixel: bool) -> Color {
+    if pixel {
+        FOREGROUND
+    } else {
+        BACKGROUND
+    }
+}
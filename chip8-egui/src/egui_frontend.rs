@@ -0,0 +1,514 @@
+use std::time::Instant;
+
+use chip8::{
+    beep::Beeper,
+    bus::{Bus, DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    cpu::{Cpu, KeyWaitPolicy},
+    delay::Delay,
+    disasm::disassemble_at,
+    keypad::Keypad,
+};
+use chip8_frontend::sprites::{self, Sprite, MAX_ROWS};
+use eframe::egui::{
+    self, Color32, ColorImage, Key, RichText, TextureHandle, TextureOptions,
+    Ui, WidgetText,
+};
+use egui_dock::{DockArea, DockState, NodeIndex, Style, TabViewer};
+
+const FOREGROUND: Color32 = Color32::from_rgb(69, 115, 13);
+const BACKGROUND: Color32 = Color32::from_rgb(124, 209, 21);
+
+const KEY_MAP: [(Key, Keypad); 16] = [
+    (Key::Num1, Keypad::Key1),
+    (Key::Num2, Keypad::Key2),
+    (Key::Num3, Keypad::Key3),
+    (Key::Num4, Keypad::KeyC),
+    (Key::A, Keypad::Key4),
+    (Key::Z, Keypad::Key5),
+    (Key::E, Keypad::Key6),
+    (Key::R, Keypad::KeyD),
+    (Key::Q, Keypad::Key7),
+    (Key::S, Keypad::Key8),
+    (Key::D, Keypad::Key9),
+    (Key::F, Keypad::KeyE),
+    (Key::W, Keypad::KeyA),
+    (Key::X, Keypad::Key0),
+    (Key::C, Keypad::KeyB),
+    (Key::V, Keypad::KeyF),
+];
+
+/// Keys in the rows of the COSMAC VIP keypad
+const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+const MEMORY_ROW_SIZE: usize = 16;
+/// Instructions shown by the disassembly, the pc on the first third
+const DISASSEMBLY_ROWS: u16 = 32;
+/// Size of a sprite pixel in the preview
+const SPRITE_PIXEL: f32 = 12.0;
+
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+enum Panel {
+    Display,
+    Registers,
+    Disassembly,
+    Keypad,
+    Memory,
+    Sprites,
+    Settings,
+}
+
+pub struct EguiFrontend {
+    emulator: Emulator,
+    dock_state: DockState<Panel>,
+}
+
+struct Emulator {
+    // chip8
+    cpu: Cpu,
+    delay: Delay,
+    beeper: Beeper,
+    bus: Bus,
+    // loop
+    loop_time: Instant,
+    cpu_cycles: f64,
+    delay_update: f64,
+    beep_update: f64,
+    running: bool,
+    // settings
+    cpu_frequency: f64,
+    foreground: Color32,
+    background: Color32,
+    //
+    display: Option<TextureHandle>,
+    /// Shown in the sprites panel
+    sprite: Sprite,
+}
+
+impl EguiFrontend {
+    pub fn new(cpu: Cpu, delay: Delay, beeper: Beeper, bus: Bus) -> Self {
+        let mut dock_state = DockState::new(vec![Panel::Display]);
+        let surface = dock_state.main_surface_mut();
+        let [display, _] = surface.split_right(
+            NodeIndex::root(),
+            0.65,
+            vec![Panel::Registers, Panel::Keypad, Panel::Settings],
+        );
+        surface.split_below(
+            display,
+            0.6,
+            vec![Panel::Disassembly, Panel::Memory, Panel::Sprites],
+        );
+
+        Self {
+            emulator: Emulator {
+                cpu,
+                delay,
+                beeper,
+                bus,
+                loop_time: Instant::now(),
+                cpu_cycles: 0.0,
+                delay_update: 0.0,
+                beep_update: 0.0,
+                running: true,
+                cpu_frequency: 500.0,
+                foreground: FOREGROUND,
+                background: BACKGROUND,
+                display: None,
+                sprite: Sprite {
+                    addr: 0x200,
+                    rows: 5,
+                },
+            },
+            dock_state,
+        }
+    }
+
+    pub fn run(self) {
+        let options = eframe::NativeOptions {
+            viewport: egui::ViewportBuilder::default()
+                .with_title("Chip8 egui")
+                .with_inner_size([1024.0, 640.0]),
+            ..Default::default()
+        };
+
+        eframe::run_native(
+            "chip8-egui",
+            options,
+            Box::new(|_cc| Ok(Box::new(self))),
+        )
+        .expect("eframe: run");
+    }
+}
+
+impl eframe::App for EguiFrontend {
+    fn ui(&mut self, ui: &mut Ui, _frame: &mut eframe::Frame) {
+        self.emulator.keyboard_inputs(ui);
+        self.emulator.tick();
+
+        DockArea::new(&mut self.dock_state)
+            .style(Style::from_egui(ui.style()))
+            .show_close_buttons(false)
+            .show_inside(ui, &mut self.emulator);
+
+        ui.ctx().request_repaint();
+    }
+}
+
+impl Emulator {
+    fn tick(&mut self) {
+        let delta = self.loop_time.elapsed().as_secs_f64();
+
+        self.cpu_cycles += delta * self.cpu_frequency;
+        while self.cpu_cycles >= 1.0 && self.running {
+            self.cpu_cycles -= 1.0;
+            self.cpu.emulate(&mut self.bus);
+        }
+
+        self.delay_update += delta / 0.0166666666667; // 60 Hz
+        while self.delay_update >= 1.0 && self.running {
+            self.delay_update -= 1.0;
+
+            self.delay.update(&mut self.bus);
+        }
+
+        self.beep_update += delta / 0.0166666666667; // 60 Hz
+        while self.beep_update >= 1.0 && self.running {
+            self.beep_update -= 1.0;
+
+            self.beeper.update(&mut self.bus);
+        }
+
+        self.loop_time = Instant::now();
+    }
+
+    fn keyboard_inputs(&mut self, ui: &Ui) {
+        ui.input(|input| {
+            for (key, keypad) in KEY_MAP {
+                self.bus.keys[keypad as usize] = input.key_down(key);
+            }
+        });
+    }
+
+    fn reset(&mut self) {
+        self.cpu.reset();
+    }
+
+    fn display_ui(&mut self, ui: &mut Ui) {
+        let mut pixels = Vec::with_capacity(DISPLAY_WIDTH * DISPLAY_HEIGHT);
+        for h in 0..DISPLAY_HEIGHT {
+            for w in 0..DISPLAY_WIDTH {
+                pixels.push(match self.bus.pixel(w, h) {
+                    true => self.foreground,
+                    false => self.background,
+                });
+            }
+        }
+        let image = ColorImage::new([DISPLAY_WIDTH, DISPLAY_HEIGHT], pixels);
+
+        let texture = match &mut self.display {
+            Some(texture) => {
+                texture.set(image, TextureOptions::NEAREST);
+                texture
+            }
+            None => self.display.insert(ui.ctx().load_texture(
+                "display",
+                image,
+                TextureOptions::NEAREST,
+            )),
+        };
+
+        // fit the panel while keeping the aspect ratio
+        let available = ui.available_size();
+        let scale = (available.x / DISPLAY_WIDTH as f32)
+            .min(available.y / DISPLAY_HEIGHT as f32)
+            .max(1.0);
+        let size = egui::vec2(
+            DISPLAY_WIDTH as f32 * scale,
+            DISPLAY_HEIGHT as f32 * scale,
+        );
+
+        ui.centered_and_justified(|ui| {
+            ui.add(egui::Image::new((texture.id(), size)));
+        });
+    }
+
+    fn registers_ui(&mut self, ui: &mut Ui) {
+        let cpu = &self.cpu;
+
+        egui::Grid::new("registers").striped(true).show(ui, |ui| {
+            ui.label("PC");
+            ui.monospace(format!("{:04X}", cpu.pc()));
+            ui.label("I");
+            ui.monospace(format!("{:04X}", cpu.index()));
+            ui.end_row();
+
+            for (x, v) in cpu.registers().chunks(2).enumerate() {
+                ui.label(format!("V{:X}", x * 2));
+                ui.monospace(format!("{:02X}", v[0]));
+                ui.label(format!("V{:X}", x * 2 + 1));
+                ui.monospace(format!("{:02X}", v[1]));
+                ui.end_row();
+            }
+
+            ui.label("DT");
+            ui.monospace(format!("{:02X}", self.bus.delay));
+            ui.label("ST");
+            ui.monospace(format!("{:02X}", self.bus.beep));
+            ui.end_row();
+        });
+
+        ui.separator();
+        ui.label("Stack");
+        if cpu.call_stack().is_empty() {
+            ui.weak("empty");
+        }
+        for (depth, addr) in cpu.call_stack().iter().enumerate().rev() {
+            ui.monospace(format!("{:2}: {:04X}", depth, addr));
+        }
+
+        ui.separator();
+        if let Some(x) = cpu.key_await() {
+            ui.label(format!("Waiting for a key (V{:X})", x));
+        }
+        if self.beeper.is_beeping() {
+            ui.label("Beeping");
+        }
+    }
+
+    fn disassembly_ui(&mut self, ui: &mut Ui) {
+        let memory = self.bus.memory();
+        let pc = self.cpu.pc();
+        let start = pc.saturating_sub(DISASSEMBLY_ROWS / 3 * 2);
+
+        egui::ScrollArea::vertical()
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                for addr in (0..DISASSEMBLY_ROWS)
+                    .map(|row| start + row * 2)
+                    .take_while(|&addr| (addr as usize) < memory.len())
+                {
+                    let (opcode, text) = disassemble_at(memory, addr);
+                    let text = RichText::new(format!(
+                        "{:03X}  {:04X}  {}",
+                        addr, opcode, text
+                    ))
+                    .monospace();
+                    ui.label(match addr == pc {
+                        true => text.color(Color32::LIGHT_RED),
+                        false => text,
+                    });
+                }
+            });
+    }
+
+    /// The keys held, in the layout of the keypad
+    fn keypad_ui(&mut self, ui: &mut Ui) {
+        egui::Grid::new("keypad").show(ui, |ui| {
+            for row in KEYPAD_LAYOUT {
+                for key in row {
+                    let text =
+                        RichText::new(format!(" {:X} ", key)).monospace();
+                    let text = match self.bus.keys[key as usize] {
+                        true => text.color(self.background).strong(),
+                        false => text.weak(),
+                    };
+                    ui.label(text);
+                }
+                ui.end_row();
+            }
+        });
+    }
+
+    fn memory_ui(&mut self, ui: &mut Ui) {
+        let memory = self.bus.memory();
+        let pc = self.cpu.pc() as usize;
+        let index = self.cpu.index() as usize;
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+
+        egui::ScrollArea::vertical().auto_shrink(false).show_rows(
+            ui,
+            row_height,
+            memory.len() / MEMORY_ROW_SIZE,
+            |ui, rows| {
+                for row in rows {
+                    let addr = row * MEMORY_ROW_SIZE;
+
+                    ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing.x = 4.0;
+                        ui.monospace(format!("{:03X}:", addr));
+
+                        for (offset, byte) in memory
+                            [addr..addr + MEMORY_ROW_SIZE]
+                            .iter()
+                            .enumerate()
+                        {
+                            let text = RichText::new(format!("{:02X}", byte))
+                                .monospace();
+                            let text = match addr + offset {
+                                a if a == pc || a == pc + 1 => {
+                                    text.color(Color32::LIGHT_RED)
+                                }
+                                a if a == index => {
+                                    text.color(Color32::LIGHT_BLUE)
+                                }
+                                _ => text,
+                            };
+                            ui.label(text);
+                        }
+                    });
+                }
+            },
+        );
+    }
+
+    /// Preview of the sprite at any address, and of the likely sprites
+    /// found in memory
+    fn sprites_ui(&mut self, ui: &mut Ui) {
+        let memory = self.bus.memory();
+
+        ui.horizontal(|ui| {
+            ui.label("Address");
+            ui.add(
+                egui::DragValue::new(&mut self.sprite.addr)
+                    .range(0..=0xFFF)
+                    .hexadecimal(3, false, true),
+            );
+            ui.add(
+                egui::Slider::new(&mut self.sprite.rows, 1..=MAX_ROWS)
+                    .text("Rows"),
+            );
+        });
+
+        let size = egui::vec2(
+            8.0 * SPRITE_PIXEL,
+            self.sprite.rows as f32 * SPRITE_PIXEL,
+        );
+        let (response, painter) =
+            ui.allocate_painter(size, egui::Sense::hover());
+        painter.rect_filled(response.rect, 0.0, self.background);
+        for (y, row) in self.sprite.pixels(memory).iter().enumerate() {
+            for (x, _) in row.iter().enumerate().filter(|(_, &lit)| lit) {
+                let min = response.rect.min
+                    + egui::vec2(x as f32, y as f32) * SPRITE_PIXEL;
+                painter.rect_filled(
+                    egui::Rect::from_min_size(
+                        min,
+                        egui::Vec2::splat(SPRITE_PIXEL),
+                    ),
+                    0.0,
+                    self.foreground,
+                );
+            }
+        }
+
+        ui.separator();
+        let found = sprites::find(memory, None);
+        ui.label(format!("{} sprites found", found.len()));
+        egui::ScrollArea::vertical()
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                for sprite in found {
+                    let text = RichText::new(format!(
+                        "{:03X}  {} rows",
+                        sprite.addr, sprite.rows
+                    ))
+                    .monospace();
+                    if ui
+                        .selectable_label(sprite == self.sprite, text)
+                        .clicked()
+                    {
+                        self.sprite = sprite;
+                    }
+                }
+            });
+    }
+
+    fn settings_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let label = match self.running {
+                true => "Pause",
+                false => "Continue",
+            };
+            if ui.button(label).clicked() {
+                self.running ^= true;
+            }
+            if ui.button("Reset").clicked() {
+                self.reset();
+            }
+        });
+
+        ui.separator();
+        ui.add(
+            egui::Slider::new(&mut self.cpu_frequency, 100.0..=2000.0)
+                .text("CPU Hz"),
+        );
+
+        let mut policy = self.cpu.key_wait_policy();
+        egui::ComboBox::from_label("FX0A key")
+            .selected_text(format!("{:?}", policy))
+            .show_ui(ui, |ui| {
+                for value in [
+                    KeyWaitPolicy::Lowest,
+                    KeyWaitPolicy::MostRecentlyPressed,
+                    KeyWaitPolicy::FirstReleased,
+                ] {
+                    ui.selectable_value(
+                        &mut policy,
+                        value,
+                        format!("{:?}", value),
+                    );
+                }
+            });
+        if policy != self.cpu.key_wait_policy() {
+            self.cpu.set_key_wait_policy(policy);
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.color_edit_button_srgba(&mut self.foreground);
+            ui.label("Foreground");
+        });
+        ui.horizontal(|ui| {
+            ui.color_edit_button_srgba(&mut self.background);
+            ui.label("Background");
+        });
+    }
+}
+
+impl TabViewer for Emulator {
+    type Tab = Panel;
+
+    fn id(&mut self, tab: &mut Self::Tab) -> egui::Id {
+        egui::Id::new(*tab)
+    }
+
+    fn title(&mut self, tab: &mut Self::Tab) -> WidgetText {
+        match tab {
+            Panel::Display => "Display",
+            Panel::Registers => "Registers",
+            Panel::Disassembly => "Disassembly",
+            Panel::Keypad => "Keypad",
+            Panel::Memory => "Memory",
+            Panel::Sprites => "Sprites",
+            Panel::Settings => "Settings",
+        }
+        .into()
+    }
+
+    fn ui(&mut self, ui: &mut Ui, tab: &mut Self::Tab) {
+        match tab {
+            Panel::Display => self.display_ui(ui),
+            Panel::Registers => self.registers_ui(ui),
+            Panel::Disassembly => self.disassembly_ui(ui),
+            Panel::Keypad => self.keypad_ui(ui),
+            Panel::Memory => self.memory_ui(ui),
+            Panel::Sprites => self.sprites_ui(ui),
+            Panel::Settings => self.settings_ui(ui),
+        }
+    }
+}
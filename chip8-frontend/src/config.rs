@@ -0,0 +1,521 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chip8::{
+    bus::KEYPAD_SIZE,
+    cpu::{KeyWaitPolicy, Quirks},
+    machine::CPU_FREQUENCY,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Parse(e) => write!(f, "{}", e),
+            ConfigError::Serialize(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+/// Colors as `#rrggbb` or `#rrggbbaa`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Palette {
+    pub background: String,
+    pub foreground: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Audio {
+    pub enabled: bool,
+    /// From 0 to 1
+    pub volume: f64,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            volume: 0.5,
+        }
+    }
+}
+
+/// Emulator commands that can be bound to an input
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub enum Hotkey {
+    Open,
+    Pause,
+    Reset,
+    Step,
+    AdvanceFrame,
+    Turbo,
+    SaveState,
+    LoadState,
+    NextSlot,
+    PreviousSlot,
+    Fullscreen,
+    ScaleUp,
+    ScaleDown,
+}
+
+/// Names of the hotkeys in the configuration, and their labels
+pub const HOTKEYS: [(Hotkey, &str, &str); 13] = [
+    (Hotkey::Open, "open", "Open"),
+    (Hotkey::Pause, "pause", "Pause"),
+    (Hotkey::Reset, "reset", "Reset"),
+    (Hotkey::Step, "step", "Step Instruction"),
+    (Hotkey::AdvanceFrame, "advance-frame", "Advance Frame"),
+    (Hotkey::Turbo, "turbo", "Turbo"),
+    (Hotkey::SaveState, "save-state", "Save State"),
+    (Hotkey::LoadState, "load-state", "Load State"),
+    (Hotkey::NextSlot, "next-slot", "Next Slot"),
+    (Hotkey::PreviousSlot, "previous-slot", "Previous Slot"),
+    (Hotkey::Fullscreen, "fullscreen", "Fullscreen"),
+    (Hotkey::ScaleUp, "scale-up", "Scale Up"),
+    (Hotkey::ScaleDown, "scale-down", "Scale Down"),
+];
+
+/// Default keyboard shortcut of each hotkey
+const SHORTCUTS: [(Hotkey, &str); 13] = [
+    (Hotkey::Open, "Ctrl+O"),
+    (Hotkey::Pause, "Space"),
+    (Hotkey::Reset, "Ctrl+R"),
+    (Hotkey::Step, "F10"),
+    (Hotkey::AdvanceFrame, "Shift+F10"),
+    (Hotkey::Turbo, "Tab"),
+    (Hotkey::SaveState, "F5"),
+    (Hotkey::LoadState, "F7"),
+    (Hotkey::NextSlot, "F6"),
+    (Hotkey::PreviousSlot, "Shift+F6"),
+    (Hotkey::Fullscreen, "F11"),
+    (Hotkey::ScaleUp, "Plus"),
+    (Hotkey::ScaleDown, "Minus"),
+];
+
+impl Hotkey {
+    pub fn name(self) -> &'static str {
+        HOTKEYS
+            .iter()
+            .find(|(hotkey, _, _)| *hotkey == self)
+            .unwrap()
+            .1
+    }
+
+    pub fn label(self) -> &'static str {
+        HOTKEYS
+            .iter()
+            .find(|(hotkey, _, _)| *hotkey == self)
+            .unwrap()
+            .2
+    }
+}
+
+impl Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        HOTKEYS
+            .iter()
+            .find(|(_, name, _)| *name == s)
+            .map(|&(hotkey, _, _)| hotkey)
+            .ok_or_else(|| format!("unknown hotkey {}", s))
+    }
+}
+
+impl TryFrom<String> for Hotkey {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Hotkey> for String {
+    fn from(hotkey: Hotkey) -> Self {
+        hotkey.to_string()
+    }
+}
+
+/// What an input does: press a keypad key or run a hotkey
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Binding {
+    Keypad(u8),
+    Hotkey(Hotkey),
+}
+
+impl Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Keypad(key) => write!(f, "{:X}", key),
+            Binding::Hotkey(hotkey) => write!(f, "{}", hotkey.name()),
+        }
+    }
+}
+
+/// A keypad key in hexadecimal, or the name of a hotkey
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(hotkey) = s.parse() {
+            return Ok(Binding::Hotkey(hotkey));
+        }
+
+        match u8::from_str_radix(s, 16) {
+            Ok(key) if s.len() == 1 => Ok(Binding::Keypad(key)),
+            _ => Err(format!("unknown binding {}", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for Binding {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Binding> for String {
+    fn from(binding: Binding) -> Self {
+        binding.to_string()
+    }
+}
+
+/// Bindings of a gamepad
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Gamepad {
+    /// Shown to the user, the gamepads are told apart by their id
+    pub name: String,
+    /// By input: a button name like `South` or `DPadUp`, or an axis name
+    /// followed by its direction like `LeftStickX+`
+    pub bindings: BTreeMap<String, Binding>,
+}
+
+impl Gamepad {
+    /// The d-pad moves on 5 7 8 9 and the south button is 6, which suits
+    /// most games
+    pub fn new(name: impl Into<String>) -> Self {
+        let bindings = [
+            ("DPadUp", 0x5),
+            ("DPadDown", 0x8),
+            ("DPadLeft", 0x7),
+            ("DPadRight", 0x9),
+            ("South", 0x6),
+        ]
+        .map(|(input, key)| (input.to_string(), Binding::Keypad(key)));
+
+        Self {
+            name: name.into(),
+            bindings: BTreeMap::from(bindings),
+        }
+    }
+}
+
+/// Settings shared by the frontends, missing fields take their default
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Instructions per second
+    pub cpu_frequency: f64,
+    #[serde(with = "key_wait_policy")]
+    pub key_wait_policy: KeyWaitPolicy,
+    /// DXYN waits for the next frame like on the COSMAC VIP, at most 60
+    /// sprites are drawn per second
+    pub display_wait: bool,
+    /// Each frontend has its own colors when there is none
+    pub palette: Option<Palette>,
+    /// Size of a chip8 pixel on the screen in a window, from 1 to 16
+    pub scale: u32,
+    /// Start in fullscreen, it follows the last toggle
+    pub fullscreen: bool,
+    /// Size of the window after it was last resized, it takes the place of
+    /// the one given by `scale`
+    pub window_size: Option<(u32, u32)>,
+    pub audio: Audio,
+    /// Tell the screen readers when the emulator pauses, waits for a key or
+    /// a memory watch rule fires
+    pub announce: bool,
+    /// Keyboard key of each keypad key from 0x0 to 0xF, named after their
+    /// position on a qwerty keyboard like `KeyboardEvent.code`
+    pub keymap: [String; KEYPAD_SIZE],
+    /// Bindings by gamepad id, the gamepads missing here use the defaults
+    /// of `Gamepad::new`
+    pub gamepads: BTreeMap<String, Gamepad>,
+    /// Keyboard shortcut of each hotkey: `Ctrl+`, `Shift+` or `Alt+`
+    /// followed by a key like `O`, `F5`, `Space`, `Plus` or `Minus`, an
+    /// empty one disables the hotkey
+    pub shortcuts: BTreeMap<Hotkey, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let keymap = [
+            "KeyX", "Digit1", "Digit2", "Digit3", "KeyQ", "KeyW", "KeyE",
+            "KeyA", "KeyS", "KeyD", "KeyZ", "KeyC", "Digit4", "KeyR", "KeyF",
+            "KeyV",
+        ];
+
+        Self {
+            cpu_frequency: CPU_FREQUENCY,
+            key_wait_policy: KeyWaitPolicy::default(),
+            display_wait: false,
+            palette: None,
+            scale: 8,
+            fullscreen: false,
+            window_size: None,
+            audio: Audio::default(),
+            announce: false,
+            keymap: keymap.map(String::from),
+            gamepads: BTreeMap::new(),
+            shortcuts: SHORTCUTS
+                .iter()
+                .map(|&(hotkey, shortcut)| (hotkey, shortcut.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl Config {
+    /// The quirks of the cpu chosen here, the others are the defaults
+    pub fn quirks(&self) -> Quirks {
+        Quirks {
+            display_wait: self.display_wait,
+            ..Quirks::default()
+        }
+    }
+
+    /// The shortcut of `hotkey`, its default one when it is missing from
+    /// the configuration, none when it is disabled
+    pub fn shortcut(&self, hotkey: Hotkey) -> Option<&str> {
+        let shortcut = match self.shortcuts.get(&hotkey) {
+            Some(shortcut) => shortcut.as_str(),
+            None => SHORTCUTS
+                .iter()
+                .find(|(known, _)| *known == hotkey)
+                .map_or("", |(_, shortcut)| shortcut),
+        };
+
+        Some(shortcut).filter(|shortcut| !shortcut.is_empty())
+    }
+
+    /// `chip8/config.toml` in the user configuration directory
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("chip8").join("config.toml"))
+    }
+
+    /// The user configuration, or the default one when it is missing or
+    /// invalid
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+
+        match Self::load_from(&path) {
+            Ok(config) => config,
+            Err(ConfigError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                Self::default()
+            }
+            Err(e) => {
+                warn!("{}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
+        toml::from_str(&fs::read_to_string(path)?).map_err(ConfigError::Parse)
+    }
+
+    /// Write the user configuration
+    pub fn save(&self) -> Result<(), ConfigError> {
+        match Self::path() {
+            Some(path) => self.save_to(&path),
+            None => Err(ConfigError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no configuration directory",
+            ))),
+        }
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), ConfigError> {
+        let text = toml::to_string(self).map_err(ConfigError::Serialize)?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)?;
+
+        Ok(())
+    }
+}
+
+/// Names of the policies in the configuration
+pub const KEY_WAIT_POLICIES: [(KeyWaitPolicy, &str); 3] = [
+    (KeyWaitPolicy::Lowest, "lowest"),
+    (KeyWaitPolicy::MostRecentlyPressed, "most-recently-pressed"),
+    (KeyWaitPolicy::FirstReleased, "first-released"),
+];
+
+mod key_wait_policy {
+    use chip8::cpu::KeyWaitPolicy;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use super::KEY_WAIT_POLICIES;
+
+    pub fn serialize<S: Serializer>(
+        policy: &KeyWaitPolicy,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let (_, name) = KEY_WAIT_POLICIES
+            .iter()
+            .find(|(known, _)| known == policy)
+            .expect("every policy has a name");
+
+        serializer.serialize_str(name)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<KeyWaitPolicy, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        KEY_WAIT_POLICIES
+            .iter()
+            .find(|(_, known)| *known == name)
+            .map(|&(policy, _)| policy)
+            .ok_or_else(|| D::Error::custom(format!("unknown policy {}", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let config = Config {
+            cpu_frequency: 1000.0,
+            key_wait_policy: KeyWaitPolicy::FirstReleased,
+            display_wait: true,
+            palette: Some(Palette {
+                background: "#000000".into(),
+                foreground: "#ffb000".into(),
+            }),
+            ..Config::default()
+        };
+
+        let text = toml::to_string(&config).unwrap();
+        assert!(text.contains("key_wait_policy = \"first-released\""));
+        assert_eq!(toml::from_str::<Config>(&text).unwrap(), config);
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config: Config =
+            toml::from_str("cpu_frequency = 700.0\n[audio]\nvolume = 0.2")
+                .unwrap();
+
+        assert_eq!(config.cpu_frequency, 700.0);
+        assert_eq!(config.key_wait_policy, KeyWaitPolicy::Lowest);
+        assert_eq!(config.quirks(), Quirks::default());
+        assert!(!config.fullscreen);
+        assert_eq!(config.window_size, None);
+        assert!(config.audio.enabled);
+        assert_eq!(config.audio.volume, 0.2);
+        assert!(!config.announce);
+        assert_eq!(config.keymap, Config::default().keymap);
+
+        assert!(toml::from_str::<Config>("key_wait_policy = \"any\"").is_err());
+    }
+
+    #[test]
+    fn test_gamepads() {
+        let mut gamepad = Gamepad::new("pad");
+        gamepad
+            .bindings
+            .insert("Start".into(), Binding::Hotkey(Hotkey::Pause));
+        gamepad
+            .bindings
+            .insert("LeftStickX-".into(), Binding::Keypad(0xA));
+        let config = Config {
+            gamepads: BTreeMap::from([("0123".into(), gamepad)]),
+            ..Config::default()
+        };
+
+        let text = toml::to_string(&config).unwrap();
+        assert!(text.contains("Start = \"pause\""));
+        assert!(text.contains("LeftStickX- = \"A\""));
+        assert_eq!(toml::from_str::<Config>(&text).unwrap(), config);
+
+        assert_eq!("F".parse(), Ok(Binding::Keypad(0xF)));
+        assert!("10".parse::<Binding>().is_err());
+        assert!("jump".parse::<Binding>().is_err());
+    }
+
+    #[test]
+    fn test_shortcuts() {
+        let config: Config = toml::from_str(
+            "[shortcuts]\nreset = \"Ctrl+Shift+R\"\npause = \"\"",
+        )
+        .unwrap();
+
+        assert_eq!(config.shortcut(Hotkey::Reset), Some("Ctrl+Shift+R"));
+        assert_eq!(config.shortcut(Hotkey::Pause), None);
+        assert_eq!(config.shortcut(Hotkey::SaveState), Some("F5"));
+
+        let text = toml::to_string(&Config::default()).unwrap();
+        assert!(text.contains("scale-up = \"Plus\""));
+        assert!(toml::from_str::<Config>("[shortcuts]\njump = \"J\"").is_err());
+    }
+
+    #[test]
+    fn test_save_to() {
+        let path = std::env::temp_dir()
+            .join(format!("chip8-config-{}", std::process::id()))
+            .join("config.toml");
+        let config = Config {
+            cpu_frequency: 250.0,
+            ..Config::default()
+        };
+
+        config.save_to(&path).unwrap();
+        assert_eq!(Config::load_from(&path).unwrap(), config);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
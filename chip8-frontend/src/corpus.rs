@@ -0,0 +1,216 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+};
+
+use chip8::{
+    machine::{Machine, CPU_FREQUENCY},
+    rom::Rom,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ConfigError,
+    script::{self, Script, Stop},
+};
+
+/// Seed of the random numbers, the same screen is drawn at each run
+const SEED: u64 = 1;
+
+/// What a test rom is expected to end with, all optional
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Expected {
+    /// `Bus::frame_hash` of the screen once the rom halted, in hex
+    pub hash: Option<String>,
+    /// Frames given to the rom to halt, in place of the runner's
+    pub frames: Option<u64>,
+    /// Instructions per second
+    pub speed: Option<f64>,
+    /// Keypad script, in the format of `Script`
+    pub input: Option<String>,
+}
+
+/// Expected results of the test roms of a directory, by their path in it
+///
+/// ```toml
+/// [roms."corax+.ch8"]
+/// hash = "0123456789abcdef"
+///
+/// [roms."keypad.ch8"]
+/// hash = "fedcba9876543210"
+/// frames = 120
+/// input = "10 5\n20 -"
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Manifest {
+    pub roms: BTreeMap<String, Expected>,
+}
+
+impl Manifest {
+    pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
+        toml::from_str(&fs::read_to_string(path)?).map_err(ConfigError::Parse)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), ConfigError> {
+        let text = toml::to_string(self).map_err(ConfigError::Serialize)?;
+        fs::write(path, text)?;
+
+        Ok(())
+    }
+}
+
+/// How a test rom ended, with the hash of its screen
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    /// The screen isn't the expected one
+    Fail(u64),
+    /// The rom ran all its frames without halting
+    Timeout(u64),
+    /// The manifest has no hash for the rom
+    Unknown(u64),
+    /// The emulator panicked
+    Crash,
+}
+
+impl Outcome {
+    /// A failure of the emulator, unknown roms aren't counted
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            Outcome::Fail(_) | Outcome::Timeout(_) | Outcome::Crash
+        )
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Pass => write!(f, "pass"),
+            Outcome::Fail(hash) => write!(f, "FAIL, screen {:016x}", hash),
+            Outcome::Timeout(hash) => {
+                write!(f, "TIMEOUT, screen {:016x}", hash)
+            }
+            Outcome::Unknown(hash) => {
+                write!(f, "unknown, screen {:016x}", hash)
+            }
+            Outcome::Crash => write!(f, "CRASH"),
+        }
+    }
+}
+
+/// Run the rom `data` until it halts or waits for a key after its input,
+/// within `frames` frames unless `expected` says otherwise
+///
+/// Returns the outcome and the frames run, the error is an invalid input.
+pub fn run(
+    data: Vec<u8>,
+    expected: &Expected,
+    frames: u64,
+) -> Result<(Outcome, u64), String> {
+    let mut script = match &expected.input {
+        Some(input) => Script::parse(input)?,
+        None => Script::default(),
+    };
+    let mut machine = Machine::new(Rom::from_bytes(data));
+    machine.set_cpu_frequency(expected.speed.unwrap_or(CPU_FREQUENCY));
+    machine.cpu_mut().set_seed(SEED);
+
+    let frames = expected.frames.unwrap_or(frames);
+    // a panic is a bug of the emulator, kept as the outcome of the rom
+    let Ok((ran, stop)) = panic::catch_unwind(AssertUnwindSafe(|| {
+        script::run(&mut machine, &mut script, frames, true)
+    })) else {
+        return Ok((Outcome::Crash, 0));
+    };
+
+    let hash = machine.bus().frame_hash();
+    let outcome = match (&expected.hash, stop) {
+        (_, Stop::FrameLimit) => Outcome::Timeout(hash),
+        (None, _) => Outcome::Unknown(hash),
+        (Some(expected), _) => match u64::from_str_radix(expected, 16) {
+            Ok(expected) if expected == hash => Outcome::Pass,
+            _ => Outcome::Fail(hash),
+        },
+    };
+
+    Ok((outcome, ran))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 00E0: clear, A000: I = font 0, D005: draw it, 1206: halt
+    const ROM: [u8; 8] = [0x00, 0xE0, 0xA0, 0x00, 0xD0, 0x05, 0x12, 0x06];
+
+    fn screen() -> String {
+        let (outcome, _) = run(ROM.to_vec(), &Expected::default(), 60).unwrap();
+        match outcome {
+            Outcome::Unknown(hash) => format!("{:016x}", hash),
+            outcome => panic!("{}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let expected = Expected {
+            hash: Some(screen()),
+            ..Expected::default()
+        };
+        let (outcome, frames) = run(ROM.to_vec(), &expected, 60).unwrap();
+        assert_eq!(outcome, Outcome::Pass);
+        assert_eq!(frames, 1);
+        assert!(!outcome.is_failure());
+
+        let wrong = Expected {
+            hash: Some("0".into()),
+            ..Expected::default()
+        };
+        let (outcome, _) = run(ROM.to_vec(), &wrong, 60).unwrap();
+        assert!(matches!(outcome, Outcome::Fail(_)));
+        assert!(outcome.is_failure());
+
+        // 1200: a loop that never ends, 7001: V0 += 1
+        let (outcome, frames) =
+            run(vec![0x70, 0x01, 0x12, 0x00], &expected, 10).unwrap();
+        assert!(matches!(outcome, Outcome::Timeout(_)));
+        assert_eq!(frames, 10);
+
+        let invalid = Expected {
+            input: Some("x".into()),
+            ..Expected::default()
+        };
+        assert!(run(ROM.to_vec(), &invalid, 60).is_err());
+    }
+
+    #[test]
+    fn test_manifest() {
+        let manifest: Manifest = toml::from_str(
+            "
+            [roms.\"corax+.ch8\"]
+            hash = \"0123456789abcdef\"
+
+            [roms.\"keypad/test.ch8\"]
+            frames = 120
+            input = \"10 5\"
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(manifest.roms.len(), 2);
+        assert_eq!(
+            manifest.roms["corax+.ch8"].hash.as_deref(),
+            Some("0123456789abcdef")
+        );
+        assert_eq!(manifest.roms["keypad/test.ch8"].frames, Some(120));
+
+        let text = toml::to_string(&manifest).unwrap();
+        assert_eq!(toml::from_str::<Manifest>(&text).unwrap(), manifest);
+    }
+}
@@ -0,0 +1,109 @@
+use std::mem;
+
+use chip8::machine::Machine;
+
+/// What an address of the memory was used for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Usage {
+    /// Part of an executed instruction
+    Code,
+    /// Drawn by DXYN
+    Sprite,
+    /// Read or written through I by FX33, FX55 or FX65
+    Data,
+}
+
+/// Addresses used by a rom while it ran, to tell its code from its data
+pub struct Coverage {
+    usage: Vec<Option<Usage>>,
+}
+
+impl Coverage {
+    pub fn new(machine: &Machine) -> Self {
+        Self {
+            usage: vec![None; machine.bus().memory().len()],
+        }
+    }
+
+    /// None when the rom never used `addr`
+    pub fn usage(&self, addr: u16) -> Option<Usage> {
+        self.usage.get(addr as usize).copied().flatten()
+    }
+
+    /// Record the instruction `machine` is about to execute
+    pub fn record(&mut self, machine: &Machine) {
+        let cpu = machine.cpu();
+        // nothing executes until a key is pressed
+        if cpu.key_await().is_some() {
+            return;
+        }
+
+        let pc = cpu.pc() as usize;
+        let memory = machine.bus().memory();
+        let opcode = match memory.get(pc..pc + 2) {
+            Some(&[high, low]) => u16::from_be_bytes([high, low]),
+            _ => return,
+        };
+        self.usage[pc] = Some(Usage::Code);
+        self.usage[pc + 1] = Some(Usage::Code);
+
+        let i = cpu.index() as usize;
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let (usage, len) = match opcode & 0xF0FF {
+            0xF033 => (Usage::Data, 3),
+            0xF055 | 0xF065 => (Usage::Data, x + 1),
+            // DXY0 draws 16 rows of 2 bytes
+            _ if opcode & 0xF000 == 0xD000 => match opcode & 0x000F {
+                0 => (Usage::Sprite, 32),
+                n => (Usage::Sprite, n as usize),
+            },
+            _ => return,
+        };
+        // the code read as data is still code
+        for addr in i..(i + len).min(self.usage.len()) {
+            if self.usage[addr] != Some(Usage::Code) {
+                self.usage[addr] = Some(usage);
+            }
+        }
+    }
+
+    /// Run a frame of `machine`, recording each of its instructions
+    pub fn run_frame(&mut self, machine: &mut Machine) {
+        loop {
+            self.record(machine);
+
+            // stopped again before the next instruction
+            let mut first = true;
+            if machine.run_frame_until(|_| !mem::replace(&mut first, false)) {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8::rom::Rom;
+
+    use super::*;
+
+    #[test]
+    fn test_run_frame() {
+        // A208: I = 208, D001: draw 1 row, 1204: loop, 0000, F0: sprite
+        let rom = Rom::from_bytes(vec![
+            0xA2, 0x08, 0xD0, 0x01, 0x12, 0x04, 0x00, 0x00, 0xF0, 0x00,
+        ]);
+        let mut machine = Machine::new(rom);
+        let mut coverage = Coverage::new(&machine);
+
+        coverage.run_frame(&mut machine);
+
+        for addr in 0x200..0x206 {
+            assert_eq!(coverage.usage(addr), Some(Usage::Code), "{:X}", addr);
+        }
+        assert_eq!(coverage.usage(0x206), None);
+        assert_eq!(coverage.usage(0x208), Some(Usage::Sprite));
+        assert_eq!(coverage.usage(0x209), None);
+        assert_eq!(coverage.usage(0x1000), None);
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    error::Error,
+    fmt::{self, Display},
+};
+
+use chip8::bus::KEYPAD_SIZE;
+
+/// Keys waiting to be pressed when the votes are off
+const QUEUE_SIZE: usize = 8;
+
+/// How the commands of the viewers are turned into keys, in frames
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CrowdConfig {
+    /// Time a chosen key is held
+    pub hold: u64,
+    /// Time a viewer waits between two commands
+    pub cooldown: u64,
+    /// Time the votes are counted before the most voted key is pressed, 0
+    /// presses the keys in the order they came
+    pub window: u64,
+}
+
+impl Default for CrowdConfig {
+    fn default() -> Self {
+        Self {
+            hold: 6,
+            cooldown: 30,
+            window: 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CrowdError {
+    /// Not a hex key
+    Invalid(String),
+    /// The viewer has to wait this many frames
+    Cooldown(u64),
+    /// Too many keys are waiting already
+    Full,
+}
+
+impl Display for CrowdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrowdError::Invalid(text) => write!(f, "invalid key {:?}", text),
+            CrowdError::Cooldown(frames) => {
+                write!(f, "wait {} more frames", frames)
+            }
+            CrowdError::Full => write!(f, "too many keys waiting"),
+        }
+    }
+}
+
+impl Error for CrowdError {}
+
+/// The keypad of a rom played by many viewers at once, each sending the
+/// keys they want pressed, for crowd controlled streams
+pub struct Crowd {
+    config: CrowdConfig,
+    frame: u64,
+    /// Frame of the last command of each viewer
+    last: HashMap<String, u64>,
+    /// Last vote of each viewer in the window
+    votes: BTreeMap<String, u8>,
+    queue: VecDeque<u8>,
+    /// Key held and the frame it is released at
+    held: Option<(u8, u64)>,
+}
+
+impl Crowd {
+    pub fn new(config: CrowdConfig) -> Self {
+        Self {
+            config,
+            frame: 0,
+            last: HashMap::new(),
+            votes: BTreeMap::new(),
+            queue: VecDeque::new(),
+            held: None,
+        }
+    }
+
+    pub fn config(&self) -> CrowdConfig {
+        self.config
+    }
+
+    /// A key in hex sent by `viewer`, a vote when the votes are on
+    pub fn command(
+        &mut self,
+        viewer: &str,
+        text: &str,
+    ) -> Result<(), CrowdError> {
+        let key = match u8::from_str_radix(text.trim(), 16) {
+            Ok(key) if (key as usize) < KEYPAD_SIZE => key,
+            _ => return Err(CrowdError::Invalid(text.to_string())),
+        };
+
+        if let Some(&last) = self.last.get(viewer) {
+            let ready = last + self.config.cooldown;
+            if self.frame < ready {
+                return Err(CrowdError::Cooldown(ready - self.frame));
+            }
+        }
+
+        match self.config.window {
+            0 if self.queue.len() >= QUEUE_SIZE => {
+                return Err(CrowdError::Full)
+            }
+            0 => self.queue.push_back(key),
+            _ => {
+                self.votes.insert(viewer.to_string(), key);
+            }
+        }
+        self.last.insert(viewer.to_string(), self.frame);
+
+        Ok(())
+    }
+
+    /// Votes for each key in the current window
+    pub fn tally(&self) -> [usize; KEYPAD_SIZE] {
+        let mut tally = [0; KEYPAD_SIZE];
+        for &key in self.votes.values() {
+            tally[key as usize] += 1;
+        }
+        tally
+    }
+
+    /// Advance a frame, returns the keys to hold during it
+    pub fn tick(&mut self) -> [bool; KEYPAD_SIZE] {
+        let window = self.config.window;
+        if window > 0 && self.frame % window == window - 1 {
+            let tally = self.tally();
+            // the lowest key wins a tie
+            let winner = (0..KEYPAD_SIZE)
+                .filter(|&key| tally[key] > 0)
+                .max_by_key(|&key| (tally[key], usize::MAX - key));
+            if let Some(key) = winner {
+                self.queue.clear();
+                self.queue.push_back(key as u8);
+            }
+            self.votes.clear();
+        }
+
+        if matches!(self.held, Some((_, until)) if until <= self.frame) {
+            self.held = None;
+        }
+        if self.held.is_none() {
+            if let Some(key) = self.queue.pop_front() {
+                self.held = Some((key, self.frame + self.config.hold.max(1)));
+            }
+        }
+
+        // now and then, forget the viewers who can send a command again
+        if self.frame & 0x3FF == 0 {
+            let (frame, cooldown) = (self.frame, self.config.cooldown);
+            self.last.retain(|_, &mut last| last + cooldown > frame);
+        }
+        self.frame += 1;
+
+        let mut keys = [false; KEYPAD_SIZE];
+        if let Some((key, _)) = self.held {
+            keys[key as usize] = true;
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(keys: [bool; KEYPAD_SIZE]) -> Option<usize> {
+        keys.iter().position(|&held| held)
+    }
+
+    #[test]
+    fn test_queue() {
+        let mut crowd = Crowd::new(CrowdConfig {
+            hold: 2,
+            cooldown: 10,
+            window: 0,
+        });
+
+        assert_eq!(crowd.command("a", "5"), Ok(()));
+        assert_eq!(crowd.command("b", "c"), Ok(()));
+        assert_eq!(crowd.command("a", "6"), Err(CrowdError::Cooldown(10)));
+        assert!(matches!(
+            crowd.command("c", "x"),
+            Err(CrowdError::Invalid(_))
+        ));
+        assert!(crowd.command("c", "10").is_err());
+
+        let keys: Vec<_> = (0..5).map(|_| held(crowd.tick())).collect();
+        assert_eq!(keys, [Some(5), Some(5), Some(0xC), Some(0xC), None]);
+
+        for _ in 0..5 {
+            crowd.tick();
+        }
+        assert_eq!(crowd.command("a", "6"), Ok(()));
+    }
+
+    #[test]
+    fn test_queue_full() {
+        let mut crowd = Crowd::new(CrowdConfig::default());
+        for viewer in 0..QUEUE_SIZE {
+            crowd.command(&viewer.to_string(), "1").unwrap();
+        }
+        assert_eq!(crowd.command("late", "1"), Err(CrowdError::Full));
+    }
+
+    #[test]
+    fn test_votes() {
+        let mut crowd = Crowd::new(CrowdConfig {
+            hold: 3,
+            cooldown: 0,
+            window: 4,
+        });
+
+        crowd.command("a", "2").unwrap();
+        crowd.command("b", "7").unwrap();
+        crowd.command("c", "7").unwrap();
+        // a changes their mind
+        crowd.command("a", "7").unwrap();
+        crowd.command("d", "2").unwrap();
+        assert_eq!(crowd.tally()[0x7], 3);
+
+        let keys: Vec<_> = (0..8).map(|_| held(crowd.tick())).collect();
+        assert_eq!(
+            keys,
+            [None, None, None, Some(7), Some(7), Some(7), None, None]
+        );
+        assert_eq!(crowd.tally(), [0; KEYPAD_SIZE]);
+
+        // a tie goes to the lowest key
+        crowd.command("a", "9").unwrap();
+        crowd.command("b", "3").unwrap();
+        let keys: Vec<_> = (0..4).map(|_| held(crowd.tick())).collect();
+        assert_eq!(keys[3], Some(3));
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ConfigError;
+
+/// The `programs.json` of the community database
+pub const COMMUNITY_URL: &str = "https://raw.githubusercontent.com/\
+                                 chip-8/chip-8-database/master/database/\
+                                 programs.json";
+
+/// What is known of a rom, all but its title are optional
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RomEntry {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub year: Option<u32>,
+    /// Like `CHIP-8`, or an id of the community database like
+    /// `originalChip8`
+    pub platform: Option<String>,
+    /// Instructions per second it is meant to run at
+    pub speed: Option<f64>,
+    pub description: Option<String>,
+}
+
+/// Known roms by the SHA-1 of their file, `RomInfo::sha1`
+///
+/// The user entries of `chip8/roms.toml` in the configuration directory
+/// take the place of the ones downloaded from the community database to
+/// `chip8/roms.toml` in the data directory.
+///
+/// ```toml
+/// [roms.0123456789abcdef0123456789abcdef01234567]
+/// title = "Pong"
+/// authors = ["Paul Vervalin"]
+/// year = 1990
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Database {
+    roms: BTreeMap<String, RomEntry>,
+}
+
+impl Database {
+    /// `chip8/roms.toml` in the user configuration directory
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("chip8").join("roms.toml"))
+    }
+
+    /// `chip8/roms.toml` in the user data directory
+    pub fn community_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("chip8").join("roms.toml"))
+    }
+
+    /// The community database and the user entries, the missing or invalid
+    /// ones are left out
+    pub fn load() -> Self {
+        let mut database = Self::default();
+        let paths = [Self::community_path(), Self::path()];
+        for path in paths.into_iter().flatten() {
+            match Self::load_from(&path) {
+                Ok(loaded) => database.roms.extend(loaded.roms),
+                Err(ConfigError::Io(e))
+                    if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => warn!("{}: {}", path.display(), e),
+            }
+        }
+
+        database
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
+        toml::from_str(&fs::read_to_string(path)?).map_err(ConfigError::Parse)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), ConfigError> {
+        let text = toml::to_string(self).map_err(ConfigError::Serialize)?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)?;
+
+        Ok(())
+    }
+
+    /// Read the `programs.json` of the community database, each program
+    /// giving an entry to each of its roms
+    pub fn from_community(json: &str) -> Result<Self, String> {
+        let programs: Vec<community::Program> =
+            serde_json::from_str(json).map_err(|e| e.to_string())?;
+
+        let mut roms = BTreeMap::new();
+        for program in programs {
+            // the first year of a date like `1990-06`
+            let year = program
+                .release
+                .as_deref()
+                .and_then(|release| release.get(..4))
+                .and_then(|year| year.parse().ok());
+
+            for (sha1, rom) in program.roms {
+                let entry = RomEntry {
+                    title: program.title.clone(),
+                    authors: program.authors.clone(),
+                    year,
+                    platform: rom.platforms.first().cloned(),
+                    // instructions per frame
+                    speed: rom.tickrate.map(|tickrate| tickrate * 60.0),
+                    description: program.description.clone(),
+                };
+                roms.insert(sha1.to_ascii_lowercase(), entry);
+            }
+        }
+
+        Ok(Self { roms })
+    }
+
+    /// The entry of the rom with this SHA-1, in any case
+    pub fn get(&self, sha1: &str) -> Option<&RomEntry> {
+        self.roms
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(sha1))
+            .map(|(_, entry)| entry)
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+}
+
+/// The parts of the community database format that are used
+mod community {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Program {
+        pub title: String,
+        pub description: Option<String>,
+        pub release: Option<String>,
+        #[serde(default)]
+        pub authors: Vec<String>,
+        /// By SHA-1
+        pub roms: BTreeMap<String, Rom>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Rom {
+        #[serde(default)]
+        pub platforms: Vec<String>,
+        pub tickrate: Option<f64>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let database: Database = toml::from_str(
+            "
+            [roms.A9993E364706816ABA3E25717850C26C9CD0D89D]
+            title = \"Pong\"
+            authors = [\"Paul Vervalin\"]
+            year = 1990
+
+            [roms.da39a3ee5e6b4b0d3255bfef95601890afd80709]
+            title = \"Empty\"
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(database.len(), 2);
+        let pong = database
+            .get("a9993e364706816aba3e25717850c26c9cd0d89d")
+            .unwrap();
+        assert_eq!(pong.title, "Pong");
+        assert_eq!(pong.authors, vec!["Paul Vervalin"]);
+        assert_eq!(pong.year, Some(1990));
+        assert_eq!(pong.speed, None);
+        assert_eq!(
+            database
+                .get("da39a3ee5e6b4b0d3255bfef95601890afd80709")
+                .map(|entry| entry.title.as_str()),
+            Some("Empty")
+        );
+        assert_eq!(database.get("0000"), None);
+    }
+
+    #[test]
+    fn test_from_community() {
+        let database = Database::from_community(
+            r#"[{
+                "title": "Pong",
+                "release": "1990-06",
+                "authors": ["Paul Vervalin"],
+                "roms": {
+                    "A9993E364706816ABA3E25717850C26C9CD0D89D": {
+                        "file": "pong.ch8",
+                        "platforms": ["originalChip8", "modernChip8"],
+                        "tickrate": 15
+                    },
+                    "da39a3ee5e6b4b0d3255bfef95601890afd80709": {
+                        "file": "pong2.ch8",
+                        "platforms": []
+                    }
+                }
+            }]"#,
+        )
+        .unwrap();
+
+        assert_eq!(database.len(), 2);
+        assert_eq!(
+            database.get("a9993e364706816aba3e25717850c26c9cd0d89d"),
+            Some(&RomEntry {
+                title: "Pong".to_string(),
+                authors: vec!["Paul Vervalin".to_string()],
+                year: Some(1990),
+                platform: Some("originalChip8".to_string()),
+                speed: Some(900.0),
+                description: None,
+            })
+        );
+
+        // written and read back as toml
+        let text = toml::to_string(&database).unwrap();
+        assert_eq!(toml::from_str::<Database>(&text).unwrap(), database);
+
+        assert!(Database::from_community("{}").is_err());
+    }
+}
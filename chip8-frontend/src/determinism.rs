@@ -0,0 +1,122 @@
+use std::{
+    fmt::{self, Display},
+    thread,
+};
+
+use chip8::{machine::Machine, rom::Rom};
+
+use crate::{
+    checksum,
+    script::{self, Script},
+};
+
+/// Seed of the random numbers of both runs
+const SEED: u64 = 1;
+
+/// First frame after which two runs of the same rom have different states
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Number of the frame, from 1
+    pub frame: u64,
+    /// `checksum` of the states
+    pub first: u32,
+    pub second: u32,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "frame {}: state {:08x} in the first run, {:08x} in the second",
+            self.frame, self.first, self.second
+        )
+    }
+}
+
+/// `checksum` of the whole state of `machine` after each of the `frames`
+/// frames, with the keys of `script`
+pub fn state_hashes(
+    machine: &mut Machine,
+    script: &mut Script,
+    frames: u64,
+) -> Vec<u32> {
+    let mut hashes = Vec::with_capacity(frames as usize);
+    script::run_with(machine, script, frames, false, |machine| {
+        machine.run_frame();
+        hashes.push(checksum(&machine.save_state()));
+    });
+
+    hashes
+}
+
+/// Run `rom` twice at once, on two threads, with the same seed and keys,
+/// and return the first frame where the states differ
+pub fn verify(
+    rom: &Rom,
+    speed: f64,
+    script: &Script,
+    frames: u64,
+) -> Option<Divergence> {
+    let run = || {
+        let mut machine = Machine::new(rom.clone());
+        machine.set_cpu_frequency(speed);
+        machine.cpu_mut().set_seed(SEED);
+
+        state_hashes(&mut machine, &mut script.clone(), frames)
+    };
+
+    let (first, second) = thread::scope(|scope| {
+        let first = scope.spawn(run);
+        (first.join(), run())
+    });
+    // the emulator panicked, go on with the panic here
+    let first = first.unwrap_or_else(|e| std::panic::resume_unwind(e));
+
+    first_divergence(&first, &second)
+}
+
+fn first_divergence(first: &[u32], second: &[u32]) -> Option<Divergence> {
+    first
+        .iter()
+        .zip(second)
+        .position(|(first, second)| first != second)
+        .map(|index| Divergence {
+            frame: index as u64 + 1,
+            first: first[index],
+            second: second[index],
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// C0FF: V0 = random, F015: delay = V0, F10A: wait for a key in V1,
+    /// 1200: loop
+    const ROM: [u8; 8] = [0xC0, 0xFF, 0xF0, 0x15, 0xF1, 0x0A, 0x12, 0x00];
+
+    #[test]
+    fn test_verify() {
+        let rom = Rom::from_bytes(ROM.to_vec());
+        let script = Script::parse("5 3\n8 -\n20 a").unwrap();
+
+        assert_eq!(verify(&rom, 500.0, &script, 60), None);
+    }
+
+    #[test]
+    fn test_divergence() {
+        let hashes = |seed| {
+            let mut machine = Machine::new(Rom::from_bytes(ROM.to_vec()));
+            machine.cpu_mut().set_seed(seed);
+            state_hashes(&mut machine, &mut Script::default(), 10)
+        };
+
+        let first = hashes(1);
+        assert_eq!(first.len(), 10);
+        assert_eq!(first_divergence(&first, &hashes(1)), None);
+
+        let divergence = first_divergence(&first, &hashes(2)).unwrap();
+        assert_eq!(divergence.frame, 1);
+        assert_eq!(divergence.first, first[0]);
+    }
+}
@@ -0,0 +1,351 @@
+use std::{fs::File, io::BufWriter};
+
+use chip8::{
+    bus::{DISPLAY_HEIGHT, DISPLAY_WIDTH, KEYPAD_SIZE},
+    cpu::Cpu,
+    machine::Machine,
+};
+
+use crate::Vram;
+
+const FOREGROUND: [u8; 3] = [69, 115, 13];
+const BACKGROUND: [u8; 3] = [124, 209, 21];
+const GAP: [u8; 3] = [0, 0, 0];
+/// Bytes on each row of a hexdump
+const HEXDUMP_ROW: usize = 16;
+/// Bytes of a memory difference shown
+const DIFF_BYTES: usize = 8;
+
+/// One line per row, `#` for lit pixels
+pub fn text(vram: &Vram) -> String {
+    let mut text = String::with_capacity((DISPLAY_WIDTH + 1) * DISPLAY_HEIGHT);
+
+    for h in 0..DISPLAY_HEIGHT {
+        text.extend(vram.iter().map(|column| match column[h] {
+            true => '#',
+            false => '.',
+        }));
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Share of the pixels lit or unlit on both screens, from 0 to 1
+pub fn similarity(a: &Vram, b: &Vram) -> f64 {
+    let same = a
+        .iter()
+        .flatten()
+        .zip(b.iter().flatten())
+        .filter(|(a, b)| a == b)
+        .count();
+
+    same as f64 / (DISPLAY_WIDTH * DISPLAY_HEIGHT) as f64
+}
+
+/// Write the screen to a png of `scale` pixels per chip8 pixel
+pub fn png(vram: &Vram, path: &str, scale: u32) -> Result<(), String> {
+    write_png(path, DISPLAY_WIDTH, DISPLAY_HEIGHT, scale, |x, y| {
+        Some(vram[x][y])
+    })
+}
+
+/// Write an image of `width` by `height` chip8 pixels to a png, `pixel`
+/// tells whether one is lit, none between the screens of a sheet
+pub(crate) fn write_png(
+    path: &str,
+    width: usize,
+    height: usize,
+    scale: u32,
+    pixel: impl Fn(usize, usize) -> Option<bool>,
+) -> Result<(), String> {
+    let scale = scale.max(1) as usize;
+    let width = width * scale;
+    let height = height * scale;
+
+    let mut data = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            data.extend_from_slice(match pixel(x / scale, y / scale) {
+                Some(true) => &FOREGROUND,
+                Some(false) => &BACKGROUND,
+                None => &GAP,
+            });
+        }
+    }
+
+    let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut encoder =
+        png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Registers and call stack, one line each
+pub fn cpu(cpu: &Cpu) -> String {
+    let registers: Vec<String> = cpu
+        .registers()
+        .iter()
+        .map(|v| format!("{:02x}", v))
+        .collect();
+    let stack: Vec<String> = cpu
+        .call_stack()
+        .iter()
+        .map(|addr| format!("{:#06x}", addr))
+        .collect();
+
+    format!(
+        "pc: {:#06x}  i: {:#06x}\nv: {}\nstack: {}\n",
+        cpu.pc(),
+        cpu.index(),
+        registers.join(" "),
+        stack.join(" ")
+    )
+}
+
+/// Rows of 16 bytes with their address and ASCII, a `*` stands for the
+/// rows repeating the one above
+pub fn hexdump(memory: &[u8]) -> String {
+    let mut text = String::new();
+    let mut previous: Option<&[u8]> = None;
+    let mut repeating = false;
+
+    for (row, bytes) in memory.chunks(HEXDUMP_ROW).enumerate() {
+        if previous == Some(bytes) {
+            if !repeating {
+                text.push_str("*\n");
+                repeating = true;
+            }
+            continue;
+        }
+        previous = Some(bytes);
+        repeating = false;
+
+        let hex: Vec<String> =
+            bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let ascii: String = bytes
+            .iter()
+            .map(|&byte| match byte {
+                0x20..=0x7E => byte as char,
+                _ => '.',
+            })
+            .collect();
+        text.push_str(&format!(
+            "{:#06x}  {:<47}  {}\n",
+            row * HEXDUMP_ROW,
+            hex.join(" "),
+            ascii
+        ));
+    }
+
+    text
+}
+
+/// Everything a saved `machine` holds: `cpu`, the timers, the keys, the
+/// screen as `text` and the memory as `hexdump`
+pub fn state(machine: &Machine) -> String {
+    let bus = machine.bus();
+    let mut out = cpu(machine.cpu());
+
+    if let Some(x) = machine.cpu().key_await() {
+        out.push_str(&format!("waiting for a key in v{:x}\n", x));
+    }
+    out.push_str(&format!(
+        "delay: {:#04x}  sound: {:#04x}\n",
+        bus.delay, bus.beep
+    ));
+    out.push_str(&format!("keys: {}\n", keys(&bus.keys)));
+    out.push_str("screen:\n");
+    out.push_str(&text(&bus.vram()));
+    out.push_str("memory:\n");
+    out.push_str(&hexdump(bus.memory()));
+
+    out
+}
+
+/// What differs from `a` to `b`, one line each, none when they are the same
+pub fn state_diff(a: &Machine, b: &Machine) -> Vec<String> {
+    let mut lines = vec![];
+    let mut field = |name: &str, a: String, b: String| {
+        if a != b {
+            lines.push(format!("{}: {} -> {}", name, a, b));
+        }
+    };
+
+    let (cpu_a, cpu_b) = (a.cpu(), b.cpu());
+    field(
+        "pc",
+        format!("{:#06x}", cpu_a.pc()),
+        format!("{:#06x}", cpu_b.pc()),
+    );
+    field(
+        "i",
+        format!("{:#06x}", cpu_a.index()),
+        format!("{:#06x}", cpu_b.index()),
+    );
+    for (x, (v_a, v_b)) in
+        cpu_a.registers().iter().zip(cpu_b.registers()).enumerate()
+    {
+        field(
+            &format!("v{:x}", x),
+            format!("{:#04x}", v_a),
+            format!("{:#04x}", v_b),
+        );
+    }
+    let stack = |cpu: &Cpu| format!("{:x?}", cpu.call_stack());
+    field("stack", stack(cpu_a), stack(cpu_b));
+    let key_wait = |cpu: &Cpu| match cpu.key_await() {
+        Some(x) => format!("v{:x}", x),
+        None => "-".to_string(),
+    };
+    field("key wait", key_wait(cpu_a), key_wait(cpu_b));
+
+    let (bus_a, bus_b) = (a.bus(), b.bus());
+    field(
+        "delay",
+        format!("{:#04x}", bus_a.delay),
+        format!("{:#04x}", bus_b.delay),
+    );
+    field(
+        "sound",
+        format!("{:#04x}", bus_a.beep),
+        format!("{:#04x}", bus_b.beep),
+    );
+    field("keys", keys(&bus_a.keys), keys(&bus_b.keys));
+
+    let pixels: u32 = bus_a
+        .rows()
+        .iter()
+        .zip(bus_b.rows())
+        .map(|(a, b)| (a ^ b).count_ones())
+        .sum();
+    if pixels > 0 {
+        lines.push(format!("screen: {} pixels differ", pixels));
+    }
+
+    // each run of different bytes
+    let (memory_a, memory_b) = (bus_a.memory(), bus_b.memory());
+    let mut addr = 0;
+    while addr < memory_a.len().min(memory_b.len()) {
+        if memory_a[addr] == memory_b[addr] {
+            addr += 1;
+            continue;
+        }
+        let end = (addr..memory_a.len().min(memory_b.len()))
+            .find(|&end| memory_a[end] == memory_b[end])
+            .unwrap_or(memory_a.len().min(memory_b.len()));
+        let bytes = |memory: &[u8]| {
+            let shown = &memory[addr..end.min(addr + DIFF_BYTES)];
+            let hex: Vec<String> =
+                shown.iter().map(|byte| format!("{:02x}", byte)).collect();
+            match end - addr > DIFF_BYTES {
+                true => format!("{} ...", hex.join(" ")),
+                false => hex.join(" "),
+            }
+        };
+        lines.push(format!(
+            "memory {:#06x}..{:#06x}: {} -> {}",
+            addr,
+            end,
+            bytes(memory_a),
+            bytes(memory_b)
+        ));
+        addr = end;
+    }
+
+    lines
+}
+
+/// The held keys in hex, `-` for none
+fn keys(keys: &[bool; KEYPAD_SIZE]) -> String {
+    let held: Vec<String> = (0..KEYPAD_SIZE)
+        .filter(|&key| keys[key])
+        .map(|key| format!("{:x}", key))
+        .collect();
+
+    match held.is_empty() {
+        true => "-".to_string(),
+        false => held.join(","),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        let mut vram = [[false; DISPLAY_HEIGHT]; DISPLAY_WIDTH];
+        vram[1][0] = true;
+
+        let text = text(&vram);
+        assert_eq!(text.lines().count(), DISPLAY_HEIGHT);
+        assert!(text.starts_with(".#..."));
+        let blank = [[false; DISPLAY_HEIGHT]; DISPLAY_WIDTH];
+        assert_eq!(similarity(&vram, &vram), 1.0);
+        assert_eq!(
+            similarity(&vram, &blank),
+            1.0 - 1.0 / (DISPLAY_WIDTH * DISPLAY_HEIGHT) as f64
+        );
+    }
+
+    #[test]
+    fn test_cpu() {
+        let text = cpu(&Cpu::new());
+
+        assert!(text.starts_with("pc: 0x0200  i: 0x0000\nv: 00 00"));
+        assert!(text.ends_with("stack: \n"));
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let mut memory = vec![0; 64];
+        memory[0] = b'A';
+
+        assert_eq!(
+            hexdump(&memory),
+            format!(
+                "0x0000  41{}  A{}\n0x0010  00{}  {}\n*\n",
+                " 00".repeat(15),
+                ".".repeat(15),
+                " 00".repeat(15),
+                ".".repeat(16)
+            )
+        );
+    }
+
+    #[test]
+    fn test_state_diff() {
+        use chip8::rom::Rom;
+
+        // 6005: V0 = 5, A300: I = 300, F033: BCD of V0, 1206: loop
+        let rom = Rom::from_bytes(vec![
+            0x60, 0x05, 0xA3, 0x00, 0xF0, 0x33, 0x12, 0x06,
+        ]);
+        let a = Machine::new(rom);
+        let mut b = a.clone();
+        assert!(state_diff(&a, &b).is_empty());
+
+        for _ in 0..3 {
+            b.step();
+        }
+        b.bus_mut().keys[0xA] = true;
+
+        assert_eq!(
+            state_diff(&a, &b),
+            vec![
+                "pc: 0x0200 -> 0x0206",
+                "i: 0x0000 -> 0x0300",
+                "v0: 0x00 -> 0x05",
+                "keys: - -> a",
+                "memory 0x0302..0x0303: 00 -> 05",
+            ]
+        );
+        assert!(state(&b).contains("keys: a\nscreen:\n"));
+    }
+}
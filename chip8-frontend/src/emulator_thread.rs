@@ -0,0 +1,286 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use chip8::machine::{FramesDue, Machine};
+
+use crate::timing::FrameTiming;
+
+/// Longest wait for a command between two frames
+const POLL_PERIOD: Duration = Duration::from_millis(1);
+
+/// Orders to the emulation thread
+#[derive(Clone, Debug)]
+pub enum Command {
+    Pause(bool),
+    Reset,
+    /// Execute a single instruction, while paused
+    Step,
+    /// Pause and run a single frame
+    AdvanceFrame,
+    /// Frames run in the time of one, 1 is the normal speed
+    Speed(f64),
+    /// Run frames back to back, as fast as possible
+    Turbo(bool),
+    /// Reply once the commands sent before are applied
+    Sync(Sender<()>),
+    Quit,
+}
+
+/// A machine run frame by frame on its own thread, at its frame rate
+/// whatever the frontend is doing
+///
+/// The frontend drives it with commands and locks the machine to draw it or
+/// press its keys. Dropping it stops the thread.
+pub struct EmulatorThread {
+    machine: Arc<Mutex<Machine>>,
+    /// Frames run so far, the screen may have changed when it moves
+    frames: Arc<AtomicU64>,
+    timing: Arc<Mutex<FrameTiming>>,
+    commands: Sender<Command>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EmulatorThread {
+    pub fn spawn(machine: Machine) -> Self {
+        Self::spawn_with(machine, Machine::run_frame)
+    }
+
+    /// Like `spawn`, with `run_frame` running each frame in place of
+    /// `Machine::run_frame`
+    pub fn spawn_with(
+        machine: Machine,
+        run_frame: impl FnMut(&mut Machine) + Send + 'static,
+    ) -> Self {
+        let machine = Arc::new(Mutex::new(machine));
+        let frames = Arc::new(AtomicU64::new(0));
+        let timing = Arc::new(Mutex::new(FrameTiming::default()));
+        let (commands, receiver) = mpsc::channel();
+
+        let handle = thread::spawn({
+            let machine = machine.clone();
+            let frames = frames.clone();
+            let timing = timing.clone();
+            move || run(&machine, &frames, &timing, &receiver, run_frame)
+        });
+
+        Self {
+            machine,
+            frames,
+            timing,
+            commands,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn send(&self, command: Command) {
+        // the thread only stops on `Quit`
+        self.commands.send(command).ok();
+    }
+
+    /// Wait for the thread to apply the commands sent so far
+    pub fn sync(&self) {
+        let (reply, applied) = mpsc::channel();
+        self.send(Command::Sync(reply));
+        // none when the thread is gone
+        applied.recv().ok();
+    }
+
+    /// The machine, the emulation waits while it is locked
+    pub fn machine(&self) -> MutexGuard<'_, Machine> {
+        // a frame that panicked leaves the machine as it was
+        self.machine.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Acquire)
+    }
+
+    /// Time spent by the frames since the start, with the drawings given to
+    /// `record_render`
+    pub fn timing(&self) -> FrameTiming {
+        self.timing
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Count the time the frontend took to draw a frame
+    pub fn record_render(&self, time: Duration) {
+        let mut timing = self.timing.lock().unwrap_or_else(|e| e.into_inner());
+        timing.render.record(time);
+    }
+}
+
+impl Drop for EmulatorThread {
+    fn drop(&mut self) {
+        self.send(Command::Quit);
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+fn run(
+    machine: &Mutex<Machine>,
+    frames: &AtomicU64,
+    timing: &Mutex<FrameTiming>,
+    commands: &Receiver<Command>,
+    mut run_frame: impl FnMut(&mut Machine),
+) {
+    let lock = || machine.lock().unwrap_or_else(|e| e.into_inner());
+    let lock_timing = || timing.lock().unwrap_or_else(|e| e.into_inner());
+    let mut loop_time = Instant::now();
+    let mut paused = lock().is_paused();
+
+    loop {
+        // paused, nothing happens until the next command
+        let was_paused = paused;
+        let timeout = match (paused, lock().is_turbo()) {
+            (true, _) => Duration::MAX,
+            (false, true) => Duration::ZERO,
+            (false, false) => POLL_PERIOD,
+        };
+        let mut command = match commands.recv_timeout(timeout) {
+            Ok(command) => Some(command),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        while let Some(received) = command {
+            match received {
+                Command::Pause(pause) => {
+                    paused = pause;
+                    lock().pause(pause);
+                }
+                Command::Reset => {
+                    lock().reset();
+                    frames.fetch_add(1, Ordering::Release);
+                }
+                Command::Step if paused => {
+                    lock().step();
+                    frames.fetch_add(1, Ordering::Release);
+                }
+                Command::Step => {}
+                Command::AdvanceFrame => {
+                    paused = true;
+                    let mut machine = lock();
+                    machine.pause(true);
+                    run_frame(&mut machine);
+                    drop(machine);
+                    frames.fetch_add(1, Ordering::Release);
+                }
+                Command::Speed(factor) => lock().set_speed(factor),
+                Command::Turbo(turbo) => lock().set_turbo(turbo),
+                Command::Sync(reply) => {
+                    reply.send(()).ok();
+                }
+                Command::Quit => return,
+            }
+            command = commands.try_recv().ok();
+        }
+
+        // the time spent paused isn't made up for
+        if was_paused {
+            loop_time = Instant::now();
+        }
+        let delta = loop_time.elapsed();
+        loop_time = Instant::now();
+        if paused {
+            continue;
+        }
+
+        // in turbo, the commands are checked between the frames
+        let turbo = lock().is_turbo();
+        let due = match turbo {
+            true => FramesDue { run: 1, missed: 0 },
+            false => lock().frames_due(delta),
+        };
+        lock_timing().missed += due.missed;
+        for _ in 0..due.run {
+            let mut machine = lock();
+            // not counting the wait for the lock
+            let start = Instant::now();
+            run_frame(&mut machine);
+            let time = start.elapsed();
+            drop(machine);
+
+            lock_timing().emulate.record(time);
+            frames.fetch_add(1, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8::rom::Rom;
+
+    use super::*;
+
+    // 7001: V0 += 1, 1200: loop
+    const PROGRAM: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+    #[test]
+    fn test_commands() {
+        let emulator = EmulatorThread::spawn(Machine::new(Rom::from_bytes(
+            PROGRAM.into(),
+        )));
+        emulator.send(Command::Pause(true));
+        emulator.sync();
+
+        let frames = emulator.frames();
+        emulator.send(Command::Reset);
+        for _ in 0..3 {
+            emulator.send(Command::Step);
+        }
+        emulator.sync();
+
+        // add, jump, add
+        assert_eq!(emulator.machine().cpu().registers()[0], 2);
+        assert_eq!(emulator.frames(), frames + 4);
+    }
+
+    #[test]
+    fn test_advance_frame() {
+        let mut machine = Machine::new(Rom::from_bytes(PROGRAM.into()));
+        machine.set_cpu_frequency(120.0);
+        machine.pause(true);
+        let emulator = EmulatorThread::spawn(machine);
+
+        emulator.send(Command::Turbo(true));
+        emulator.send(Command::AdvanceFrame);
+        emulator.sync();
+
+        // paused, the turbo doesn't run
+        assert_eq!(emulator.frames(), 1);
+        assert_eq!(emulator.machine().cpu().registers()[0], 1);
+
+        emulator.send(Command::Pause(false));
+        while emulator.frames() < 100 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(emulator.machine().is_turbo());
+    }
+
+    #[test]
+    fn test_timing() {
+        let emulator = EmulatorThread::spawn(Machine::new(Rom::from_bytes(
+            PROGRAM.into(),
+        )));
+        while emulator.frames() < 3 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        emulator.send(Command::Pause(true));
+        emulator.record_render(Duration::from_millis(2));
+
+        let timing = emulator.timing();
+        assert!(timing.emulate.count() >= 3);
+        assert_eq!(timing.render.count(), 1);
+        assert_eq!(timing.render.max(), Duration::from_millis(2));
+    }
+}
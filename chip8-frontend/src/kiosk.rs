@@ -0,0 +1,393 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chip8::{
+    bus::KEYPAD_SIZE,
+    machine::{Machine, FRAME_RATE},
+    rom::Rom,
+};
+use log::{info, warn};
+
+/// Time given to a rom when the playlist doesn't say
+pub const DEFAULT_SECONDS: f64 = 30.0;
+
+#[derive(Debug)]
+pub enum PlaylistError {
+    Io(PathBuf, io::Error),
+    /// A line of the playlist or of a demo script can't be parsed
+    Syntax(PathBuf, usize, String),
+    Empty,
+}
+
+impl Display for PlaylistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlaylistError::Io(path, e) => {
+                write!(f, "{}: {}", path.display(), e)
+            }
+            PlaylistError::Syntax(path, line, message) => {
+                write!(f, "{}:{}: {}", path.display(), line, message)
+            }
+            PlaylistError::Empty => write!(f, "empty playlist"),
+        }
+    }
+}
+
+impl Error for PlaylistError {}
+
+/// Keys pressed and released at given frames since the rom started
+///
+/// One event per line, `FRAME KEY down|up` with the key in hexadecimal,
+/// `#` starts a comment
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DemoScript {
+    events: Vec<(u64, usize, bool)>,
+}
+
+impl DemoScript {
+    /// Update `keys` with the events of `frame`
+    pub fn apply(&self, frame: u64, keys: &mut [bool; KEYPAD_SIZE]) {
+        for &(_, key, pressed) in
+            self.events.iter().filter(|(at, _, _)| *at == frame)
+        {
+            keys[key] = pressed;
+        }
+    }
+}
+
+impl FromStr for DemoScript {
+    /// Line number and message
+    type Err = (usize, String);
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut events = vec![];
+
+        for (number, line) in lines(s) {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let &[frame, key, state] = words.as_slice() else {
+                return Err((number, "expected FRAME KEY down|up".into()));
+            };
+
+            let frame = frame
+                .parse()
+                .map_err(|_| (number, format!("invalid frame: {}", frame)))?;
+            let key = match usize::from_str_radix(key, 16) {
+                Ok(key) if key < KEYPAD_SIZE => key,
+                _ => return Err((number, format!("invalid key: {}", key))),
+            };
+            let pressed = match state {
+                "down" => true,
+                "up" => false,
+                _ => return Err((number, format!("invalid state: {}", state))),
+            };
+
+            events.push((frame, key, pressed));
+        }
+
+        // keep the file order for events on the same frame
+        events.sort_by_key(|&(frame, _, _)| frame);
+
+        Ok(Self { events })
+    }
+}
+
+pub struct PlaylistEntry {
+    pub name: String,
+    pub rom: Rom,
+    pub seconds: f64,
+    /// Drives the keypad instead of the player
+    pub demo: Option<DemoScript>,
+}
+
+/// Roms played in turn
+///
+/// One rom per line, `PATH [SECONDS] [DEMO]` with paths relative to the
+/// playlist, `#` starts a comment
+pub struct Playlist {
+    entries: Vec<PlaylistEntry>,
+}
+
+impl Playlist {
+    pub fn new(entries: Vec<PlaylistEntry>) -> Result<Self, PlaylistError> {
+        match entries.is_empty() {
+            true => Err(PlaylistError::Empty),
+            false => Ok(Self { entries }),
+        }
+    }
+
+    /// Read the playlist at `path` and every rom and demo script it lists
+    pub fn load(path: &Path) -> Result<Self, PlaylistError> {
+        let text = read_to_string(path)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut entries = vec![];
+
+        for (number, line) in lines(&text) {
+            let (rom_path, seconds, demo_path) =
+                parse_entry(line).map_err(|message| {
+                    PlaylistError::Syntax(path.into(), number, message)
+                })?;
+
+            let rom_path = dir.join(rom_path);
+            let rom = fs::read(&rom_path)
+                .map_err(|e| PlaylistError::Io(rom_path.clone(), e))?;
+
+            let demo = match demo_path {
+                Some(demo_path) => {
+                    let demo_path = dir.join(demo_path);
+                    let script = read_to_string(&demo_path)?;
+                    Some(script.parse().map_err(|(number, message)| {
+                        PlaylistError::Syntax(demo_path, number, message)
+                    })?)
+                }
+                None => None,
+            };
+
+            entries.push(PlaylistEntry {
+                name: rom_path.display().to_string(),
+                rom: Rom::from_bytes(rom),
+                seconds,
+                demo,
+            });
+        }
+
+        Self::new(entries)
+    }
+
+    pub fn entries(&self) -> &[PlaylistEntry] {
+        &self.entries
+    }
+}
+
+/// Attract mode, cycles through a playlist and restarts the roms that
+/// crash the emulator
+pub struct Kiosk {
+    playlist: Playlist,
+    current: usize,
+    /// Frames since the entry started, crashes included
+    entry_frames: u64,
+    /// Frames since the rom (re)started, for the demo script
+    rom_frames: u64,
+}
+
+impl Kiosk {
+    pub fn new(playlist: Playlist) -> Self {
+        Self {
+            playlist,
+            current: 0,
+            entry_frames: 0,
+            rom_frames: 0,
+        }
+    }
+
+    pub fn current(&self) -> &PlaylistEntry {
+        &self.playlist.entries[self.current]
+    }
+
+    /// A fresh machine for the current entry
+    pub fn start(&self) -> Machine {
+        Machine::new(self.current().rom.clone())
+    }
+
+    /// Run a frame of the current entry, `machine` is replaced when the
+    /// rom crashes or its time is over
+    /// Returns true when another entry started
+    pub fn run_frame(&mut self, machine: &mut Machine) -> bool {
+        let entry = &self.playlist.entries[self.current];
+        let frames = (entry.seconds * FRAME_RATE) as u64;
+        if let Some(demo) = &entry.demo {
+            demo.apply(self.rom_frames, &mut machine.bus_mut().keys);
+        }
+
+        // no rom should make the emulator panic, if one finds a bug the
+        // kiosk goes on with a fresh machine
+        let crashed =
+            panic::catch_unwind(AssertUnwindSafe(|| machine.run_frame()))
+                .is_err();
+        self.entry_frames += 1;
+        self.rom_frames += 1;
+
+        if crashed {
+            warn!("{} crashed, restarting", entry.name);
+            self.restart(machine);
+        }
+
+        if self.entry_frames < frames {
+            return false;
+        }
+
+        self.current = (self.current + 1) % self.playlist.entries.len();
+        self.entry_frames = 0;
+        info!("playing {}", self.current().name);
+        self.restart(machine);
+
+        true
+    }
+
+    /// Start the current rom again, keeping the machine settings
+    fn restart(&mut self, machine: &mut Machine) {
+        let frequency = machine.cpu_frequency();
+        let speed = machine.speed();
+        let quirks = machine.cpu().quirks();
+
+        *machine = self.start();
+        machine.set_cpu_frequency(frequency);
+        machine.set_speed(speed);
+        machine.cpu_mut().set_quirks(quirks);
+        self.rom_frames = 0;
+    }
+}
+
+fn read_to_string(path: &Path) -> Result<String, PlaylistError> {
+    fs::read_to_string(path).map_err(|e| PlaylistError::Io(path.into(), e))
+}
+
+/// Numbered lines without comments and blank lines
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| {
+            let line = line.split('#').next().unwrap_or("").trim();
+            (index + 1, line)
+        })
+        .filter(|(_, line)| !line.is_empty())
+}
+
+fn parse_entry(line: &str) -> Result<(&str, f64, Option<&str>), String> {
+    let mut words = line.split_whitespace();
+    let path = words.next().ok_or("missing rom path")?;
+
+    let seconds = match words.next() {
+        Some(seconds) => match seconds.parse::<f64>() {
+            Ok(seconds) if seconds > 0.0 => seconds,
+            _ => return Err(format!("invalid duration: {}", seconds)),
+        },
+        None => DEFAULT_SECONDS,
+    };
+    let demo = words.next();
+
+    if let Some(extra) = words.next() {
+        return Err(format!("unexpected argument: {}", extra));
+    }
+
+    Ok((path, seconds, demo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_entry(program: &[u8], seconds: f64) -> PlaylistEntry {
+        PlaylistEntry {
+            name: format!("{:02X?}", program),
+            rom: Rom::from_bytes(program.to_vec()),
+            seconds,
+            demo: None,
+        }
+    }
+
+    #[test]
+    fn test_demo_script() {
+        let demo: DemoScript = "# frame key state\n\
+                                2 5 down\n\
+                                1 a down # comment\n\
+                                \n\
+                                2 A up"
+            .parse()
+            .unwrap();
+        let mut keys = [false; KEYPAD_SIZE];
+
+        demo.apply(0, &mut keys);
+        assert_eq!(keys, [false; KEYPAD_SIZE]);
+        demo.apply(1, &mut keys);
+        assert!(keys[0xA]);
+        demo.apply(2, &mut keys);
+        assert!(keys[0x5] && !keys[0xA]);
+
+        assert_eq!(
+            "1 5".parse::<DemoScript>(),
+            Err((1, "expected FRAME KEY down|up".into()))
+        );
+        assert_eq!(
+            "\n1 10 down".parse::<DemoScript>(),
+            Err((2, "invalid key: 10".into()))
+        );
+        assert_eq!(
+            "1 5 held".parse::<DemoScript>(),
+            Err((1, "invalid state: held".into()))
+        );
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        assert_eq!(parse_entry("pong.ch8"), Ok(("pong.ch8", 30.0, None)));
+        assert_eq!(
+            parse_entry("pong.ch8  12.5 pong.demo"),
+            Ok(("pong.ch8", 12.5, Some("pong.demo")))
+        );
+        assert!(parse_entry("pong.ch8 0").is_err());
+        assert!(parse_entry("pong.ch8 1 pong.demo more").is_err());
+        assert!(Playlist::new(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_kiosk() {
+        // 7001: add 1 to V0, 1200: jump back
+        let counter = create_entry(&[0x70, 0x01, 0x12, 0x00], 0.05);
+        // 1200: jump to itself
+        let halt = create_entry(&[0x12, 0x00], 0.05);
+        let playlist = Playlist::new(vec![counter, halt]).unwrap();
+
+        let mut kiosk = Kiosk::new(playlist);
+        let mut machine = kiosk.start();
+        machine.set_cpu_frequency(120.0);
+        machine.set_speed(2.0);
+
+        assert!(!kiosk.run_frame(&mut machine));
+        assert!(!kiosk.run_frame(&mut machine));
+        assert_eq!(machine.cpu().registers()[0], 2);
+
+        // 3 frames at 60Hz
+        assert!(kiosk.run_frame(&mut machine));
+        assert_eq!(machine.cpu().registers()[0], 0);
+        assert_eq!(machine.cpu_frequency(), 120.0);
+        assert_eq!(machine.speed(), 2.0);
+
+        // a halted rom waits for the end of its time
+        assert!(!kiosk.run_frame(&mut machine));
+        assert!(machine.is_halted());
+        assert!(!kiosk.run_frame(&mut machine));
+        assert!(kiosk.run_frame(&mut machine));
+        assert_eq!(kiosk.current().name, "[70, 01, 12, 00]");
+    }
+
+    #[test]
+    fn test_kiosk_demo() {
+        // E19E: skip if key V1, 1200: jump back, 7001: add 1 to V0
+        let program = [0xE1, 0x9E, 0x12, 0x00, 0x70, 0x01, 0x12, 0x00];
+        let mut entry = create_entry(&program, 1.0);
+        entry.demo = Some("1 0 down\n2 0 up".parse().unwrap());
+        let playlist = Playlist::new(vec![entry]).unwrap();
+
+        let mut kiosk = Kiosk::new(playlist);
+        let mut machine = kiosk.start();
+        machine.set_cpu_frequency(120.0);
+
+        kiosk.run_frame(&mut machine);
+        assert!(!machine.bus().keys[0x0]);
+        assert_eq!(machine.cpu().registers()[0], 0);
+
+        kiosk.run_frame(&mut machine);
+        assert!(machine.bus().keys[0x0]);
+        assert_eq!(machine.cpu().registers()[0], 1);
+
+        kiosk.run_frame(&mut machine);
+        assert!(!machine.bus().keys[0x0]);
+        assert_eq!(machine.cpu().registers()[0], 1);
+    }
+}
@@ -0,0 +1,154 @@
+pub mod config;
+pub mod corpus;
+pub mod coverage;
+pub mod crowd;
+pub mod database;
+pub mod determinism;
+pub mod dump;
+pub mod emulator_thread;
+pub mod kiosk;
+pub mod listing;
+pub mod netplay;
+pub mod parallel;
+pub mod rewind;
+#[cfg(feature = "rhai")]
+pub mod rhai_script;
+pub mod rom_info;
+pub mod script;
+pub mod slots;
+pub mod sprites;
+pub mod timing;
+pub mod trace;
+pub mod watch;
+
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use chip8::{
+    bus::{DISPLAY_HEIGHT, DISPLAY_WIDTH, KEYPAD_SIZE},
+    keypad::Keypad,
+    machine::{Machine, FRAME_RATE},
+};
+
+pub type Vram = [[bool; DISPLAY_HEIGHT]; DISPLAY_WIDTH];
+
+/// Keys in their position on the keypad, row by row
+pub const KEYPAD_LAYOUT: [Keypad; KEYPAD_SIZE] = [
+    Keypad::Key1,
+    Keypad::Key2,
+    Keypad::Key3,
+    Keypad::KeyC,
+    Keypad::Key4,
+    Keypad::Key5,
+    Keypad::Key6,
+    Keypad::KeyD,
+    Keypad::Key7,
+    Keypad::Key8,
+    Keypad::Key9,
+    Keypad::KeyE,
+    Keypad::KeyA,
+    Keypad::Key0,
+    Keypad::KeyB,
+    Keypad::KeyF,
+];
+
+/// The platform side of an emulator: screen, keypad and buzzer
+pub trait Frontend {
+    /// Update the pressed keys, returns false once the user wants to quit
+    fn poll_input(&mut self, keys: &mut [bool; KEYPAD_SIZE]) -> bool;
+
+    fn draw(&mut self, vram: &Vram);
+
+    /// Called when the sound timer starts or stops
+    fn set_beeping(&mut self, _beeping: bool) {}
+}
+
+/// Run `machine` at its frame rate until the frontend asks to quit
+pub fn run(machine: &mut Machine, frontend: &mut impl Frontend) {
+    let frame_time = Duration::from_secs_f64(1.0 / FRAME_RATE);
+    let mut next_frame = Instant::now();
+    let mut beeping = false;
+
+    loop {
+        let mut keys = machine.bus().keys;
+        if !frontend.poll_input(&mut keys) {
+            break;
+        }
+        machine.bus_mut().keys = keys;
+
+        machine.run_frame();
+
+        if machine.is_beeping() != beeping {
+            beeping = machine.is_beeping();
+            frontend.set_beeping(beeping);
+        }
+        frontend.draw(&machine.bus().vram());
+
+        // don't try to catch up when a frame took too long
+        next_frame += frame_time;
+        match next_frame.checked_duration_since(Instant::now()) {
+            Some(wait) => sleep(wait),
+            None => next_frame = Instant::now(),
+        }
+    }
+
+    if beeping {
+        frontend.set_beeping(false);
+    }
+}
+
+/// FNV-1a hash, identifies roms and states
+pub fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0x811C9DC5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8::rom::Rom;
+
+    use super::*;
+
+    /// Holds key 5 and quits after `frames` frames
+    struct FrontendTest {
+        frames: usize,
+        draws: usize,
+        beeps: Vec<bool>,
+    }
+
+    impl Frontend for FrontendTest {
+        fn poll_input(&mut self, keys: &mut [bool; KEYPAD_SIZE]) -> bool {
+            keys[0x5] = true;
+            self.draws < self.frames
+        }
+
+        fn draw(&mut self, _vram: &Vram) {
+            self.draws += 1;
+        }
+
+        fn set_beeping(&mut self, beeping: bool) {
+            self.beeps.push(beeping);
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        // 6002: V0 = 2, F018: sound = V0, F10A: wait key in V1
+        let rom = Rom::from_bytes(vec![0x60, 0x02, 0xF0, 0x18, 0xF1, 0x0A]);
+        let mut machine = Machine::new(rom);
+        let mut frontend = FrontendTest {
+            frames: 4,
+            draws: 0,
+            beeps: vec![],
+        };
+
+        run(&mut machine, &mut frontend);
+
+        assert_eq!(frontend.draws, 4);
+        assert_eq!(frontend.beeps, vec![true, false]);
+        assert_eq!(machine.cpu().registers()[1], 0x5);
+    }
+}
@@ -0,0 +1,213 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use chip8::disasm::disassemble;
+
+use crate::coverage::{Coverage, Usage};
+
+/// Address of the first byte of a rom
+const PROGRAM_START: u16 = 0x200;
+const MAX_ROM_SIZE: usize = 0x1000 - PROGRAM_START as usize;
+/// Bytes of data shown on each row
+const DATA_ROW: usize = 8;
+
+const STYLE: &str = "
+body { font-family: monospace; background: #fff; color: #222 }
+table { border-collapse: collapse }
+td { padding: 0 1em 0 0; vertical-align: top }
+tr:target { background: #ff8 }
+.addr { color: #888 }
+.bytes { color: #46a }
+.sprite, .data { color: #a64 }
+.unreached { color: #aaa }
+.xref { color: #888 }
+svg { background: #7cd115; vertical-align: top }
+rect { fill: #45730d }
+";
+
+/// An HTML page listing `rom` as code and data, from what `coverage` saw
+/// of it running
+///
+/// The targets of jumps, calls and `LD I` are links, each row lists the
+/// instructions going to it. The sprites are drawn next to their bytes,
+/// what never ran is disassembled greyed out.
+pub fn html(title: &str, rom: &[u8], coverage: &Coverage) -> String {
+    let byte = |addr: u16| {
+        rom.get((addr - PROGRAM_START) as usize)
+            .copied()
+            .unwrap_or(0)
+    };
+    // the rest of a bigger rom isn't loaded
+    let end = PROGRAM_START + rom.len().min(MAX_ROM_SIZE) as u16;
+
+    // rows, from their address to their content
+    let mut rows = vec![];
+    let mut addr = PROGRAM_START;
+    while addr < end {
+        let usage = coverage.usage(addr);
+        // the rest of a region of the same usage, on a single row
+        let len = match usage {
+            Some(Usage::Code) | None => 2,
+            Some(Usage::Sprite) | Some(Usage::Data) => (addr..end)
+                .take(DATA_ROW)
+                .take_while(|&next| coverage.usage(next) == usage)
+                .count()
+                as u16,
+        };
+        let len = len.min(end - addr);
+        // unreached code doesn't run over reached code
+        let len = match (usage, coverage.usage(addr + 1)) {
+            (None, Some(_)) => 1,
+            _ => len,
+        };
+
+        let bytes: Vec<u8> = (addr..addr + len).map(byte).collect();
+        rows.push((addr, usage, bytes));
+        addr += len;
+    }
+
+    // instructions of each target
+    let mut xrefs: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+    for (addr, usage, bytes) in &rows {
+        if let (Some(Usage::Code), &[high, low]) = (usage, bytes.as_slice()) {
+            if let Some(target) = target(u16::from_be_bytes([high, low])) {
+                xrefs.entry(target).or_default().push(*addr);
+            }
+        }
+    }
+
+    let mut page = String::new();
+    writeln!(
+        page,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n\
+         <h1>{}</h1>\n<table>",
+        escape(title),
+        STYLE,
+        escape(title)
+    )
+    .ok();
+
+    for (addr, usage, bytes) in &rows {
+        let hex: Vec<String> =
+            bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let content = match (usage, bytes.as_slice()) {
+            (Some(Usage::Code), &[high, low]) => {
+                instruction(u16::from_be_bytes([high, low]))
+            }
+            (None, &[high, low]) => format!(
+                "<span class=\"unreached\">{}</span>",
+                disassemble(u16::from_be_bytes([high, low]))
+            ),
+            (None, _) => format!(
+                "<span class=\"unreached\">DB {}</span>",
+                hex.join(", ")
+            ),
+            (Some(Usage::Sprite), _) => {
+                format!("<span class=\"sprite\">DB</span> {}", sprite(bytes))
+            }
+            _ => format!("<span class=\"data\">DB {}</span>", hex.join(", ")),
+        };
+        let referrers = match xrefs.get(addr) {
+            Some(referrers) => referrers
+                .iter()
+                .map(|from| format!("<a href=\"#L{0:03X}\">{0:03X}</a>", from))
+                .collect::<Vec<_>>()
+                .join(" "),
+            None => String::new(),
+        };
+
+        writeln!(
+            page,
+            "<tr id=\"L{0:03X}\"><td class=\"addr\">{0:03X}</td>\
+             <td class=\"bytes\">{1}</td><td>{2}</td>\
+             <td class=\"xref\">{3}</td></tr>",
+            addr,
+            hex.join(" "),
+            content,
+            referrers
+        )
+        .ok();
+    }
+
+    page.push_str("</table>\n</body>\n</html>\n");
+    page
+}
+
+/// Address used by an instruction, if it has one
+fn target(opcode: u16) -> Option<u16> {
+    match opcode & 0xF000 {
+        0x1000 | 0x2000 | 0xA000 | 0xB000 => Some(opcode & 0x0FFF),
+        _ => None,
+    }
+}
+
+/// Disassembly of `opcode`, its target linked to its row
+fn instruction(opcode: u16) -> String {
+    let text = disassemble(opcode);
+    match target(opcode) {
+        Some(addr) => {
+            let operand = format!("0x{:03X}", addr);
+            let link = format!("<a href=\"#L{:03X}\">{}</a>", addr, operand);
+            text.replacen(&operand, &link, 1)
+        }
+        None => text,
+    }
+}
+
+/// The rows of a sprite as an SVG, 2 pixels per chip8 pixel
+fn sprite(bytes: &[u8]) -> String {
+    let mut svg = format!("<svg width=\"16\" height=\"{}\">", bytes.len() * 2);
+    for (y, byte) in bytes.iter().enumerate() {
+        for x in (0..8).filter(|x| byte & (0x80 >> x) != 0) {
+            write!(
+                svg,
+                "<rect x=\"{}\" y=\"{}\" width=\"2\" height=\"2\"/>",
+                x * 2,
+                y * 2
+            )
+            .ok();
+        }
+    }
+    svg.push_str("</svg>");
+
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8::{machine::Machine, rom::Rom};
+
+    use super::*;
+
+    #[test]
+    fn test_html() {
+        // A208: I = 208, D001: draw 1 row, 1204: loop, 0000, F0: sprite
+        let data =
+            vec![0xA2, 0x08, 0xD0, 0x01, 0x12, 0x04, 0x00, 0x00, 0xF0, 0x00];
+        let mut machine = Machine::new(Rom::from_bytes(data.clone()));
+        let mut coverage = Coverage::new(&machine);
+        coverage.run_frame(&mut machine);
+
+        let page = html("<test>", &data, &coverage);
+
+        assert!(page.contains("<title>&lt;test&gt;</title>"));
+        assert!(page.contains("LD I, <a href=\"#L208\">0x208</a>"));
+        // the loop jumps to itself
+        assert!(page.contains(
+            "<tr id=\"L204\"><td class=\"addr\">204</td><td class=\"bytes\">\
+             12 04</td><td>JP <a href=\"#L204\">0x204</a></td>\
+             <td class=\"xref\"><a href=\"#L204\">204</a></td></tr>"
+        ));
+        assert!(page.contains("<span class=\"unreached\">SYS 0x000</span>"));
+        assert!(page.contains("<svg width=\"16\" height=\"2\"><rect x=\"0\""));
+        // the byte after the sprite was never used
+        assert!(page.contains("<tr id=\"L209\">"));
+    }
+}
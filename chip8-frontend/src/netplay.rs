@@ -0,0 +1,392 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::{self, Display},
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use chip8::{
+    bus::KEYPAD_SIZE,
+    machine::{Machine, FRAME_RATE},
+};
+use log::info;
+
+use crate::{checksum, Frontend};
+
+/// Frames between a key press and its effect, hides the network latency
+pub const DEFAULT_INPUT_DELAY: u8 = 2;
+
+const MAGIC: &[u8; 4] = b"CH8N";
+const VERSION: u8 = 1;
+/// Instructions per second sent by a host at most, past any game
+const MAX_FREQUENCY: f64 = 1_000_000.0;
+
+#[derive(Debug)]
+pub enum NetplayError {
+    Io(io::Error),
+    /// The peer isn't a compatible chip8 instance
+    Handshake(&'static str),
+    /// The peer runs another rom
+    RomMismatch,
+    /// The machines stopped being identical at the given frame
+    Desync(u32),
+}
+
+impl Display for NetplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetplayError::Io(e) => write!(f, "{}", e),
+            NetplayError::Handshake(message) => write!(f, "{}", message),
+            NetplayError::RomMismatch => write!(f, "the peer runs another rom"),
+            NetplayError::Desync(frame) => {
+                write!(f, "desynchronized at frame {}", frame)
+            }
+        }
+    }
+}
+
+impl Error for NetplayError {}
+
+impl From<io::Error> for NetplayError {
+    fn from(e: io::Error) -> Self {
+        NetplayError::Io(e)
+    }
+}
+
+/// Lockstep session between two instances sharing the keypad
+///
+/// Each side sends its keys `input_delay` frames ahead and waits for the
+/// peer keys before running a frame, the pressed keys of both players are
+/// merged. The host settings and random seed are sent to the guest so both
+/// machines stay identical, which is checked every frame.
+pub struct Netplay {
+    stream: TcpStream,
+    input_delay: u32,
+    frame: u32,
+    /// Local keys of the frames not run yet
+    local: VecDeque<u16>,
+    /// Own state checksums not yet compared with the peer ones
+    checksums: VecDeque<u32>,
+}
+
+impl Netplay {
+    /// Wait for a guest on `listener` and send it the `machine` settings,
+    /// the machine must not have run yet
+    pub fn host(
+        listener: &TcpListener,
+        machine: &mut Machine,
+        input_delay: u8,
+    ) -> Result<Self, NetplayError> {
+        let (mut stream, peer) = listener.accept()?;
+        info!("netplay: {} joined", peer);
+
+        let seed = rand::random::<u64>();
+        machine.cpu_mut().set_seed(seed);
+
+        let mut hello = header();
+        hello.push(input_delay);
+        hello.extend(seed.to_le_bytes());
+        hello.extend(machine.cpu_frequency().to_bits().to_le_bytes());
+        hello.extend(rom_checksum(machine).to_le_bytes());
+        stream.write_all(&hello)?;
+
+        let mut reply = [0; 9];
+        stream.read_exact(&mut reply)?;
+        check_header(&reply)?;
+        if reply[5..9] != rom_checksum(machine).to_le_bytes() {
+            return Err(NetplayError::RomMismatch);
+        }
+
+        Self::new(stream, input_delay)
+    }
+
+    /// Join the host at `addr` and take its settings, the machine must not
+    /// have run yet
+    pub fn join(
+        addr: impl ToSocketAddrs,
+        machine: &mut Machine,
+    ) -> Result<Self, NetplayError> {
+        let mut stream = TcpStream::connect(addr)?;
+
+        let mut hello = [0; 26];
+        stream.read_exact(&mut hello)?;
+        check_header(&hello)?;
+
+        // answer first, the host reports the rom mismatch too
+        let mut reply = header();
+        reply.extend(rom_checksum(machine).to_le_bytes());
+        stream.write_all(&reply)?;
+
+        let input_delay = hello[5];
+        let seed = u64::from_le_bytes(hello[6..14].try_into().unwrap());
+        let frequency = u64::from_le_bytes(hello[14..22].try_into().unwrap());
+        let frequency = f64::from_bits(frequency);
+        if hello[22..26] != rom_checksum(machine).to_le_bytes() {
+            return Err(NetplayError::RomMismatch);
+        }
+        if !(0.0..=MAX_FREQUENCY).contains(&frequency) {
+            return Err(NetplayError::Handshake("invalid cpu frequency"));
+        }
+
+        machine.cpu_mut().set_seed(seed);
+        machine.set_cpu_frequency(frequency);
+        info!("netplay: joined with {} frames of delay", input_delay);
+
+        Self::new(stream, input_delay)
+    }
+
+    fn new(stream: TcpStream, input_delay: u8) -> Result<Self, NetplayError> {
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            stream,
+            input_delay: input_delay as u32,
+            frame: 0,
+            // nobody presses anything during the first frames
+            local: VecDeque::from(vec![0; input_delay as usize]),
+            checksums: VecDeque::new(),
+        })
+    }
+
+    pub fn input_delay(&self) -> u32 {
+        self.input_delay
+    }
+
+    /// Run a frame with the keys of both players, `keys` are the local ones
+    /// and take effect `input_delay` frames later
+    /// Blocks until the peer keys arrive
+    pub fn run_frame(
+        &mut self,
+        machine: &mut Machine,
+        keys: &[bool; KEYPAD_SIZE],
+    ) -> Result<(), NetplayError> {
+        let checksum = checksum(&machine.save_state());
+        let local = keys_to_bits(keys);
+
+        let mut message = Vec::with_capacity(10);
+        message.extend((self.frame + self.input_delay).to_le_bytes());
+        message.extend(local.to_le_bytes());
+        message.extend(checksum.to_le_bytes());
+        self.stream.write_all(&message)?;
+
+        self.local.push_back(local);
+        self.checksums.push_back(checksum);
+
+        let mut remote = 0;
+        if self.frame >= self.input_delay {
+            let mut message = [0; 10];
+            self.stream.read_exact(&mut message)?;
+
+            let frame = u32::from_le_bytes(message[0..4].try_into().unwrap());
+            if frame != self.frame {
+                return Err(NetplayError::Handshake("unexpected frame"));
+            }
+            remote = u16::from_le_bytes([message[4], message[5]]);
+
+            // sent by the peer `input_delay` frames ago
+            let peer_checksum = &message[6..10];
+            if self.checksums.pop_front().map(u32::to_le_bytes)
+                != Some(peer_checksum.try_into().unwrap())
+            {
+                return Err(NetplayError::Desync(frame - self.input_delay));
+            }
+        }
+
+        let local = self.local.pop_front().unwrap_or(0);
+        machine.bus_mut().keys = bits_to_keys(local | remote);
+        machine.run_frame();
+        self.frame += 1;
+
+        Ok(())
+    }
+}
+
+/// Run `machine` at its frame rate with a remote player until the frontend
+/// asks to quit or the session fails
+pub fn run(
+    machine: &mut Machine,
+    frontend: &mut impl Frontend,
+    netplay: &mut Netplay,
+) -> Result<(), NetplayError> {
+    let frame_time = Duration::from_secs_f64(1.0 / FRAME_RATE);
+    let mut next_frame = Instant::now();
+    let mut keys = [false; KEYPAD_SIZE];
+    let mut beeping = false;
+
+    let result = loop {
+        if !frontend.poll_input(&mut keys) {
+            break Ok(());
+        }
+
+        if let Err(e) = netplay.run_frame(machine, &keys) {
+            break Err(e);
+        }
+
+        if machine.is_beeping() != beeping {
+            beeping = machine.is_beeping();
+            frontend.set_beeping(beeping);
+        }
+        frontend.draw(&machine.bus().vram());
+
+        // the slowest side sets the pace, waiting for its keys
+        next_frame += frame_time;
+        match next_frame.checked_duration_since(Instant::now()) {
+            Some(wait) => sleep(wait),
+            None => next_frame = Instant::now(),
+        }
+    };
+
+    if beeping {
+        frontend.set_beeping(false);
+    }
+
+    result
+}
+
+fn header() -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    header.push(VERSION);
+
+    header
+}
+
+fn check_header(data: &[u8]) -> Result<(), NetplayError> {
+    if &data[0..4] != MAGIC {
+        return Err(NetplayError::Handshake("not a chip8 netplay peer"));
+    }
+    if data[4] != VERSION {
+        return Err(NetplayError::Handshake("unsupported netplay version"));
+    }
+
+    Ok(())
+}
+
+fn rom_checksum(machine: &Machine) -> u32 {
+    // the rom is loaded from 0x200 on a fresh machine
+    checksum(&machine.bus().memory()[0x200..])
+}
+
+fn keys_to_bits(keys: &[bool; KEYPAD_SIZE]) -> u16 {
+    keys.iter()
+        .enumerate()
+        .filter(|(_, &pressed)| pressed)
+        .fold(0, |bits, (key, _)| bits | 1 << key)
+}
+
+fn bits_to_keys(bits: u16) -> [bool; KEYPAD_SIZE] {
+    let mut keys = [false; KEYPAD_SIZE];
+    for (key, pressed) in keys.iter_mut().enumerate() {
+        *pressed = bits & 1 << key != 0;
+    }
+
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use chip8::rom::Rom;
+
+    use super::*;
+
+    // C0FF: V0 = random, 7101: add 1 to V1, 1200: jump back
+    const PROGRAM: [u8; 6] = [0xC0, 0xFF, 0x71, 0x01, 0x12, 0x00];
+
+    fn create_machine(program: &[u8]) -> Machine {
+        Machine::new(Rom::from_bytes(program.to_vec()))
+    }
+
+    #[test]
+    fn test_keys_bits() {
+        let mut keys = [false; KEYPAD_SIZE];
+        keys[0x1] = true;
+        keys[0xF] = true;
+
+        assert_eq!(keys_to_bits(&keys), 0x8002);
+        assert_eq!(bits_to_keys(0x8002), keys);
+    }
+
+    #[test]
+    fn test_lockstep() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let guest = thread::spawn(move || {
+            let mut machine = create_machine(&PROGRAM);
+            let mut netplay = Netplay::join(addr, &mut machine).unwrap();
+            let mut keys = [false; KEYPAD_SIZE];
+            keys[0xA] = true;
+
+            for _ in 0..10 {
+                netplay.run_frame(&mut machine, &keys).unwrap();
+            }
+
+            // closing with unread keys would reset the host connection
+            (machine, netplay)
+        });
+
+        let mut machine = create_machine(&PROGRAM);
+        machine.set_cpu_frequency(120.0);
+        let mut netplay = Netplay::host(&listener, &mut machine, 2).unwrap();
+        let mut keys = [false; KEYPAD_SIZE];
+        keys[0x1] = true;
+
+        for _ in 0..10 {
+            netplay.run_frame(&mut machine, &keys).unwrap();
+        }
+        let (guest, _netplay) = guest.join().unwrap();
+
+        assert_eq!(guest.cpu_frequency(), 120.0);
+        assert_eq!(guest.save_state(), machine.save_state());
+        assert!(machine.bus().keys[0x1] && machine.bus().keys[0xA]);
+    }
+
+    #[test]
+    fn test_invalid_frequency() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let guest = thread::spawn(move || {
+            let mut machine = create_machine(&PROGRAM);
+            Netplay::join(addr, &mut machine).map(|_| ())
+        });
+
+        // a host asking for an endless frame
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut hello = header();
+        hello.push(0);
+        hello.extend(1_u64.to_le_bytes());
+        hello.extend(f64::INFINITY.to_bits().to_le_bytes());
+        hello.extend(rom_checksum(&create_machine(&PROGRAM)).to_le_bytes());
+        stream.write_all(&hello).unwrap();
+
+        assert!(matches!(
+            guest.join().unwrap(),
+            Err(NetplayError::Handshake("invalid cpu frequency"))
+        ));
+    }
+
+    #[test]
+    fn test_rom_mismatch() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let guest = thread::spawn(move || {
+            let mut machine = create_machine(&[0x12, 0x00]);
+            Netplay::join(addr, &mut machine).map(|_| ())
+        });
+
+        let mut machine = create_machine(&PROGRAM);
+        let host = Netplay::host(&listener, &mut machine, 0).map(|_| ());
+
+        assert!(matches!(host, Err(NetplayError::RomMismatch)));
+        assert!(matches!(
+            guest.join().unwrap(),
+            Err(NetplayError::RomMismatch)
+        ));
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    collections::BTreeMap,
+    panic::{self, AssertUnwindSafe},
+};
+
+use chip8::{machine::Machine, rom::Rom};
+use rayon::prelude::*;
+
+use crate::{
+    checksum,
+    coverage::{Coverage, Usage},
+    script::{self, Script, Stop},
+};
+
+/// A machine to run, on its own rom, random numbers and keys
+#[derive(Clone)]
+pub struct Instance {
+    /// Shown with its report, the path of the rom usually
+    pub name: String,
+    pub rom: Rom,
+    /// Instructions per second
+    pub speed: f64,
+    /// Seed of the random numbers
+    pub seed: u64,
+    pub script: Script,
+}
+
+/// How an instance ended
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub name: String,
+    pub seed: u64,
+    /// Frames run
+    pub frames: u64,
+    /// Why it stopped, none when the emulator panicked
+    pub stop: Option<Stop>,
+    /// `Bus::frame_hash` of the last screen
+    pub screen: u64,
+    /// `checksum` of the last state
+    pub state: u32,
+    /// Addresses executed as code, when the coverage was recorded
+    pub code: Option<usize>,
+}
+
+/// Run each instance for up to `frames` frames, until it halts or waits
+/// for a key after its script, on all the cores
+///
+/// The reports are in the order of `instances`. Recording the coverage
+/// makes the runs several times slower.
+pub fn run(instances: &[Instance], frames: u64, coverage: bool) -> Vec<Report> {
+    instances
+        .par_iter()
+        .map(|instance| run_one(instance, frames, coverage))
+        .collect()
+}
+
+fn run_one(instance: &Instance, frames: u64, coverage: bool) -> Report {
+    let mut machine = Machine::new(instance.rom.clone());
+    machine.set_cpu_frequency(instance.speed);
+    machine.cpu_mut().set_seed(instance.seed);
+    let mut script = instance.script.clone();
+    let mut recorded = coverage.then(|| Coverage::new(&machine));
+
+    // any rom runs without panicking, a bug of the emulator stops only
+    // this instance instead of the whole pool
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        script::run_with(&mut machine, &mut script, frames, true, |machine| {
+            match &mut recorded {
+                Some(coverage) => coverage.run_frame(machine),
+                None => machine.run_frame(),
+            }
+        })
+    }));
+    let (frames, stop) = match result {
+        Ok((frames, stop)) => (frames, Some(stop)),
+        Err(_) => (0, None),
+    };
+
+    let code = recorded.map(|coverage| {
+        (0..machine.bus().memory().len() as u16)
+            .filter(|&addr| coverage.usage(addr) == Some(Usage::Code))
+            .count()
+    });
+
+    Report {
+        name: instance.name.clone(),
+        seed: instance.seed,
+        frames,
+        stop,
+        screen: machine.bus().frame_hash(),
+        state: checksum(&machine.save_state()),
+        code,
+    }
+}
+
+/// The reports of a run put together
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub instances: usize,
+    pub halted: usize,
+    /// Waiting for a key once their script was over
+    pub waiting: usize,
+    /// Still running after all the frames
+    pub timeouts: usize,
+    pub crashes: usize,
+    /// Number of instances by last screen
+    pub screens: BTreeMap<u64, usize>,
+    /// Most addresses executed by an instance
+    pub max_code: Option<usize>,
+}
+
+impl Summary {
+    pub fn of(reports: &[Report]) -> Self {
+        let mut summary = Summary {
+            instances: reports.len(),
+            ..Summary::default()
+        };
+
+        for report in reports {
+            match report.stop {
+                Some(Stop::Halted) => summary.halted += 1,
+                Some(Stop::KeyWait) => summary.waiting += 1,
+                Some(Stop::FrameLimit) => summary.timeouts += 1,
+                None => summary.crashes += 1,
+            }
+            if report.stop.is_some() {
+                *summary.screens.entry(report.screen).or_default() += 1;
+            }
+            summary.max_code = summary.max_code.max(report.code);
+        }
+
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8::machine::CPU_FREQUENCY;
+
+    use super::*;
+
+    fn instance(rom: &[u8], seed: u64) -> Instance {
+        Instance {
+            name: format!("{}", seed),
+            rom: Rom::from_bytes(rom.to_vec()),
+            speed: CPU_FREQUENCY,
+            seed,
+            script: Script::default(),
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        // C00F: V0 = random, F029: I = its digit, D115: draw it, 1206: halt
+        let rom = [0xC0, 0x0F, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06];
+        // the first random numbers of close seeds are the same
+        let seeds = (0..32).map(|n| n << 48);
+        let instances: Vec<_> =
+            seeds.clone().map(|seed| instance(&rom, seed)).collect();
+
+        let reports = run(&instances, 60, true);
+        assert_eq!(reports.len(), 32);
+        for (report, seed) in reports.iter().zip(seeds) {
+            assert_eq!(report.seed, seed);
+            assert_eq!(report.stop, Some(Stop::Halted));
+            assert_eq!(report.code, Some(rom.len()));
+        }
+        // the same for each run
+        assert_eq!(run(&instances, 60, false)[5].state, reports[5].state);
+
+        let summary = Summary::of(&reports);
+        assert_eq!(summary.instances, 32);
+        assert_eq!(summary.halted, 32);
+        assert!(summary.screens.len() > 1);
+        assert_eq!(summary.screens.values().sum::<usize>(), 32);
+        assert_eq!(summary.max_code, Some(rom.len()));
+    }
+
+    #[test]
+    fn test_timeout_and_key_wait() {
+        let instances = [
+            instance(&[0x70, 0x01, 0x12, 0x00], 0), // V0 += 1 forever
+            instance(&[0xF0, 0x0A], 0),             // wait for a key
+        ];
+        let reports = run(&instances, 10, false);
+        assert_eq!(reports[0].frames, 10);
+        assert_eq!(reports[1].stop, Some(Stop::KeyWait));
+
+        let summary = Summary::of(&reports);
+        assert_eq!((summary.timeouts, summary.waiting), (1, 1));
+        assert_eq!((summary.halted, summary.crashes), (0, 0));
+        assert_eq!(summary.max_code, None);
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::VecDeque;
+
+/// States of 60 seconds of frames
+pub const REWIND_FRAMES: usize = 60 * 60;
+
+/// Memory used by a `Rewind`, to tune its length
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RewindStats {
+    /// States that can be restored, the newest included
+    pub states: usize,
+    /// Bytes of the deltas and of the newest state
+    pub bytes: usize,
+    /// Bytes allocated by the pool, used or free
+    pub allocated: usize,
+    /// Bytes the states would take saved in full
+    pub raw: usize,
+}
+
+/// The last states of a machine from `Machine::save_state`, for rewinding
+/// frame by frame
+///
+/// Only the newest state is kept in full, each older one is stored as the
+/// bytes that differ from the state after it, so that the oldest can be
+/// dropped alone. The buffers of the dropped deltas are reused by the next
+/// ones, a full `Rewind` doesn't allocate.
+pub struct Rewind {
+    newest: Vec<u8>,
+    /// Oldest first, `deltas[i]` turns the state after it into its own
+    deltas: VecDeque<Vec<u8>>,
+    free: Vec<Vec<u8>>,
+    len: usize,
+}
+
+impl Rewind {
+    /// Keeping at most `len` states, at least 1
+    pub fn new(len: usize) -> Self {
+        Self {
+            newest: vec![],
+            deltas: VecDeque::with_capacity(len),
+            free: vec![],
+            len: len.max(1),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self.newest.is_empty() {
+            true => 0,
+            false => self.deltas.len() + 1,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Record the state of a frame, the oldest is dropped when full; a state
+    /// of another size, from another machine, starts over
+    pub fn push(&mut self, state: &[u8]) {
+        if state.len() != self.newest.len() {
+            self.clear();
+        } else if self.len() == self.len {
+            if let Some(delta) = self.deltas.pop_front() {
+                self.free.push(delta);
+            }
+        }
+
+        if self.len() < self.len && !self.newest.is_empty() {
+            let mut delta = self.free.pop().unwrap_or_default();
+            delta.clear();
+            encode(&self.newest, state, &mut delta);
+            self.deltas.push_back(delta);
+        }
+
+        self.newest.clear();
+        self.newest.extend_from_slice(state);
+    }
+
+    /// Drop the newest state and return the one before, None when there is
+    /// none to go back to
+    pub fn pop(&mut self) -> Option<&[u8]> {
+        let delta = self.deltas.pop_back()?;
+        apply(&delta, &mut self.newest);
+        self.free.push(delta);
+
+        Some(&self.newest)
+    }
+
+    /// The state last pushed, or restored by `pop`
+    pub fn newest(&self) -> Option<&[u8]> {
+        match self.newest.is_empty() {
+            true => None,
+            false => Some(&self.newest),
+        }
+    }
+
+    /// Forget the states, the buffers are kept
+    pub fn clear(&mut self) {
+        self.free.extend(self.deltas.drain(..));
+        self.newest.clear();
+    }
+
+    pub fn stats(&self) -> RewindStats {
+        let bytes =
+            self.newest.len() + self.deltas.iter().map(Vec::len).sum::<usize>();
+        let allocated = self.newest.capacity()
+            + self.deltas.iter().map(Vec::capacity).sum::<usize>()
+            + self.free.iter().map(Vec::capacity).sum::<usize>();
+
+        RewindStats {
+            states: self.len(),
+            bytes,
+            allocated,
+            raw: self.len() * self.newest.len(),
+        }
+    }
+}
+
+/// Append to `delta` the runs of bytes of `to` differing from `from`, as the
+/// number of equal bytes before the run, its length, then the bytes XORed
+fn encode(from: &[u8], to: &[u8], delta: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < to.len() {
+        let equal = from[i..]
+            .iter()
+            .zip(&to[i..])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if i + equal == to.len() {
+            break;
+        }
+        let start = i + equal;
+        let changed = from[start..]
+            .iter()
+            .zip(&to[start..])
+            .take_while(|(a, b)| a != b)
+            .count();
+
+        write_varint(delta, equal);
+        write_varint(delta, changed);
+        delta.extend((start..start + changed).map(|j| from[j] ^ to[j]));
+        i = start + changed;
+    }
+}
+
+/// Turn the `to` of `encode` into its `from`, or the other way
+fn apply(delta: &[u8], state: &mut [u8]) {
+    let mut bytes = delta.iter().copied();
+    let mut i = 0;
+    while let (Some(equal), Some(changed)) =
+        (read_varint(&mut bytes), read_varint(&mut bytes))
+    {
+        i += equal;
+        for byte in &mut state[i..i + changed] {
+            *byte ^= bytes.next().unwrap_or(0);
+        }
+        i += changed;
+    }
+}
+
+/// LEB128, 7 bits per byte with the high bit set on all but the last
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Option<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes.next()?;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8::{machine::Machine, rom::Rom};
+
+    use super::*;
+
+    fn create_machine() -> Machine {
+        let mut machine = Machine::new(Rom::from_bytes(vec![
+            0xC0, 0x3F, // V0 = random x
+            0xC1, 0x1F, // V1 = random y
+            0xA0, 0x00, // I = font 0
+            0xD0, 0x15, // draw it
+            0x72, 0x01, // V2 += 1
+            0x12, 0x00, // loop
+        ]));
+        machine.cpu_mut().set_seed(1);
+        machine
+    }
+
+    #[test]
+    fn test_encode_apply() {
+        let from = [0, 1, 2, 3, 4, 5, 6, 7];
+        let to = [0, 9, 9, 3, 4, 5, 6, 8];
+        let mut delta = vec![];
+        encode(&from, &to, &mut delta);
+        assert_eq!(delta, [1, 2, 1 ^ 9, 2 ^ 9, 4, 1, 7 ^ 8]);
+
+        let mut state = to;
+        apply(&delta, &mut state);
+        assert_eq!(state, from);
+
+        delta.clear();
+        encode(&from, &from, &mut delta);
+        assert!(delta.is_empty());
+    }
+
+    #[test]
+    fn test_varint() {
+        let mut bytes = vec![];
+        for value in [0, 0x7F, 0x80, 5000, usize::MAX] {
+            write_varint(&mut bytes, value);
+        }
+        let mut iter = bytes.into_iter();
+        for value in [0, 0x7F, 0x80, 5000, usize::MAX] {
+            assert_eq!(read_varint(&mut iter), Some(value));
+        }
+        assert_eq!(read_varint(&mut iter), None);
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut machine = create_machine();
+        let mut rewind = Rewind::new(10);
+        let mut states = vec![];
+        for _ in 0..15 {
+            machine.run_frame();
+            states.push(machine.save_state());
+            rewind.push(states.last().unwrap());
+        }
+        assert_eq!(rewind.len(), 10);
+        assert_eq!(rewind.newest(), states.last().map(Vec::as_slice));
+
+        for state in states[5..14].iter().rev() {
+            assert_eq!(rewind.pop(), Some(state.as_slice()));
+        }
+        assert_eq!(rewind.pop(), None);
+        assert_eq!(rewind.len(), 1);
+
+        // going on from a restored state
+        machine.load_state(&states[5]).unwrap();
+        machine.run_frame();
+        rewind.push(&machine.save_state());
+        assert_eq!(rewind.pop(), Some(states[5].as_slice()));
+    }
+
+    #[test]
+    fn test_hires_toggle() {
+        let mut machine = Machine::new(Rom::from_bytes(vec![
+            0x00, 0xFF, // hires
+            0x22, 0x06, // call 0x206
+            0x12, 0x00, // loop
+            0x00, 0xFE, // lores
+            0x00, 0xEE, // return
+        ]));
+        let mut rewind = Rewind::new(10);
+        let mut states = vec![machine.save_state()];
+        rewind.push(&states[0]);
+        for _ in 0..9 {
+            machine.step();
+            states.push(machine.save_state());
+            rewind.push(states.last().unwrap());
+        }
+
+        // the screen modes and the calls keep the size of the states
+        assert_eq!(rewind.len(), 10);
+        for state in states[..9].iter().rev() {
+            assert_eq!(rewind.pop(), Some(state.as_slice()));
+        }
+    }
+
+    #[test]
+    fn test_other_size() {
+        let mut rewind = Rewind::new(10);
+        rewind.push(&[1, 2, 3]);
+        rewind.push(&[1, 2, 4]);
+        rewind.push(&[1, 2]);
+
+        assert_eq!(rewind.len(), 1);
+        assert_eq!(rewind.pop(), None);
+        assert_eq!(rewind.newest(), Some([1, 2].as_slice()));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut machine = create_machine();
+        let mut rewind = Rewind::new(REWIND_FRAMES);
+        for _ in 0..2 * REWIND_FRAMES {
+            machine.run_frame();
+            rewind.push(&machine.save_state());
+        }
+
+        let stats = rewind.stats();
+        assert_eq!(stats.states, REWIND_FRAMES);
+        assert_eq!(stats.raw, REWIND_FRAMES * machine.save_state().len());
+        // a few hundred bytes per state instead of a few kilobytes
+        assert!(stats.bytes < 500_000, "{:?}", stats);
+        assert!(stats.allocated < 2 * stats.bytes, "{:?}", stats);
+    }
+}
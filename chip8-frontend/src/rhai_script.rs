@@ -0,0 +1,393 @@
+use std::{
+    fs, mem,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use chip8::{bus::KEYPAD_SIZE, cpu::V_SIZE, machine::Machine};
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+
+/// Function of the script called before each frame
+const ON_FRAME: &str = "on_frame";
+/// Limit of a call, so that an endless loop stops with an error
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+type RhaiResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// What the script sees of the machine, read before each call to
+/// `on_frame` and written back after it
+#[derive(Default)]
+struct State {
+    memory: Vec<u8>,
+    registers: [u8; V_SIZE],
+    pc: u16,
+    index: u16,
+    keys: [bool; KEYPAD_SIZE],
+    frame: u64,
+    hud: Vec<String>,
+    /// Printed text, until taken
+    output: Vec<String>,
+    /// Breakpoints set or cleared, until taken
+    breakpoints: Vec<(u16, bool)>,
+}
+
+impl State {
+    fn read(&mut self, machine: &Machine, frame: u64) {
+        self.memory.clear();
+        self.memory.extend_from_slice(machine.bus().memory());
+        self.registers = *machine.cpu().registers();
+        self.pc = machine.cpu().pc();
+        self.index = machine.cpu().index();
+        self.keys = machine.bus().keys;
+        self.frame = frame;
+        self.hud.clear();
+    }
+
+    fn write(&self, machine: &mut Machine) {
+        machine.bus_mut().memory_mut().copy_from_slice(&self.memory);
+        machine.bus_mut().keys = self.keys;
+        let cpu = machine.cpu_mut();
+        *cpu.registers_mut() = self.registers;
+        cpu.set_pc(self.pc);
+        cpu.set_index(self.index);
+    }
+
+    fn address(&self, addr: i64) -> RhaiResult<usize> {
+        match usize::try_from(addr) {
+            Ok(addr) if addr < self.memory.len() => Ok(addr),
+            _ => Err(format!("address {:#x} out of memory", addr).into()),
+        }
+    }
+}
+
+fn register(x: i64) -> RhaiResult<usize> {
+    match usize::try_from(x) {
+        Ok(x) if x < V_SIZE => Ok(x),
+        _ => Err(format!("no register V{:X}", x).into()),
+    }
+}
+
+fn key(key: i64) -> RhaiResult<usize> {
+    match usize::try_from(key) {
+        Ok(key) if key < KEYPAD_SIZE => Ok(key),
+        _ => Err(format!("no key {:X}", key).into()),
+    }
+}
+
+/// A rhai script run along the machine, for cheats, bots and text over
+/// the screen
+///
+/// The script is run once when it is loaded, then its `on_frame` function
+/// before each frame. `this` is a map kept from one call to the next.
+///
+/// ```rhai
+/// fn on_frame() {
+///     poke(0x3f0, 3);                 // infinite lives
+///     if frame() % 60 == 0 { press(5) } else { release(5) }
+///     hud("V0 " + reg(0));
+/// }
+/// ```
+///
+/// - `peek(addr)`, `poke(addr, value)`: bytes of the memory
+/// - `reg(x)`, `set_reg(x, value)`: registers V0 to VF
+/// - `pc()`, `set_pc(addr)`, `index()`, `set_index(addr)`
+/// - `press(key)`, `release(key)`, `is_pressed(key)`: hex keys
+/// - `frame()`: number of the frame about to run, from 0
+/// - `hud(text)`: a line shown over the screen until the next frame
+/// - `set_breakpoint(addr, enabled)`: for the frontends with breakpoints
+pub struct RhaiScript {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    this: Dynamic,
+    state: Arc<Mutex<State>>,
+    frame: u64,
+    hud: Vec<String>,
+}
+
+impl RhaiScript {
+    pub fn new_from(path: &str) -> Result<Self, String> {
+        let source =
+            fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+
+        Self::new(&source).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn new(source: &str) -> Result<Self, String> {
+        let state = Arc::new(Mutex::new(State::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print({
+            let state = state.clone();
+            move |text| lock(&state).output.push(text.to_string())
+        });
+        register_functions(&mut engine, &state);
+
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            engine,
+            ast,
+            scope,
+            this: Dynamic::from_map(Map::new()),
+            state,
+            frame: 0,
+            hud: vec![],
+        })
+    }
+
+    /// Call `on_frame` with the state of `machine`, then run its frame
+    ///
+    /// The frame isn't run when the script fails.
+    pub fn run_frame(&mut self, machine: &mut Machine) -> Result<(), String> {
+        self.on_frame(machine)?;
+        machine.run_frame();
+
+        Ok(())
+    }
+
+    /// Call `on_frame` with the state of `machine`, which is about to run a
+    /// frame
+    pub fn on_frame(&mut self, machine: &mut Machine) -> Result<(), String> {
+        let defined = self
+            .ast
+            .iter_functions()
+            .any(|f| f.name == ON_FRAME && f.params.is_empty());
+        if defined {
+            self.call(machine)?;
+        }
+        self.frame += 1;
+
+        Ok(())
+    }
+
+    /// Run `command` with the state of `machine`, returns its value as
+    /// text, empty for none
+    ///
+    /// The variables and functions it defines are kept for the next
+    /// commands, a new `on_frame` replaces the previous one.
+    pub fn eval(
+        &mut self,
+        machine: &mut Machine,
+        command: &str,
+    ) -> Result<String, String> {
+        let ast = self.engine.compile(command).map_err(|e| e.to_string())?;
+        self.ast.combine(ast.clone_functions_only());
+        let ast = self.ast.clone_functions_only().merge(&ast);
+
+        lock(&self.state).read(machine, self.frame);
+        let value = self
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut self.scope, &ast)
+            .map_err(|e| e.to_string())?;
+        lock(&self.state).write(machine);
+
+        Ok(match value.is_unit() {
+            true => String::new(),
+            false => value.to_string(),
+        })
+    }
+
+    /// Lines given to `hud` by the last call
+    pub fn hud(&self) -> &[String] {
+        &self.hud
+    }
+
+    /// Lines printed since the last call
+    pub fn take_output(&mut self) -> Vec<String> {
+        mem::take(&mut lock(&self.state).output)
+    }
+
+    /// Breakpoints set, or cleared when false, since the last call
+    pub fn take_breakpoints(&mut self) -> Vec<(u16, bool)> {
+        mem::take(&mut lock(&self.state).breakpoints)
+    }
+
+    fn call(&mut self, machine: &mut Machine) -> Result<(), String> {
+        lock(&self.state).read(machine, self.frame);
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.this);
+        self.engine
+            .call_fn_with_options::<Dynamic>(
+                options,
+                &mut self.scope,
+                &self.ast,
+                ON_FRAME,
+                (),
+            )
+            .map_err(|e| format!("frame {}: {}", self.frame, e))?;
+
+        let state = lock(&self.state);
+        state.write(machine);
+        self.hud.clone_from(&state.hud);
+
+        Ok(())
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn register_functions(engine: &mut Engine, state: &Arc<Mutex<State>>) {
+    let shared = state.clone();
+    engine.register_fn("peek", move |addr: i64| -> RhaiResult<i64> {
+        let state = lock(&shared);
+        Ok(state.memory[state.address(addr)?] as i64)
+    });
+    let shared = state.clone();
+    engine.register_fn("poke", move |addr: i64, value: i64| {
+        let mut state = lock(&shared);
+        let addr = state.address(addr)?;
+        state.memory[addr] = value as u8;
+        RhaiResult::Ok(())
+    });
+
+    let shared = state.clone();
+    engine.register_fn("reg", move |x: i64| -> RhaiResult<i64> {
+        Ok(lock(&shared).registers[register(x)?] as i64)
+    });
+    let shared = state.clone();
+    engine.register_fn("set_reg", move |x: i64, value: i64| {
+        lock(&shared).registers[register(x)?] = value as u8;
+        RhaiResult::Ok(())
+    });
+
+    let shared = state.clone();
+    engine.register_fn("pc", move || lock(&shared).pc as i64);
+    let shared = state.clone();
+    engine.register_fn("set_pc", move |addr: i64| {
+        let mut state = lock(&shared);
+        state.pc = state.address(addr)? as u16;
+        RhaiResult::Ok(())
+    });
+    let shared = state.clone();
+    engine.register_fn("index", move || lock(&shared).index as i64);
+    let shared = state.clone();
+    engine.register_fn("set_index", move |addr: i64| {
+        let mut state = lock(&shared);
+        state.index = state.address(addr)? as u16;
+        RhaiResult::Ok(())
+    });
+
+    let shared = state.clone();
+    engine.register_fn("press", move |k: i64| {
+        lock(&shared).keys[key(k)?] = true;
+        RhaiResult::Ok(())
+    });
+    let shared = state.clone();
+    engine.register_fn("release", move |k: i64| {
+        lock(&shared).keys[key(k)?] = false;
+        RhaiResult::Ok(())
+    });
+    let shared = state.clone();
+    engine.register_fn("is_pressed", move |k: i64| -> RhaiResult<bool> {
+        Ok(lock(&shared).keys[key(k)?])
+    });
+
+    let shared = state.clone();
+    engine.register_fn("frame", move || lock(&shared).frame as i64);
+    let shared = state.clone();
+    engine.register_fn("hud", move |text: &str| {
+        lock(&shared).hud.push(text.to_string());
+    });
+    let shared = state.clone();
+    engine.register_fn("set_breakpoint", move |addr: i64, enabled: bool| {
+        let mut state = lock(&shared);
+        let addr = state.address(addr)? as u16;
+        state.breakpoints.push((addr, enabled));
+        RhaiResult::Ok(())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8::rom::Rom;
+
+    use super::*;
+
+    fn machine() -> Machine {
+        // 6005: V0 = 5, 1202: loop
+        Machine::new(Rom::from_bytes(vec![0x60, 0x05, 0x12, 0x02]))
+    }
+
+    #[test]
+    fn test_run_frame() {
+        let mut machine = machine();
+        let mut script = RhaiScript::new(
+            "
+            fn on_frame() {
+                this.calls = if this.calls == () { 1 } else { this.calls + 1 };
+                if frame() == 1 {
+                    poke(0x300, reg(0) + 1);
+                    set_reg(1, 7);
+                    press(0xA);
+                }
+                hud(`frame ${frame()} calls ${this.calls}`);
+            }
+            ",
+        )
+        .unwrap();
+
+        script.run_frame(&mut machine).unwrap();
+        assert_eq!(script.hud(), ["frame 0 calls 1"]);
+        assert_eq!(machine.bus().memory()[0x300], 0);
+
+        script.run_frame(&mut machine).unwrap();
+        assert_eq!(script.hud(), ["frame 1 calls 2"]);
+        assert_eq!(machine.bus().memory()[0x300], 6);
+        assert_eq!(machine.cpu().registers()[1], 7);
+        assert!(machine.bus().keys[0xA]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(RhaiScript::new("fn on_frame() {").is_err());
+
+        let mut machine = machine();
+        let mut script =
+            RhaiScript::new("fn on_frame() { peek(0x1000) }").unwrap();
+        let error = script.run_frame(&mut machine).unwrap_err();
+        assert!(error.starts_with("frame 0: "), "{}", error);
+        assert_eq!(machine.cpu().pc(), 0x200);
+
+        // without on_frame, the frames run as they are
+        let mut script = RhaiScript::new("let x = 1;").unwrap();
+        script.run_frame(&mut machine).unwrap();
+        assert!(script.hud().is_empty());
+    }
+
+    #[test]
+    fn test_eval() {
+        let mut machine = machine();
+        let mut script = RhaiScript::new("").unwrap();
+
+        assert_eq!(script.eval(&mut machine, "peek(0x200)"), Ok("96".into()));
+        assert_eq!(
+            script.eval(&mut machine, "let x = 3; poke(0x300, x)"),
+            Ok(String::new())
+        );
+        assert_eq!(machine.bus().memory()[0x300], 3);
+        assert_eq!(script.eval(&mut machine, "x + 1"), Ok("4".into()));
+
+        script
+            .eval(&mut machine, "set_breakpoint(0x202, true); print(pc())")
+            .unwrap();
+        assert_eq!(script.take_output(), ["512"]);
+        assert_eq!(script.take_breakpoints(), [(0x202, true)]);
+        assert!(script.take_breakpoints().is_empty());
+
+        // a function defined by a command is called before the frames
+        script
+            .eval(&mut machine, "fn on_frame() { set_reg(2, 9) }")
+            .unwrap();
+        script.run_frame(&mut machine).unwrap();
+        assert_eq!(machine.cpu().registers()[2], 9);
+
+        assert!(script.eval(&mut machine, "reg(16)").is_err());
+    }
+}
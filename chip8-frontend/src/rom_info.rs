@@ -0,0 +1,307 @@
+use std::fmt::{self, Display};
+
+use chip8::disasm::disassemble_at;
+
+use crate::checksum;
+
+/// Address of the first byte of a rom
+const PROGRAM_START: u16 = 0x200;
+const MAX_ROM_SIZE: usize = 0x1000 - PROGRAM_START as usize;
+/// Instructions disassembled from the entry point
+const ENTRY_INSTRUCTIONS: usize = 8;
+
+/// Chip8 variants, each extending the previous one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What can be told of a rom without running it
+///
+/// The opcodes are read at every even offset, the data of a rom may also
+/// look like instructions or extensions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RomInfo {
+    pub size: usize,
+    /// FNV-1a, as used by the save slots
+    pub checksum: u32,
+    /// SHA-1 in hex, the key of the rom databases
+    pub sha1: String,
+    /// The most recent variant one of the opcodes belongs to
+    pub platform: Platform,
+    /// Address and opcode of the instructions of the variants, the XO-CHIP
+    /// ones only run in the XO-CHIP mode of the machine
+    pub extensions: Vec<(u16, u16)>,
+    /// Key wait, keys, sound, random or subroutines
+    pub features: Vec<&'static str>,
+    /// Opcodes of each instruction like `8XY4`, `data` for the words which
+    /// aren't any, the most used first
+    pub histogram: Vec<(&'static str, usize)>,
+    /// Address, opcode and disassembly of the first instructions
+    pub entry: Vec<(u16, u16, String)>,
+}
+
+impl RomInfo {
+    pub fn new(data: &[u8]) -> Self {
+        let opcodes: Vec<(u16, u16)> = (0..data.len())
+            .step_by(2)
+            .map(|offset| {
+                let (opcode, _) = disassemble_at(data, offset as u16);
+                (PROGRAM_START + offset as u16, opcode)
+            })
+            .collect();
+
+        let extensions: Vec<(u16, u16)> = opcodes
+            .iter()
+            .copied()
+            .filter(|&(_, opcode)| platform(opcode) != Platform::Chip8)
+            .collect();
+        let platform = extensions
+            .iter()
+            .map(|&(_, opcode)| platform(opcode))
+            .max()
+            .unwrap_or(Platform::Chip8);
+
+        let features = [
+            ("key wait", 0xF0FF, 0xF00A),
+            ("keys", 0xF000, 0xE000),
+            ("sound", 0xF0FF, 0xF018),
+            ("random", 0xF000, 0xC000),
+            ("subroutines", 0xF000, 0x2000),
+        ]
+        .iter()
+        .filter(|&&(_, mask, pattern)| {
+            opcodes.iter().any(|(_, opcode)| opcode & mask == pattern)
+        })
+        .map(|&(feature, _, _)| feature)
+        .collect();
+
+        let mut histogram: Vec<(&'static str, usize)> = vec![];
+        for &(_, opcode) in &opcodes {
+            let name = instruction(opcode);
+            match histogram.iter_mut().find(|(known, _)| *known == name) {
+                Some((_, count)) => *count += 1,
+                None => histogram.push((name, 1)),
+            }
+        }
+        histogram.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        let entry = (0..data.len().min(ENTRY_INSTRUCTIONS * 2))
+            .step_by(2)
+            .map(|offset| {
+                let (opcode, text) = disassemble_at(data, offset as u16);
+                (PROGRAM_START + offset as u16, opcode, text)
+            })
+            .collect();
+
+        Self {
+            size: data.len(),
+            checksum: checksum(data),
+            sha1: sha1(data).iter().map(|b| format!("{:02x}", b)).collect(),
+            platform,
+            extensions,
+            features,
+            histogram,
+            entry,
+        }
+    }
+
+    /// The end of the rom is cut when it is loaded
+    pub fn too_big(&self) -> bool {
+        self.size > MAX_ROM_SIZE
+    }
+}
+
+/// The variant `opcode` first appeared in, DXY0 is only counted as a 16x16
+/// SUPER-CHIP sprite
+fn platform(opcode: u16) -> Platform {
+    match opcode {
+        0x00FB..=0x00FF | 0x00C1..=0x00CF => Platform::SuperChip,
+        _ if opcode & 0xF00F == 0xD000 => Platform::SuperChip,
+        _ if matches!(opcode & 0xF0FF, 0xF030 | 0xF075 | 0xF085) => {
+            Platform::SuperChip
+        }
+        0x00D1..=0x00DF | 0xF000 | 0xF002 => Platform::XoChip,
+        _ if matches!(opcode & 0xF00F, 0x5002 | 0x5003) => Platform::XoChip,
+        _ if opcode & 0xF0FF == 0xF03A => Platform::XoChip,
+        _ if opcode & 0xF0FF == 0xF001 => Platform::XoChip,
+        _ => Platform::Chip8,
+    }
+}
+
+/// The chip8 instruction of `opcode`, by its opcode pattern
+fn instruction(opcode: u16) -> &'static str {
+    let x = (opcode & 0x0F00) >> 8;
+    match (opcode >> 12, x, (opcode & 0x00F0) >> 4, opcode & 0x000F) {
+        (0x0, 0x0, 0xE, 0x0) => "00E0",
+        (0x0, 0x0, 0xE, 0xE) => "00EE",
+        (0x0, _, _, _) => "0NNN",
+        (0x1, _, _, _) => "1NNN",
+        (0x2, _, _, _) => "2NNN",
+        (0x3, _, _, _) => "3XNN",
+        (0x4, _, _, _) => "4XNN",
+        (0x5, _, _, 0x0) => "5XY0",
+        (0x6, _, _, _) => "6XNN",
+        (0x7, _, _, _) => "7XNN",
+        (0x8, _, _, 0x0) => "8XY0",
+        (0x8, _, _, 0x1) => "8XY1",
+        (0x8, _, _, 0x2) => "8XY2",
+        (0x8, _, _, 0x3) => "8XY3",
+        (0x8, _, _, 0x4) => "8XY4",
+        (0x8, _, _, 0x5) => "8XY5",
+        (0x8, _, _, 0x6) => "8XY6",
+        (0x8, _, _, 0x7) => "8XY7",
+        (0x8, _, _, 0xE) => "8XYE",
+        (0x9, _, _, 0x0) => "9XY0",
+        (0xA, _, _, _) => "ANNN",
+        (0xB, _, _, _) => "BNNN",
+        (0xC, _, _, _) => "CXNN",
+        (0xD, _, _, _) => "DXYN",
+        (0xE, _, 0x9, 0xE) => "EX9E",
+        (0xE, _, 0xA, 0x1) => "EXA1",
+        (0xF, _, 0x0, 0x7) => "FX07",
+        (0xF, _, 0x0, 0xA) => "FX0A",
+        (0xF, _, 0x1, 0x5) => "FX15",
+        (0xF, _, 0x1, 0x8) => "FX18",
+        (0xF, _, 0x1, 0xE) => "FX1E",
+        (0xF, _, 0x2, 0x9) => "FX29",
+        (0xF, _, 0x3, 0x3) => "FX33",
+        (0xF, _, 0x5, 0x5) => "FX55",
+        (0xF, _, 0x6, 0x5) => "FX65",
+        _ => "data",
+    }
+}
+
+/// SHA-1 digest of `data`
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] =
+        [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    // a 1 bit, zeros up to 8 bytes before the end of a block, the length
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend((data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, h) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_sha1() {
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(&sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        // two blocks
+        assert_eq!(
+            hex(&sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    #[test]
+    fn test_new() {
+        // 00E0: clear, 00FF: hires, 6005: V0 = 5, 6105: V1 = 5,
+        // F00A: wait key, 1208: loop
+        let data = [
+            0x00, 0xE0, 0x00, 0xFF, 0x60, 0x05, 0x61, 0x05, 0xF0, 0x0A, 0x12,
+            0x08,
+        ];
+        let info = RomInfo::new(&data);
+
+        assert_eq!(info.size, 12);
+        assert_eq!(info.checksum, checksum(&data));
+        assert_eq!(info.sha1.len(), 40);
+        assert_eq!(info.platform, Platform::SuperChip);
+        assert_eq!(info.extensions, vec![(0x202, 0x00FF)]);
+        assert_eq!(info.features, vec!["key wait"]);
+        assert_eq!(
+            info.histogram,
+            vec![
+                ("6XNN", 2),
+                ("00E0", 1),
+                ("0NNN", 1),
+                ("1NNN", 1),
+                ("FX0A", 1)
+            ]
+        );
+        assert_eq!(info.entry.len(), 6);
+        assert_eq!(info.entry[5], (0x20A, 0x1208, "JP 0x208".to_string()));
+        assert!(!info.too_big());
+    }
+
+    #[test]
+    fn test_platform() {
+        assert_eq!(platform(0x00E0), Platform::Chip8);
+        assert_eq!(platform(0xD125), Platform::Chip8);
+        assert_eq!(platform(0xD120), Platform::SuperChip);
+        assert_eq!(platform(0xF375), Platform::SuperChip);
+        assert_eq!(platform(0x5122), Platform::XoChip);
+        assert_eq!(platform(0xF201), Platform::XoChip);
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    fmt::{self, Display},
+    fs,
+};
+
+use chip8::{bus::KEYPAD_SIZE, machine::Machine};
+
+/// Why `run` returned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    FrameLimit,
+    Halted,
+    KeyWait,
+}
+
+impl Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::FrameLimit => write!(f, "frame limit"),
+            Stop::Halted => write!(f, "halted"),
+            Stop::KeyWait => write!(f, "waiting for a key"),
+        }
+    }
+}
+
+/// Keypad state changes, read from lines of `FRAME KEYS`
+///
+/// `KEYS` lists the hex keys held from `FRAME` on, separated by commas, or
+/// `-` to release everything. Text after `#` is ignored.
+///
+/// ```text
+/// 0   -
+/// 60  5      # hold key 5
+/// 65  4,5,6
+/// 70  -
+/// ```
+#[derive(Clone, Default)]
+pub struct Script {
+    events: Vec<(u64, [bool; KEYPAD_SIZE])>,
+    next: usize,
+}
+
+impl Script {
+    pub fn new_from(path: &str) -> Result<Self, String> {
+        let source =
+            fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+
+        Self::parse(&source).map_err(|e| format!("{}:{}", path, e))
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut events: Vec<(u64, [bool; KEYPAD_SIZE])> = vec![];
+
+        for (number, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = |msg: &str| format!("{}: {}", number + 1, msg);
+
+            let mut fields = line.split_whitespace();
+            let frame = fields
+                .next()
+                .and_then(|frame| frame.parse().ok())
+                .ok_or_else(|| error("invalid frame"))?;
+            let keys = match fields.next() {
+                Some(keys) => {
+                    parse_keys(keys).ok_or_else(|| error("invalid keys"))?
+                }
+                None => return Err(error("missing keys")),
+            };
+            if fields.next().is_some() {
+                return Err(error("unexpected field"));
+            }
+
+            if let Some(&(last, _)) = events.last() {
+                if frame <= last {
+                    return Err(error("frames must be increasing"));
+                }
+            }
+            events.push((frame, keys));
+        }
+
+        Ok(Self { events, next: 0 })
+    }
+
+    /// A key chosen from `seed`, or none, held every `period` frames up to
+    /// `frames`, for searching the inputs a rom reacts to
+    pub fn random(seed: u64, frames: u64, period: u64) -> Self {
+        // xorshift, never seeded with 0
+        let mut state = seed | 1;
+        let events = (0..frames)
+            .step_by(period.max(1) as usize)
+            .map(|frame| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+
+                let mut keys = [false; KEYPAD_SIZE];
+                // one chance out of 17 to release everything
+                if let Some(key) = keys.get_mut((state % 17) as usize) {
+                    *key = true;
+                }
+                (frame, keys)
+            })
+            .collect();
+
+        Self { events, next: 0 }
+    }
+
+    /// Keypad state to apply before running `frame`, if it changes
+    pub fn keys_at(&mut self, frame: u64) -> Option<[bool; KEYPAD_SIZE]> {
+        match self.events.get(self.next) {
+            Some(&(at, keys)) if at <= frame => {
+                self.next += 1;
+                Some(keys)
+            }
+            _ => None,
+        }
+    }
+
+    /// No more changes will be applied
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.events.len()
+    }
+}
+
+/// Run `frames` frames of `machine` with the keys of `script`, returns the
+/// frames run
+///
+/// With `until_halt`, it stops early once the rom jumps to itself or waits
+/// for a key that the script will never press.
+pub fn run(
+    machine: &mut Machine,
+    script: &mut Script,
+    frames: u64,
+    until_halt: bool,
+) -> (u64, Stop) {
+    run_with(machine, script, frames, until_halt, Machine::run_frame)
+}
+
+/// `run`, each frame being run by `run_frame`
+pub fn run_with(
+    machine: &mut Machine,
+    script: &mut Script,
+    frames: u64,
+    until_halt: bool,
+    mut run_frame: impl FnMut(&mut Machine),
+) -> (u64, Stop) {
+    for frame in 0..frames {
+        if let Some(keys) = script.keys_at(frame) {
+            machine.bus_mut().keys = keys;
+        }
+
+        run_frame(machine);
+
+        if until_halt {
+            if machine.is_halted() {
+                return (frame + 1, Stop::Halted);
+            }
+            if machine.cpu().key_await().is_some() && script.is_finished() {
+                return (frame + 1, Stop::KeyWait);
+            }
+        }
+    }
+
+    (frames, Stop::FrameLimit)
+}
+
+fn parse_keys(keys: &str) -> Option<[bool; KEYPAD_SIZE]> {
+    let mut state = [false; KEYPAD_SIZE];
+    if keys == "-" {
+        return Some(state);
+    }
+
+    for key in keys.split(',') {
+        let key = u8::from_str_radix(key, 16).ok()? as usize;
+        *state.get_mut(key)? = true;
+    }
+
+    Some(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8::rom::Rom;
+
+    use super::*;
+
+    #[test]
+    fn test_random() {
+        let mut script = Script::random(7, 20, 5);
+        let changes: Vec<_> =
+            (0..20).filter_map(|frame| script.keys_at(frame)).collect();
+        assert_eq!(changes.len(), 4);
+        assert!(changes
+            .iter()
+            .all(|keys| keys.iter().filter(|&&k| k).count() <= 1));
+        assert!(script.is_finished());
+
+        let mut again = Script::random(7, 20, 5);
+        assert_eq!(again.keys_at(0), Some(changes[0]));
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut script = Script::parse("0 -\n2 4,a  # hold\n3 -").unwrap();
+
+        assert_eq!(script.keys_at(0), Some([false; KEYPAD_SIZE]));
+        assert_eq!(script.keys_at(1), None);
+        let keys = script.keys_at(2).unwrap();
+        assert!(keys[0x4] && keys[0xA] && !keys[0x5]);
+        assert!(!script.is_finished());
+        assert_eq!(script.keys_at(3), Some([false; KEYPAD_SIZE]));
+        assert!(script.is_finished());
+
+        assert!(Script::parse("1 -\n1 5").is_err());
+        assert!(Script::parse("1 g").is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        // F00A: wait a key in V0, 6101: V1 = 1, 1204: loop
+        let rom = Rom::from_bytes(vec![0xF0, 0x0A, 0x61, 0x01, 0x12, 0x04]);
+        let mut machine = Machine::new(rom.clone());
+
+        let mut script = Script::parse("1 7").unwrap();
+        let (frames, stop) = run(&mut machine, &mut script, 100, true);
+        assert_eq!((frames, stop), (2, Stop::Halted));
+        assert_eq!(machine.cpu().registers()[0], 0x7);
+
+        let mut machine = Machine::new(rom);
+        let (frames, stop) =
+            run(&mut machine, &mut Script::default(), 100, true);
+        assert_eq!((frames, stop), (1, Stop::KeyWait));
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chip8::{
+    bus::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    machine::Machine,
+    rom::Rom,
+    state::StateError,
+};
+
+use crate::{checksum, Vram};
+
+/// Number of slots of each rom
+pub const SLOTS: usize = 10;
+
+const SCREEN_SIZE: usize = DISPLAY_WIDTH * DISPLAY_HEIGHT / 8;
+
+#[derive(Debug)]
+pub enum SlotError {
+    Io(io::Error),
+    State(StateError),
+    /// Nothing was saved in the slot
+    Empty(usize),
+}
+
+impl Display for SlotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlotError::Io(e) => write!(f, "{}", e),
+            SlotError::State(e) => write!(f, "{}", e),
+            SlotError::Empty(slot) => write!(f, "slot {} is empty", slot),
+        }
+    }
+}
+
+impl Error for SlotError {}
+
+impl From<io::Error> for SlotError {
+    fn from(e: io::Error) -> Self {
+        SlotError::Io(e)
+    }
+}
+
+impl From<StateError> for SlotError {
+    fn from(e: StateError) -> Self {
+        SlotError::State(e)
+    }
+}
+
+/// Numbered save states of a rom, kept in files
+///
+/// A slot file holds the screen, 1 bit per pixel row by row, then the
+/// machine state. The screen is read alone to show the slot.
+pub struct Slots {
+    dir: PathBuf,
+    current: usize,
+}
+
+impl Slots {
+    /// Slots in `dir`, starting on the first one
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            current: 0,
+        }
+    }
+
+    /// Slots of the rom made of `rom`, in the user data directory
+    pub fn for_rom(rom: &[u8]) -> Option<Self> {
+        let dir = dirs::data_dir()?
+            .join("chip8")
+            .join("states")
+            .join(format!("{:08x}", checksum(rom)));
+
+        Some(Self::new(dir))
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn set_current(&mut self, slot: usize) {
+        self.current = slot % SLOTS;
+    }
+
+    /// Select the next slot, after the last one comes the first
+    pub fn next(&mut self) {
+        self.set_current(self.current + 1);
+    }
+
+    /// Select the previous slot, before the first one comes the last
+    pub fn previous(&mut self) {
+        self.set_current(self.current + SLOTS - 1);
+    }
+
+    fn path(&self, slot: usize) -> PathBuf {
+        self.dir.join(format!("slot{}.state", slot))
+    }
+
+    pub fn is_used(&self, slot: usize) -> bool {
+        self.path(slot).is_file()
+    }
+
+    pub fn save(
+        &self,
+        slot: usize,
+        machine: &Machine,
+    ) -> Result<(), SlotError> {
+        let mut data = pack(&machine.bus().vram());
+        data.extend(machine.save_state());
+
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(slot), data)?;
+
+        Ok(())
+    }
+
+    /// Restore `machine` as it was saved in `slot`, it is left untouched on
+    /// error
+    pub fn load(
+        &self,
+        slot: usize,
+        machine: &mut Machine,
+    ) -> Result<(), SlotError> {
+        let data = read(&self.path(slot), slot)?;
+        match data.get(SCREEN_SIZE..) {
+            Some(state) => Ok(machine.load_state(state)?),
+            None => Err(SlotError::State(StateError::UnexpectedEnd)),
+        }
+    }
+
+    /// The screen saved in `slot`, none when it is empty or invalid
+    pub fn thumbnail(&self, slot: usize) -> Option<Vram> {
+        let data = read(&self.path(slot), slot).ok()?;
+
+        data.get(..SCREEN_SIZE).map(unpack)
+    }
+
+    pub fn save_current(&self, machine: &Machine) -> Result<(), SlotError> {
+        self.save(self.current, machine)
+    }
+
+    pub fn load_current(&self, machine: &mut Machine) -> Result<(), SlotError> {
+        self.load(self.current, machine)
+    }
+}
+
+/// The machine saved in `data`, a slot file or a state of
+/// `Machine::save_state`, without its rom
+pub fn saved_machine(data: &[u8]) -> Result<Machine, StateError> {
+    let mut machine = Machine::new(Rom::from_bytes(vec![]));
+    match machine.load_state(data) {
+        Err(StateError::InvalidSignature) if data.len() > SCREEN_SIZE => {
+            machine.load_state(&data[SCREEN_SIZE..])?
+        }
+        result => result?,
+    }
+
+    Ok(machine)
+}
+
+fn read(path: &Path, slot: usize) -> Result<Vec<u8>, SlotError> {
+    fs::read(path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => SlotError::Empty(slot),
+        _ => SlotError::Io(e),
+    })
+}
+
+/// Pixels row by row, 8 per byte with the leftmost in the high bit
+fn pack(vram: &Vram) -> Vec<u8> {
+    let mut screen = vec![0; SCREEN_SIZE];
+
+    for h in 0..DISPLAY_HEIGHT {
+        for (w, column) in vram.iter().enumerate() {
+            if column[h] {
+                let index = h * DISPLAY_WIDTH + w;
+                screen[index / 8] |= 0x80 >> (index % 8);
+            }
+        }
+    }
+
+    screen
+}
+
+fn unpack(screen: &[u8]) -> Vram {
+    let mut vram = [[false; DISPLAY_HEIGHT]; DISPLAY_WIDTH];
+
+    for (w, column) in vram.iter_mut().enumerate() {
+        for (h, pixel) in column.iter_mut().enumerate() {
+            let index = h * DISPLAY_WIDTH + w;
+            *pixel = screen[index / 8] & 0x80 >> (index % 8) != 0;
+        }
+    }
+
+    vram
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8::rom::Rom;
+
+    use super::*;
+
+    // 00E0: clear, A000: I = font 0, D005: draw it, 1206: loop
+    const PROGRAM: [u8; 8] = [0x00, 0xE0, 0xA0, 0x00, 0xD0, 0x05, 0x12, 0x06];
+
+    fn temp_slots(name: &str) -> Slots {
+        Slots::new(std::env::temp_dir().join(format!(
+            "chip8-slots-{}-{}",
+            name,
+            std::process::id()
+        )))
+    }
+
+    #[test]
+    fn test_save_load() {
+        let slots = temp_slots("save");
+        let mut machine = Machine::new(Rom::from_bytes(PROGRAM.to_vec()));
+        machine.run_frame();
+
+        assert!(!slots.is_used(3));
+        slots.save(3, &machine).unwrap();
+        assert!(slots.is_used(3));
+
+        let vram = machine.bus().vram();
+        let state = machine.save_state();
+        machine.reset();
+        slots.load(3, &mut machine).unwrap();
+        assert_eq!(machine.save_state(), state);
+        assert_eq!(slots.thumbnail(3), Some(vram));
+        assert!(vram[0][0]);
+
+        let data = fs::read(slots.path(3)).unwrap();
+        assert_eq!(saved_machine(&data).unwrap().save_state(), state);
+        assert_eq!(saved_machine(&state).unwrap().save_state(), state);
+        assert_eq!(
+            saved_machine(&data[1..]).err(),
+            Some(StateError::InvalidSignature)
+        );
+
+        fs::remove_dir_all(&slots.dir).unwrap();
+    }
+
+    #[test]
+    fn test_empty() {
+        let slots = temp_slots("empty");
+        let mut machine = Machine::new(Rom::from_bytes(PROGRAM.to_vec()));
+
+        assert!(matches!(
+            slots.load(1, &mut machine),
+            Err(SlotError::Empty(1))
+        ));
+        assert_eq!(slots.thumbnail(1), None);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut slots = temp_slots("cycle");
+
+        slots.previous();
+        assert_eq!(slots.current(), SLOTS - 1);
+        slots.next();
+        assert_eq!(slots.current(), 0);
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    coverage::{Coverage, Usage},
+    dump,
+};
+
+/// Address of the first byte of a rom
+const PROGRAM_START: usize = 0x200;
+/// Rows of the biggest sprite DXYN draws
+pub const MAX_ROWS: u8 = 15;
+/// Instructions after an `LD I` where a DXYN is looked for
+const DRAW_DISTANCE: usize = 8;
+/// Sprites on each row of a sheet
+const SHEET_COLUMNS: usize = 8;
+
+/// Bytes of memory that are likely drawn as a sprite
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sprite {
+    pub addr: u16,
+    /// From 1 to `MAX_ROWS`
+    pub rows: u8,
+}
+
+impl Sprite {
+    /// Its pixels, row by row from the top
+    pub fn pixels(&self, memory: &[u8]) -> Vec<[bool; 8]> {
+        (0..self.rows as usize)
+            .map(|row| {
+                let byte =
+                    memory.get(self.addr as usize + row).copied().unwrap_or(0);
+                std::array::from_fn(|x| byte & (0x80 >> x) != 0)
+            })
+            .collect()
+    }
+}
+
+/// Sprites of the rom loaded in `memory`, by address
+///
+/// A sprite is an `LD I, NNN` followed by a DXYN drawing from NNN, or a
+/// region drawn while `coverage` was recorded. The bytes drawn through an
+/// I computed at run time are only found by the coverage.
+pub fn find(memory: &[u8], coverage: Option<&Coverage>) -> Vec<Sprite> {
+    // rows of each sprite, the biggest draw wins
+    let mut sprites: BTreeMap<u16, u8> = BTreeMap::new();
+    let mut add = |addr: u16, rows: u8| {
+        if rows > 0 && (addr as usize) < memory.len() {
+            let known = sprites.entry(addr).or_default();
+            *known = (*known).max(rows.min(MAX_ROWS));
+        }
+    };
+
+    let opcode = |addr: usize| match memory.get(addr..addr + 2) {
+        Some(&[high, low]) => u16::from_be_bytes([high, low]),
+        _ => 0,
+    };
+    for addr in (PROGRAM_START..memory.len()).step_by(2) {
+        if opcode(addr) & 0xF000 != 0xA000 {
+            continue;
+        }
+
+        // until I or the flow changes
+        let drawn = (1..=DRAW_DISTANCE)
+            .map(|next| opcode(addr + next * 2))
+            .take_while(|&next| {
+                !matches!(next & 0xF000, 0x1000 | 0x2000 | 0xA000 | 0xB000)
+                    && next & 0xF0FF != 0xF01E
+                    && next & 0xF0FF != 0xF029
+                    && next != 0x00EE
+            })
+            .find(|&next| next & 0xF000 == 0xD000);
+        if let Some(draw) = drawn {
+            add(opcode(addr) & 0x0FFF, (draw & 0x000F) as u8);
+        }
+    }
+
+    if let Some(coverage) = coverage {
+        let mut addr = PROGRAM_START as u16;
+        while (addr as usize) < memory.len() {
+            let rows = (addr..memory.len() as u16)
+                .take(MAX_ROWS as usize)
+                .take_while(|&next| coverage.usage(next) == Some(Usage::Sprite))
+                .count() as u8;
+            add(addr, rows);
+            addr += rows.max(1) as u16;
+        }
+    }
+
+    sprites
+        .into_iter()
+        .map(|(addr, rows)| Sprite { addr, rows })
+        .collect()
+}
+
+/// Write `sprites` to a png, `SHEET_COLUMNS` by row with a 1 pixel gap,
+/// `scale` pixels per chip8 pixel
+pub fn sheet(
+    memory: &[u8],
+    sprites: &[Sprite],
+    path: &str,
+    scale: u32,
+) -> Result<(), String> {
+    let pixels: Vec<Vec<[bool; 8]>> =
+        sprites.iter().map(|sprite| sprite.pixels(memory)).collect();
+    let (cell_width, cell_height) = (8 + 1, MAX_ROWS as usize + 1);
+    let columns = sprites.len().clamp(1, SHEET_COLUMNS);
+    let rows = sprites.len().div_ceil(SHEET_COLUMNS).max(1);
+
+    dump::write_png(
+        path,
+        columns * cell_width + 1,
+        rows * cell_height + 1,
+        scale,
+        |x, y| {
+            // the gaps are on the left and the top of each cell
+            let (x, y) = (x.checked_sub(1)?, y.checked_sub(1)?);
+            let sprite =
+                pixels.get(y / cell_height * columns + x / cell_width)?;
+            sprite.get(y % cell_height)?.get(x % cell_width).copied()
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8::{machine::Machine, rom::Rom};
+
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        // A20C: I = 20C, 6000: V0 = 0, D005: draw 5 rows, A211: I = 211,
+        // D002: draw 2 rows, 120A: loop, then the sprites
+        let rom = Rom::from_bytes(vec![
+            0xA2, 0x0C, 0x60, 0x00, 0xD0, 0x05, 0xA2, 0x11, 0xD0, 0x02, 0x12,
+            0x0A, 0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60,
+        ]);
+        let mut machine = Machine::new(rom);
+        let memory = machine.bus().memory().to_vec();
+
+        let sprites = find(&memory, None);
+        assert_eq!(
+            sprites,
+            vec![
+                Sprite {
+                    addr: 0x20C,
+                    rows: 5
+                },
+                Sprite {
+                    addr: 0x211,
+                    rows: 2
+                },
+            ]
+        );
+        assert!(sprites[1].pixels(&memory)[1][1]);
+        assert!(!sprites[1].pixels(&memory)[0][1]);
+
+        let mut coverage = Coverage::new(&machine);
+        coverage.run_frame(&mut machine);
+        // the two draws are contiguous
+        assert_eq!(
+            find(&memory, Some(&coverage)),
+            vec![
+                Sprite {
+                    addr: 0x20C,
+                    rows: 7
+                },
+                Sprite {
+                    addr: 0x211,
+                    rows: 2
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+/// Buckets of a histogram, the last one takes all the longer durations
+const BUCKETS: usize = 16;
+
+/// Upper bound of the first bucket, the next ones double it
+const FIRST_BUCKET: Duration = Duration::from_micros(64);
+
+/// Durations counted in buckets of doubling length
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    pub fn record(&mut self, duration: Duration) {
+        let first = FIRST_BUCKET.as_micros();
+        let index = (u128::BITS
+            - (duration.as_micros() / first).leading_zeros())
+            as usize;
+        self.buckets[index.min(BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => self.total / count as u32,
+        }
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Duration under which `fraction` of the ones recorded are, rounded
+    /// up to the end of its bucket
+    pub fn percentile(&self, fraction: f64) -> Duration {
+        let target = (fraction.clamp(0.0, 1.0) * self.count as f64).ceil();
+        let mut seen = 0;
+        for (index, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen > 0 && seen as f64 >= target {
+                return (FIRST_BUCKET * (1 << index)).min(self.max);
+            }
+        }
+
+        self.max
+    }
+
+    /// The upper bound and count of each bucket
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(index, &count)| (FIRST_BUCKET * (1 << index), count))
+    }
+}
+
+/// Where the time of the frames goes, to tell a slow emulation from a slow
+/// drawing when the games stutter
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameTiming {
+    /// Time of each `Machine::run_frame`
+    pub emulate: Histogram,
+    /// Time of each drawing of the frontend
+    pub render: Histogram,
+    /// 60Hz ticks dropped because the frames were late
+    pub missed: u64,
+}
+
+impl FrameTiming {
+    /// A summary, for an overlay or the logs
+    pub fn lines(&self) -> Vec<String> {
+        let line = |name: &str, histogram: &Histogram| {
+            format!(
+                "{} {:.2} P99 {:.2} MAX {:.2} MS",
+                name,
+                millis(histogram.mean()),
+                millis(histogram.percentile(0.99)),
+                millis(histogram.max()),
+            )
+        };
+
+        vec![
+            line("EMU ", &self.emulate),
+            line("DRAW", &self.render),
+            format!("MISSED {} OF {}", self.missed, self.emulate.count()),
+        ]
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.mean(), Duration::ZERO);
+        assert_eq!(histogram.percentile(0.5), Duration::ZERO);
+
+        for micros in [10, 20, 30, 100, 5000] {
+            histogram.record(Duration::from_micros(micros));
+        }
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.mean(), Duration::from_micros(1032));
+        assert_eq!(histogram.max(), Duration::from_millis(5));
+        assert_eq!(histogram.percentile(0.5), Duration::from_micros(64));
+        assert_eq!(histogram.percentile(0.8), Duration::from_micros(128));
+        assert_eq!(histogram.percentile(1.0), Duration::from_millis(5));
+
+        let counts: Vec<u64> = histogram.buckets().map(|(_, n)| n).collect();
+        assert_eq!(counts[..8], [3, 1, 0, 0, 0, 0, 0, 1]);
+
+        // the last bucket takes the rest
+        histogram.record(Duration::from_secs(100));
+        assert_eq!(
+            histogram.buckets().last(),
+            Some((FIRST_BUCKET * (1 << 15), 1))
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut timing = FrameTiming::default();
+        timing.emulate.record(Duration::from_micros(250));
+        timing.missed = 2;
+
+        assert_eq!(
+            timing.lines(),
+            [
+                "EMU  0.25 P99 0.25 MAX 0.25 MS",
+                "DRAW 0.00 P99 0.00 MAX 0.00 MS",
+                "MISSED 2 OF 1",
+            ]
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use std::{
+    fmt::{self, Display},
+    io::{self, BufRead, Write},
+    mem,
+    str::FromStr,
+};
+
+use chip8::{cpu::V_SIZE, machine::Machine};
+
+use crate::script::{self, Script, Stop};
+
+/// State of the cpu before it executes an instruction, a line of a trace
+///
+/// The fields are hex numbers separated by spaces: the pc, the opcode, I
+/// then V0 to VF, so that other emulators can write the same trace. Text
+/// after `#` is ignored.
+///
+/// ```text
+/// # pc opcode i    v0 v1 v2 v3 v4 v5 v6 v7 v8 v9 va vb vc vd ve vf
+/// 0200 6005 0000 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+/// 0202 a20c 0000 05 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub pc: u16,
+    pub opcode: u16,
+    pub index: u16,
+    pub registers: [u8; V_SIZE],
+}
+
+impl Entry {
+    /// The instruction `machine` is about to execute
+    pub fn new(machine: &Machine) -> Self {
+        let cpu = machine.cpu();
+        let pc = cpu.pc() as usize;
+        let opcode = match machine.bus().memory().get(pc..pc + 2) {
+            Some(&[high, low]) => u16::from_be_bytes([high, low]),
+            _ => 0,
+        };
+
+        Self {
+            pc: cpu.pc(),
+            opcode,
+            index: cpu.index(),
+            registers: *cpu.registers(),
+        }
+    }
+
+    /// Names of the fields that differ from `other`
+    pub fn differences(&self, other: &Entry) -> Vec<String> {
+        let mut fields = vec![];
+        if self.pc != other.pc {
+            fields.push("pc".to_string());
+        }
+        if self.opcode != other.opcode {
+            fields.push("opcode".to_string());
+        }
+        if self.index != other.index {
+            fields.push("I".to_string());
+        }
+        for x in 0..V_SIZE {
+            if self.registers[x] != other.registers[x] {
+                fields.push(format!("V{:X}", x));
+            }
+        }
+
+        fields
+    }
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04x} {:04x} {:04x}", self.pc, self.opcode, self.index)?;
+        for v in self.registers {
+            write!(f, " {:02x}", v)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Entry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        if fields.len() != 3 + V_SIZE {
+            return Err(format!(
+                "expected {} fields, found {}",
+                3 + V_SIZE,
+                fields.len()
+            ));
+        }
+
+        let word = |field: &str| {
+            u16::from_str_radix(field, 16)
+                .map_err(|_| format!("invalid word {}", field))
+        };
+        let mut registers = [0; V_SIZE];
+        for (v, field) in registers.iter_mut().zip(&fields[3..]) {
+            *v = u8::from_str_radix(field, 16)
+                .map_err(|_| format!("invalid register {}", field))?;
+        }
+
+        Ok(Self {
+            pc: word(fields[0])?,
+            opcode: word(fields[1])?,
+            index: word(fields[2])?,
+            registers,
+        })
+    }
+}
+
+/// Run a frame of `machine`, writing an entry for each of its
+/// instructions
+pub fn run_frame(
+    machine: &mut Machine,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    loop {
+        // nothing executes until a key is pressed
+        if machine.cpu().key_await().is_none() {
+            writeln!(out, "{}", Entry::new(machine))?;
+        }
+
+        // stopped again before the next instruction
+        let mut first = true;
+        if machine.run_frame_until(|_| !mem::replace(&mut first, false)) {
+            return Ok(());
+        }
+    }
+}
+
+/// `script::run`, writing an entry for each instruction to `out`
+///
+/// The rom runs to the end even once the trace can't be written, the
+/// error is returned then.
+pub fn run(
+    machine: &mut Machine,
+    script: &mut Script,
+    frames: u64,
+    until_halt: bool,
+    out: &mut impl Write,
+) -> io::Result<(u64, Stop)> {
+    let mut result = Ok(());
+    let ran =
+        script::run_with(machine, script, frames, until_halt, |machine| {
+            match result.is_ok() {
+                true => result = run_frame(machine, out),
+                false => machine.run_frame(),
+            }
+        });
+
+    result.and_then(|_| out.flush()).map(|_| ran)
+}
+
+/// Where two traces stop agreeing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Number of the instruction, from 1
+    pub instruction: usize,
+    /// The last instruction both traces agree on
+    pub previous: Option<Entry>,
+    /// None for the trace which ended first
+    pub left: Option<Entry>,
+    pub right: Option<Entry>,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "instruction {}: ", self.instruction)?;
+        match (self.left, self.right) {
+            (Some(left), Some(right)) => {
+                write!(f, "different {}", left.differences(&right).join(", "))
+            }
+            (None, _) => write!(f, "the left trace ends"),
+            (_, None) => write!(f, "the right trace ends"),
+        }
+    }
+}
+
+/// The first instruction where the traces differ, none when they are the
+/// same
+pub fn diff(
+    left: impl BufRead,
+    right: impl BufRead,
+) -> Result<Option<Divergence>, String> {
+    let mut left = entries(left, "left");
+    let mut right = entries(right, "right");
+    let mut previous = None;
+
+    for instruction in 1.. {
+        let (left, right) =
+            (left.next().transpose()?, right.next().transpose()?);
+        if left.is_none() && right.is_none() {
+            break;
+        }
+        if left != right {
+            return Ok(Some(Divergence {
+                instruction,
+                previous,
+                left,
+                right,
+            }));
+        }
+        previous = left;
+    }
+
+    Ok(None)
+}
+
+/// The entries of a trace, its errors named after `name` and their line
+fn entries(
+    trace: impl BufRead,
+    name: &'static str,
+) -> impl Iterator<Item = Result<Entry, String>> {
+    trace.lines().enumerate().filter_map(move |(number, line)| {
+        let error = |e: String| format!("{}:{}: {}", name, number + 1, e);
+
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Some(Err(error(e.to_string()))),
+        };
+        let line = line.split('#').next().unwrap_or_default().trim();
+        match line.is_empty() {
+            true => None,
+            false => Some(line.parse().map_err(error)),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8::rom::Rom;
+
+    use super::*;
+
+    fn trace(rom: Vec<u8>) -> Vec<u8> {
+        let mut machine = Machine::new(Rom::from_bytes(rom));
+        let mut out = vec![];
+        run_frame(&mut machine, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_run_frame() {
+        // 6005: V0 = 5, 1202: loop
+        let out =
+            String::from_utf8(trace(vec![0x60, 0x05, 0x12, 0x02])).unwrap();
+        let mut lines = out.lines();
+
+        let registers = " 00".repeat(V_SIZE);
+        assert_eq!(
+            lines.next(),
+            Some(format!("0200 6005 0000{}", registers).as_str())
+        );
+        let entry: Entry = lines.next().unwrap().parse().unwrap();
+        assert_eq!(entry.pc, 0x202);
+        assert_eq!(entry.opcode, 0x1202);
+        assert_eq!(entry.registers[0], 5);
+        assert!(lines.all(|line| line.starts_with("0202 1202")));
+    }
+
+    #[test]
+    fn test_diff() {
+        // 6005: V0 = 5, 6105 or 6106: V1 = 5 or 6, 1204: loop
+        let left = trace(vec![0x60, 0x05, 0x61, 0x05, 0x12, 0x04]);
+        let right = trace(vec![0x60, 0x05, 0x61, 0x06, 0x12, 0x04]);
+
+        assert_eq!(diff(&left[..], &left[..]), Ok(None));
+
+        let divergence = diff(&left[..], &right[..]).unwrap().unwrap();
+        assert_eq!(divergence.instruction, 2);
+        assert_eq!(divergence.previous.map(|entry| entry.pc), Some(0x200));
+        assert_eq!(divergence.to_string(), "instruction 2: different opcode");
+
+        // the right one runs a step further, with comments
+        let first = &left[..left.iter().position(|&b| b == b'\n').unwrap() + 1];
+        let longer = [first, b"# after\n\n", first].concat();
+        let divergence = diff(first, &longer[..]).unwrap().unwrap();
+        assert_eq!(divergence.instruction, 2);
+        assert_eq!(divergence.left, None);
+        assert_eq!(
+            divergence.to_string(),
+            "instruction 2: the left trace ends"
+        );
+
+        assert!(diff(&b"0200 00e0"[..], &left[..])
+            .unwrap_err()
+            .starts_with("left:1:"));
+    }
+}
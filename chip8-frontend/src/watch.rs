@@ -0,0 +1,286 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chip8::machine::Machine;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ConfigError;
+
+/// When a rule fires, comparing its value with the one of the last frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    Change,
+    Increase,
+    Decrease,
+    /// Becomes the `value` of the rule
+    Equals,
+}
+
+/// A value in memory, like the score or the lives of a game
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rule {
+    /// Given to its events
+    pub name: String,
+    pub address: u16,
+    /// Bytes of the value, 1 or 2 in big endian
+    #[serde(default = "Rule::default_size")]
+    pub size: u8,
+    pub when: Trigger,
+    /// Compared with by `Trigger::Equals`
+    #[serde(default)]
+    pub value: u16,
+}
+
+impl Rule {
+    fn default_size() -> u8 {
+        1
+    }
+
+    fn read(&self, memory: &[u8]) -> Option<u16> {
+        let start = self.address as usize;
+        match *memory.get(start..start + self.size as usize)? {
+            [byte] => Some(byte as u16),
+            [high, low] => Some(u16::from_be_bytes([high, low])),
+            _ => None,
+        }
+    }
+
+    fn fires(&self, old: u16, new: u16) -> bool {
+        match self.when {
+            Trigger::Change => new != old,
+            Trigger::Increase => new > old,
+            Trigger::Decrease => new < old,
+            Trigger::Equals => new == self.value && old != self.value,
+        }
+    }
+}
+
+/// Memory rules of the roms, by the SHA-1 of their file like the
+/// `Database`, from `chip8/watch.toml` in the configuration directory
+///
+/// ```toml
+/// [[roms.0123456789abcdef0123456789abcdef01234567]]
+/// name = "score"
+/// address = 0x3A0
+/// when = "increase"
+///
+/// [[roms.0123456789abcdef0123456789abcdef01234567]]
+/// name = "game over"
+/// address = 0x3A2
+/// when = "equals"
+/// value = 0
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchRules {
+    roms: BTreeMap<String, Vec<Rule>>,
+}
+
+impl WatchRules {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("chip8").join("watch.toml"))
+    }
+
+    /// The rules of the configuration directory, none when they are missing
+    /// or invalid
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+
+        match Self::load_from(&path) {
+            Ok(rules) => rules,
+            Err(ConfigError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                Self::default()
+            }
+            Err(e) => {
+                warn!("{}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
+        toml::from_str(&fs::read_to_string(path)?).map_err(ConfigError::Parse)
+    }
+
+    /// The rules of the rom with this SHA-1, in any case
+    pub fn get(&self, sha1: &str) -> &[Rule] {
+        self.roms
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(sha1))
+            .map_or(&[], |(_, rules)| rules.as_slice())
+    }
+}
+
+/// A rule that fired at the end of a frame
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchEvent {
+    pub rule: String,
+    /// Number of the frame, from 1
+    pub frame: u64,
+    pub old: u16,
+    pub new: u16,
+}
+
+/// Evaluates rules on a running machine, for overlays, achievements or
+/// statistics that need no code for each game
+pub struct Watcher {
+    rules: Vec<Rule>,
+    /// Of the last frame, none before the first one
+    values: Vec<Option<u16>>,
+    frame: u64,
+}
+
+impl Watcher {
+    /// The rules out of the memory are left out
+    pub fn new(rules: &[Rule], machine: &Machine) -> Self {
+        let memory = machine.bus().memory();
+        let rules: Vec<Rule> = rules
+            .iter()
+            .filter(|rule| {
+                let valid = rule.read(memory).is_some();
+                if !valid {
+                    warn!("rule {:?}: out of the memory", rule.name);
+                }
+                valid
+            })
+            .cloned()
+            .collect();
+
+        Self {
+            values: vec![None; rules.len()],
+            rules,
+            frame: 0,
+        }
+    }
+
+    /// Read the values after a frame of `machine`, returns the events of
+    /// the rules that fired since the last call
+    pub fn update(&mut self, machine: &Machine) -> Vec<WatchEvent> {
+        self.frame += 1;
+        let memory = machine.bus().memory();
+
+        let mut events = vec![];
+        for (rule, last) in self.rules.iter().zip(&mut self.values) {
+            let Some(new) = rule.read(memory) else {
+                continue;
+            };
+            if let Some(old) = last.replace(new) {
+                if rule.fires(old, new) {
+                    events.push(WatchEvent {
+                        rule: rule.name.clone(),
+                        frame: self.frame,
+                        old,
+                        new,
+                    });
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Value of the rule named `name` at the last update, for an overlay
+    pub fn value(&self, name: &str) -> Option<u16> {
+        let index = self.rules.iter().position(|rule| rule.name == name)?;
+        self.values[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8::rom::Rom;
+
+    use super::*;
+
+    fn rule(name: &str, address: u16, size: u8, when: Trigger) -> Rule {
+        Rule {
+            name: name.to_string(),
+            address,
+            size,
+            when,
+            value: 3,
+        }
+    }
+
+    #[test]
+    fn test_watcher() {
+        let mut machine = Machine::new(Rom::from_bytes(vec![
+            0x70, 0x01, // V0 += 1
+            0xA3, 0x00, // I = 300
+            0xF0, 0x55, // write V0 there
+            0x12, 0x00, // loop
+        ]));
+        machine.set_cpu_frequency(240.0); // a loop per frame
+
+        let rules = [
+            rule("score", 0x300, 1, Trigger::Increase),
+            rule("word", 0x2FF, 2, Trigger::Change),
+            rule("three", 0x300, 1, Trigger::Equals),
+            rule("lost", 0x300, 1, Trigger::Decrease),
+            rule("outside", 0xFFF, 2, Trigger::Change),
+        ];
+        let mut watcher = Watcher::new(&rules, &machine);
+        assert_eq!(watcher.value("score"), None);
+
+        machine.run_frame();
+        assert!(watcher.update(&machine).is_empty());
+        assert_eq!(watcher.value("score"), Some(1));
+
+        machine.run_frame();
+        let events = watcher.update(&machine);
+        let names: Vec<_> = events.iter().map(|e| e.rule.as_str()).collect();
+        assert_eq!(names, ["score", "word"]);
+        assert_eq!(
+            events[0],
+            WatchEvent {
+                rule: "score".into(),
+                frame: 2,
+                old: 1,
+                new: 2
+            }
+        );
+
+        machine.run_frame();
+        let events = watcher.update(&machine);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2].rule, "three");
+        assert_eq!(watcher.value("outside"), None);
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules: WatchRules = toml::from_str(
+            "
+            [[roms.A9993E364706816ABA3E25717850C26C9CD0D89D]]
+            name = \"score\"
+            address = 0x3A0
+            when = \"increase\"
+
+            [[roms.A9993E364706816ABA3E25717850C26C9CD0D89D]]
+            name = \"game over\"
+            address = 0x3A2
+            size = 2
+            when = \"equals\"
+            value = 0
+            ",
+        )
+        .unwrap();
+
+        let pong = rules.get("a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(pong.len(), 2);
+        assert_eq!(pong[0].address, 0x3A0);
+        assert_eq!(pong[0].size, 1);
+        assert_eq!(pong[1].when, Trigger::Equals);
+        assert_eq!(pong[1].size, 2);
+        assert!(rules
+            .get("da39a3ee5e6b4b0d3255bfef95601890afd80709")
+            .is_empty());
+    }
+}
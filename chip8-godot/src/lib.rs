@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+use chip8::{
+    bus::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    machine::Machine,
+    rom::Rom,
+};
+use godot::{
+    classes::{image::Format, INode, Image, ImageTexture, Node},
+    prelude::*,
+};
+
+const FOREGROUND: [u8; 4] = [69, 115, 13, 255];
+const BACKGROUND: [u8; 4] = [124, 209, 21, 255];
+
+struct Chip8Extension;
+
+#[gdextension]
+unsafe impl ExtensionLibrary for Chip8Extension {}
+
+/// Emulator node, runs the loaded rom at 60Hz while `running` is set and
+/// draws into `texture`
+#[derive(GodotClass)]
+#[class(base = Node)]
+pub struct Chip8Emulator {
+    machine: Option<Machine>,
+    image: Vec<u8>,
+    texture: Gd<ImageTexture>,
+    beeping: bool,
+    /// Emulation runs in `_process` when set
+    #[export]
+    running: bool,
+    /// Instructions per second
+    #[export]
+    #[var(get = get_cpu_frequency, set = set_cpu_frequency)]
+    cpu_frequency: f64,
+    base: Base<Node>,
+}
+
+#[godot_api]
+impl INode for Chip8Emulator {
+    fn init(base: Base<Node>) -> Self {
+        let image = vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT * 4];
+        let texture = ImageTexture::create_from_image(&create_image(&image))
+            .expect("Failed to create texture");
+
+        let mut emulator = Self {
+            machine: None,
+            image,
+            texture,
+            beeping: false,
+            running: true,
+            cpu_frequency: chip8::machine::CPU_FREQUENCY,
+            base,
+        };
+        emulator.draw();
+
+        emulator
+    }
+
+    fn process(&mut self, delta: f64) {
+        let machine = match &mut self.machine {
+            Some(machine) if self.running => machine,
+            _ => return,
+        };
+
+        if machine.tick(Duration::from_secs_f64(delta.max(0.0))) > 0 {
+            self.update_beeping();
+            self.draw();
+        }
+    }
+}
+
+#[godot_api]
+impl Chip8Emulator {
+    /// Emitted when the sound timer starts or stops
+    #[signal]
+    fn beep_changed(beeping: bool);
+
+    /// Start `data` from a fresh machine
+    #[func]
+    fn load_rom(&mut self, data: PackedByteArray) {
+        let mut machine = Machine::new(Rom::from_bytes(data.to_vec()));
+        machine.set_cpu_frequency(self.cpu_frequency);
+        self.machine = Some(machine);
+
+        self.update_beeping();
+        self.draw();
+    }
+
+    #[func]
+    fn is_loaded(&self) -> bool {
+        self.machine.is_some()
+    }
+
+    #[func]
+    fn reset(&mut self) {
+        if let Some(machine) = &mut self.machine {
+            machine.reset();
+        }
+
+        self.update_beeping();
+        self.draw();
+    }
+
+    /// Emulate one 60Hz frame and update the texture, called by `_process`
+    /// while running
+    #[func]
+    fn run_frame(&mut self) {
+        match &mut self.machine {
+            Some(machine) => machine.run_frame(),
+            None => return,
+        }
+
+        self.update_beeping();
+        self.draw();
+    }
+
+    /// Press or release a keypad key, 0x0 to 0xF
+    #[func]
+    fn set_key(&mut self, key: i64, pressed: bool) {
+        let machine = match &mut self.machine {
+            Some(machine) => machine,
+            None => return,
+        };
+
+        match usize::try_from(key)
+            .ok()
+            .and_then(|key| machine.bus_mut().keys.get_mut(key))
+        {
+            Some(state) => *state = pressed,
+            None => godot_warn!("chip8: invalid key {}", key),
+        }
+    }
+
+    /// The screen, updated after every frame
+    #[func]
+    fn get_texture(&self) -> Gd<ImageTexture> {
+        self.texture.clone()
+    }
+
+    #[func]
+    fn is_beeping(&self) -> bool {
+        self.beeping
+    }
+
+    /// Empty when no rom is loaded
+    #[func]
+    fn save_state(&self) -> PackedByteArray {
+        match &self.machine {
+            Some(machine) => {
+                PackedByteArray::from(machine.save_state().as_slice())
+            }
+            None => PackedByteArray::new(),
+        }
+    }
+
+    /// Restore a state from `save_state` of the same rom, returns false if
+    /// it is invalid
+    #[func]
+    fn load_state(&mut self, data: PackedByteArray) -> bool {
+        let machine = match &mut self.machine {
+            Some(machine) => machine,
+            None => return false,
+        };
+
+        if let Err(e) = machine.load_state(data.as_slice()) {
+            godot_error!("chip8: {}", e);
+            return false;
+        }
+
+        self.update_beeping();
+        self.draw();
+
+        true
+    }
+
+    #[func]
+    fn get_cpu_frequency(&self) -> f64 {
+        self.cpu_frequency
+    }
+
+    #[func]
+    fn set_cpu_frequency(&mut self, frequency: f64) {
+        self.cpu_frequency = frequency.max(0.0);
+        if let Some(machine) = &mut self.machine {
+            machine.set_cpu_frequency(self.cpu_frequency);
+        }
+    }
+
+    fn update_beeping(&mut self) {
+        let beeping = self.machine.as_ref().is_some_and(Machine::is_beeping);
+
+        if beeping != self.beeping {
+            self.beeping = beeping;
+            self.signals().beep_changed().emit(beeping);
+        }
+    }
+
+    fn draw(&mut self) {
+        let bus = self.machine.as_ref().map(|machine| machine.bus());
+
+        for (index, pixel) in self.image.chunks_exact_mut(4).enumerate() {
+            let (w, h) = (index % DISPLAY_WIDTH, index / DISPLAY_WIDTH);
+            let lit = bus.is_some_and(|bus| bus.pixel(w, h));
+
+            pixel.copy_from_slice(match lit {
+                true => &FOREGROUND,
+                false => &BACKGROUND,
+            });
+        }
+
+        self.texture.update(&create_image(&self.image));
+    }
+}
+
+fn create_image(data: &[u8]) -> Gd<Image> {
+    Image::create_from_data(
+        DISPLAY_WIDTH as i32,
+        DISPLAY_HEIGHT as i32,
+        false,
+        Format::RGBA8,
+        &PackedByteArray::from(data),
+    )
+    .expect("Failed to create image")
+}
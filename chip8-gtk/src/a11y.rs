@@ -0,0 +1,70 @@
+use std::cell::Cell;
+
+use gtk::{gdk, prelude::*};
+
+use crate::{emulator::Emulator, status_bar};
+
+/// Name read by the screen readers for `widget`, for the controls without
+/// a text of their own
+pub fn set_label(widget: &impl IsA<gtk::Accessible>, label: &str) {
+    widget.update_property(&[gtk::accessible::Property::Label(label)]);
+}
+
+/// Escape closes `window`, like the built-in dialogs
+pub fn close_on_escape(window: &gtk::Window) {
+    let shortcuts = gtk::ShortcutController::new();
+    shortcuts.add_shortcut(gtk::Shortcut::new(
+        Some(gtk::KeyvalTrigger::new(
+            gdk::Key::Escape,
+            gdk::ModifierType::empty(),
+        )),
+        Some(gtk::NamedAction::new("window.close")),
+    ));
+    window.add_controller(shortcuts);
+}
+
+/// Tells the screen readers what the screen can't: the emulator paused or
+/// waiting for a key, and the memory watch rules of the rom that fired
+#[derive(Default)]
+pub struct Announcer {
+    /// State of the status bar at the last update, none before the first
+    state: Cell<Option<&'static str>>,
+}
+
+impl Announcer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Announce through `widget` the changes since the last update, when
+    /// `enabled`; the events are taken either way
+    pub fn update(
+        &self,
+        widget: &impl IsA<gtk::Accessible>,
+        emulator: &mut Emulator,
+        enabled: bool,
+    ) {
+        let events = emulator.take_watch_events();
+        let state = status_bar::state(emulator);
+        let last = self.state.replace(Some(state));
+        if !enabled {
+            return;
+        }
+
+        let announce = |message: &str| {
+            widget
+                .announce(message, gtk::AccessibleAnnouncementPriority::Medium)
+        };
+        match last {
+            // the start and the same state aren't news
+            None => {}
+            Some(last) if last == state => {}
+            // each key pressed for FX0A resumes the rom
+            Some(status_bar::WAITING) if state == status_bar::RUNNING => {}
+            Some(_) => announce(state),
+        }
+        for event in events {
+            announce(&format!("{}: {}", event.rule, event.new));
+        }
+    }
+}
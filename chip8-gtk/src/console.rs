@@ -0,0 +1,183 @@
+use std::{cell::RefCell, rc::Rc};
+
+use gtk::{gdk, glib, prelude::*};
+
+use crate::{a11y, emulator::Emulator};
+
+/// Lines kept in the output, the oldest are removed first
+const MAX_LINES: i32 = 1000;
+
+/// Commands entered before, browsed with Up and Down
+#[derive(Default)]
+struct History {
+    commands: Vec<String>,
+    /// Shown in the entry, none when it is a new command
+    position: Option<usize>,
+}
+
+impl History {
+    fn push(&mut self, command: &str) {
+        if self.commands.last().map(String::as_str) != Some(command) {
+            self.commands.push(command.to_string());
+        }
+        self.position = None;
+    }
+
+    fn previous(&mut self) -> Option<&str> {
+        let position = match self.position {
+            Some(position) => position.checked_sub(1)?,
+            None => self.commands.len().checked_sub(1)?,
+        };
+        self.position = Some(position);
+
+        Some(&self.commands[position])
+    }
+
+    /// Empty past the last command
+    fn next(&mut self) -> &str {
+        self.position = self
+            .position
+            .map(|position| position + 1)
+            .filter(|&position| position < self.commands.len());
+
+        self.position
+            .map_or("", |position| &self.commands[position])
+    }
+}
+
+/// Window running rhai commands on the emulator, like `peek(0x200)`,
+/// `press(5)` or `set_breakpoint(0x2a0, true)`, with what they print and
+/// their values
+///
+/// A `fn on_frame()` defined there is called before each frame.
+pub struct Console {
+    window: gtk::Window,
+    output: gtk::TextView,
+}
+
+impl Console {
+    pub fn new(
+        parent: &gtk::ApplicationWindow,
+        emulator: &Rc<RefCell<Emulator>>,
+    ) -> Self {
+        let output = gtk::TextView::builder()
+            .editable(false)
+            .cursor_visible(false)
+            .wrap_mode(gtk::WrapMode::WordChar)
+            .build();
+        output.add_css_class("monospace");
+        a11y::set_label(&output, "Output");
+        let scrolled = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .vexpand(true)
+            .child(&output)
+            .build();
+
+        let entry = gtk::Entry::builder().placeholder_text("Command").build();
+        entry.add_css_class("monospace");
+        a11y::set_label(&entry, "Command");
+        let history = Rc::new(RefCell::new(History::default()));
+
+        entry.connect_activate({
+            let emulator = emulator.clone();
+            let output = output.clone();
+            let history = history.clone();
+            move |entry| {
+                let command = entry.text().trim().to_string();
+                if command.is_empty() {
+                    return;
+                }
+                entry.set_text("");
+                history.borrow_mut().push(&command);
+
+                append(&output, &format!("> {}", command));
+                let mut emulator = emulator.borrow_mut();
+                let result = emulator.eval(&command);
+                // printed before the value is returned
+                for line in emulator.take_script_output() {
+                    append(&output, &line);
+                }
+                match result {
+                    Ok(value) if value.is_empty() => {}
+                    Ok(value) => append(&output, &value),
+                    Err(e) => append(&output, &format!("error: {}", e)),
+                }
+            }
+        });
+
+        // before the entry moves its cursor
+        let keys = gtk::EventControllerKey::new();
+        keys.set_propagation_phase(gtk::PropagationPhase::Capture);
+        keys.connect_key_pressed({
+            let entry = entry.clone();
+            move |_, key, _, _| {
+                let mut history = history.borrow_mut();
+                let command = match key {
+                    gdk::Key::Up => history.previous(),
+                    gdk::Key::Down => Some(history.next()),
+                    _ => return glib::Propagation::Proceed,
+                };
+                if let Some(command) = command {
+                    entry.set_text(command);
+                    entry.set_position(-1);
+                }
+                glib::Propagation::Stop
+            }
+        });
+        entry.add_controller(keys);
+
+        let container = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .margin_top(6)
+            .margin_bottom(6)
+            .margin_start(6)
+            .margin_end(6)
+            .build();
+        container.append(&scrolled);
+        container.append(&entry);
+
+        let window = gtk::Window::builder()
+            .title("Console")
+            .transient_for(parent)
+            .destroy_with_parent(true)
+            .hide_on_close(true)
+            .default_width(480)
+            .default_height(320)
+            .child(&container)
+            .build();
+        a11y::close_on_escape(&window);
+
+        Self { window, output }
+    }
+
+    pub fn present(&self) {
+        self.window.present();
+    }
+
+    /// Show what `on_frame` printed, also while hidden
+    pub fn update(&self, emulator: &mut Emulator) {
+        for line in emulator.take_script_output() {
+            append(&self.output, &line);
+        }
+    }
+}
+
+/// Add `line` at the end of `output` and scroll to it
+fn append(output: &gtk::TextView, line: &str) {
+    let buffer = output.buffer();
+    if buffer.char_count() > 0 {
+        buffer.insert(&mut buffer.end_iter(), "\n");
+    }
+    buffer.insert(&mut buffer.end_iter(), line);
+
+    let excess = buffer.line_count() - MAX_LINES;
+    if excess > 0 {
+        if let Some(mut end) = buffer.iter_at_line(excess) {
+            buffer.delete(&mut buffer.start_iter(), &mut end);
+        }
+    }
+
+    buffer.place_cursor(&buffer.end_iter());
+    output.scroll_mark_onscreen(&buffer.get_insert());
+}
@@ -0,0 +1,186 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use chip8::disasm::disassemble_at;
+use gtk::{glib, prelude::*};
+
+use crate::emulator::Emulator;
+
+/// Instructions shown around the PC, which is in the middle
+const DISASSEMBLY_ROWS: u16 = 17;
+const MEMORY_SIZE: u16 = 0x1000;
+
+/// A disassembly line with its breakpoint toggle
+struct Row {
+    addr: Rc<Cell<u16>>,
+    breakpoint: gtk::CheckButton,
+    label: gtk::Label,
+}
+
+/// Side panel with the registers, the stack and the disassembly around the
+/// PC, the breakpoints are toggled on the disassembly lines
+pub struct DebugPanel {
+    revealer: gtk::Revealer,
+    registers: gtk::Label,
+    stack: gtk::Label,
+    rows: Vec<Row>,
+    /// Set while the rows are filled, the toggles aren't from the user
+    updating: Rc<Cell<bool>>,
+}
+
+impl DebugPanel {
+    pub fn new(emulator: &Rc<RefCell<Emulator>>) -> Self {
+        let container = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .margin_top(6)
+            .margin_bottom(6)
+            .margin_start(6)
+            .margin_end(6)
+            .build();
+
+        let registers = monospace_label();
+        let stack = monospace_label();
+        container.append(&section("Registers", &registers));
+        container.append(&section("Stack", &stack));
+
+        let disassembly = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        let updating = Rc::new(Cell::new(false));
+        let rows: Vec<Row> = (0..DISASSEMBLY_ROWS)
+            .map(|_| {
+                let row = Row {
+                    addr: Rc::default(),
+                    breakpoint: gtk::CheckButton::builder()
+                        .tooltip_text("Breakpoint")
+                        .focus_on_click(false)
+                        .build(),
+                    label: monospace_label(),
+                };
+
+                // read with the instruction it stops at
+                row.breakpoint.update_relation(&[
+                    gtk::accessible::Relation::LabelledBy(&[row
+                        .label
+                        .upcast_ref()]),
+                ]);
+                let line = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+                line.append(&row.breakpoint);
+                line.append(&row.label);
+                disassembly.append(&line);
+
+                row
+            })
+            .collect();
+        container.append(&section("Disassembly", &disassembly));
+
+        for row in &rows {
+            row.breakpoint.connect_toggled({
+                let emulator = emulator.clone();
+                let updating = updating.clone();
+                let addr = row.addr.clone();
+                move |breakpoint| {
+                    if !updating.get() {
+                        emulator
+                            .borrow_mut()
+                            .set_breakpoint(addr.get(), breakpoint.is_active());
+                    }
+                }
+            });
+        }
+
+        let revealer = gtk::Revealer::builder()
+            .transition_type(gtk::RevealerTransitionType::SlideLeft)
+            .reveal_child(false)
+            .child(&container)
+            .build();
+
+        Self {
+            revealer,
+            registers,
+            stack,
+            rows,
+            updating,
+        }
+    }
+
+    pub fn widget(&self) -> &gtk::Revealer {
+        &self.revealer
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.revealer.reveals_child()
+    }
+
+    pub fn set_visible(&self, visible: bool) {
+        self.revealer.set_reveal_child(visible);
+    }
+
+    /// Show the current state of `emulator`, nothing is done while hidden
+    pub fn update(&self, emulator: &Emulator) {
+        if !self.is_visible() {
+            return;
+        }
+
+        let machine = emulator.machine();
+        let cpu = machine.cpu();
+
+        let mut registers =
+            format!("PC 0x{:03X}  I 0x{:03X}", cpu.pc(), cpu.index());
+        for (index, value) in cpu.registers().iter().enumerate() {
+            let separator = match index % 4 {
+                0 => "\n",
+                _ => "  ",
+            };
+            registers
+                .push_str(&format!("{}V{:X} {:02X}", separator, index, value));
+        }
+        self.registers.set_label(&registers);
+
+        // the last call first
+        let stack: Vec<String> = cpu
+            .call_stack()
+            .iter()
+            .rev()
+            .map(|addr| format!("0x{:03X}", addr))
+            .collect();
+        match stack.is_empty() {
+            true => self.stack.set_label("empty"),
+            false => self.stack.set_label(&stack.join("\n")),
+        }
+
+        let pc = cpu.pc();
+        let start = pc
+            .saturating_sub(DISASSEMBLY_ROWS / 2 * 2)
+            .min(MEMORY_SIZE - DISASSEMBLY_ROWS * 2);
+        self.updating.set(true);
+        for (row, addr) in self.rows.iter().zip((start..).step_by(2)) {
+            let (opcode, text) = disassemble_at(machine.bus().memory(), addr);
+            let line = glib::markup_escape_text(&format!(
+                "0x{:03X}  {:04X}  {}",
+                addr, opcode, text
+            ));
+
+            row.addr.set(addr);
+            row.breakpoint
+                .set_active(emulator.breakpoints().contains(&addr));
+            row.label.set_markup(&match addr == pc {
+                true => format!("<b>{}</b>", line),
+                false => line.to_string(),
+            });
+        }
+        self.updating.set(false);
+    }
+}
+
+fn monospace_label() -> gtk::Label {
+    let label = gtk::Label::builder().xalign(0.0).build();
+    label.add_css_class("monospace");
+
+    label
+}
+
+fn section(title: &str, child: &impl IsA<gtk::Widget>) -> gtk::Frame {
+    gtk::Frame::builder().label(title).child(child).build()
+}
@@ -0,0 +1,287 @@
+use chip8::bus::{Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8_frontend::config;
+use gtk::{gdk, glib, prelude::*, subclass::prelude::*};
+
+type Vram = [[bool; DISPLAY_HEIGHT]; DISPLAY_WIDTH];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub background: gdk::RGBA,
+    /// Lit pixels, blended over the background
+    pub foreground: gdk::RGBA,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        PALETTES[0].2
+    }
+}
+
+impl Palette {
+    /// Colors of the configuration, none when one isn't valid
+    pub fn from_config(palette: &config::Palette) -> Option<Self> {
+        Some(Self {
+            background: gdk::RGBA::parse(&palette.background).ok()?,
+            foreground: gdk::RGBA::parse(&palette.foreground).ok()?,
+        })
+    }
+
+    pub fn to_config(self) -> config::Palette {
+        config::Palette {
+            background: to_hex(self.background),
+            foreground: to_hex(self.foreground),
+        }
+    }
+}
+
+/// Built-in palettes: id, label and colors
+pub const PALETTES: [(&str, &str, Palette); 3] = [
+    (
+        "classic",
+        "Classic",
+        Palette {
+            background: rgba(69, 115, 13, 0xFF),
+            foreground: rgba(124, 209, 13, 0x99),
+        },
+    ),
+    (
+        "black-and-white",
+        "Black and White",
+        Palette {
+            background: rgba(0, 0, 0, 0xFF),
+            foreground: rgba(0xFF, 0xFF, 0xFF, 0xFF),
+        },
+    ),
+    (
+        "amber",
+        "Amber",
+        Palette {
+            background: rgba(40, 20, 0, 0xFF),
+            foreground: rgba(0xFF, 0xB0, 0x00, 0xFF),
+        },
+    ),
+];
+
+const fn rgba(r: u8, g: u8, b: u8, a: u8) -> gdk::RGBA {
+    gdk::RGBA::new(
+        r as f32 / 255.,
+        g as f32 / 255.,
+        b as f32 / 255.,
+        a as f32 / 255.,
+    )
+}
+
+/// `#rrggbbaa`
+fn to_hex(color: gdk::RGBA) -> String {
+    let [r, g, b, a] =
+        [color.red(), color.green(), color.blue(), color.alpha()]
+            .map(|component| (component * 255.).round() as u8);
+
+    format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+}
+
+/// `vram` as an opaque texture, 1 pixel per chip8 pixel
+pub fn thumbnail(vram: &Vram, palette: Palette) -> gdk::Texture {
+    let Palette {
+        background,
+        foreground,
+    } = palette;
+    // the lit pixels are blended like on the display
+    let blend = |back: f32, front: f32| {
+        ((front * foreground.alpha() + back * (1. - foreground.alpha())) * 255.)
+            .round() as u8
+    };
+    let off = [background.red(), background.green(), background.blue()]
+        .map(|component| (component * 255.).round() as u8);
+    let on = [
+        blend(background.red(), foreground.red()),
+        blend(background.green(), foreground.green()),
+        blend(background.blue(), foreground.blue()),
+    ];
+    let mut data = vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT * 3];
+
+    for (w, column) in vram.iter().enumerate() {
+        for (h, &pixel) in column.iter().enumerate() {
+            let index = (DISPLAY_WIDTH * h + w) * 3;
+            data[index..index + 3].copy_from_slice(match pixel {
+                true => &on,
+                false => &off,
+            });
+        }
+    }
+
+    gdk::MemoryTexture::new(
+        DISPLAY_WIDTH as i32,
+        DISPLAY_HEIGHT as i32,
+        gdk::MemoryFormat::R8g8b8,
+        &glib::Bytes::from_owned(data),
+        DISPLAY_WIDTH * 3,
+    )
+    .upcast()
+}
+
+glib::wrapper! {
+    /// Widget showing the chip8 screen, scaled with nearest filtering
+    pub struct Display(ObjectSubclass<imp::Display>)
+        @extends gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    pub fn palette(&self) -> Palette {
+        self.imp().palette.get()
+    }
+
+    pub fn set_palette(&self, palette: Palette) {
+        self.imp().palette.set(palette);
+
+        let frame = self.imp().frame.borrow().clone();
+        if let Some(frame) = frame {
+            self.set_frame(&frame);
+        }
+    }
+
+    /// Scale the frame by a whole factor inside black bars, for the
+    /// fullscreen
+    pub fn set_letterbox(&self, letterbox: bool) {
+        self.imp().letterbox.set(letterbox);
+        self.queue_draw();
+    }
+
+    /// Replace the displayed frame, the pixels lit on the first plane are
+    /// drawn
+    pub fn set_frame(&self, frame: &Framebuffer) {
+        // the buffer of the last frame is reused
+        {
+            let mut kept = self.imp().frame.borrow_mut();
+            let kept = kept.get_or_insert_with(Framebuffer::default);
+            kept.width = frame.width;
+            kept.height = frame.height;
+            kept.pixels.clone_from(&frame.pixels);
+        }
+
+        let foreground = self.palette().foreground;
+        let color = [
+            foreground.red(),
+            foreground.green(),
+            foreground.blue(),
+            foreground.alpha(),
+        ]
+        .map(|component| (component * 255.).round() as u8);
+        let mut data = vec![0; frame.pixels.len() * 4];
+
+        for (texel, &pixel) in data.chunks_exact_mut(4).zip(&frame.pixels) {
+            if pixel & 1 != 0 {
+                texel.copy_from_slice(&color);
+            }
+        }
+
+        let texture = gdk::MemoryTexture::new(
+            frame.width as i32,
+            frame.height as i32,
+            gdk::MemoryFormat::R8g8b8a8,
+            &glib::Bytes::from_owned(data),
+            frame.width * 4,
+        );
+
+        self.imp().texture.replace(Some(texture.upcast()));
+        self.queue_draw();
+    }
+}
+
+mod imp {
+    use std::cell::{Cell, RefCell};
+
+    use chip8::bus::{Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+    use gtk::{gdk, glib, graphene, gsk, prelude::*, subclass::prelude::*};
+
+    use super::Palette;
+
+    #[derive(Default)]
+    pub struct Display {
+        pub(super) texture: RefCell<Option<gdk::Texture>>,
+        pub(super) palette: Cell<Palette>,
+        /// Kept to redraw the frame with another palette
+        pub(super) frame: RefCell<Option<Framebuffer>>,
+        pub(super) letterbox: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Display {
+        const NAME: &'static str = "Chip8Display";
+        type Type = super::Display;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.set_accessible_role(gtk::AccessibleRole::Img);
+        }
+    }
+
+    impl ObjectImpl for Display {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            // focusable so that Tab comes back to it from the controls
+            let widget = self.obj();
+            widget.set_focusable(true);
+            widget.update_property(&[gtk::accessible::Property::Label(
+                "Chip8 screen",
+            )]);
+        }
+    }
+
+    impl WidgetImpl for Display {
+        fn snapshot(&self, snapshot: &gtk::Snapshot) {
+            let widget = self.obj();
+            let width = widget.width() as f32;
+            let height = widget.height() as f32;
+
+            // fit the widget while keeping the aspect ratio
+            let mut scale = (width / DISPLAY_WIDTH as f32)
+                .min(height / DISPLAY_HEIGHT as f32);
+            if self.letterbox.get() {
+                scale = scale.floor().max(1.0);
+            }
+            let frame_width = DISPLAY_WIDTH as f32 * scale;
+            let frame_height = DISPLAY_HEIGHT as f32 * scale;
+            let frame = graphene::Rect::new(
+                (width - frame_width) / 2.0,
+                (height - frame_height) / 2.0,
+                frame_width,
+                frame_height,
+            );
+
+            let background = self.palette.get().background;
+            if self.letterbox.get() {
+                snapshot.append_color(
+                    &gdk::RGBA::BLACK,
+                    &graphene::Rect::new(0.0, 0.0, width, height),
+                );
+                snapshot.append_color(&background, &frame);
+            } else {
+                snapshot.append_color(
+                    &background,
+                    &graphene::Rect::new(0.0, 0.0, width, height),
+                );
+            }
+
+            if let Some(texture) = self.texture.borrow().as_ref() {
+                snapshot.append_scaled_texture(
+                    texture,
+                    gsk::ScalingFilter::Nearest,
+                    &frame,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,529 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use chip8::{
+    bus::KEYPAD_SIZE,
+    cpu::{CpuBus, KeyWaitPolicy},
+    machine::Machine,
+    rom::Rom,
+};
+use chip8_frontend::{
+    checksum,
+    config::{Binding, Config, Gamepad, Hotkey},
+    rhai_script::RhaiScript,
+    rom_info,
+    slots::{SlotError, Slots},
+    watch::{Rule, WatchEvent, WatchRules, Watcher},
+};
+use log::{debug, warn};
+
+use crate::keymap;
+
+/// Frames run in the time of one with the turbo on
+const TURBO_SPEED: f64 = 4.0;
+/// Axis position past which its direction is pressed
+const AXIS_THRESHOLD: f32 = 0.5;
+/// Period over which the rates are measured
+const STATS_PERIOD: Duration = Duration::from_secs(1);
+
+/// The loaded rom file
+pub struct RomInfo {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: usize,
+    /// FNV-1a of the content, which also names the save slots directory
+    pub checksum: u32,
+}
+
+pub struct Emulator {
+    // chip8
+    machine: Machine,
+    /// Nothing runs until a rom is loaded
+    rom: Option<RomInfo>,
+    /// States of the loaded rom
+    slots: Option<Slots>,
+    breakpoints: BTreeSet<u16>,
+    /// The next instruction runs even on a breakpoint, to leave it
+    resuming: bool,
+    /// Commands of the console, its `on_frame` is called before each frame
+    script: RhaiScript,
+    /// Why `on_frame` paused the emulator, until taken with the output
+    script_error: Option<String>,
+    /// Memory rules of the loaded rom, from `WatchRules`
+    watch_rules: Vec<Rule>,
+    watcher: Watcher,
+    /// Fired since the last `take_watch_events`
+    watch_events: Vec<WatchEvent>,
+    /// Hardware keycode of each keypad key, 0 when unmapped
+    keymap: [u32; KEYPAD_SIZE],
+    //
+    loop_time: Instant,
+    /// The screen changed outside of `tick`
+    redraw: bool,
+    // stats
+    stats_time: Instant,
+    stats_frames: u32,
+    stats_instructions: u64,
+    fps: f64,
+    ips: f64,
+    //
+    gilrs: gilrs::Gilrs,
+    /// Bindings by gamepad id
+    gamepads: BTreeMap<String, Gamepad>,
+    /// Direction pressed on each axis, -1, 0 or 1
+    axes: HashMap<(gilrs::GamepadId, gilrs::Axis), i8>,
+    /// Hotkeys pressed since the last `take_hotkeys`
+    hotkeys: Vec<Hotkey>,
+    /// The next gamepad input is kept here instead of being used, once
+    /// requested by `capture_input`
+    captured: Option<Option<(String, String)>>,
+}
+
+impl Emulator {
+    pub fn new() -> Self {
+        let machine = Machine::new(Rom::from_bytes(vec![]));
+
+        Self {
+            watcher: Watcher::new(&[], &machine),
+            machine,
+            rom: None,
+            slots: None,
+            breakpoints: BTreeSet::new(),
+            resuming: false,
+            script: RhaiScript::new("").expect("empty script"),
+            script_error: None,
+            watch_rules: vec![],
+            watch_events: vec![],
+            keymap: [0; KEYPAD_SIZE],
+            loop_time: Instant::now(),
+            redraw: false,
+            stats_time: Instant::now(),
+            stats_frames: 0,
+            stats_instructions: 0,
+            fps: 0.0,
+            ips: 0.0,
+            gilrs: gilrs::Gilrs::new().expect("GilRs init"),
+            gamepads: BTreeMap::new(),
+            axes: HashMap::new(),
+            hotkeys: vec![],
+            captured: None,
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn rom(&self) -> Option<&RomInfo> {
+        self.rom.as_ref()
+    }
+
+    pub fn rom_name(&self) -> Option<&str> {
+        self.rom.as_ref().map(|rom| rom.name.as_str())
+    }
+
+    /// Start the rom at `path` on a fresh machine, the settings are kept
+    pub fn load_rom(&mut self, path: &Path) -> io::Result<()> {
+        let data = fs::read(path)?;
+        let slots = Slots::for_rom(&data);
+        let sha1: String = rom_info::sha1(&data)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let info = RomInfo {
+            name: path.file_name().map_or_else(
+                || path.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+            path: path.to_path_buf(),
+            size: data.len(),
+            checksum: checksum(&data),
+        };
+        let platform = rom_info::RomInfo::new(&data).platform;
+        let rom = Rom::from_bytes(data);
+        debug!("loaded: {}", rom);
+        let machine = match platform {
+            rom_info::Platform::XoChip => Machine::try_new_xo_chip(rom),
+            _ => Machine::try_new(rom),
+        }
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let frequency = self.machine.cpu_frequency();
+        let policy = self.machine.cpu().key_wait_policy();
+        let quirks = self.machine.cpu().quirks();
+        let speed = self.machine.speed();
+        let paused = self.machine.is_paused();
+        self.machine = machine;
+        self.machine.set_cpu_frequency(frequency);
+        self.machine.cpu_mut().set_key_wait_policy(policy);
+        self.machine.cpu_mut().set_quirks(quirks);
+        self.machine.set_speed(speed);
+        self.machine.pause(paused);
+        self.rom = Some(info);
+        self.slots = slots;
+        self.watch_rules = WatchRules::load().get(&sha1).to_vec();
+        self.restart_watcher();
+        self.redraw = true;
+
+        Ok(())
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.machine.is_paused()
+    }
+
+    pub fn set_running(&mut self, running: bool) {
+        self.machine.pause(!running);
+        self.resuming = running;
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<u16> {
+        &self.breakpoints
+    }
+
+    /// Pause before the instruction at `addr` when `enabled`
+    pub fn set_breakpoint(&mut self, addr: u16, enabled: bool) {
+        match enabled {
+            true => self.breakpoints.insert(addr),
+            false => self.breakpoints.remove(&addr),
+        };
+    }
+
+    /// Run a command of the console, returns its value as text
+    pub fn eval(&mut self, command: &str) -> Result<String, String> {
+        let result = self.script.eval(&mut self.machine, command);
+        self.apply_script_breakpoints();
+        self.redraw = true;
+
+        result
+    }
+
+    /// Lines printed by the console commands and `on_frame`, and its error
+    pub fn take_script_output(&mut self) -> Vec<String> {
+        let mut output = self.script.take_output();
+        output
+            .extend(self.script_error.take().map(|e| format!("error: {}", e)));
+
+        output
+    }
+
+    fn apply_script_breakpoints(&mut self) {
+        for (addr, enabled) in self.script.take_breakpoints() {
+            self.set_breakpoint(addr, enabled);
+        }
+    }
+
+    /// Frames run per second, over the last second
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    /// Instructions executed per second, over the last second
+    pub fn ips(&self) -> f64 {
+        self.ips
+    }
+
+    pub fn reset(&mut self) {
+        self.machine.reset();
+        self.restart_watcher();
+        self.redraw = true;
+    }
+
+    /// The values before the restart would fire the rules
+    fn restart_watcher(&mut self) {
+        self.watcher = Watcher::new(&self.watch_rules, &self.machine);
+        self.watch_events.clear();
+    }
+
+    /// Memory rules of the rom that fired since the last call
+    pub fn take_watch_events(&mut self) -> Vec<WatchEvent> {
+        std::mem::take(&mut self.watch_events)
+    }
+
+    pub fn cpu_frequency(&self) -> f64 {
+        self.machine.cpu_frequency()
+    }
+
+    pub fn set_cpu_frequency(&mut self, frequency: f64) {
+        self.machine.set_cpu_frequency(frequency);
+    }
+
+    pub fn is_turbo(&self) -> bool {
+        self.machine.speed() == TURBO_SPEED
+    }
+
+    /// Run the frames faster than their rate, the speed of the cpu is kept
+    pub fn set_turbo(&mut self, turbo: bool) {
+        self.machine.set_speed(match turbo {
+            true => TURBO_SPEED,
+            false => 1.0,
+        });
+    }
+
+    pub fn set_key_wait_policy(&mut self, policy: KeyWaitPolicy) {
+        self.machine.cpu_mut().set_key_wait_policy(policy);
+    }
+
+    /// Map the keypad to the keys named in `keymap`
+    pub fn set_keymap(&mut self, keymap: &[String; KEYPAD_SIZE]) {
+        for (keycode, name) in self.keymap.iter_mut().zip(keymap) {
+            *keycode = keymap::keycode(name).unwrap_or_else(|| {
+                warn!("unknown key {}", name);
+                0
+            });
+        }
+        self.machine.bus_mut().keys = [false; KEYPAD_SIZE];
+    }
+
+    /// Take the settings of `config`
+    pub fn apply_config(&mut self, config: &Config) {
+        self.set_cpu_frequency(config.cpu_frequency);
+        self.set_key_wait_policy(config.key_wait_policy);
+        self.machine.cpu_mut().set_quirks(config.quirks());
+        self.set_keymap(&config.keymap);
+        self.gamepads = config.gamepads.clone();
+    }
+
+    /// Id and name of the connected gamepads
+    pub fn gamepads(&self) -> Vec<(String, String)> {
+        self.gilrs
+            .gamepads()
+            .map(|(_, gamepad)| {
+                (gamepad_id(&gamepad), gamepad.name().to_string())
+            })
+            .collect()
+    }
+
+    /// Keep the next gamepad input for `take_captured` instead of using it
+    pub fn capture_input(&mut self) {
+        self.captured = Some(None);
+    }
+
+    pub fn cancel_capture(&mut self) {
+        self.captured = None;
+    }
+
+    /// Gamepad id and input name of the captured input, once there is one
+    pub fn take_captured(&mut self) -> Option<(String, String)> {
+        match self.captured {
+            Some(Some(_)) => self.captured.take().flatten(),
+            _ => None,
+        }
+    }
+
+    /// Hotkeys pressed on the gamepads since the last call
+    pub fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys)
+    }
+
+    pub fn write_memory(&mut self, addr: u16, byte: u8) {
+        self.machine.bus_mut().write_byte(addr, byte);
+    }
+
+    /// Execute a single instruction, while paused
+    pub fn step(&mut self) {
+        if self.machine.is_paused() && self.rom.is_some() {
+            self.machine.step();
+            self.redraw = true;
+        }
+    }
+
+    /// Finish the current frame, while paused
+    pub fn advance_frame(&mut self) {
+        if self.machine.is_paused() && self.rom.is_some() {
+            self.machine.run_frame();
+            self.redraw = true;
+        }
+    }
+
+    /// Save slots of the loaded rom, none without a rom or a data
+    /// directory
+    pub fn slots(&self) -> Option<&Slots> {
+        self.slots.as_ref()
+    }
+
+    pub fn set_slot(&mut self, slot: usize) {
+        if let Some(slots) = &mut self.slots {
+            slots.set_current(slot);
+        }
+    }
+
+    /// Save the machine to the current slot
+    pub fn save_state(&self) -> Result<(), SlotError> {
+        match &self.slots {
+            Some(slots) => slots.save_current(&self.machine),
+            None => Ok(()),
+        }
+    }
+
+    /// Restore the machine from the current slot
+    pub fn load_state(&mut self) -> Result<(), SlotError> {
+        if let Some(slots) = &self.slots {
+            slots.load_current(&mut self.machine)?;
+            self.restart_watcher();
+            self.redraw = true;
+        }
+
+        Ok(())
+    }
+
+    /// Run the frames due since the last call, returns true when the screen
+    /// may have changed
+    /// Reaching a breakpoint pauses the emulator
+    pub fn tick(&mut self) -> bool {
+        // Examine new events
+        while let Some(gilrs::Event { id, event, time: _ }) =
+            self.gilrs.next_event()
+        {
+            match event {
+                gilrs::EventType::ButtonPressed(button, _code) => {
+                    self.gamepad_input(id, &format!("{:?}", button), true)
+                }
+
+                gilrs::EventType::ButtonReleased(button, _code) => {
+                    self.gamepad_input(id, &format!("{:?}", button), false)
+                }
+
+                gilrs::EventType::AxisChanged(axis, value, _code) => {
+                    self.axis_input(id, axis, value)
+                }
+
+                _ => {}
+            }
+        }
+
+        let delta = self.loop_time.elapsed();
+        self.loop_time = Instant::now();
+        self.update_stats();
+
+        if self.rom.is_none() {
+            return std::mem::take(&mut self.redraw);
+        }
+
+        let mut updated = std::mem::take(&mut self.redraw);
+        for _ in 0..self.machine.frames_due(delta).run {
+            let result = self.script.on_frame(&mut self.machine);
+            self.apply_script_breakpoints();
+            if let Err(e) = result {
+                warn!("script: {}", e);
+                self.script_error = Some(e);
+                self.machine.pause(true);
+                break;
+            }
+
+            let breakpoints = &self.breakpoints;
+            let resuming = &mut self.resuming;
+            let instructions = &mut self.stats_instructions;
+            // called before each instruction
+            let complete = self.machine.run_frame_until(|cpu| {
+                let stop = !std::mem::take(resuming)
+                    && breakpoints.contains(&cpu.pc());
+                *instructions += !stop as u64;
+                stop
+            });
+            updated = true;
+
+            if !complete {
+                debug!("breakpoint at 0x{:03X}", self.machine.cpu().pc());
+                self.machine.pause(true);
+                break;
+            }
+            self.stats_frames += 1;
+            self.watch_events.extend(self.watcher.update(&self.machine));
+        }
+
+        updated
+    }
+
+    fn update_stats(&mut self) {
+        let elapsed = self.stats_time.elapsed();
+        if elapsed < STATS_PERIOD {
+            return;
+        }
+
+        let seconds = elapsed.as_secs_f64();
+        self.fps = self.stats_frames as f64 / seconds;
+        self.ips = self.stats_instructions as f64 / seconds;
+        self.stats_time = Instant::now();
+        self.stats_frames = 0;
+        self.stats_instructions = 0;
+    }
+
+    pub fn keyboard_inputs(&mut self, keycode: u32, val: bool) {
+        let keys = &mut self.machine.bus_mut().keys;
+
+        for (key, &mapped) in keys.iter_mut().zip(&self.keymap) {
+            if mapped == keycode {
+                *key = val;
+            }
+        }
+    }
+
+    /// Press or release the direction past the threshold
+    fn axis_input(
+        &mut self,
+        id: gilrs::GamepadId,
+        axis: gilrs::Axis,
+        value: f32,
+    ) {
+        let direction = match value {
+            value if value > AXIS_THRESHOLD => 1,
+            value if value < -AXIS_THRESHOLD => -1,
+            _ => 0,
+        };
+        let previous = self.axes.insert((id, axis), direction).unwrap_or(0);
+        if direction == previous {
+            return;
+        }
+
+        let input = |direction| {
+            format!("{:?}{}", axis, if direction > 0 { '+' } else { '-' })
+        };
+        if previous != 0 {
+            self.gamepad_input(id, &input(previous), false);
+        }
+        if direction != 0 {
+            self.gamepad_input(id, &input(direction), true);
+        }
+    }
+
+    fn gamepad_input(&mut self, id: gilrs::GamepadId, input: &str, val: bool) {
+        debug!("gamepad input: {}, {}", input, val);
+
+        let gamepad = self.gilrs.gamepad(id);
+        let gamepad_id = gamepad_id(&gamepad);
+
+        if let Some(captured) = &mut self.captured {
+            if val && captured.is_none() {
+                *captured = Some((gamepad_id, input.to_string()));
+            }
+            return;
+        }
+
+        let binding = match self.gamepads.get(&gamepad_id) {
+            Some(bindings) => bindings.bindings.get(input).copied(),
+            None => Gamepad::new(gamepad.name()).bindings.get(input).copied(),
+        };
+        match binding {
+            Some(Binding::Keypad(key)) => {
+                self.machine.bus_mut().keys[key as usize] = val
+            }
+            // on press only
+            Some(Binding::Hotkey(hotkey)) if val => self.hotkeys.push(hotkey),
+            _ => {}
+        }
+    }
+}
+
+/// Gamepads are told apart by the uuid of their model
+fn gamepad_id(gamepad: &gilrs::Gamepad) -> String {
+    gamepad
+        .uuid()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
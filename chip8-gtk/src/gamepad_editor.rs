@@ -0,0 +1,272 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use chip8::bus::KEYPAD_SIZE;
+use chip8_frontend::config::{Binding, Config, Gamepad, HOTKEYS};
+use gtk::{glib, prelude::*};
+
+use crate::{a11y, emulator::Emulator, window::save_config};
+
+/// A binding for each position of the target drop downs: the keypad keys
+/// then the hotkeys
+fn targets() -> Vec<(Binding, String)> {
+    let keys = (0..KEYPAD_SIZE as u8)
+        .map(|key| (Binding::Keypad(key), format!("Key {:X}", key)));
+    let hotkeys = HOTKEYS.iter().map(|&(hotkey, _, label)| {
+        (Binding::Hotkey(hotkey), label.to_string())
+    });
+
+    keys.chain(hotkeys).collect()
+}
+
+/// Everything the editor parts share
+struct Editor {
+    emulator: Rc<RefCell<Emulator>>,
+    config: Rc<RefCell<Config>>,
+    apply: Box<dyn Fn()>,
+    /// Id and name of the gamepad being edited
+    gamepad: RefCell<Option<(String, String)>>,
+    bindings: gtk::ListBox,
+    add: gtk::Button,
+    /// Waiting for an input of the gamepad to bind
+    capturing: Cell<bool>,
+}
+
+impl Editor {
+    /// Change the bindings of the edited gamepad, starting from the defaults
+    /// when it has no section yet
+    fn edit(&self, change: impl FnOnce(&mut Gamepad)) {
+        let Some((id, name)) = self.gamepad.borrow().clone() else {
+            return;
+        };
+
+        let mut config = self.config.borrow_mut();
+        change(
+            config
+                .gamepads
+                .entry(id)
+                .or_insert_with(|| Gamepad::new(name)),
+        );
+        drop(config);
+        (self.apply)();
+    }
+
+    /// Show the bindings of the edited gamepad
+    fn fill(self: &Rc<Self>) {
+        while let Some(row) = self.bindings.first_child() {
+            self.bindings.remove(&row);
+        }
+        self.add.set_sensitive(self.gamepad.borrow().is_some());
+
+        let Some((id, name)) = self.gamepad.borrow().clone() else {
+            return;
+        };
+        let gamepad = self
+            .config
+            .borrow()
+            .gamepads
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| Gamepad::new(name));
+
+        let choices = targets();
+        let labels: Vec<&str> =
+            choices.iter().map(|(_, label)| label.as_str()).collect();
+
+        for (input, binding) in gamepad.bindings {
+            let target = gtk::DropDown::from_strings(&labels);
+            a11y::set_label(&target, &input);
+            if let Some(index) =
+                choices.iter().position(|(known, _)| *known == binding)
+            {
+                target.set_selected(index as u32);
+            }
+            target.connect_selected_notify({
+                let editor = self.clone();
+                let input = input.clone();
+                move |target| {
+                    if let Some(&(binding, _)) =
+                        targets().get(target.selected() as usize)
+                    {
+                        editor.edit(|gamepad| {
+                            gamepad.bindings.insert(input.clone(), binding);
+                        });
+                    }
+                }
+            });
+
+            let remove = gtk::Button::from_icon_name("list-remove-symbolic");
+            remove.set_tooltip_text(Some("Remove"));
+            a11y::set_label(&remove, &format!("Remove {}", input));
+            remove.connect_clicked({
+                let editor = self.clone();
+                let input = input.clone();
+                move |_| {
+                    editor.edit(|gamepad| {
+                        gamepad.bindings.remove(&input);
+                    });
+                    editor.fill();
+                }
+            });
+
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+            row.append(
+                &gtk::Label::builder()
+                    .label(&input)
+                    .xalign(0.0)
+                    .hexpand(true)
+                    .build(),
+            );
+            row.append(&target);
+            row.append(&remove);
+            self.bindings.append(&row);
+        }
+    }
+
+    /// Bind the captured input, to the first keypad key until another
+    /// target is chosen
+    fn captured(self: &Rc<Self>, id: &str, input: String) {
+        let edited = self.gamepad.borrow().as_ref().map(|(id, _)| id.clone());
+        // an input of another gamepad is ignored
+        if edited.as_deref() != Some(id) {
+            self.emulator.borrow_mut().capture_input();
+            return;
+        }
+
+        self.capturing.set(false);
+        self.add.set_label("Add Binding…");
+        self.edit(|gamepad| {
+            gamepad.bindings.entry(input).or_insert(Binding::Keypad(0));
+        });
+        self.fill();
+    }
+}
+
+/// Show a window editing the bindings of the connected gamepads in
+/// `config`, `apply` is called after each change and the configuration is
+/// saved when the window is closed
+pub fn show(
+    parent: &gtk::ApplicationWindow,
+    emulator: &Rc<RefCell<Emulator>>,
+    config: &Rc<RefCell<Config>>,
+    apply: impl Fn() + 'static,
+) {
+    let gamepads = emulator.borrow().gamepads();
+
+    let container = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(6)
+        .margin_top(12)
+        .margin_bottom(12)
+        .margin_start(12)
+        .margin_end(12)
+        .width_request(360)
+        .build();
+
+    let names: Vec<&str> =
+        gamepads.iter().map(|(_, name)| name.as_str()).collect();
+    let selector = gtk::DropDown::from_strings(&names);
+    a11y::set_label(&selector, "Gamepad");
+    if gamepads.is_empty() {
+        container.append(&gtk::Label::new(Some("No gamepad detected")));
+    } else {
+        container.append(&selector);
+    }
+
+    let bindings = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .build();
+    bindings.add_css_class("boxed-list");
+    container.append(
+        &gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .min_content_height(240)
+            .vexpand(true)
+            .child(&bindings)
+            .build(),
+    );
+
+    let add = gtk::Button::with_label("Add Binding…");
+    let reset = gtk::Button::with_label("Reset to Defaults");
+    let buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    buttons.append(&add);
+    buttons.append(&reset);
+    container.append(&buttons);
+
+    let editor = Rc::new(Editor {
+        emulator: emulator.clone(),
+        config: config.clone(),
+        apply: Box::new(apply),
+        gamepad: RefCell::new(gamepads.first().cloned()),
+        bindings,
+        add: add.clone(),
+        capturing: Cell::new(false),
+    });
+    editor.fill();
+
+    selector.connect_selected_notify({
+        let editor = editor.clone();
+        move |selector| {
+            editor
+                .gamepad
+                .replace(gamepads.get(selector.selected() as usize).cloned());
+            editor.fill();
+        }
+    });
+
+    add.connect_clicked({
+        let editor = editor.clone();
+        move |add| {
+            editor.capturing.set(true);
+            editor.emulator.borrow_mut().capture_input();
+            add.set_label("Press a button…");
+        }
+    });
+
+    reset.connect_clicked({
+        let editor = editor.clone();
+        move |_| {
+            if let Some((id, _)) = editor.gamepad.borrow().clone() {
+                editor.config.borrow_mut().gamepads.remove(&id);
+                (editor.apply)();
+            }
+            editor.fill();
+        }
+    });
+
+    let window = gtk::Window::builder()
+        .title("Gamepads")
+        .transient_for(parent)
+        .destroy_with_parent(true)
+        .child(&container)
+        .build();
+    a11y::close_on_escape(&window);
+
+    // the emulator reads the gamepads, the captured input is taken here
+    window.add_tick_callback({
+        let editor = editor.clone();
+        move |_, _| {
+            if editor.capturing.get() {
+                let captured = editor.emulator.borrow_mut().take_captured();
+                if let Some((id, input)) = captured {
+                    editor.captured(&id, input);
+                }
+            }
+            glib::ControlFlow::Continue
+        }
+    });
+
+    window.connect_close_request({
+        let config = config.clone();
+        let emulator = emulator.clone();
+        move |_| {
+            emulator.borrow_mut().cancel_capture();
+            save_config(&config.borrow());
+            glib::Propagation::Proceed
+        }
+    });
+
+    window.present();
+}
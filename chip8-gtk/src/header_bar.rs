@@ -0,0 +1,61 @@
+use gtk::prelude::*;
+
+use crate::{a11y, emulator::Emulator};
+
+/// Titlebar of the window, with indicators telling why the screen may be
+/// still: the emulator is paused, or the rom waits for a key (FX0A)
+pub struct HeaderBar {
+    header_bar: gtk::HeaderBar,
+    paused: gtk::Image,
+    waiting: gtk::Image,
+    beeping: gtk::Image,
+}
+
+impl HeaderBar {
+    pub fn new() -> Self {
+        let header_bar = gtk::HeaderBar::new();
+
+        let indicator = |icon: &str, tooltip: &str| {
+            let image = gtk::Image::builder()
+                .icon_name(icon)
+                .tooltip_text(tooltip)
+                .visible(false)
+                .build();
+            a11y::set_label(&image, tooltip);
+            image
+        };
+        let paused = indicator("media-playback-pause-symbolic", "Paused");
+        let waiting =
+            indicator("input-keyboard-symbolic", "Waiting for a key (FX0A)");
+        let beeping = indicator("audio-volume-high-symbolic", "Beeping");
+
+        // from the right edge
+        header_bar.pack_end(&beeping);
+        header_bar.pack_end(&waiting);
+        header_bar.pack_end(&paused);
+
+        Self {
+            header_bar,
+            paused,
+            waiting,
+            beeping,
+        }
+    }
+
+    pub fn widget(&self) -> &gtk::HeaderBar {
+        &self.header_bar
+    }
+
+    /// Show the indicators matching the state of `emulator`, none without a
+    /// rom
+    pub fn update(&self, emulator: &Emulator) {
+        let loaded = emulator.rom().is_some();
+        let machine = emulator.machine();
+        let running = loaded && emulator.is_running();
+
+        self.paused.set_visible(loaded && !emulator.is_running());
+        self.waiting
+            .set_visible(loaded && machine.cpu().key_await().is_some());
+        self.beeping.set_visible(running && machine.is_beeping());
+    }
+}
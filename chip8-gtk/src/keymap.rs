@@ -0,0 +1,100 @@
+/// Names of the keyboard keys, like `KeyboardEvent.code`, with their
+/// hardware keycode (evdev code + 8), the position on the keyboard doesn't
+/// depend on the layout
+const KEYCODES: [(&str, u32); 51] = [
+    ("Digit1", 10),
+    ("Digit2", 11),
+    ("Digit3", 12),
+    ("Digit4", 13),
+    ("Digit5", 14),
+    ("Digit6", 15),
+    ("Digit7", 16),
+    ("Digit8", 17),
+    ("Digit9", 18),
+    ("Digit0", 19),
+    ("KeyQ", 24),
+    ("KeyW", 25),
+    ("KeyE", 26),
+    ("KeyR", 27),
+    ("KeyT", 28),
+    ("KeyY", 29),
+    ("KeyU", 30),
+    ("KeyI", 31),
+    ("KeyO", 32),
+    ("KeyP", 33),
+    ("KeyA", 38),
+    ("KeyS", 39),
+    ("KeyD", 40),
+    ("KeyF", 41),
+    ("KeyG", 42),
+    ("KeyH", 43),
+    ("KeyJ", 44),
+    ("KeyK", 45),
+    ("KeyL", 46),
+    ("KeyZ", 52),
+    ("KeyX", 53),
+    ("KeyC", 54),
+    ("KeyV", 55),
+    ("KeyB", 56),
+    ("KeyN", 57),
+    ("KeyM", 58),
+    ("Space", 65),
+    ("Numpad7", 79),
+    ("Numpad8", 80),
+    ("Numpad9", 81),
+    ("Numpad4", 83),
+    ("Numpad5", 84),
+    ("Numpad6", 85),
+    ("Numpad1", 87),
+    ("Numpad2", 88),
+    ("Numpad3", 89),
+    ("Numpad0", 90),
+    ("ArrowUp", 111),
+    ("ArrowLeft", 113),
+    ("ArrowRight", 114),
+    ("ArrowDown", 116),
+];
+
+pub fn keycode(name: &str) -> Option<u32> {
+    KEYCODES
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|&(_, keycode)| keycode)
+}
+
+pub fn name(keycode: u32) -> Option<&'static str> {
+    KEYCODES
+        .iter()
+        .find(|&&(_, known)| known == keycode)
+        .map(|&(name, _)| name)
+}
+
+/// The GTK accelerator of a configuration shortcut like `Ctrl+O`, none when
+/// it isn't valid
+pub fn accelerator(shortcut: &str) -> Option<String> {
+    let mut parts: Vec<&str> = shortcut.split('+').collect();
+    let key = parts.pop()?;
+
+    let mut accelerator = String::new();
+    for modifier in parts {
+        accelerator.push_str(match modifier {
+            "Ctrl" => "<Control>",
+            "Shift" => "<Shift>",
+            "Alt" => "<Alt>",
+            _ => return None,
+        });
+    }
+    // the keys are named after their GDK key names
+    match key {
+        "Space" => accelerator.push_str("space"),
+        "Plus" => accelerator.push_str("plus"),
+        "Minus" => accelerator.push_str("minus"),
+        "Enter" => accelerator.push_str("Return"),
+        key if key.chars().count() == 1 => {
+            accelerator.push_str(&key.to_lowercase())
+        }
+        key => accelerator.push_str(key),
+    }
+
+    gtk::accelerator_parse(&accelerator).map(|_| accelerator)
+}
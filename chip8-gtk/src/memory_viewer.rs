@@ -0,0 +1,230 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use gtk::{glib, prelude::*};
+
+use crate::{a11y, emulator::Emulator};
+
+const BYTES_PER_ROW: usize = 16;
+/// Ticks between two refreshes while the emulator runs
+const REFRESH_TICKS: u32 = 10;
+/// Refreshes during which a written address stays highlighted
+const WRITTEN_REFRESHES: u8 = 3;
+
+const PC_COLOR: &str = "#3584e4";
+const INDEX_COLOR: &str = "#33d17a";
+const WRITTEN_COLOR: &str = "#f6d32d";
+
+/// Window with the memory in hexadecimal, highlighting the PC, I and the
+/// addresses written recently, a byte can be changed from its address
+pub struct MemoryViewer {
+    window: gtk::Window,
+    dump: gtk::Label,
+    /// Memory at the last refresh
+    previous: RefCell<Vec<u8>>,
+    /// Refreshes left to highlight each address
+    written: RefCell<Vec<u8>>,
+    /// Last shown, the label keeps its selection when it doesn't change
+    markup: RefCell<String>,
+    ticks: Cell<u32>,
+}
+
+impl MemoryViewer {
+    pub fn new(
+        parent: &gtk::ApplicationWindow,
+        emulator: &Rc<RefCell<Emulator>>,
+    ) -> Self {
+        let dump = gtk::Label::builder()
+            .xalign(0.0)
+            .yalign(0.0)
+            .selectable(true)
+            .build();
+        dump.add_css_class("monospace");
+        let scrolled = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .vexpand(true)
+            .child(&dump)
+            .build();
+
+        let address = gtk::Entry::builder()
+            .placeholder_text("Address")
+            .max_width_chars(6)
+            .build();
+        let value = gtk::Entry::builder()
+            .placeholder_text("Value")
+            .max_width_chars(4)
+            .build();
+        let write = gtk::Button::with_label("Write");
+        a11y::set_label(&dump, "Memory");
+        a11y::set_label(&address, "Address");
+        a11y::set_label(&value, "Value");
+        let edit = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        edit.append(&address);
+        edit.append(&value);
+        edit.append(&write);
+
+        let apply = {
+            let emulator = emulator.clone();
+            let address = address.clone();
+            let value = value.clone();
+            move || {
+                let addr = parse_hex(&address.text()).filter(|&addr| {
+                    (addr as usize)
+                        < emulator.borrow().machine().bus().memory().len()
+                });
+                let byte = parse_hex(&value.text())
+                    .and_then(|byte| u8::try_from(byte).ok());
+                set_error(&address, addr.is_none());
+                set_error(&value, byte.is_none());
+
+                if let (Some(addr), Some(byte)) = (addr, byte) {
+                    emulator.borrow_mut().write_memory(addr, byte);
+                }
+            }
+        };
+        value.connect_activate({
+            let apply = apply.clone();
+            move |_| apply()
+        });
+        write.connect_clicked(move |_| apply());
+
+        let container = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .margin_top(6)
+            .margin_bottom(6)
+            .margin_start(6)
+            .margin_end(6)
+            .build();
+        container.append(&scrolled);
+        container.append(&edit);
+
+        let window = gtk::Window::builder()
+            .title("Memory")
+            .transient_for(parent)
+            .destroy_with_parent(true)
+            .hide_on_close(true)
+            .default_height(480)
+            .child(&container)
+            .build();
+        a11y::close_on_escape(&window);
+
+        Self {
+            window,
+            dump,
+            previous: RefCell::default(),
+            written: RefCell::default(),
+            markup: RefCell::default(),
+            ticks: Cell::new(0),
+        }
+    }
+
+    pub fn present(&self) {
+        // shown at once, even while running
+        self.ticks.set(REFRESH_TICKS);
+        self.window.present();
+    }
+
+    /// Refresh from `emulator`, every tick while paused and every
+    /// `REFRESH_TICKS` while running, nothing is done while hidden
+    pub fn update(&self, emulator: &Emulator) {
+        if !self.window.is_visible() {
+            return;
+        }
+        if emulator.is_running() {
+            self.ticks.set(self.ticks.get() + 1);
+            if self.ticks.get() < REFRESH_TICKS {
+                return;
+            }
+        }
+        self.ticks.set(0);
+
+        let machine = emulator.machine();
+        let memory = machine.bus().memory();
+
+        let mut previous = self.previous.borrow_mut();
+        let mut written = self.written.borrow_mut();
+        // nothing is highlighted on the first refresh
+        if previous.len() != memory.len() {
+            *previous = memory.to_vec();
+            *written = vec![0; memory.len()];
+        }
+        for ((old, &new), written) in
+            previous.iter_mut().zip(memory).zip(written.iter_mut())
+        {
+            if *old != new {
+                *old = new;
+                *written = WRITTEN_REFRESHES;
+            } else if emulator.is_running() {
+                // the edits made while paused stay highlighted
+                *written = written.saturating_sub(1);
+            }
+        }
+
+        let pc = machine.cpu().pc() as usize;
+        let index = machine.cpu().index() as usize;
+        let mut markup = String::with_capacity(memory.len() * 4);
+        for (row, bytes) in memory.chunks(BYTES_PER_ROW).enumerate() {
+            let start = row * BYTES_PER_ROW;
+            markup.push_str(&format!("{:03X} ", start));
+
+            for (offset, byte) in bytes.iter().enumerate() {
+                let addr = start + offset;
+                let color = if addr == pc || addr == pc + 1 {
+                    Some(PC_COLOR)
+                } else if addr == index {
+                    Some(INDEX_COLOR)
+                } else if written[addr] > 0 {
+                    Some(WRITTEN_COLOR)
+                } else {
+                    None
+                };
+
+                match color {
+                    Some(color) => markup.push_str(&format!(
+                        " <span background=\"{}\">{:02X}</span>",
+                        color, byte
+                    )),
+                    None => markup.push_str(&format!(" {:02X}", byte)),
+                }
+            }
+
+            // the printable characters, like hexdump -C
+            let text: String = bytes
+                .iter()
+                .map(|&byte| match byte.is_ascii_graphic() {
+                    true => byte as char,
+                    false => '.',
+                })
+                .collect();
+            markup
+                .push_str(&format!("  {}\n", glib::markup_escape_text(&text)));
+        }
+        markup.pop();
+
+        if *self.markup.borrow() != markup {
+            self.dump.set_markup(&markup);
+            self.markup.replace(markup);
+        }
+    }
+}
+
+/// An hexadecimal number, with or without `0x`
+fn parse_hex(text: &str) -> Option<u16> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+
+    u16::from_str_radix(digits, 16).ok()
+}
+
+fn set_error(entry: &gtk::Entry, error: bool) {
+    match error {
+        true => entry.add_css_class("error"),
+        false => entry.remove_css_class("error"),
+    }
+}
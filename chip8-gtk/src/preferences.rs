@@ -0,0 +1,351 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use chip8::bus::KEYPAD_SIZE;
+use chip8_frontend::{
+    config::{Config, KEY_WAIT_POLICIES},
+    KEYPAD_LAYOUT,
+};
+use gtk::{gdk, glib, prelude::*};
+
+use crate::{
+    a11y,
+    display::{Palette, PALETTES},
+    keymap,
+    window::save_config,
+};
+
+/// Labels of the FX0A policies, in the order of `KEY_WAIT_POLICIES`
+pub const KEY_WAIT_LABELS: [&str; 3] =
+    ["Lowest key", "Most recently pressed", "First released"];
+
+const ESCAPE_KEYCODE: u32 = 9;
+
+/// A built-in palette or custom colors, the dropdown shows Custom when the
+/// colors aren't those of a built-in one
+fn palette_picker(
+    config: &Rc<RefCell<Config>>,
+    current: &Config,
+    apply: &Rc<impl Fn() + 'static>,
+) -> gtk::Box {
+    let colors = current
+        .palette
+        .as_ref()
+        .and_then(Palette::from_config)
+        .unwrap_or_default();
+
+    let mut labels: Vec<&str> =
+        PALETTES.iter().map(|(_, label, _)| *label).collect();
+    labels.push("Custom");
+    let themes = gtk::DropDown::from_strings(&labels);
+
+    let picker = |title: &str, color: &gdk::RGBA| {
+        let button = gtk::ColorDialogButton::new(Some(
+            gtk::ColorDialog::builder().title(title).build(),
+        ));
+        button.set_rgba(color);
+        button.set_tooltip_text(Some(title));
+        a11y::set_label(&button, title);
+        button
+    };
+    let background = picker("Background", &colors.background);
+    let foreground = picker("Lit pixels", &colors.foreground);
+
+    // the controls follow each other, the changes aren't from the user
+    let updating = Rc::new(Cell::new(false));
+    let show = {
+        let themes = themes.clone();
+        let background = background.clone();
+        let foreground = foreground.clone();
+        let updating = updating.clone();
+        move |colors: Palette| {
+            updating.set(true);
+            let index = PALETTES
+                .iter()
+                .position(|(_, _, palette)| *palette == colors)
+                .unwrap_or(PALETTES.len());
+            themes.set_selected(index as u32);
+            background.set_rgba(&colors.background);
+            foreground.set_rgba(&colors.foreground);
+            updating.set(false);
+        }
+    };
+    show(colors);
+
+    let pick = {
+        let config = config.clone();
+        let apply = apply.clone();
+        move |colors: Palette| {
+            config.borrow_mut().palette = Some(colors.to_config());
+            apply();
+        }
+    };
+
+    themes.connect_selected_notify({
+        let updating = updating.clone();
+        let show = show.clone();
+        let pick = pick.clone();
+        move |themes| {
+            if updating.get() {
+                return;
+            }
+            // Custom keeps the colors of the buttons
+            if let Some((_, _, colors)) =
+                PALETTES.get(themes.selected() as usize)
+            {
+                show(*colors);
+                pick(*colors);
+            }
+        }
+    });
+    for button in [&background, &foreground] {
+        button.connect_rgba_notify({
+            let background = background.clone();
+            let foreground = foreground.clone();
+            let updating = updating.clone();
+            let show = show.clone();
+            let pick = pick.clone();
+            move |_| {
+                if updating.get() {
+                    return;
+                }
+                let colors = Palette {
+                    background: background.rgba(),
+                    foreground: foreground.rgba(),
+                };
+                show(colors);
+                pick(colors);
+            }
+        });
+    }
+
+    let container = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    container.append(&themes);
+    container.append(&background);
+    container.append(&foreground);
+
+    container
+}
+
+/// Show a window editing `config`, `apply` is called after each change and
+/// the configuration is saved when the window is closed
+pub fn show(
+    parent: &gtk::ApplicationWindow,
+    config: &Rc<RefCell<Config>>,
+    apply: impl Fn() + 'static,
+) {
+    let apply = Rc::new(apply);
+    let current = config.borrow().clone();
+
+    let grid = gtk::Grid::builder()
+        .row_spacing(6)
+        .column_spacing(12)
+        .margin_top(12)
+        .margin_bottom(12)
+        .margin_start(12)
+        .margin_end(12)
+        .build();
+    let mut row = 0;
+    // Alt and the underlined letter of a label moves to its control, which
+    // the screen readers name after it
+    let mut add_row = |label: &str, widget: &gtk::Widget| {
+        grid.attach(
+            &gtk::Label::builder()
+                .label(label)
+                .use_underline(true)
+                .mnemonic_widget(widget)
+                .xalign(0.0)
+                .build(),
+            0,
+            row,
+            1,
+            1,
+        );
+        grid.attach(widget, 1, row, 1, 1);
+        row += 1;
+    };
+
+    add_row(
+        "_Palette",
+        palette_picker(config, &current, &apply).upcast_ref(),
+    );
+
+    let speed = gtk::SpinButton::with_range(50.0, 5000.0, 50.0);
+    speed.set_value(current.cpu_frequency);
+    speed.connect_value_changed({
+        let config = config.clone();
+        let apply = apply.clone();
+        move |speed| {
+            config.borrow_mut().cpu_frequency = speed.value();
+            apply();
+        }
+    });
+    add_row("_Speed (Hz)", speed.upcast_ref());
+
+    let key_wait = gtk::DropDown::from_strings(&KEY_WAIT_LABELS);
+    if let Some(index) = KEY_WAIT_POLICIES
+        .iter()
+        .position(|(policy, _)| *policy == current.key_wait_policy)
+    {
+        key_wait.set_selected(index as u32);
+    }
+    key_wait.connect_selected_notify({
+        let config = config.clone();
+        let apply = apply.clone();
+        move |key_wait| {
+            if let Some((policy, _)) =
+                KEY_WAIT_POLICIES.get(key_wait.selected() as usize)
+            {
+                config.borrow_mut().key_wait_policy = *policy;
+                apply();
+            }
+        }
+    });
+    add_row("FX0A _key", key_wait.upcast_ref());
+
+    let display_wait = gtk::Switch::builder()
+        .active(current.display_wait)
+        .halign(gtk::Align::Start)
+        .tooltip_text(
+            "DXYN waits for the next frame like on the COSMAC VIP, some \
+             games run too fast without it",
+        )
+        .build();
+    display_wait.connect_active_notify({
+        let config = config.clone();
+        let apply = apply.clone();
+        move |display_wait| {
+            config.borrow_mut().display_wait = display_wait.is_active();
+            apply();
+        }
+    });
+    add_row("_Display wait", display_wait.upcast_ref());
+
+    let sound = gtk::Switch::builder()
+        .active(current.audio.enabled)
+        .halign(gtk::Align::Start)
+        .build();
+    sound.connect_active_notify({
+        let config = config.clone();
+        let apply = apply.clone();
+        move |sound| {
+            config.borrow_mut().audio.enabled = sound.is_active();
+            apply();
+        }
+    });
+    add_row("S_ound", sound.upcast_ref());
+
+    let volume =
+        gtk::Scale::with_range(gtk::Orientation::Horizontal, 0.0, 1.0, 0.05);
+    volume.set_value(current.audio.volume);
+    volume.connect_value_changed({
+        let config = config.clone();
+        let apply = apply.clone();
+        move |volume| {
+            config.borrow_mut().audio.volume = volume.value();
+            apply();
+        }
+    });
+    add_row("_Volume", volume.upcast_ref());
+
+    let announce = gtk::Switch::builder()
+        .active(current.announce)
+        .halign(gtk::Align::Start)
+        .tooltip_text(
+            "Tell the screen reader when the emulator pauses, waits for a \
+             key or a memory watch rule fires",
+        )
+        .build();
+    announce.connect_active_notify({
+        let config = config.clone();
+        let apply = apply.clone();
+        move |announce| {
+            config.borrow_mut().announce = announce.is_active();
+            apply();
+        }
+    });
+    add_row("_Announce changes", announce.upcast_ref());
+
+    let keypad = gtk::Grid::builder()
+        .row_spacing(6)
+        .column_spacing(6)
+        .build();
+    // the next key pressed is mapped to this keypad key
+    let waiting: Rc<Cell<Option<usize>>> = Rc::new(Cell::new(None));
+    let buttons: Rc<RefCell<Vec<(usize, gtk::Button)>>> = Rc::default();
+    for (index, &key) in KEYPAD_LAYOUT.iter().enumerate() {
+        let key = key as usize;
+        let button = gtk::Button::with_label(&key_label(key, &current.keymap));
+        button.connect_clicked({
+            let waiting = waiting.clone();
+            move |button| {
+                waiting.set(Some(key));
+                button.set_label(&format!("{:X}: …", key));
+            }
+        });
+        keypad.attach(&button, index as i32 % 4, index as i32 / 4, 1, 1);
+        buttons.borrow_mut().push((key, button));
+    }
+    add_row("Key_map", keypad.upcast_ref());
+
+    let window = gtk::Window::builder()
+        .title("Preferences")
+        .transient_for(parent)
+        .destroy_with_parent(true)
+        .resizable(false)
+        .child(&grid)
+        .build();
+    a11y::close_on_escape(&window);
+
+    let key_controller = gtk::EventControllerKey::new();
+    // before the focused button, which would take space or enter
+    key_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+    key_controller.connect_key_pressed({
+        let config = config.clone();
+        move |_, _, keycode, _| {
+            let Some(key) = waiting.take() else {
+                return glib::Propagation::Proceed;
+            };
+
+            // escape keeps the current key
+            if keycode != ESCAPE_KEYCODE {
+                match keymap::name(keycode) {
+                    Some(name) => {
+                        config.borrow_mut().keymap[key] = name.to_string();
+                        apply();
+                    }
+                    None => waiting.set(Some(key)),
+                }
+            }
+
+            if waiting.get().is_none() {
+                for (mapped, button) in buttons.borrow().iter() {
+                    if *mapped == key {
+                        button.set_label(&key_label(
+                            key,
+                            &config.borrow().keymap,
+                        ));
+                    }
+                }
+            }
+            glib::Propagation::Stop
+        }
+    });
+    window.add_controller(key_controller);
+
+    window.connect_close_request({
+        let config = config.clone();
+        move |_| {
+            save_config(&config.borrow());
+            glib::Propagation::Proceed
+        }
+    });
+
+    window.present();
+}
+
+fn key_label(key: usize, keymap: &[String; KEYPAD_SIZE]) -> String {
+    format!("{:X}: {}", key, keymap[key])
+}
@@ -0,0 +1,62 @@
+use gtk::{gio, prelude::*};
+use log::warn;
+
+const APP_NAME: &str = "chip8-gtk";
+/// Keeps the roms apart from the other files the user opened recently
+const GROUP: &str = "chip8-rom";
+const MAX_ITEMS: usize = 10;
+
+/// Remember `file` in the recently used files of the desktop
+pub fn add(file: &gio::File) {
+    let data = gtk::RecentData::new(
+        None,
+        None,
+        "application/octet-stream",
+        APP_NAME,
+        &format!("{} %f", APP_NAME),
+        &[GROUP],
+        false,
+    );
+
+    if !gtk::RecentManager::default().add_full(&file.uri(), &data) {
+        warn!("{}: not added to the recent files", file.uri());
+    }
+}
+
+/// Menu opening the recent roms with `win.open-recent`, updated when the
+/// list changes
+pub fn menu() -> gio::Menu {
+    let menu = gio::Menu::new();
+    let manager = gtk::RecentManager::default();
+
+    fill(&menu, &manager);
+    manager.connect_changed({
+        let menu = menu.clone();
+        move |manager| {
+            menu.remove_all();
+            fill(&menu, manager);
+        }
+    });
+
+    menu
+}
+
+fn fill(menu: &gio::Menu, manager: &gtk::RecentManager) {
+    let mut items: Vec<gtk::RecentInfo> = manager
+        .items()
+        .into_iter()
+        .filter(|info| info.has_group(GROUP) && info.exists())
+        .collect();
+    items.sort_by_key(|info| std::cmp::Reverse(info.modified()));
+
+    for info in items.iter().take(MAX_ITEMS) {
+        // underscores would be taken as mnemonics
+        let label = info.display_name().replace('_', "__");
+        let item = gio::MenuItem::new(Some(&label), None);
+        item.set_action_and_target_value(
+            Some("win.open-recent"),
+            Some(&info.uri().to_variant()),
+        );
+        menu.append_item(&item);
+    }
+}
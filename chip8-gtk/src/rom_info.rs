@@ -0,0 +1,70 @@
+use chip8_frontend::config::KEY_WAIT_POLICIES;
+use gtk::prelude::*;
+
+use crate::{a11y, emulator::Emulator, preferences::KEY_WAIT_LABELS};
+
+/// Show a window with the loaded rom and the settings it runs with, nothing
+/// is shown without a rom
+pub fn show(parent: &gtk::ApplicationWindow, emulator: &Emulator) {
+    let Some(rom) = emulator.rom() else {
+        return;
+    };
+    let machine = emulator.machine();
+
+    let policy = machine.cpu().key_wait_policy();
+    let key_wait = KEY_WAIT_POLICIES
+        .iter()
+        .zip(KEY_WAIT_LABELS)
+        .find(|((known, _), _)| *known == policy)
+        .map_or("", |(_, label)| label);
+
+    let rows = [
+        ("File", rom.name.clone()),
+        ("Path", rom.path.display().to_string()),
+        ("Size", format!("{} bytes", rom.size)),
+        ("Checksum", format!("{:08x} (FNV-1a)", rom.checksum)),
+        // the only platform emulated, without extensions
+        ("Platform", "CHIP-8".to_string()),
+        ("Speed", format!("{} Hz", machine.cpu_frequency())),
+        ("FX0A key", key_wait.to_string()),
+    ];
+
+    let grid = gtk::Grid::builder()
+        .row_spacing(6)
+        .column_spacing(12)
+        .margin_top(12)
+        .margin_bottom(12)
+        .margin_start(12)
+        .margin_end(12)
+        .build();
+    for (row, (label, value)) in rows.iter().enumerate() {
+        grid.attach(
+            &gtk::Label::builder().label(*label).xalign(0.0).build(),
+            0,
+            row as i32,
+            1,
+            1,
+        );
+        grid.attach(
+            &gtk::Label::builder()
+                .label(value)
+                .xalign(0.0)
+                .selectable(true)
+                .build(),
+            1,
+            row as i32,
+            1,
+            1,
+        );
+    }
+
+    let window = gtk::Window::builder()
+        .title("Rom Info")
+        .transient_for(parent)
+        .destroy_with_parent(true)
+        .resizable(false)
+        .child(&grid)
+        .build();
+    a11y::close_on_escape(&window);
+    window.present();
+}
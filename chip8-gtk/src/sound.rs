@@ -0,0 +1,81 @@
+use std::cell::Cell;
+
+use gtk::{gio, glib, prelude::*};
+
+const SAMPLE_RATE: u32 = 44100;
+// 441 Hz, the loop holds a whole number of periods
+const BEEP_PERIOD: usize = 100;
+const BEEP_PERIODS: usize = 44;
+
+/// The chip8 buzzer, a looping square wave
+pub struct Beep {
+    media: gtk::MediaFile,
+    enabled: Cell<bool>,
+}
+
+impl Beep {
+    pub fn new() -> Self {
+        let stream = gio::MemoryInputStream::from_bytes(
+            &glib::Bytes::from_owned(beep_wav()),
+        );
+        let media = gtk::MediaFile::for_input_stream(&stream);
+        media.set_loop(true);
+
+        Self {
+            media,
+            enabled: Cell::new(true),
+        }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+        if !enabled {
+            self.media.pause();
+        }
+    }
+
+    /// From 0 to 1
+    pub fn set_volume(&self, volume: f64) {
+        self.media.set_volume(volume);
+    }
+
+    /// Follow the machine buzzer, nothing plays while disabled
+    pub fn set_beeping(&self, beeping: bool) {
+        let playing = beeping && self.enabled.get();
+        if playing != self.media.is_playing() {
+            self.media.set_playing(playing);
+        }
+    }
+}
+
+/// A square wave as a 16 bits mono wav file
+fn beep_wav() -> Vec<u8> {
+    let samples = BEEP_PERIOD * BEEP_PERIODS;
+    let data_size = (samples * 2) as u32;
+
+    let mut wav = Vec::with_capacity(44 + samples * 2);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // pcm
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+
+    // full scale, the volume is set on the media
+    for i in 0..samples {
+        let sample = match i % BEEP_PERIOD < BEEP_PERIOD / 2 {
+            true => i16::MAX / 2,
+            false => -i16::MAX / 2,
+        };
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    wav
+}
@@ -0,0 +1,92 @@
+use gtk::{pango, prelude::*};
+
+use crate::emulator::Emulator;
+
+/// States of the emulator shown at the end of the bar
+pub const STOPPED: &str = "Stopped";
+pub const PAUSED: &str = "Paused";
+pub const WAITING: &str = "Waiting for a key";
+pub const RUNNING: &str = "Running";
+
+/// State of `emulator`, one of the above
+pub fn state(emulator: &Emulator) -> &'static str {
+    if emulator.rom_name().is_none() {
+        STOPPED
+    } else if !emulator.is_running() {
+        PAUSED
+    } else if emulator.machine().cpu().key_await().is_some() {
+        WAITING
+    } else {
+        RUNNING
+    }
+}
+
+/// Line under the screen showing the rom, the emulation speed, the timers
+/// and the state of the emulator
+pub struct StatusBar {
+    container: gtk::Box,
+    rom: gtk::Label,
+    speed: gtk::Label,
+    timers: gtk::Label,
+    state: gtk::Label,
+}
+
+impl StatusBar {
+    pub fn new() -> Self {
+        let container = gtk::Box::builder()
+            .accessible_role(gtk::AccessibleRole::Status)
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(18)
+            .margin_top(2)
+            .margin_bottom(2)
+            .margin_start(6)
+            .margin_end(6)
+            .build();
+
+        let rom = gtk::Label::builder()
+            .xalign(0.0)
+            .hexpand(true)
+            .ellipsize(pango::EllipsizeMode::End)
+            .build();
+        let speed = gtk::Label::new(None);
+        let timers = gtk::Label::new(None);
+        let state = gtk::Label::new(None);
+        // the width doesn't change with the values
+        for label in [&speed, &timers] {
+            label.add_css_class("monospace");
+        }
+
+        container.append(&rom);
+        container.append(&speed);
+        container.append(&timers);
+        container.append(&state);
+
+        Self {
+            container,
+            rom,
+            speed,
+            timers,
+            state,
+        }
+    }
+
+    pub fn widget(&self) -> &gtk::Box {
+        &self.container
+    }
+
+    /// Show the current values of `emulator`
+    pub fn update(&self, emulator: &Emulator) {
+        let machine = emulator.machine();
+        let bus = machine.bus();
+
+        self.rom.set_label(emulator.rom_name().unwrap_or("No rom"));
+        self.speed.set_label(&format!(
+            "{:3.0} FPS {:5.0} IPS",
+            emulator.fps(),
+            emulator.ips()
+        ));
+        self.timers
+            .set_label(&format!("DT {:02X} ST {:02X}", bus.delay, bus.beep));
+        self.state.set_label(state(emulator));
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use chip8_frontend::slots::SLOTS;
+use gtk::prelude::*;
+
+use crate::{
+    a11y,
+    display::{self, Display},
+    emulator::Emulator,
+};
+
+/// Range of the speed slider, in Hz
+const MIN_SPEED: f64 = 100.0;
+const MAX_SPEED: f64 = 2000.0;
+
+/// Controls under the screen, they activate the window actions
+/// They don't take the focus when clicked, the keyboard stays on the keypad,
+/// but Tab reaches them
+pub struct Toolbar {
+    container: gtk::Box,
+    speed: gtk::Adjustment,
+    slot: gtk::MenuButton,
+    /// Set while the controls follow the emulator, the changes aren't from
+    /// the user
+    updating: Rc<Cell<bool>>,
+}
+
+impl Toolbar {
+    pub fn new(emulator: &Rc<RefCell<Emulator>>, display: &Display) -> Self {
+        let container = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(6)
+            .margin_top(2)
+            .margin_bottom(2)
+            .margin_start(6)
+            .margin_end(6)
+            .build();
+
+        let speed = gtk::Adjustment::new(
+            MIN_SPEED, MIN_SPEED, MAX_SPEED, 10.0, 100.0, 0.0,
+        );
+        let updating = Rc::new(Cell::new(false));
+        speed.connect_value_changed({
+            let container = container.clone();
+            let updating = updating.clone();
+            move |speed| {
+                if !updating.get() {
+                    let hz = speed.value().round() as i32;
+                    container
+                        .activate_action("win.speed", Some(&hz.to_variant()))
+                        .ok();
+                }
+            }
+        });
+
+        let slider = gtk::Scale::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .adjustment(&speed)
+            .draw_value(false)
+            .width_request(160)
+            .tooltip_text("CPU speed")
+            .focus_on_click(false)
+            .build();
+        a11y::set_label(&slider, "CPU speed in Hz");
+        // its text would take the keypad keys, the slider has the same
+        // value for the keyboard
+        let spin = gtk::SpinButton::builder()
+            .adjustment(&speed)
+            .numeric(true)
+            .can_focus(false)
+            .build();
+        let turbo = gtk::ToggleButton::builder()
+            .label("Turbo")
+            .action_name("win.turbo")
+            .tooltip_text("Run the frames faster")
+            .focus_on_click(false)
+            .build();
+
+        for (label, action, tooltip) in [
+            ("Step", "win.step", "Execute one instruction"),
+            (
+                "Frame",
+                "win.advance-frame",
+                "Run until the end of the frame",
+            ),
+        ] {
+            container.append(
+                &gtk::Button::builder()
+                    .label(label)
+                    .action_name(action)
+                    .tooltip_text(tooltip)
+                    .focus_on_click(false)
+                    .build(),
+            );
+        }
+        container.append(&gtk::Separator::new(gtk::Orientation::Vertical));
+        for (label, action, tooltip) in [
+            ("Save", "win.save-state", "Save the state to the slot"),
+            ("Load", "win.load-state", "Load the state of the slot"),
+        ] {
+            container.append(
+                &gtk::Button::builder()
+                    .label(label)
+                    .action_name(action)
+                    .tooltip_text(tooltip)
+                    .focus_on_click(false)
+                    .build(),
+            );
+        }
+        let slots = gtk::Popover::new();
+        slots.connect_show({
+            let emulator = emulator.clone();
+            let display = display.clone();
+            move |slots| {
+                slots.set_child(Some(&slot_grid(
+                    &emulator.borrow(),
+                    &display,
+                    slots,
+                )))
+            }
+        });
+        let slot = gtk::MenuButton::builder()
+            .popover(&slots)
+            .tooltip_text("Slot of the states")
+            .focus_on_click(false)
+            .build();
+        container.append(&slot);
+        container.append(&gtk::Separator::new(gtk::Orientation::Vertical));
+        container.append(&gtk::Label::new(Some("Speed")));
+        container.append(&slider);
+        container.append(&spin);
+        container.append(&gtk::Label::new(Some("Hz")));
+        container.append(&turbo);
+
+        Self {
+            container,
+            speed,
+            slot,
+            updating,
+        }
+    }
+
+    pub fn widget(&self) -> &gtk::Box {
+        &self.container
+    }
+
+    /// Follow the settings of `emulator` changed from elsewhere
+    pub fn update(&self, emulator: &Emulator) {
+        let hz = emulator.cpu_frequency();
+        if self.speed.value() != hz.clamp(MIN_SPEED, MAX_SPEED) {
+            self.updating.set(true);
+            self.speed.set_value(hz);
+            self.updating.set(false);
+        }
+
+        let slot = emulator.slots().map_or(0, |slots| slots.current());
+        self.slot.set_label(&format!("Slot {}", slot));
+    }
+}
+
+/// A button for each slot, with the screen saved in it
+fn slot_grid(
+    emulator: &Emulator,
+    display: &Display,
+    popover: &gtk::Popover,
+) -> gtk::Grid {
+    let grid = gtk::Grid::builder()
+        .row_spacing(6)
+        .column_spacing(6)
+        .build();
+
+    for slot in 0..SLOTS {
+        let thumbnail =
+            emulator.slots().and_then(|slots| slots.thumbnail(slot));
+        let content = gtk::Box::new(gtk::Orientation::Vertical, 3);
+        match thumbnail {
+            Some(vram) => {
+                let picture = gtk::Picture::for_paintable(&display::thumbnail(
+                    &vram,
+                    display.palette(),
+                ));
+                picture.set_size_request(128, 64);
+                picture.set_alternative_text(Some("Saved screen"));
+                content.append(&picture);
+            }
+            None => {
+                let empty = gtk::Label::new(Some("Empty"));
+                empty.set_size_request(128, 64);
+                content.append(&empty);
+            }
+        }
+        content.append(&gtk::Label::new(Some(&format!("Slot {}", slot))));
+
+        let button = gtk::Button::builder()
+            .child(&content)
+            .action_name("win.slot")
+            .action_target(&(slot as i32).to_variant())
+            .build();
+        button.connect_clicked({
+            let popover = popover.clone();
+            move |_| popover.popdown()
+        });
+        grid.attach(&button, slot as i32 % 5, slot as i32 / 5, 1, 1);
+    }
+
+    grid
+}
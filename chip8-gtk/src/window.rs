@@ -0,0 +1,825 @@
+use std::{cell::RefCell, rc::Rc};
+
+use chip8::bus::{Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8_frontend::{
+    config::{Config, HOTKEYS},
+    slots::{Slots, SLOTS},
+};
+use gtk::{gdk, gio, glib, prelude::*};
+use log::{error, warn};
+
+use crate::{
+    a11y::Announcer,
+    console::Console,
+    debug_panel::DebugPanel,
+    display::{Display, Palette, PALETTES},
+    emulator::Emulator,
+    gamepad_editor,
+    header_bar::HeaderBar,
+    keymap,
+    memory_viewer::MemoryViewer,
+    preferences, recent, rom_info,
+    sound::Beep,
+    status_bar::StatusBar,
+    toolbar::Toolbar,
+};
+
+/// CPU frequencies of the Speed menu, in Hz
+const SPEEDS: [i32; 5] = [250, 500, 700, 1000, 2000];
+/// Largest display scale of the View menu, they go from 1x
+const MAX_SCALE: i32 = 16;
+
+pub fn build_ui(
+    emulator: &Rc<RefCell<Emulator>>,
+    config: &Rc<RefCell<Config>>,
+    application: &gtk::Application,
+) {
+    let window = gtk::ApplicationWindow::builder()
+        .application(application)
+        .title("Chip8 GTK")
+        .show_menubar(true)
+        .build();
+    let header_bar = HeaderBar::new();
+    window.set_titlebar(Some(header_bar.widget()));
+
+    let display = Display::new();
+    display.set_vexpand(true);
+    display.set_hexpand(true);
+    let status_bar = StatusBar::new();
+    let debug_panel = Rc::new(DebugPanel::new(emulator));
+    let screen = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+    screen.append(&display);
+    screen.append(debug_panel.widget());
+    let memory_viewer = Rc::new(MemoryViewer::new(&window, emulator));
+    let console = Rc::new(Console::new(&window, emulator));
+    let toolbar = Toolbar::new(emulator, &display);
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
+    content.append(&screen);
+    content.append(toolbar.widget());
+    content.append(status_bar.widget());
+    window.set_child(Some(&content));
+
+    let beep = Rc::new(Beep::new());
+    let announcer = Announcer::new();
+
+    application.set_menubar(Some(&create_menu()));
+    add_app_actions(application);
+    add_file_actions(&window, emulator);
+    add_emulation_actions(&window, emulator, config);
+    add_view_actions(&window, &display, &debug_panel, config);
+    let memory = gio::SimpleAction::new("memory-viewer", None);
+    memory.connect_activate({
+        let memory_viewer = memory_viewer.clone();
+        move |_, _| memory_viewer.present()
+    });
+    window.add_action(&memory);
+    let console_action = gio::SimpleAction::new("console", None);
+    console_action.connect_activate({
+        let console = console.clone();
+        move |_, _| console.present()
+    });
+    window.add_action(&console_action);
+    add_preferences_action(&window, emulator, &display, &beep, config);
+    apply_config(&window, emulator, &display, &beep, &config.borrow());
+    update_title(&window, &emulator.borrow());
+
+    // also when the window manager changes it
+    window.connect_fullscreened_notify({
+        let display = display.clone();
+        let toolbar = toolbar.widget().clone();
+        let status_bar = status_bar.widget().clone();
+        move |window| {
+            set_fullscreen_layout(
+                window,
+                &display,
+                &[toolbar.upcast_ref(), status_bar.upcast_ref()],
+            )
+        }
+    });
+
+    let drop_target =
+        gtk::DropTarget::new(gio::File::static_type(), gdk::DragAction::COPY);
+    drop_target.connect_drop({
+        let window = window.clone();
+        let emulator = emulator.clone();
+        move |_, value, _, _| match value.get::<gio::File>() {
+            Ok(file) => {
+                open_rom(&window, &emulator, &file);
+                true
+            }
+            Err(_) => false,
+        }
+    });
+    window.add_controller(drop_target);
+
+    window.add_tick_callback({
+        let emulator = emulator.clone();
+        let config = config.clone();
+        let display = display.clone();
+        let frame = RefCell::new(Framebuffer::default());
+        move |window, _| {
+            let mut emulator = emulator.borrow_mut();
+            if emulator.tick() {
+                let mut frame = frame.borrow_mut();
+                emulator.machine().bus().framebuffer_into(&mut frame);
+                display.set_frame(&frame);
+            }
+            beep.set_beeping(
+                emulator.is_running() && emulator.machine().is_beeping(),
+            );
+            status_bar.update(&emulator);
+            header_bar.update(&emulator);
+            toolbar.update(&emulator);
+            debug_panel.update(&emulator);
+            memory_viewer.update(&emulator);
+            console.update(&mut emulator);
+            announcer.update(window, &mut emulator, config.borrow().announce);
+            // a breakpoint pauses the emulator
+            let paused = !emulator.is_running();
+            set_action_state(window, "pause", paused.to_variant());
+            set_action_enabled(window, "step", paused);
+            set_action_enabled(window, "advance-frame", paused);
+
+            // the actions borrow the emulator
+            let hotkeys = emulator.take_hotkeys();
+            drop(emulator);
+            for hotkey in hotkeys {
+                ActionGroupExt::activate_action(window, hotkey.name(), None);
+            }
+            glib::ControlFlow::Continue
+        }
+    });
+
+    let key_controller = gtk::EventControllerKey::new();
+    key_controller.connect_key_pressed({
+        let emulator = emulator.clone();
+        move |_, _, keycode, _| {
+            emulator.borrow_mut().keyboard_inputs(keycode, true);
+            glib::Propagation::Proceed
+        }
+    });
+    key_controller.connect_key_released({
+        let emulator = emulator.clone();
+        move |_, _, keycode, _| {
+            emulator.borrow_mut().keyboard_inputs(keycode, false);
+        }
+    });
+    window.add_controller(key_controller);
+
+    window.present();
+    // the keypad works from anywhere, the screen is where Tab starts from
+    display.grab_focus();
+}
+
+fn create_menu() -> gio::Menu {
+    let file = gio::Menu::new();
+    let open = gio::Menu::new();
+    open.append(Some("_Open…"), Some("win.open"));
+    open.append_submenu(Some("Open _Recent"), &recent::menu());
+    open.append(Some("Rom _Info…"), Some("win.rom-info"));
+    file.append_section(None, &open);
+    let preferences = gio::Menu::new();
+    preferences.append(Some("_Preferences…"), Some("win.preferences"));
+    preferences.append(Some("_Gamepads…"), Some("win.gamepads"));
+    file.append_section(None, &preferences);
+    let quit = gio::Menu::new();
+    quit.append(Some("_Quit"), Some("app.quit"));
+    file.append_section(None, &quit);
+
+    let speed = gio::Menu::new();
+    for hz in SPEEDS {
+        speed.append(
+            Some(&format!("{} Hz", hz)),
+            Some(&format!("win.speed({})", hz)),
+        );
+    }
+
+    let emulation = gio::Menu::new();
+    let run = gio::Menu::new();
+    run.append(Some("_Pause"), Some("win.pause"));
+    run.append(Some("_Reset"), Some("win.reset"));
+    run.append(Some("Step _Instruction"), Some("win.step"));
+    run.append(Some("_Advance Frame"), Some("win.advance-frame"));
+    run.append_submenu(Some("_Speed"), &speed);
+    run.append(Some("_Turbo"), Some("win.turbo"));
+    emulation.append_section(None, &run);
+    let state = gio::Menu::new();
+    state.append(Some("_Save State"), Some("win.save-state"));
+    state.append(Some("_Load State"), Some("win.load-state"));
+    let slot = gio::Menu::new();
+    for index in 0..SLOTS {
+        slot.append(
+            Some(&format!("Slot {}", index)),
+            Some(&format!("win.slot({})", index)),
+        );
+    }
+    state.append_submenu(Some("S_lot"), &slot);
+    emulation.append_section(None, &state);
+
+    let scale = gio::Menu::new();
+    for factor in 1..=MAX_SCALE {
+        scale.append(
+            Some(&format!("{}x", factor)),
+            Some(&format!("win.scale({})", factor)),
+        );
+    }
+
+    let palette = gio::Menu::new();
+    for (id, label, _) in PALETTES {
+        palette.append(Some(label), Some(&format!("win.palette('{}')", id)));
+    }
+    let custom = gio::Menu::new();
+    custom.append(Some("_Custom…"), Some("win.preferences"));
+    palette.append_section(None, &custom);
+
+    let view = gio::Menu::new();
+    view.append_submenu(Some("_Scale"), &scale);
+    view.append_submenu(Some("_Palette"), &palette);
+    view.append(Some("_Fullscreen"), Some("win.fullscreen"));
+    view.append(Some("_Debug Panel"), Some("win.debug-panel"));
+    view.append(Some("_Memory…"), Some("win.memory-viewer"));
+    view.append(Some("_Console…"), Some("win.console"));
+
+    let help = gio::Menu::new();
+    help.append(Some("_About"), Some("app.about"));
+
+    let menu = gio::Menu::new();
+    menu.append_submenu(Some("_File"), &file);
+    menu.append_submenu(Some("_Emulation"), &emulation);
+    menu.append_submenu(Some("_View"), &view);
+    menu.append_submenu(Some("_Help"), &help);
+
+    menu
+}
+
+fn add_app_actions(application: &gtk::Application) {
+    let quit = gio::SimpleAction::new("quit", None);
+    quit.connect_activate({
+        let application = application.clone();
+        move |_, _| application.quit()
+    });
+    application.add_action(&quit);
+
+    let about = gio::SimpleAction::new("about", None);
+    about.connect_activate({
+        let application = application.clone();
+        move |_, _| {
+            gtk::AboutDialog::builder()
+                .transient_for(&application.active_window().unwrap())
+                .modal(true)
+                .program_name("Chip8 GTK")
+                .version(env!("CARGO_PKG_VERSION"))
+                .comments("A chip8 emulator")
+                .build()
+                .present();
+        }
+    });
+    application.add_action(&about);
+}
+
+fn add_file_actions(
+    window: &gtk::ApplicationWindow,
+    emulator: &Rc<RefCell<Emulator>>,
+) {
+    let open = gio::SimpleAction::new("open", None);
+    open.connect_activate({
+        let window = window.clone();
+        let emulator = emulator.clone();
+        move |_, _| {
+            let roms = gtk::FileFilter::new();
+            roms.set_name(Some("Chip8 roms"));
+            roms.add_suffix("ch8");
+            roms.add_suffix("c8");
+            let all = gtk::FileFilter::new();
+            all.set_name(Some("All files"));
+            all.add_pattern("*");
+
+            let filters = gio::ListStore::new::<gtk::FileFilter>();
+            filters.append(&roms);
+            filters.append(&all);
+
+            let dialog = gtk::FileDialog::builder()
+                .title("Open Rom")
+                .modal(true)
+                .filters(&filters)
+                .build();
+            dialog.open(Some(&window), gio::Cancellable::NONE, {
+                let window = window.clone();
+                let emulator = emulator.clone();
+                move |file| {
+                    // an error here is the dialog being dismissed
+                    if let Ok(file) = file {
+                        open_rom(&window, &emulator, &file);
+                    }
+                }
+            });
+        }
+    });
+    window.add_action(&open);
+
+    let open_recent =
+        gio::SimpleAction::new("open-recent", Some(glib::VariantTy::STRING));
+    open_recent.connect_activate({
+        let window = window.clone();
+        let emulator = emulator.clone();
+        move |_, uri| {
+            if let Some(uri) = uri.and_then(String::from_variant) {
+                open_rom(&window, &emulator, &gio::File::for_uri(&uri));
+            }
+        }
+    });
+    window.add_action(&open_recent);
+
+    let rom_info = gio::SimpleAction::new("rom-info", None);
+    rom_info.connect_activate({
+        let window = window.clone();
+        let emulator = emulator.clone();
+        move |_, _| rom_info::show(&window, &emulator.borrow())
+    });
+    window.add_action(&rom_info);
+}
+
+/// Start `file` in place of the running rom, an error is shown when it
+/// can't be read
+fn open_rom(
+    window: &gtk::ApplicationWindow,
+    emulator: &Rc<RefCell<Emulator>>,
+    file: &gio::File,
+) {
+    let Some(path) = file.path() else {
+        return show_error(window, "Only local files can be opened", "");
+    };
+
+    let result = emulator.borrow_mut().load_rom(&path);
+    match result {
+        Ok(()) => {
+            recent::add(file);
+            update_title(window, &emulator.borrow());
+            // the slots are those of the new rom
+            update_slot_actions(window, &emulator.borrow());
+        }
+        Err(e) => show_error(
+            window,
+            &format!("Failed to open {}", path.display()),
+            &e.to_string(),
+        ),
+    }
+}
+
+fn show_error(window: &gtk::ApplicationWindow, message: &str, detail: &str) {
+    error!("{} {}", message, detail);
+
+    gtk::AlertDialog::builder()
+        .modal(true)
+        .message(message)
+        .detail(detail)
+        .build()
+        .show(Some(window));
+}
+
+fn update_title(window: &gtk::ApplicationWindow, emulator: &Emulator) {
+    set_action_enabled(window, "rom-info", emulator.rom().is_some());
+    match emulator.rom_name() {
+        Some(name) => window.set_title(Some(&format!("{} - Chip8 GTK", name))),
+        None => window.set_title(Some("Chip8 GTK")),
+    }
+}
+
+fn add_emulation_actions(
+    window: &gtk::ApplicationWindow,
+    emulator: &Rc<RefCell<Emulator>>,
+    config: &Rc<RefCell<Config>>,
+) {
+    let pause =
+        gio::SimpleAction::new_stateful("pause", None, &false.to_variant());
+    pause.connect_activate({
+        let emulator = emulator.clone();
+        move |action, _| {
+            let mut emulator = emulator.borrow_mut();
+            let running = !emulator.is_running();
+            emulator.set_running(running);
+            action.set_state(&(!running).to_variant());
+        }
+    });
+    window.add_action(&pause);
+
+    let reset = gio::SimpleAction::new("reset", None);
+    reset.connect_activate({
+        let emulator = emulator.clone();
+        move |_, _| emulator.borrow_mut().reset()
+    });
+    window.add_action(&reset);
+
+    // only while paused
+    let step = gio::SimpleAction::new("step", None);
+    step.set_enabled(!emulator.borrow().is_running());
+    step.connect_activate({
+        let emulator = emulator.clone();
+        move |_, _| emulator.borrow_mut().step()
+    });
+    window.add_action(&step);
+
+    let advance_frame = gio::SimpleAction::new("advance-frame", None);
+    advance_frame.set_enabled(!emulator.borrow().is_running());
+    advance_frame.connect_activate({
+        let emulator = emulator.clone();
+        move |_, _| emulator.borrow_mut().advance_frame()
+    });
+    window.add_action(&advance_frame);
+
+    let frequency = emulator.borrow().cpu_frequency() as i32;
+    let speed = gio::SimpleAction::new_stateful(
+        "speed",
+        Some(glib::VariantTy::INT32),
+        &frequency.to_variant(),
+    );
+    speed.connect_activate({
+        let emulator = emulator.clone();
+        let config = config.clone();
+        move |action, hz| {
+            let Some(hz) = hz.and_then(i32::from_variant) else {
+                return;
+            };
+            emulator.borrow_mut().set_cpu_frequency(hz as f64);
+            action.set_state(&hz.to_variant());
+
+            let mut config = config.borrow_mut();
+            config.cpu_frequency = hz as f64;
+            save_config(&config);
+        }
+    });
+    window.add_action(&speed);
+
+    let turbo =
+        gio::SimpleAction::new_stateful("turbo", None, &false.to_variant());
+    turbo.connect_activate({
+        let emulator = emulator.clone();
+        move |action, _| {
+            let mut emulator = emulator.borrow_mut();
+            let turbo = !emulator.is_turbo();
+            emulator.set_turbo(turbo);
+            action.set_state(&turbo.to_variant());
+        }
+    });
+    window.add_action(&turbo);
+
+    let load_state = gio::SimpleAction::new("load-state", None);
+    load_state.connect_activate({
+        let window = window.clone();
+        let emulator = emulator.clone();
+        move |_, _| {
+            let result = emulator.borrow_mut().load_state();
+            if let Err(e) = result {
+                show_error(&window, "Failed to load the state", &e.to_string());
+            }
+        }
+    });
+    window.add_action(&load_state);
+
+    let save_state = gio::SimpleAction::new("save-state", None);
+    save_state.connect_activate({
+        let window = window.clone();
+        let emulator = emulator.clone();
+        move |_, _| {
+            let emulator = emulator.borrow();
+            match emulator.save_state() {
+                Ok(()) => update_slot_actions(&window, &emulator),
+                Err(e) => show_error(
+                    &window,
+                    "Failed to save the state",
+                    &e.to_string(),
+                ),
+            }
+        }
+    });
+    window.add_action(&save_state);
+
+    let slot = gio::SimpleAction::new_stateful(
+        "slot",
+        Some(glib::VariantTy::INT32),
+        &0.to_variant(),
+    );
+    slot.connect_activate({
+        let window = window.clone();
+        let emulator = emulator.clone();
+        move |_, slot| {
+            if let Some(slot) = slot.and_then(i32::from_variant) {
+                let mut emulator = emulator.borrow_mut();
+                emulator.set_slot(slot as usize);
+                update_slot_actions(&window, &emulator);
+            }
+        }
+    });
+    window.add_action(&slot);
+
+    for (name, offset) in [("next-slot", 1), ("previous-slot", SLOTS - 1)] {
+        let action = gio::SimpleAction::new(name, None);
+        action.connect_activate({
+            let window = window.clone();
+            let emulator = emulator.clone();
+            move |_, _| {
+                let mut emulator = emulator.borrow_mut();
+                if let Some(current) = emulator.slots().map(Slots::current) {
+                    emulator.set_slot(current + offset);
+                    update_slot_actions(&window, &emulator);
+                }
+            }
+        });
+        window.add_action(&action);
+    }
+    update_slot_actions(window, &emulator.borrow());
+}
+
+/// The states can only be saved with a rom, and loaded from a used slot
+fn update_slot_actions(window: &gtk::ApplicationWindow, emulator: &Emulator) {
+    let slots = emulator.slots();
+
+    set_action_enabled(window, "save-state", slots.is_some());
+    set_action_enabled(
+        window,
+        "load-state",
+        slots.is_some_and(|slots| slots.is_used(slots.current())),
+    );
+    set_action_state(
+        window,
+        "slot",
+        (slots.map_or(0, Slots::current) as i32).to_variant(),
+    );
+}
+
+fn add_view_actions(
+    window: &gtk::ApplicationWindow,
+    display: &Display,
+    debug_panel: &Rc<DebugPanel>,
+    config: &Rc<RefCell<Config>>,
+) {
+    let initial = (config.borrow().scale as i32).clamp(1, MAX_SCALE);
+    let scale = gio::SimpleAction::new_stateful(
+        "scale",
+        Some(glib::VariantTy::INT32),
+        &initial.to_variant(),
+    );
+    scale.connect_activate({
+        let window = window.clone();
+        let display = display.clone();
+        let config = config.clone();
+        move |action, factor| {
+            let Some(factor) = factor
+                .and_then(i32::from_variant)
+                .filter(|factor| (1..=MAX_SCALE).contains(factor))
+            else {
+                return;
+            };
+            set_scale(&window, &display, factor);
+            action.set_state(&factor.to_variant());
+
+            let mut config = config.borrow_mut();
+            config.scale = factor as u32;
+            save_config(&config);
+        }
+    });
+    window.add_action(&scale);
+    set_scale(window, display, initial);
+
+    for (name, step) in [("scale-up", 1), ("scale-down", -1)] {
+        let action = gio::SimpleAction::new(name, None);
+        action.connect_activate({
+            let window = window.clone();
+            move |_, _| {
+                let Some(current) = window
+                    .action_state("scale")
+                    .and_then(|state| i32::from_variant(&state))
+                else {
+                    return;
+                };
+                let factor = (current + step).clamp(1, MAX_SCALE);
+                if factor != current {
+                    ActionGroupExt::activate_action(
+                        &window,
+                        "scale",
+                        Some(&factor.to_variant()),
+                    );
+                }
+            }
+        });
+        window.add_action(&action);
+    }
+
+    let palette = gio::SimpleAction::new_stateful(
+        "palette",
+        Some(glib::VariantTy::STRING),
+        &PALETTES[0].0.to_variant(),
+    );
+    palette.connect_activate({
+        let display = display.clone();
+        let config = config.clone();
+        move |action, id| {
+            let Some(id) = id.and_then(String::from_variant) else {
+                return;
+            };
+            if let Some((_, _, palette)) =
+                PALETTES.iter().find(|(palette_id, _, _)| *palette_id == id)
+            {
+                display.set_palette(*palette);
+                action.set_state(&id.to_variant());
+
+                let mut config = config.borrow_mut();
+                config.palette = Some(palette.to_config());
+                save_config(&config);
+            }
+        }
+    });
+    window.add_action(&palette);
+
+    let fullscreen = gio::SimpleAction::new_stateful(
+        "fullscreen",
+        None,
+        &false.to_variant(),
+    );
+    // the state follows the window, in `set_fullscreen_layout`
+    fullscreen.connect_activate({
+        let window = window.clone();
+        move |_, _| window.set_fullscreened(!window.is_fullscreen())
+    });
+    window.add_action(&fullscreen);
+
+    let panel = gio::SimpleAction::new_stateful(
+        "debug-panel",
+        None,
+        &debug_panel.is_visible().to_variant(),
+    );
+    panel.connect_activate({
+        let debug_panel = debug_panel.clone();
+        move |action, _| {
+            let visible = !debug_panel.is_visible();
+            debug_panel.set_visible(visible);
+            action.set_state(&visible.to_variant());
+        }
+    });
+    window.add_action(&panel);
+}
+
+/// The display can't get smaller than the scale, the window shrinks back
+/// to fit it
+fn set_scale(window: &gtk::ApplicationWindow, display: &Display, factor: i32) {
+    // the fullscreen display fits the monitor whatever the scale
+    if window.is_fullscreen() {
+        return;
+    }
+    display.set_size_request(
+        DISPLAY_WIDTH as i32 * factor,
+        DISPLAY_HEIGHT as i32 * factor,
+    );
+    window.set_default_size(-1, -1);
+}
+
+/// In fullscreen only the screen is shown, letterboxed, the bars come back
+/// with the window
+fn set_fullscreen_layout(
+    window: &gtk::ApplicationWindow,
+    display: &Display,
+    bars: &[&gtk::Widget],
+) {
+    let fullscreen = window.is_fullscreen();
+
+    window.set_show_menubar(!fullscreen);
+    for bar in bars {
+        bar.set_visible(!fullscreen);
+    }
+    display.set_letterbox(fullscreen);
+    match fullscreen {
+        true => display.set_size_request(-1, -1),
+        false => {
+            let factor = window
+                .action_state("scale")
+                .and_then(|state| i32::from_variant(&state))
+                .unwrap_or(1);
+            set_scale(window, display, factor);
+        }
+    }
+    set_action_state(window, "fullscreen", fullscreen.to_variant());
+}
+
+/// The preferences and gamepads windows, their changes are applied at once
+fn add_preferences_action(
+    window: &gtk::ApplicationWindow,
+    emulator: &Rc<RefCell<Emulator>>,
+    display: &Display,
+    beep: &Rc<Beep>,
+    config: &Rc<RefCell<Config>>,
+) {
+    let apply = {
+        let window = window.clone();
+        let emulator = emulator.clone();
+        let display = display.clone();
+        let beep = beep.clone();
+        let config = config.clone();
+        move || {
+            apply_config(&window, &emulator, &display, &beep, &config.borrow())
+        }
+    };
+
+    let action = gio::SimpleAction::new("preferences", None);
+    action.connect_activate({
+        let window = window.clone();
+        let config = config.clone();
+        let apply = apply.clone();
+        move |_, _| preferences::show(&window, &config, apply.clone())
+    });
+    window.add_action(&action);
+
+    let action = gio::SimpleAction::new("gamepads", None);
+    action.connect_activate({
+        let window = window.clone();
+        let emulator = emulator.clone();
+        let config = config.clone();
+        move |_, _| {
+            gamepad_editor::show(&window, &emulator, &config, apply.clone())
+        }
+    });
+    window.add_action(&action);
+}
+
+/// Use the settings of `config` and show them in the menus
+fn apply_config(
+    window: &gtk::ApplicationWindow,
+    emulator: &Rc<RefCell<Emulator>>,
+    display: &Display,
+    beep: &Beep,
+    config: &Config,
+) {
+    emulator.borrow_mut().apply_config(config);
+
+    let palette = config
+        .palette
+        .as_ref()
+        .and_then(Palette::from_config)
+        .unwrap_or_default();
+    display.set_palette(palette);
+
+    beep.set_enabled(config.audio.enabled);
+    beep.set_volume(config.audio.volume);
+
+    // a speed or palette missing from the menus leaves them unchecked
+    set_action_state(
+        window,
+        "speed",
+        (config.cpu_frequency.round() as i32).to_variant(),
+    );
+    let id = PALETTES
+        .iter()
+        .find(|(_, _, colors)| *colors == palette)
+        .map_or("custom", |(id, _, _)| id);
+    set_action_state(window, "palette", id.to_variant());
+
+    // each hotkey is the window action of the same name
+    let Some(application) = window.application() else {
+        return;
+    };
+    for (hotkey, name, _) in HOTKEYS {
+        let accelerator = config.shortcut(hotkey).and_then(|shortcut| {
+            let accelerator = keymap::accelerator(shortcut);
+            if accelerator.is_none() {
+                warn!("unknown shortcut {} for {}", shortcut, name);
+            }
+            accelerator
+        });
+        let accelerators: Vec<&str> =
+            accelerator.iter().map(String::as_str).collect();
+        application
+            .set_accels_for_action(&format!("win.{}", name), &accelerators);
+    }
+}
+
+fn set_action_state(
+    window: &gtk::ApplicationWindow,
+    name: &str,
+    state: glib::Variant,
+) {
+    if let Some(action) = window
+        .lookup_action(name)
+        .and_downcast::<gio::SimpleAction>()
+    {
+        action.set_state(&state);
+    }
+}
+
+fn set_action_enabled(
+    window: &gtk::ApplicationWindow,
+    name: &str,
+    enabled: bool,
+) {
+    if let Some(action) = window
+        .lookup_action(name)
+        .and_downcast::<gio::SimpleAction>()
+    {
+        action.set_enabled(enabled);
+    }
+}
+
+/// Write the user configuration, a failure is only logged
+pub fn save_config(config: &Config) {
+    if let Err(e) = config.save() {
+        error!("config: {}", e);
+    }
+}
@@ -0,0 +1,182 @@
+mod serve;
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    process::ExitCode,
+};
+
+use chip8::{
+    machine::{Machine, CPU_FREQUENCY},
+    rom::Rom,
+};
+use chip8_frontend::{
+    crowd::{Crowd, CrowdConfig},
+    dump,
+    script::{self, Script, Stop},
+    trace,
+};
+use clap::Parser;
+use log::debug;
+
+/// Run a chip8 rom without window, then dump the screen and the cpu state
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Rom to run
+    rom: String,
+    /// Number of 60Hz frames to run
+    #[arg(short, long, default_value_t = 600)]
+    frames: u64,
+    /// Stop early once the rom jumps to itself or waits for a key that the
+    /// script will never press
+    #[arg(long)]
+    until_halt: bool,
+    /// Instructions per second
+    #[arg(long, default_value_t = CPU_FREQUENCY)]
+    speed: f64,
+    /// Keypad script, one `FRAME KEYS` line per change, `KEYS` being hex
+    /// keys separated by commas or `-` for none
+    #[arg(short, long)]
+    input: Option<String>,
+    /// Print the screen as text
+    #[arg(long)]
+    text: bool,
+    /// Print a hash of the screen
+    #[arg(long)]
+    hash: bool,
+    /// Write the screen to a png file
+    #[arg(long)]
+    png: Option<String>,
+    /// Size of a chip8 pixel in the png
+    #[arg(long, default_value_t = 8)]
+    scale: u32,
+    /// Write the pc, opcode, I and registers before each instruction to
+    /// this file, `-` for the standard output
+    #[arg(long)]
+    trace: Option<String>,
+    /// Run in real time instead, streaming the screen to WebSocket clients
+    /// on this address and taking their keys
+    #[arg(
+        long,
+        value_name = "ADDR",
+        conflicts_with_all = ["frames", "input", "trace"]
+    )]
+    serve: Option<String>,
+    /// Let the clients play as a crowd: each sends one key at a time, the
+    /// keys are pressed in turn or by vote
+    #[arg(long, requires = "serve")]
+    crowd: bool,
+    /// Frames a key of the crowd is held
+    #[arg(long, default_value_t = 6, requires = "crowd")]
+    hold: u64,
+    /// Frames between two keys of a member of the crowd
+    #[arg(long, default_value_t = 30, requires = "crowd")]
+    cooldown: u64,
+    /// Frames the votes of the crowd are counted before the most voted key
+    /// is pressed, 0 to press every key in turn
+    #[arg(long, default_value_t = 0, requires = "crowd")]
+    vote_window: u64,
+    /// Also take the keys of the crowd from chat bots on this TCP address,
+    /// one `VIEWER KEY` line per command
+    #[arg(long, value_name = "ADDR", requires = "crowd")]
+    relay: Option<String>,
+}
+
+fn main() -> ExitCode {
+    dotenv::dotenv().ok();
+    env_logger::builder().format_timestamp_nanos().init();
+
+    let args = Args::parse();
+
+    match run(&args) {
+        Ok(Stop::FrameLimit) if args.until_halt => ExitCode::from(1),
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<Stop, String> {
+    let rom =
+        Rom::new_from(&args.rom).map_err(|e| format!("{}: {}", args.rom, e))?;
+    debug!("loaded: {}", rom);
+
+    let mut script = match &args.input {
+        Some(path) => Script::new_from(path)?,
+        None => Script::default(),
+    };
+
+    let mut machine =
+        Machine::try_new(rom).map_err(|e| format!("{}: {}", args.rom, e))?;
+    machine.set_cpu_frequency(args.speed);
+
+    let (frames, stop) = match &args.serve {
+        Some(addr) => {
+            let crowd = args.crowd.then(|| {
+                Crowd::new(CrowdConfig {
+                    hold: args.hold,
+                    cooldown: args.cooldown,
+                    window: args.vote_window,
+                })
+            });
+            serve::serve(
+                &mut machine,
+                addr,
+                args.until_halt,
+                crowd,
+                args.relay.as_deref(),
+            )?;
+            (0, Stop::Halted)
+        }
+        None => match &args.trace {
+            Some(path) => {
+                let mut out = create(path)?;
+                trace::run(
+                    &mut machine,
+                    &mut script,
+                    args.frames,
+                    args.until_halt,
+                    &mut out,
+                )
+                .map_err(|e| format!("{}: {}", path, e))?
+            }
+            None => script::run(
+                &mut machine,
+                &mut script,
+                args.frames,
+                args.until_halt,
+            ),
+        },
+    };
+
+    let vram = &machine.bus().vram();
+    if args.text {
+        print!("{}", dump::text(vram));
+    }
+    if args.hash {
+        println!("hash: {:016x}", machine.bus().frame_hash());
+    }
+    if let Some(path) = &args.png {
+        dump::png(vram, path, args.scale)?;
+    }
+
+    println!("stop: {}", stop);
+    println!("frames: {}", frames);
+    print!("{}", dump::cpu(machine.cpu()));
+
+    Ok(stop)
+}
+
+/// A buffered writer to the file at `path`, or to the standard output for
+/// `-`
+fn create(path: &str) -> Result<Box<dyn Write>, String> {
+    match path {
+        "-" => Ok(Box::new(io::stdout().lock())),
+        path => File::create(path)
+            .map(|file| Box::new(BufWriter::new(file)) as Box<dyn Write>)
+            .map_err(|e| format!("{}: {}", path, e)),
+    }
+}
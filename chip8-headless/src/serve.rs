@@ -0,0 +1,495 @@
+use std::{
+    io::{ErrorKind, Read},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use chip8::{
+    bus::{DISPLAY_HEIGHT, DISPLAY_WIDTH, KEYPAD_SIZE},
+    machine::{Machine, FRAME_RATE},
+};
+use chip8_frontend::crowd::Crowd;
+use log::{debug, info, warn};
+use tungstenite::{
+    handshake::{
+        server::{NoCallback, ServerHandshake},
+        HandshakeError, MidHandshake,
+    },
+    Error, Message, WebSocket,
+};
+
+type Vram = [[bool; DISPLAY_HEIGHT]; DISPLAY_WIDTH];
+type Accepting = Result<
+    WebSocket<TcpStream>,
+    HandshakeError<ServerHandshake<TcpStream, NoCallback>>,
+>;
+
+// server messages, binary
+const SCREEN: u8 = 0x00; // followed by the pixels, row by row, 1 bit each
+const BEEP: u8 = 0x01; // followed by 1 while beeping, else 0
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Longest line of a relay
+const MAX_LINE: usize = 1024;
+
+/// A browser viewing the screen, its keys are merged with the other ones
+struct Client {
+    socket: WebSocket<TcpStream>,
+    peer: SocketAddr,
+    keys: [bool; KEYPAD_SIZE],
+    /// Last state sent, none until the first one
+    screen: Option<Vec<u8>>,
+    beeping: Option<bool>,
+}
+
+/// A connection whose WebSocket handshake is going on, without blocking
+/// the frames
+struct Handshake {
+    mid: MidHandshake<ServerHandshake<TcpStream, NoCallback>>,
+    peer: SocketAddr,
+    start: Instant,
+}
+
+impl Handshake {
+    /// Go on with what the browser sent since, it fails after
+    /// `HANDSHAKE_TIMEOUT`
+    fn resume(self) -> Result<Connection, String> {
+        if self.start.elapsed() > HANDSHAKE_TIMEOUT {
+            return Err("handshake timed out".to_string());
+        }
+        Client::connection(self.mid.handshake(), self.peer, self.start)
+    }
+}
+
+enum Connection {
+    Connected(Client),
+    Pending(Handshake),
+}
+
+impl Client {
+    /// Start the handshake of a new connection
+    fn accept(
+        stream: TcpStream,
+        peer: SocketAddr,
+    ) -> Result<Connection, String> {
+        stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Self::connection(tungstenite::accept(stream), peer, Instant::now())
+    }
+
+    fn connection(
+        accepting: Accepting,
+        peer: SocketAddr,
+        start: Instant,
+    ) -> Result<Connection, String> {
+        match accepting {
+            Ok(socket) => Ok(Connection::Connected(Self {
+                socket,
+                peer,
+                keys: [false; KEYPAD_SIZE],
+                screen: None,
+                beeping: None,
+            })),
+            Err(HandshakeError::Interrupted(mid)) => {
+                Ok(Connection::Pending(Handshake { mid, peer, start }))
+            }
+            Err(HandshakeError::Failure(e)) => Err(e.to_string()),
+        }
+    }
+
+    /// Apply the pending messages, returns false once disconnected
+    fn read(
+        &mut self,
+        machine: &mut Machine,
+        mut crowd: Option<&mut Crowd>,
+    ) -> bool {
+        loop {
+            match self.socket.read() {
+                Ok(Message::Text(text)) => {
+                    self.command(machine, crowd.as_deref_mut(), &text)
+                }
+                Ok(Message::Close(_)) => return false,
+                Ok(_) => {}
+                Err(Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => {
+                    return true
+                }
+                Err(e) => {
+                    warn!("{}: {}", self.peer, e);
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// `down KEY`, `up KEY` with the key in hexadecimal, or `reset`; in a
+    /// crowd, `down KEY` sends the key for the address of the client and
+    /// the rest is ignored
+    fn command(
+        &mut self,
+        machine: &mut Machine,
+        crowd: Option<&mut Crowd>,
+        text: &str,
+    ) {
+        let mut words = text.split_whitespace();
+        if let Some(crowd) = crowd {
+            if words.next() == Some("down") {
+                let viewer = self.peer.ip().to_string();
+                let key = words.next().unwrap_or_default();
+                if let Err(e) = crowd.command(&viewer, key) {
+                    debug!("{}: {}", self.peer, e);
+                }
+            }
+            return;
+        }
+
+        let pressed = match words.next() {
+            Some("down") => true,
+            Some("up") => false,
+            Some("reset") => return machine.reset(),
+            _ => return warn!("{}: unknown command {:?}", self.peer, text),
+        };
+
+        match words.next().map(|key| u8::from_str_radix(key, 16)) {
+            Some(Ok(key)) if (key as usize) < KEYPAD_SIZE => {
+                self.keys[key as usize] = pressed
+            }
+            _ => warn!("{}: invalid key in {:?}", self.peer, text),
+        }
+    }
+
+    /// Send what changed since the last call, returns false once
+    /// disconnected
+    fn update(&mut self, screen: &[u8], beeping: bool) -> bool {
+        let mut messages = vec![];
+        if self.screen.as_deref() != Some(screen) {
+            self.screen = Some(screen.to_vec());
+            messages.push([&[SCREEN], screen].concat());
+        }
+        if self.beeping != Some(beeping) {
+            self.beeping = Some(beeping);
+            messages.push(vec![BEEP, beeping as u8]);
+        }
+
+        for message in messages {
+            if let Err(e) = self.socket.send(Message::binary(message)) {
+                if !would_block(&e) {
+                    warn!("{}: {}", self.peer, e);
+                    return false;
+                }
+            }
+        }
+
+        // what the socket couldn't take yet goes with the next flush
+        match self.socket.flush() {
+            Err(e) if !would_block(&e) => {
+                warn!("{}: {}", self.peer, e);
+                false
+            }
+            _ => true,
+        }
+    }
+}
+
+/// A bot relaying the chat of a stream to the crowd, one `VIEWER KEY` line
+/// per command
+struct Relay {
+    stream: TcpStream,
+    peer: SocketAddr,
+    /// Received after the last complete line
+    pending: Vec<u8>,
+}
+
+impl Relay {
+    fn accept(stream: TcpStream, peer: SocketAddr) -> Result<Self, String> {
+        stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            stream,
+            peer,
+            pending: vec![],
+        })
+    }
+
+    /// Send the commands of the received lines to `crowd`, returns false
+    /// once disconnected
+    fn read(&mut self, crowd: &mut Crowd) -> bool {
+        let mut buffer = [0; 512];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return false,
+                Ok(len) => {
+                    self.pending.extend_from_slice(&buffer[..len]);
+                    self.commands(crowd);
+                    // before the next read, a relay sending no newline
+                    // can't fill the memory
+                    if self.pending.len() > MAX_LINE {
+                        warn!("{}: line too long", self.peer);
+                        return false;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return true,
+                Err(e) => {
+                    warn!("{}: {}", self.peer, e);
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// Send the commands of the complete lines of `pending` to `crowd`
+    fn commands(&mut self, crowd: &mut Crowd) {
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some(viewer), Some(key)) => {
+                    if let Err(e) = crowd.command(viewer, key) {
+                        debug!("{}: {}", viewer, e);
+                    }
+                }
+                (None, _) => {}
+                _ => warn!("{}: invalid line {:?}", self.peer, line.trim()),
+            }
+        }
+    }
+}
+
+/// Run `machine` in real time, streaming the screen to WebSocket clients on
+/// `addr` and taking their keys, until the process is killed or the rom
+/// halts with `until_halt`
+///
+/// With a `crowd`, the keys of the clients and of the chat bots connected
+/// to `relay` are its commands instead.
+pub fn serve(
+    machine: &mut Machine,
+    addr: &str,
+    until_halt: bool,
+    mut crowd: Option<Crowd>,
+    relay: Option<&str>,
+) -> Result<(), String> {
+    let listener =
+        TcpListener::bind(addr).map_err(|e| format!("{}: {}", addr, e))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    info!("serving on ws://{}", addr);
+
+    let relay_listener = match relay {
+        Some(addr) => {
+            let listener = TcpListener::bind(addr)
+                .map_err(|e| format!("{}: {}", addr, e))?;
+            listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+            info!("taking the chat on {}", addr);
+            Some(listener)
+        }
+        None => None,
+    };
+    let mut relays: Vec<Relay> = vec![];
+
+    let frame_time = Duration::from_secs_f64(1.0 / FRAME_RATE);
+    let mut next_frame = Instant::now();
+    let mut clients: Vec<Client> = vec![];
+    let mut handshakes: Vec<Handshake> = vec![];
+
+    loop {
+        for handshake in std::mem::take(&mut handshakes) {
+            let peer = handshake.peer;
+            connect(handshake.resume(), peer, &mut clients, &mut handshakes);
+        }
+        loop {
+            match listener.accept() {
+                Ok((stream, peer)) => connect(
+                    Client::accept(stream, peer),
+                    peer,
+                    &mut clients,
+                    &mut handshakes,
+                ),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        if let Some(listener) = &relay_listener {
+            loop {
+                match listener.accept() {
+                    Ok((stream, peer)) => match Relay::accept(stream, peer) {
+                        Ok(relay) => {
+                            info!("relay {} connected", peer);
+                            relays.push(relay);
+                        }
+                        Err(e) => warn!("{}: {}", peer, e),
+                    },
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => return Err(e.to_string()),
+                }
+            }
+        }
+
+        clients.retain_mut(|client| client.read(machine, crowd.as_mut()));
+
+        machine.bus_mut().keys = match &mut crowd {
+            Some(crowd) => {
+                relays.retain_mut(|relay| {
+                    let connected = relay.read(crowd);
+                    if !connected {
+                        info!("relay {} disconnected", relay.peer);
+                    }
+                    connected
+                });
+                crowd.tick()
+            }
+            None => {
+                let mut keys = [false; KEYPAD_SIZE];
+                for client in &clients {
+                    for (key, &pressed) in keys.iter_mut().zip(&client.keys) {
+                        *key |= pressed;
+                    }
+                }
+                keys
+            }
+        };
+
+        machine.run_frame();
+
+        let screen = pack(&machine.bus().vram());
+        let beeping = machine.is_beeping();
+        clients.retain_mut(|client| {
+            let connected = client.update(&screen, beeping);
+            if !connected {
+                info!("{} disconnected", client.peer);
+            }
+            connected
+        });
+
+        if until_halt && machine.is_halted() {
+            return Ok(());
+        }
+
+        // don't try to catch up when a frame took too long
+        next_frame += frame_time;
+        match next_frame.checked_duration_since(Instant::now()) {
+            Some(wait) => sleep(wait),
+            None => next_frame = Instant::now(),
+        }
+    }
+}
+
+/// Keep the client once connected, or its handshake until then
+fn connect(
+    connection: Result<Connection, String>,
+    peer: SocketAddr,
+    clients: &mut Vec<Client>,
+    handshakes: &mut Vec<Handshake>,
+) {
+    match connection {
+        Ok(Connection::Connected(client)) => {
+            info!("{} connected", peer);
+            clients.push(client);
+        }
+        Ok(Connection::Pending(handshake)) => handshakes.push(handshake),
+        Err(e) => warn!("{}: {}", peer, e),
+    }
+}
+
+fn would_block(e: &Error) -> bool {
+    matches!(e, Error::Io(e) if e.kind() == ErrorKind::WouldBlock)
+}
+
+/// Pixels row by row, 8 per byte with the leftmost in the high bit
+fn pack(vram: &Vram) -> Vec<u8> {
+    let mut screen = vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT / 8];
+
+    for h in 0..DISPLAY_HEIGHT {
+        for (w, column) in vram.iter().enumerate() {
+            if column[h] {
+                let index = h * DISPLAY_WIDTH + w;
+                screen[index / 8] |= 0x80 >> (index % 8);
+            }
+        }
+    }
+
+    screen
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, thread};
+
+    use chip8::rom::Rom;
+    use chip8_frontend::crowd::CrowdConfig;
+
+    use super::*;
+
+    /// Call `done` until it is true, failing after a second
+    fn until(mut done: impl FnMut() -> bool) {
+        let start = Instant::now();
+        while !done() {
+            assert!(start.elapsed() < Duration::from_secs(1), "timed out");
+            sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // a connection that never sends its handshake doesn't hold the next
+        let _silent = TcpStream::connect(addr).unwrap();
+        let browser = thread::spawn(move || {
+            let (mut socket, _) =
+                tungstenite::connect(format!("ws://{}", addr)).unwrap();
+            socket.send(Message::text("down A")).unwrap();
+            socket.read().unwrap()
+        });
+
+        let mut clients = vec![];
+        let mut handshakes: Vec<Handshake> = vec![];
+        until(|| {
+            for handshake in std::mem::take(&mut handshakes) {
+                let peer = handshake.peer;
+                let connection = handshake.resume();
+                connect(connection, peer, &mut clients, &mut handshakes);
+            }
+            if let Ok((stream, peer)) = listener.accept() {
+                let connection = Client::accept(stream, peer);
+                connect(connection, peer, &mut clients, &mut handshakes);
+            }
+            !clients.is_empty()
+        });
+        assert_eq!(handshakes.len(), 1);
+
+        let mut client = clients.pop().unwrap();
+        let mut machine = Machine::new(Rom::from_bytes(vec![0x12, 0x00]));
+        until(|| {
+            assert!(client.read(&mut machine, None));
+            client.keys[0xA]
+        });
+
+        let screen = pack(&machine.bus().vram());
+        assert!(client.update(&screen, false));
+        assert_eq!(
+            browser.join().unwrap(),
+            Message::binary([&[SCREEN], screen.as_slice()].concat())
+        );
+    }
+
+    #[test]
+    fn test_relay_long_line() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut bot =
+            TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, peer) = listener.accept().unwrap();
+        let mut relay = Relay::accept(stream, peer).unwrap();
+
+        // sent at once, more than a read and than a line
+        bot.write_all(b"viewer A\n").unwrap();
+        bot.write_all(&[b'A'; 8 * MAX_LINE]).unwrap();
+
+        let mut crowd = Crowd::new(CrowdConfig::default());
+        until(|| !relay.read(&mut crowd));
+        assert!(relay.pending.len() <= MAX_LINE + 512);
+        assert!(crowd.tick()[0xA]);
+    }
+}
@@ -0,0 +1,654 @@
+//! libretro core, the `retro_*` functions are the entry points called by the
+//! frontend and follow the contract of `libretro.h`
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    ffi::{c_char, c_uint, c_void, CStr},
+    ptr, slice,
+    sync::Mutex,
+};
+
+use chip8::{
+    bus::{
+        Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_HEIGHT, HIRES_WIDTH,
+    },
+    cpu::{KeyWaitPolicy, Quirks},
+    keypad::Keypad,
+    machine::{Machine, FRAME_RATE},
+    rom::Rom,
+};
+use libretro_sys::{
+    AudioSampleBatchFn, AudioSampleFn, EnvironmentFn, GameGeometry, GameInfo,
+    InputDescriptor, InputPollFn, InputStateFn, Key, PixelFormat, SystemAvInfo,
+    SystemInfo, SystemTiming, Variable, VideoRefreshFn,
+};
+use log::{debug, warn};
+
+const SAMPLE_RATE: f64 = 44100.0;
+const AUDIO_FRAMES: usize = (SAMPLE_RATE / FRAME_RATE) as usize;
+const BEEP_FREQUENCY: f64 = 440.0;
+const BEEP_VOLUME: i16 = 0x1000;
+
+const VAR_SPEED: &CStr = c"chip8_speed";
+const VAR_PALETTE: &CStr = c"chip8_palette";
+const VAR_KEY_WAIT: &CStr = c"chip8_key_wait";
+
+/// The flag of a quirk in `Quirks`
+type QuirkField = fn(&mut Quirks) -> &mut bool;
+
+/// Core option of each quirk, off by default
+const QUIRK_VARIABLES: [(&CStr, &CStr, QuirkField); 6] = [
+    (
+        c"chip8_quirks_shift",
+        c"8XY6/8XYE shift VX in place; disabled|enabled",
+        |quirks| &mut quirks.shift_vx,
+    ),
+    (
+        c"chip8_quirks_load_store",
+        c"FX55/FX65 leave I unchanged; disabled|enabled",
+        |quirks| &mut quirks.keep_i,
+    ),
+    (
+        c"chip8_quirks_jump",
+        c"BNNN jumps to NNN + VX; disabled|enabled",
+        |quirks| &mut quirks.jump_vx,
+    ),
+    (
+        c"chip8_quirks_vf_reset",
+        c"8XY1/8XY2/8XY3 reset VF; disabled|enabled",
+        |quirks| &mut quirks.vf_reset,
+    ),
+    (
+        c"chip8_quirks_clipping",
+        c"Clip sprites at the screen edges; disabled|enabled",
+        |quirks| &mut quirks.clip_sprites,
+    ),
+    (
+        c"chip8_quirks_display_wait",
+        c"DXYN waits for the display; disabled|enabled",
+        |quirks| &mut quirks.display_wait,
+    ),
+];
+
+/// (on, off) colors as XRGB8888
+const PALETTES: [(&str, (u32, u32)); 4] = [
+    ("green", (0x45730D, 0x7CD115)),
+    ("white", (0xFFFFFF, 0x000000)),
+    ("amber", (0xFFB000, 0x281800)),
+    ("lcd", (0x0F380F, 0x9BBC0F)),
+];
+
+const KEYBOARD_MAP: [(Key, Keypad); 16] = [
+    (Key::Number_1, Keypad::Key1),
+    (Key::Number_2, Keypad::Key2),
+    (Key::Number_3, Keypad::Key3),
+    (Key::Number_4, Keypad::KeyC),
+    (Key::A, Keypad::Key4),
+    (Key::Z, Keypad::Key5),
+    (Key::E, Keypad::Key6),
+    (Key::R, Keypad::KeyD),
+    (Key::Q, Keypad::Key7),
+    (Key::S, Keypad::Key8),
+    (Key::D, Keypad::Key9),
+    (Key::F, Keypad::KeyE),
+    (Key::W, Keypad::KeyA),
+    (Key::X, Keypad::Key0),
+    (Key::C, Keypad::KeyB),
+    (Key::V, Keypad::KeyF),
+];
+
+const JOYPAD_MAP: [(c_uint, Keypad, &CStr); 6] = [
+    (libretro_sys::DEVICE_ID_JOYPAD_UP, Keypad::Key5, c"Key 5"),
+    (libretro_sys::DEVICE_ID_JOYPAD_DOWN, Keypad::Key8, c"Key 8"),
+    (libretro_sys::DEVICE_ID_JOYPAD_LEFT, Keypad::Key7, c"Key 7"),
+    (libretro_sys::DEVICE_ID_JOYPAD_RIGHT, Keypad::Key9, c"Key 9"),
+    (libretro_sys::DEVICE_ID_JOYPAD_B, Keypad::Key6, c"Key 6"),
+    (libretro_sys::DEVICE_ID_JOYPAD_A, Keypad::Key4, c"Key 4"),
+];
+
+/// Frontend callbacks, registered before `retro_init`
+struct Callbacks {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+fn callbacks() -> std::sync::MutexGuard<'static, Callbacks> {
+    CALLBACKS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn core() -> std::sync::MutexGuard<'static, Option<Core>> {
+    CORE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+unsafe fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match callbacks().environment {
+        Some(environment) => environment(cmd, data),
+        None => false,
+    }
+}
+
+/// Current value of a core option
+unsafe fn variable(key: &CStr) -> Option<String> {
+    let mut variable = Variable {
+        key: key.as_ptr(),
+        value: ptr::null(),
+    };
+
+    let found = environment(
+        libretro_sys::ENVIRONMENT_GET_VARIABLE,
+        &mut variable as *mut _ as *mut c_void,
+    );
+    if !found || variable.value.is_null() {
+        return None;
+    }
+
+    Some(
+        CStr::from_ptr(variable.value)
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+struct Core {
+    machine: Machine,
+    palette: (u32, u32),
+    /// The screen of the machine, kept from frame to frame
+    screen: Framebuffer,
+    /// Pixels of the screen in use, `frame_size` of them
+    frame: Vec<u32>,
+    frame_size: (usize, usize),
+    audio: Vec<i16>,
+    audio_phase: f64,
+    state_size: usize,
+}
+
+impl Core {
+    fn new(machine: Machine) -> Self {
+        // the states of a machine have the same size, in hires too
+        let state_size = machine.save_state().len();
+
+        Self {
+            machine,
+            palette: PALETTES[0].1,
+            screen: Framebuffer::default(),
+            frame: vec![0; HIRES_WIDTH * HIRES_HEIGHT],
+            frame_size: (DISPLAY_WIDTH, DISPLAY_HEIGHT),
+            audio: vec![0; AUDIO_FRAMES * 2],
+            audio_phase: 0.0,
+            state_size,
+        }
+    }
+
+    unsafe fn update_variables(&mut self) {
+        if let Some(speed) = variable(VAR_SPEED) {
+            match speed.parse() {
+                Ok(speed) => self.machine.set_cpu_frequency(speed),
+                Err(_) => warn!("invalid speed: {}", speed),
+            }
+        }
+
+        if let Some(palette) = variable(VAR_PALETTE) {
+            if let Some((_, colors)) =
+                PALETTES.iter().find(|(name, _)| *name == palette)
+            {
+                self.palette = *colors;
+            }
+        }
+
+        if let Some(key_wait) = variable(VAR_KEY_WAIT) {
+            let policy = match key_wait.as_str() {
+                "most recently pressed" => KeyWaitPolicy::MostRecentlyPressed,
+                "first released" => KeyWaitPolicy::FirstReleased,
+                _ => KeyWaitPolicy::Lowest,
+            };
+            self.machine.cpu_mut().set_key_wait_policy(policy);
+        }
+
+        let mut quirks = self.machine.cpu().quirks();
+        for (key, _, quirk) in QUIRK_VARIABLES {
+            if let Some(value) = variable(key) {
+                *quirk(&mut quirks) = value == "enabled";
+            }
+        }
+        self.machine.cpu_mut().set_quirks(quirks);
+    }
+
+    fn update_inputs(&mut self, input_state: InputStateFn) {
+        let mut keys = [false; 16];
+
+        for (id, key, _) in JOYPAD_MAP {
+            let pressed = unsafe {
+                input_state(0, libretro_sys::DEVICE_JOYPAD, 0, id) != 0
+            };
+            keys[key as usize] |= pressed;
+        }
+
+        for (code, key) in KEYBOARD_MAP {
+            let pressed = unsafe {
+                input_state(0, libretro_sys::DEVICE_KEYBOARD, 0, code.to_uint())
+                    != 0
+            };
+            keys[key as usize] |= pressed;
+        }
+
+        for (_, key) in KEYBOARD_MAP {
+            self.machine.set_key(key, keys[key as usize]);
+        }
+    }
+
+    /// The screen at its resolution, 128x64 while the SUPER-CHIP one is on
+    fn render_video(&mut self) {
+        let (on, off) = self.palette;
+        self.machine.bus().framebuffer_into(&mut self.screen);
+
+        for (pixel, &lit) in self.frame.iter_mut().zip(&self.screen.pixels) {
+            *pixel = if lit & 1 != 0 { on } else { off };
+        }
+        self.frame_size = (self.screen.width, self.screen.height);
+    }
+
+    /// Square wave while the sound timer is running
+    fn render_audio(&mut self) {
+        let beeping = self.machine.is_beeping();
+
+        for frame in self.audio.chunks_exact_mut(2) {
+            let sample = match beeping {
+                true if self.audio_phase < 0.5 => BEEP_VOLUME,
+                true => -BEEP_VOLUME,
+                false => 0,
+            };
+            frame.fill(sample);
+
+            self.audio_phase += BEEP_FREQUENCY / SAMPLE_RATE;
+            self.audio_phase %= 1.0;
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    libretro_sys::API_VERSION
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_environment(callback: EnvironmentFn) {
+    callbacks().environment = Some(callback);
+
+    let mut variables = vec![
+        Variable {
+            key: VAR_SPEED.as_ptr(),
+            value: c"CPU speed (Hz); 500|600|700|800|1000|1500|2000|100|200|300|400"
+                .as_ptr(),
+        },
+        Variable {
+            key: VAR_PALETTE.as_ptr(),
+            value: c"Palette; green|white|amber|lcd".as_ptr(),
+        },
+        Variable {
+            key: VAR_KEY_WAIT.as_ptr(),
+            value: c"FX0A key selection; lowest|most recently pressed|first released"
+                .as_ptr(),
+        },
+    ];
+    variables.extend(QUIRK_VARIABLES.iter().map(|(key, value, _)| Variable {
+        key: key.as_ptr(),
+        value: value.as_ptr(),
+    }));
+    variables.push(Variable {
+        key: ptr::null(),
+        value: ptr::null(),
+    });
+
+    environment(
+        libretro_sys::ENVIRONMENT_SET_VARIABLES,
+        variables.as_ptr() as *mut c_void,
+    );
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: VideoRefreshFn) {
+    callbacks().video_refresh = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: AudioSampleBatchFn) {
+    callbacks().audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: InputPollFn) {
+    callbacks().input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: InputStateFn) {
+    callbacks().input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    core().take();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    *info = SystemInfo {
+        library_name: c"chip8".as_ptr(),
+        library_version: c"0.1.0".as_ptr(),
+        valid_extensions: c"ch8|c8".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    *info = SystemAvInfo {
+        geometry: GameGeometry {
+            base_width: DISPLAY_WIDTH as c_uint,
+            base_height: DISPLAY_HEIGHT as c_uint,
+            max_width: HIRES_WIDTH as c_uint,
+            max_height: HIRES_HEIGHT as c_uint,
+            aspect_ratio: DISPLAY_WIDTH as f32 / DISPLAY_HEIGHT as f32,
+        },
+        timing: SystemTiming {
+            fps: FRAME_RATE,
+            sample_rate: SAMPLE_RATE,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(
+    _port: c_uint,
+    _device: c_uint,
+) {
+}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = core().as_mut() {
+        core.machine.reset();
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_run() {
+    let (video_refresh, audio_sample_batch, input_poll, input_state) = {
+        let callbacks = callbacks();
+        (
+            callbacks.video_refresh,
+            callbacks.audio_sample_batch,
+            callbacks.input_poll,
+            callbacks.input_state,
+        )
+    };
+
+    let mut core = core();
+    let core = match core.as_mut() {
+        Some(core) => core,
+        None => return,
+    };
+
+    let mut updated = false;
+    environment(
+        libretro_sys::ENVIRONMENT_GET_VARIABLE_UPDATE,
+        &mut updated as *mut _ as *mut c_void,
+    );
+    if updated {
+        core.update_variables();
+    }
+
+    if let Some(input_poll) = input_poll {
+        input_poll();
+    }
+    if let Some(input_state) = input_state {
+        core.update_inputs(input_state);
+    }
+
+    core.machine.run_frame();
+
+    core.render_video();
+    if let Some(video_refresh) = video_refresh {
+        let (width, height) = core.frame_size;
+        video_refresh(
+            core.frame.as_ptr() as *const c_void,
+            width as c_uint,
+            height as c_uint,
+            width * 4,
+        );
+    }
+
+    core.render_audio();
+    if let Some(audio_sample_batch) = audio_sample_batch {
+        audio_sample_batch(core.audio.as_ptr(), AUDIO_FRAMES);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    core().as_ref().map_or(0, |core| core.state_size)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(
+    data: *mut c_void,
+    size: usize,
+) -> bool {
+    let core = core();
+    let core = match core.as_ref() {
+        Some(core) => core,
+        None => return false,
+    };
+
+    let state = core.machine.save_state();
+    if state.len() > size {
+        warn!("state doesn't fit in {} bytes", size);
+        return false;
+    }
+
+    // the remaining bytes are padding, ignored on load
+    let data = slice::from_raw_parts_mut(data as *mut u8, size);
+    data[..state.len()].copy_from_slice(&state);
+    data[state.len()..].fill(0);
+
+    true
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(
+    data: *const c_void,
+    size: usize,
+) -> bool {
+    let mut core = core();
+    let core = match core.as_mut() {
+        Some(core) => core,
+        None => return false,
+    };
+
+    let data = slice::from_raw_parts(data as *const u8, size);
+    match core.machine.load_state(data) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("unable to load state: {}", e);
+            false
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(
+    _index: c_uint,
+    _enabled: bool,
+    _code: *const c_char,
+) {
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    if game.is_null() || (*game).data.is_null() {
+        return false;
+    }
+
+    let mut format = PixelFormat::ARGB8888;
+    if !environment(
+        libretro_sys::ENVIRONMENT_SET_PIXEL_FORMAT,
+        &mut format as *mut _ as *mut c_void,
+    ) {
+        warn!("XRGB8888 is not supported");
+        return false;
+    }
+
+    let mut descriptors = JOYPAD_MAP
+        .iter()
+        .map(|(id, _, description)| InputDescriptor {
+            port: 0,
+            device: libretro_sys::DEVICE_JOYPAD,
+            index: 0,
+            id: *id,
+            description: description.as_ptr(),
+        })
+        .collect::<Vec<_>>();
+    descriptors.push(InputDescriptor {
+        port: 0,
+        device: 0,
+        index: 0,
+        id: 0,
+        description: ptr::null(),
+    });
+    environment(
+        libretro_sys::ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+        descriptors.as_mut_ptr() as *mut c_void,
+    );
+
+    let data = slice::from_raw_parts((*game).data as *const u8, (*game).size);
+    let rom = Rom::from_bytes(data.to_vec());
+    debug!("loaded: {}", rom);
+    let machine = match Machine::try_new(rom) {
+        Ok(machine) => machine,
+        Err(e) => {
+            warn!("unable to load the rom: {}", e);
+            return false;
+        }
+    };
+
+    let mut core_state = Core::new(machine);
+    core_state.update_variables();
+    core().replace(core_state);
+
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const GameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    core().take();
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    libretro_sys::Region::NTSC.to_uint()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_hires() {
+        // 00FF: hires, D010: 16x16 sprite of the memory at 0, 1204: loop
+        let rom = Rom::from_bytes(vec![0x00, 0xFF, 0xD0, 0x10, 0x12, 0x04]);
+        core().replace(Core::new(Machine::new(rom)));
+        let size = retro_serialize_size();
+        if let Some(core) = core().as_mut() {
+            core.machine.run_frame();
+            assert!(core.machine.bus().hires_rows().is_some());
+        }
+
+        let mut data = vec![0; size];
+        unsafe {
+            assert!(retro_serialize(data.as_mut_ptr() as *mut c_void, size));
+            assert!(retro_unserialize(data.as_ptr() as *const c_void, size));
+        }
+        core().take();
+    }
+
+    unsafe extern "C" fn quirks_environment(
+        cmd: c_uint,
+        data: *mut c_void,
+    ) -> bool {
+        if cmd != libretro_sys::ENVIRONMENT_GET_VARIABLE {
+            return false;
+        }
+        let variable = &mut *(data as *mut Variable);
+        variable.value = match CStr::from_ptr(variable.key).to_bytes() {
+            b"chip8_quirks_shift" | b"chip8_quirks_clipping" => c"enabled",
+            b"chip8_quirks_vf_reset" => c"disabled",
+            _ => return false,
+        }
+        .as_ptr();
+
+        true
+    }
+
+    #[test]
+    fn test_quirk_variables() {
+        let rom = Rom::from_bytes(vec![0x12, 0x00]);
+        let mut core = Core::new(Machine::new(rom));
+        let mut quirks = Quirks {
+            vf_reset: true,
+            keep_i: true,
+            ..Quirks::default()
+        };
+        core.machine.cpu_mut().set_quirks(quirks);
+
+        callbacks().environment = Some(quirks_environment);
+        unsafe { core.update_variables() };
+        callbacks().environment = None;
+
+        quirks.shift_vx = true;
+        quirks.clip_sprites = true;
+        quirks.vf_reset = false;
+        assert_eq!(core.machine.cpu().quirks(), quirks);
+    }
+}
@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use chip8::{
+    bus::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    machine::Machine,
+};
+use chip8_frontend::KEYPAD_LAYOUT;
+use log::error;
+use macroquad::{
+    audio::{
+        load_sound_from_bytes, play_sound, stop_sound, PlaySoundParams, Sound,
+    },
+    prelude::*,
+};
+
+const FOREGROUND: [u8; 4] = [69, 115, 13, 0xFF];
+const BACKGROUND: [u8; 4] = [124, 209, 21, 0xFF];
+
+// logical keys, in the same order as KEYPAD_LAYOUT
+const KEYS: [KeyCode; 16] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::A,
+    KeyCode::Z,
+    KeyCode::E,
+    KeyCode::R,
+    KeyCode::Q,
+    KeyCode::S,
+    KeyCode::D,
+    KeyCode::F,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::C,
+    KeyCode::V,
+];
+
+const SAMPLE_RATE: u32 = 44100;
+// 100 samples per period, close enough to 440Hz and loops without a click
+const BEEP_PERIOD: usize = 100;
+const BEEP_PERIODS: usize = 44;
+const BEEP_VOLUME: i16 = 4000;
+
+pub struct MacroquadFrontend {
+    // chip8
+    machine: Machine,
+    // display
+    image: Image,
+    texture: Texture2D,
+    // audio
+    beep: Option<Sound>,
+    beeping: bool,
+}
+
+impl MacroquadFrontend {
+    pub async fn new(machine: Machine) -> Self {
+        let image = Image::gen_image_color(
+            DISPLAY_WIDTH as u16,
+            DISPLAY_HEIGHT as u16,
+            Color::from_rgba(
+                BACKGROUND[0],
+                BACKGROUND[1],
+                BACKGROUND[2],
+                BACKGROUND[3],
+            ),
+        );
+        let texture = Texture2D::from_image(&image);
+        texture.set_filter(FilterMode::Nearest);
+
+        // the game keeps running silently without a sound device
+        let beep = match load_sound_from_bytes(&beep_wav()).await {
+            Ok(sound) => Some(sound),
+            Err(e) => {
+                error!("beep: {}", e);
+                None
+            }
+        };
+
+        Self {
+            machine,
+            image,
+            texture,
+            beep,
+            beeping: false,
+        }
+    }
+
+    pub async fn run(mut self) {
+        while !is_key_pressed(KeyCode::Escape) {
+            self.update();
+            self.draw();
+
+            next_frame().await;
+        }
+
+        if let Some(beep) = &self.beep {
+            stop_sound(beep);
+        }
+    }
+
+    fn update(&mut self) {
+        for (&code, &key) in KEYS.iter().zip(KEYPAD_LAYOUT.iter()) {
+            self.machine.set_key(key, is_key_down(code));
+        }
+
+        // the frames missed in background aren't caught up
+        self.machine.tick(Duration::from_secs_f32(get_frame_time()));
+
+        if self.machine.is_beeping() != self.beeping {
+            self.beeping = self.machine.is_beeping();
+            if let Some(beep) = &self.beep {
+                match self.beeping {
+                    true => play_sound(
+                        beep,
+                        PlaySoundParams {
+                            looped: true,
+                            volume: 1.0,
+                        },
+                    ),
+                    false => stop_sound(beep),
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self) {
+        let vram = &self.machine.bus().vram();
+        for (index, pixel) in self.image.bytes.chunks_exact_mut(4).enumerate() {
+            let (w, h) = (index % DISPLAY_WIDTH, index / DISPLAY_WIDTH);
+            pixel.copy_from_slice(match vram[w][h] {
+                true => &FOREGROUND,
+                false => &BACKGROUND,
+            });
+        }
+        self.texture.update(&self.image);
+
+        // keep the aspect ratio, black bars on the sides
+        clear_background(BLACK);
+        let scale = (screen_width() / DISPLAY_WIDTH as f32)
+            .min(screen_height() / DISPLAY_HEIGHT as f32);
+        let size = vec2(DISPLAY_WIDTH as f32, DISPLAY_HEIGHT as f32) * scale;
+        draw_texture_ex(
+            &self.texture,
+            (screen_width() - size.x) / 2.0,
+            (screen_height() - size.y) / 2.0,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(size),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+/// A square wave as a 16 bits mono wav file
+fn beep_wav() -> Vec<u8> {
+    let samples = BEEP_PERIOD * BEEP_PERIODS;
+    let data_size = (samples * 2) as u32;
+
+    let mut wav = Vec::with_capacity(44 + samples * 2);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // pcm
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+
+    for i in 0..samples {
+        let sample = match i % BEEP_PERIOD < BEEP_PERIOD / 2 {
+            true => BEEP_VOLUME,
+            false => -BEEP_VOLUME,
+        };
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    wav
+}
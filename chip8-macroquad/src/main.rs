@@ -0,0 +1,37 @@
+mod macroquad_frontend;
+
+use chip8::{machine::Machine, rom::Rom};
+use log::debug;
+use macroquad::window::Conf;
+
+use std::env;
+
+use crate::macroquad_frontend::MacroquadFrontend;
+
+fn window_conf() -> Conf {
+    Conf {
+        window_title: String::from("Chip8 macroquad"),
+        window_width: 640,
+        window_height: 320,
+        ..Default::default()
+    }
+}
+
+#[macroquad::main(window_conf)]
+async fn main() {
+    dotenv::dotenv().ok();
+    env_logger::builder().format_timestamp_nanos().init();
+
+    let args: Vec<String> = env::args().collect();
+
+    let rom_path_default = String::from("roms/slipperyslope.ch8");
+    let rom_path = args.get(1).unwrap_or(&rom_path_default);
+
+    debug!("start");
+
+    let rom = Rom::new_from(rom_path).expect("Failed to read rom file");
+
+    debug!("loaded: {}", rom);
+
+    MacroquadFrontend::new(Machine::new(rom)).await.run().await;
+}
@@ -0,0 +1,62 @@
+mod minifb_frontend;
+
+use chip8::{machine::Machine, rom::Rom};
+use chip8_frontend::netplay::{self, Netplay, DEFAULT_INPUT_DELAY};
+use log::{debug, error};
+
+use std::{env, net::TcpListener};
+
+use crate::minifb_frontend::MinifbFrontend;
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::builder().format_timestamp_nanos().init();
+
+    let args: Vec<String> = env::args().collect();
+
+    let rom_path_default = String::from("roms/slipperyslope.ch8");
+    let rom_path = args.get(1).unwrap_or(&rom_path_default);
+
+    debug!("start");
+
+    let rom = Rom::new_from(rom_path).expect("Failed to read rom file");
+
+    debug!("loaded: {}", rom);
+
+    let mut machine = Machine::new(rom);
+
+    // chip8-minifb ROM --host ADDR [DELAY] | --join ADDR
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let netplay = match args.get(2..).unwrap_or_default() {
+        [] => None,
+        ["--host", addr, delay @ ..] => {
+            let delay = match delay.first() {
+                Some(delay) => delay.parse().expect("Invalid input delay"),
+                None => DEFAULT_INPUT_DELAY,
+            };
+            let listener = TcpListener::bind(addr).expect("Failed to listen");
+
+            debug!("waiting for a player on {}", addr);
+
+            Some(Netplay::host(&listener, &mut machine, delay))
+        }
+        ["--join", addr] => Some(Netplay::join(addr, &mut machine)),
+        _ => panic!(
+            "Usage: chip8-minifb ROM [--host ADDR [DELAY] | --join ADDR]"
+        ),
+    };
+
+    let mut frontend = MinifbFrontend::new();
+
+    match netplay {
+        Some(netplay) => {
+            let mut netplay = netplay.expect("Failed to start netplay");
+            if let Err(e) =
+                netplay::run(&mut machine, &mut frontend, &mut netplay)
+            {
+                error!("netplay: {}", e);
+            }
+        }
+        None => chip8_frontend::run(&mut machine, &mut frontend),
+    }
+}
@@ -0,0 +1,80 @@
+use chip8::bus::{DISPLAY_HEIGHT, DISPLAY_WIDTH, KEYPAD_SIZE};
+use chip8_frontend::{Frontend, Vram, KEYPAD_LAYOUT};
+use log::error;
+use minifb::{Key, Scale, ScaleMode, Window, WindowOptions};
+
+const FOREGROUND: u32 = 0x45730D;
+const BACKGROUND: u32 = 0x7CD115;
+
+/// Keyboard keys in the order of `KEYPAD_LAYOUT`
+const KEYS: [Key; KEYPAD_SIZE] = [
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::A,
+    Key::Z,
+    Key::E,
+    Key::R,
+    Key::Q,
+    Key::S,
+    Key::D,
+    Key::F,
+    Key::W,
+    Key::X,
+    Key::C,
+    Key::V,
+];
+
+pub struct MinifbFrontend {
+    window: Window,
+    buffer: Vec<u32>,
+}
+
+impl MinifbFrontend {
+    pub fn new() -> Self {
+        let options = WindowOptions {
+            resize: true,
+            scale: Scale::X8,
+            scale_mode: ScaleMode::AspectRatioStretch,
+            ..WindowOptions::default()
+        };
+
+        let mut window =
+            Window::new("Chip8 minifb", DISPLAY_WIDTH, DISPLAY_HEIGHT, options)
+                .expect("Failed to create window");
+        // the frame rate is handled by the emulation loop
+        window.set_target_fps(0);
+
+        Self {
+            window,
+            buffer: vec![BACKGROUND; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+        }
+    }
+}
+
+impl Frontend for MinifbFrontend {
+    fn poll_input(&mut self, keys: &mut [bool; KEYPAD_SIZE]) -> bool {
+        for (key, keypad) in KEYS.iter().zip(KEYPAD_LAYOUT) {
+            keys[keypad as usize] = self.window.is_key_down(*key);
+        }
+
+        self.window.is_open() && !self.window.is_key_down(Key::Escape)
+    }
+
+    fn draw(&mut self, vram: &Vram) {
+        for (index, pixel) in self.buffer.iter_mut().enumerate() {
+            let (w, h) = (index % DISPLAY_WIDTH, index / DISPLAY_WIDTH);
+            *pixel = if vram[w][h] { FOREGROUND } else { BACKGROUND };
+        }
+
+        // also processes the window events
+        if let Err(e) = self.window.update_with_buffer(
+            &self.buffer,
+            DISPLAY_WIDTH,
+            DISPLAY_HEIGHT,
+        ) {
+            error!("update: {}", e);
+        }
+    }
+}
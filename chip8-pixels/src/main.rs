@@ -0,0 +1,26 @@
+mod pixels_frontend;
+
+use chip8::{machine::Machine, rom::Rom};
+use log::debug;
+
+use std::env;
+
+use crate::pixels_frontend::PixelsFrontend;
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::builder().format_timestamp_nanos().init();
+
+    let args: Vec<String> = env::args().collect();
+
+    let rom_path_default = String::from("roms/slipperyslope.ch8");
+    let rom_path = args.get(1).unwrap_or(&rom_path_default);
+
+    debug!("start");
+
+    let rom = Rom::new_from(rom_path).expect("Failed to read rom file");
+
+    debug!("loaded: {}", rom);
+
+    PixelsFrontend::new(Machine::new(rom)).run();
+}
@@ -0,0 +1,195 @@
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use chip8::{
+    bus::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    keypad::Keypad,
+    machine::Machine,
+};
+use log::error;
+use pixels::{Pixels, SurfaceTexture};
+use winit::{
+    application::ApplicationHandler,
+    dpi::LogicalSize,
+    event::{ElementState, KeyEvent, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{KeyCode, PhysicalKey},
+    window::{Window, WindowId},
+};
+
+const FOREGROUND: [u8; 4] = [69, 115, 13, 0xFF];
+const BACKGROUND: [u8; 4] = [124, 209, 21, 0xFF];
+
+const DISPLAY_SCALE: u32 = 10;
+
+pub struct PixelsFrontend {
+    // chip8
+    machine: Machine,
+    // window, created once the event loop is running
+    window: Option<Arc<Window>>,
+    pixels: Option<Pixels<'static>>,
+    key_map: HashMap<KeyCode, Keypad>,
+    // loop
+    loop_time: Instant,
+    frames: f64,
+}
+
+impl PixelsFrontend {
+    pub fn new(machine: Machine) -> Self {
+        // physical keys, the same positions on any keyboard layout
+        let mut key_map = HashMap::new();
+        key_map.insert(KeyCode::Digit1, Keypad::Key1);
+        key_map.insert(KeyCode::Digit2, Keypad::Key2);
+        key_map.insert(KeyCode::Digit3, Keypad::Key3);
+        key_map.insert(KeyCode::Digit4, Keypad::KeyC);
+        key_map.insert(KeyCode::KeyQ, Keypad::Key4);
+        key_map.insert(KeyCode::KeyW, Keypad::Key5);
+        key_map.insert(KeyCode::KeyE, Keypad::Key6);
+        key_map.insert(KeyCode::KeyR, Keypad::KeyD);
+        key_map.insert(KeyCode::KeyA, Keypad::Key7);
+        key_map.insert(KeyCode::KeyS, Keypad::Key8);
+        key_map.insert(KeyCode::KeyD, Keypad::Key9);
+        key_map.insert(KeyCode::KeyF, Keypad::KeyE);
+        key_map.insert(KeyCode::KeyZ, Keypad::KeyA);
+        key_map.insert(KeyCode::KeyX, Keypad::Key0);
+        key_map.insert(KeyCode::KeyC, Keypad::KeyB);
+        key_map.insert(KeyCode::KeyV, Keypad::KeyF);
+
+        Self {
+            machine,
+            window: None,
+            pixels: None,
+            key_map,
+            loop_time: Instant::now(),
+            frames: 0.0,
+        }
+    }
+
+    pub fn run(mut self) {
+        let event_loop = EventLoop::new().expect("Failed to create event loop");
+        event_loop.set_control_flow(ControlFlow::Poll);
+
+        event_loop.run_app(&mut self).expect("Event loop error");
+    }
+
+    fn update(&mut self) {
+        let delta = self.loop_time.elapsed().as_secs_f64();
+        self.loop_time = Instant::now();
+
+        self.frames += delta / 0.0166666666667; // 60Hz
+        while self.frames >= 1.0 {
+            self.frames -= 1.0;
+            self.machine.run_frame();
+        }
+    }
+
+    fn draw(&mut self) {
+        let pixels = match &mut self.pixels {
+            Some(pixels) => pixels,
+            None => return,
+        };
+
+        let vram = &self.machine.bus().vram();
+        for (index, pixel) in pixels.frame_mut().chunks_exact_mut(4).enumerate()
+        {
+            let (w, h) = (index % DISPLAY_WIDTH, index / DISPLAY_WIDTH);
+            pixel.copy_from_slice(match vram[w][h] {
+                true => &FOREGROUND,
+                false => &BACKGROUND,
+            });
+        }
+
+        if let Err(e) = pixels.render() {
+            error!("render: {}", e);
+        }
+    }
+}
+
+impl ApplicationHandler for PixelsFrontend {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+
+        let size = LogicalSize::new(
+            DISPLAY_WIDTH as u32 * DISPLAY_SCALE,
+            DISPLAY_HEIGHT as u32 * DISPLAY_SCALE,
+        );
+        let attributes = Window::default_attributes()
+            .with_title("Chip8 pixels")
+            .with_inner_size(size)
+            .with_min_inner_size(LogicalSize::new(
+                DISPLAY_WIDTH as u32,
+                DISPLAY_HEIGHT as u32,
+            ));
+        let window = Arc::new(
+            event_loop
+                .create_window(attributes)
+                .expect("Failed to create window"),
+        );
+
+        let window_size = window.inner_size();
+        let surface = SurfaceTexture::new(
+            window_size.width,
+            window_size.height,
+            window.clone(),
+        );
+        let pixels =
+            Pixels::new(DISPLAY_WIDTH as u32, DISPLAY_HEIGHT as u32, surface)
+                .expect("Failed to create pixels");
+
+        self.window = Some(window);
+        self.pixels = Some(pixels);
+        self.loop_time = Instant::now();
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        _id: WindowId,
+        event: WindowEvent,
+    ) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+
+            WindowEvent::Resized(size) => {
+                if let Some(pixels) = &mut self.pixels {
+                    if let Err(e) =
+                        pixels.resize_surface(size.width, size.height)
+                    {
+                        error!("resize: {}", e);
+                    }
+                }
+            }
+
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code),
+                        state,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                if code == KeyCode::Escape {
+                    event_loop.exit();
+                } else if let Some(&key) = self.key_map.get(&code) {
+                    self.machine.set_key(key, state == ElementState::Pressed);
+                }
+            }
+
+            WindowEvent::RedrawRequested => {
+                self.update();
+                self.draw();
+            }
+
+            _ => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::{
+    io::Read,
+    os::raw::{c_char, c_int},
+};
+
+use chip8::{machine::Machine, rom::Rom};
+use log::error;
+use sdl2::rwops::RWops;
+
+use crate::sdl2_frontend::SDL2Frontend;
+
+/// Rom bundled in the apk `assets` folder
+const ROM_ASSET: &str = "rom.ch8";
+const STATE_FILE: &str = "state.ch8s";
+
+/// Entry point called by `SDLActivity` once the library is loaded
+#[allow(non_snake_case)]
+#[no_mangle]
+pub extern "C" fn SDL_main(_argc: c_int, _argv: *const *const c_char) -> c_int {
+    // on android, RWops reads files from the apk assets
+    let mut data = Vec::new();
+    let read = RWops::from_file(ROM_ASSET, "rb").and_then(|mut file| {
+        file.read_to_end(&mut data).map_err(|e| e.to_string())
+    });
+    if let Err(e) = read {
+        error!("{}: {}", ROM_ASSET, e);
+        return 1;
+    }
+
+    let mut frontend = SDL2Frontend::new(Machine::new(Rom::from_bytes(data)));
+    frontend.set_touch_keypad(true);
+
+    match sdl2::filesystem::pref_path("gbredz1", "chip8") {
+        Ok(path) => frontend
+            .set_state_file([path.as_str(), STATE_FILE].iter().collect()),
+        Err(e) => error!("no state file: {}", e),
+    }
+
+    frontend.run();
+
+    0
+}
@@ -0,0 +1,265 @@
+use std::{
+    thread::sleep,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use chip8::{
+    bus::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    cpu::KeyWaitPolicy,
+    keypad::Keypad,
+    machine::Machine,
+    rom::Rom,
+};
+use chip8_frontend::config::{Config, KEY_WAIT_POLICIES};
+use log::{info, warn};
+use sdl2::{
+    event::Event, keyboard::Keycode, pixels::Color, rect::Rect, render::Canvas,
+    video::Window,
+};
+
+use crate::keymap;
+
+const FOREGROUND: Color = Color::RGB(69, 115, 13);
+const BACKGROUND: Color = Color::RGB(124, 209, 21);
+/// Pixels lit on one side only, once the screens diverged
+const DIFFERENCE: Color = Color::RGB(220, 40, 40);
+const PIXEL_SIZE: u32 = 8;
+/// Space between the two screens, in output pixels
+const GAP: u32 = 8;
+
+/// Two machines running the same rom with different quirks, from the same
+/// inputs and random numbers
+pub struct Comparison {
+    machines: [Machine; 2],
+    policies: [KeyWaitPolicy; 2],
+    frame: u64,
+    /// First frame where the screens differ
+    diverged: Option<u64>,
+}
+
+impl Comparison {
+    pub fn new(rom: Rom, policies: [KeyWaitPolicy; 2]) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        let machines = policies.map(|policy| {
+            let mut machine = Machine::new(rom.clone());
+            machine.cpu_mut().set_key_wait_policy(policy);
+            machine.cpu_mut().set_seed(seed);
+            machine
+        });
+
+        Self {
+            machines,
+            policies,
+            frame: 0,
+            diverged: None,
+        }
+    }
+
+    /// Run a frame on both machines, returns true when their screens
+    /// differ for the first time
+    pub fn run_frame(&mut self) -> bool {
+        for machine in &mut self.machines {
+            machine.run_frame();
+        }
+        self.frame += 1;
+
+        let [left, right] = &self.machines;
+        if self.diverged.is_some() || left.bus().rows() == right.bus().rows() {
+            return false;
+        }
+
+        self.diverged = Some(self.frame);
+        true
+    }
+
+    /// Frames due after `delta` of real time, both machines run at the
+    /// pace of the first one
+    pub fn frames_due(&mut self, delta: Duration) -> u32 {
+        self.machines[0].frames_due(delta).run
+    }
+
+    pub fn set_key(&mut self, key: Keypad, pressed: bool) {
+        for machine in &mut self.machines {
+            machine.set_key(key, pressed);
+        }
+    }
+
+    fn title(&self, paused: bool) -> String {
+        let names = self.policies.map(policy_name);
+        let mut title = format!("chip8 compare - {} | {}", names[0], names[1]);
+        match self.diverged {
+            Some(frame) => {
+                title.push_str(&format!(" - diverged at frame {}", frame))
+            }
+            None => title.push_str(&format!(" - frame {}", self.frame)),
+        }
+        if paused {
+            title.push_str(" [Paused]");
+        }
+
+        title
+    }
+}
+
+/// Name of `policy` in the configuration
+pub fn policy_name(policy: KeyWaitPolicy) -> &'static str {
+    KEY_WAIT_POLICIES
+        .iter()
+        .find(|(known, _)| *known == policy)
+        .map_or("", |(_, name)| name)
+}
+
+/// The policy named `name` in the configuration
+pub fn parse_policy(name: &str) -> Option<KeyWaitPolicy> {
+    KEY_WAIT_POLICIES
+        .iter()
+        .find(|(_, known)| *known == name)
+        .map(|&(policy, _)| policy)
+}
+
+/// Show the two machines of `comparison` side by side until the window is
+/// closed
+///
+/// Both get the keys pressed. They pause on the first frame where their
+/// screens differ, with the differences highlighted; Space resumes, F10
+/// runs a single frame while paused and Escape quits.
+pub fn run(mut comparison: Comparison) {
+    let sdl = sdl2::init().expect("SDL2 Init");
+    let video_subsystem = sdl.video().expect("SDL2: video");
+    let window = video_subsystem
+        .window(
+            "chip8 compare",
+            DISPLAY_WIDTH as u32 * PIXEL_SIZE * 2 + GAP,
+            DISPLAY_HEIGHT as u32 * PIXEL_SIZE,
+        )
+        .position_centered()
+        .build()
+        .expect("SDL2: window");
+    let mut canvas = window.into_canvas().build().expect("SDL2: Canvas");
+    let mut event_pump = sdl.event_pump().expect("SDL2: EventPump");
+    let key_map = keymap::key_map(&Config::load().keymap);
+
+    let mut loop_time = Instant::now();
+    let mut paused = false;
+    let mut dirty = true;
+
+    loop {
+        let mut step = false;
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return,
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::Space),
+                    repeat: false,
+                    ..
+                } => {
+                    paused = !paused;
+                    dirty = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    ..
+                } => step = paused,
+
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    ..
+                } => {
+                    if let Some(&key) = key_map.get(&scancode) {
+                        comparison.set_key(key, true);
+                    }
+                }
+                Event::KeyUp {
+                    scancode: Some(scancode),
+                    ..
+                } => {
+                    if let Some(&key) = key_map.get(&scancode) {
+                        comparison.set_key(key, false);
+                    }
+                }
+
+                _ => {}
+            }
+        }
+
+        let delta = loop_time.elapsed();
+        loop_time = Instant::now();
+
+        if step {
+            comparison.run_frame();
+            dirty = true;
+        } else if !paused {
+            for _ in 0..comparison.frames_due(delta) {
+                dirty = true;
+                if comparison.run_frame() {
+                    info!("screens diverged at frame {}", comparison.frame);
+                    paused = true;
+                    break;
+                }
+            }
+        }
+
+        if dirty {
+            dirty = false;
+            draw(&mut canvas, &comparison).expect("draw comparison");
+            let title = comparison.title(paused);
+            if let Err(e) = canvas.window_mut().set_title(&title) {
+                warn!("title: {}", e);
+            }
+        }
+
+        sleep(Duration::from_millis(5));
+    }
+}
+
+fn draw(
+    canvas: &mut Canvas<Window>,
+    comparison: &Comparison,
+) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+
+    let [left, right] = &comparison.machines;
+    for (index, (machine, other)) in
+        [(left, right), (right, left)].into_iter().enumerate()
+    {
+        let x =
+            (index as u32 * (DISPLAY_WIDTH as u32 * PIXEL_SIZE + GAP)) as i32;
+        canvas.set_draw_color(BACKGROUND);
+        canvas.fill_rect(Rect::new(
+            x,
+            0,
+            DISPLAY_WIDTH as u32 * PIXEL_SIZE,
+            DISPLAY_HEIGHT as u32 * PIXEL_SIZE,
+        ))?;
+
+        let vram = &machine.bus().vram();
+        let other_vram = &other.bus().vram();
+        for (w, column) in vram.iter().enumerate() {
+            for (h, _) in column.iter().enumerate().filter(|(_, &lit)| lit) {
+                let color = match other_vram[w][h] {
+                    false if comparison.diverged.is_some() => DIFFERENCE,
+                    _ => FOREGROUND,
+                };
+                canvas.set_draw_color(color);
+                canvas.fill_rect(Rect::new(
+                    x + (w as u32 * PIXEL_SIZE) as i32,
+                    (h as u32 * PIXEL_SIZE) as i32,
+                    PIXEL_SIZE,
+                    PIXEL_SIZE,
+                ))?;
+            }
+        }
+    }
+
+    canvas.present();
+
+    Ok(())
+}
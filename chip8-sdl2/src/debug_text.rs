@@ -0,0 +1,95 @@
+use chip8::{disasm::disassemble_at, machine::Machine};
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::Canvas,
+    video::Window,
+};
+
+use crate::font;
+
+/// Instructions shown before and after the one at the PC
+const DISASSEMBLY_CONTEXT: u16 = 3;
+
+/// Registers, timers and the instructions around the PC drawn as text over
+/// the screen, a lighter debugger than the imgui overlay
+pub struct DebugText {
+    visible: bool,
+}
+
+impl DebugText {
+    pub fn new() -> Self {
+        Self { visible: false }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Draw the text in the top right corner of `area`, on a box of
+    /// `background`
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        area: Rect,
+        pixel: u32,
+        machine: &Machine,
+        foreground: Color,
+        background: Color,
+    ) -> Result<(), String> {
+        if !self.visible {
+            return Ok(());
+        }
+
+        let lines = lines(machine);
+        let width = lines
+            .iter()
+            .map(|line| font::text_width(line, pixel))
+            .max()
+            .unwrap_or(0)
+            + 2 * pixel;
+        let height = (lines.len() as u32 * font::LINE_HEIGHT + 1) * pixel;
+        let x = area.right() - width as i32;
+        canvas.set_draw_color(background);
+        canvas.fill_rect(Rect::new(x, area.y(), width, height))?;
+
+        for (index, line) in lines.iter().enumerate() {
+            let y = (index as u32 * font::LINE_HEIGHT + 1) * pixel;
+            font::draw_text(
+                canvas,
+                Point::new(x + pixel as i32, area.y() + y as i32),
+                pixel,
+                line,
+                foreground,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn lines(machine: &Machine) -> Vec<String> {
+    let cpu = machine.cpu();
+    let bus = machine.bus();
+    let v = cpu.registers();
+
+    let mut lines = vec![format!("PC {:03X}  I {:03X}", cpu.pc(), cpu.index())];
+    for (x, (low, high)) in v[..8].iter().zip(&v[8..]).enumerate() {
+        lines.push(format!("V{:X} {:02X}   V{:X} {:02X}", x, low, x + 8, high));
+    }
+    lines.push(format!("DT {:02X}   ST {:02X}", bus.delay, bus.beep));
+    if let Some(x) = cpu.key_await() {
+        lines.push(format!("KEY IN V{:X}", x));
+    }
+
+    lines.push(String::new());
+    let pc = cpu.pc();
+    let start = pc.saturating_sub(DISASSEMBLY_CONTEXT * 2);
+    for addr in (start..=pc + DISASSEMBLY_CONTEXT * 2).step_by(2) {
+        let (_, text) = disassemble_at(bus.memory(), addr);
+        let marker = if addr == pc { '>' } else { ' ' };
+        lines.push(format!("{}{:03X} {}", marker, addr, text));
+    }
+
+    lines
+}
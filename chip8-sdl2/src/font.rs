@@ -0,0 +1,106 @@
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::Canvas,
+    video::Window,
+};
+
+/// Width of a glyph and the space after it, in font pixels
+pub const ADVANCE: u32 = 4;
+/// Height of a line and the space under it, in font pixels
+pub const LINE_HEIGHT: u32 = 6;
+
+/// Characters of the font, in the order of `GLYPHS`
+const CHARACTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789%.:-,[]>_";
+/// 3x5 glyphs, one row per byte with the leftmost pixel in bit 2
+#[rustfmt::skip]
+const GLYPHS: [[u8; 5]; 45] = [
+    [0b010, 0b101, 0b111, 0b101, 0b101], // A
+    [0b110, 0b101, 0b110, 0b101, 0b110], // B
+    [0b011, 0b100, 0b100, 0b100, 0b011], // C
+    [0b110, 0b101, 0b101, 0b101, 0b110], // D
+    [0b111, 0b100, 0b110, 0b100, 0b111], // E
+    [0b111, 0b100, 0b110, 0b100, 0b100], // F
+    [0b011, 0b100, 0b101, 0b101, 0b011], // G
+    [0b101, 0b101, 0b111, 0b101, 0b101], // H
+    [0b111, 0b010, 0b010, 0b010, 0b111], // I
+    [0b001, 0b001, 0b001, 0b101, 0b010], // J
+    [0b101, 0b101, 0b110, 0b101, 0b101], // K
+    [0b100, 0b100, 0b100, 0b100, 0b111], // L
+    [0b101, 0b111, 0b111, 0b101, 0b101], // M
+    [0b110, 0b101, 0b101, 0b101, 0b101], // N
+    [0b010, 0b101, 0b101, 0b101, 0b010], // O
+    [0b110, 0b101, 0b110, 0b100, 0b100], // P
+    [0b010, 0b101, 0b101, 0b110, 0b011], // Q
+    [0b110, 0b101, 0b110, 0b101, 0b101], // R
+    [0b011, 0b100, 0b010, 0b001, 0b110], // S
+    [0b111, 0b010, 0b010, 0b010, 0b010], // T
+    [0b101, 0b101, 0b101, 0b101, 0b111], // U
+    [0b101, 0b101, 0b101, 0b101, 0b010], // V
+    [0b101, 0b101, 0b111, 0b111, 0b101], // W
+    [0b101, 0b101, 0b010, 0b101, 0b101], // X
+    [0b101, 0b101, 0b010, 0b010, 0b010], // Y
+    [0b111, 0b001, 0b010, 0b100, 0b111], // Z
+    [0b111, 0b101, 0b101, 0b101, 0b111], // 0
+    [0b010, 0b110, 0b010, 0b010, 0b111], // 1
+    [0b110, 0b001, 0b010, 0b100, 0b111], // 2
+    [0b110, 0b001, 0b010, 0b001, 0b110], // 3
+    [0b101, 0b101, 0b111, 0b001, 0b001], // 4
+    [0b111, 0b100, 0b110, 0b001, 0b110], // 5
+    [0b011, 0b100, 0b110, 0b101, 0b010], // 6
+    [0b111, 0b001, 0b010, 0b010, 0b010], // 7
+    [0b010, 0b101, 0b010, 0b101, 0b010], // 8
+    [0b010, 0b101, 0b011, 0b001, 0b110], // 9
+    [0b101, 0b001, 0b010, 0b100, 0b101], // %
+    [0b000, 0b000, 0b000, 0b000, 0b010], // .
+    [0b000, 0b010, 0b000, 0b010, 0b000], // :
+    [0b000, 0b000, 0b111, 0b000, 0b000], // -
+    [0b000, 0b000, 0b000, 0b010, 0b100], // ,
+    [0b110, 0b100, 0b100, 0b100, 0b110], // [
+    [0b011, 0b001, 0b001, 0b001, 0b011], // ]
+    [0b100, 0b010, 0b001, 0b010, 0b100], // >
+    [0b000, 0b000, 0b000, 0b000, 0b111], // _
+];
+
+/// Size of `text` drawn with `pixel` output pixels per font pixel, without
+/// the space after the last glyph
+pub fn text_width(text: &str, pixel: u32) -> u32 {
+    (text.chars().count() as u32 * ADVANCE).saturating_sub(1) * pixel
+}
+
+/// Draw `text` with its top left corner `at`, in upper case letters, digits
+/// and a few signs, other characters are left blank
+pub fn draw_text(
+    canvas: &mut Canvas<Window>,
+    at: Point,
+    pixel: u32,
+    text: &str,
+    color: Color,
+) -> Result<(), String> {
+    canvas.set_draw_color(color);
+
+    for (index, character) in text.chars().enumerate() {
+        let Some(glyph) = CHARACTERS
+            .find(character.to_ascii_uppercase())
+            .map(|index| &GLYPHS[index])
+        else {
+            continue;
+        };
+
+        let x = at.x() + (index as u32 * ADVANCE * pixel) as i32;
+        for (row, line) in glyph.iter().enumerate() {
+            for column in 0..3 {
+                if line & (0b100 >> column) != 0 {
+                    canvas.fill_rect(Rect::new(
+                        x + (column * pixel) as i32,
+                        at.y() + (row as u32 * pixel) as i32,
+                        pixel,
+                        pixel,
+                    ))?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
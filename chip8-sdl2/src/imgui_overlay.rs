@@ -0,0 +1,330 @@
+use chip8::{
+    bus::Bus,
+    cpu::{Cpu, CpuBus, KeyWaitPolicy},
+    disasm::disassemble_at,
+    machine::Machine,
+};
+use std::time::Instant;
+
+use imgui::{
+    Condition, Context, Io, Key, ListClipper, MouseButton, StyleVar, Ui,
+};
+use imgui_glow_renderer::{glow, AutoRenderer};
+use log::error;
+use sdl2::{
+    event::Event,
+    keyboard::{Keycode, Mod},
+    mouse::MouseButton as SdlMouseButton,
+    video::Window,
+    EventPump,
+};
+
+const TOGGLE_KEY: Keycode = Keycode::F1;
+
+const MEMORY_SIZE: usize = 0x1000;
+const MEMORY_ROW: usize = 8;
+
+const KEY_WAIT_POLICIES: [(KeyWaitPolicy, &str); 3] = [
+    (KeyWaitPolicy::Lowest, "Lowest"),
+    (KeyWaitPolicy::MostRecentlyPressed, "Most recently pressed"),
+    (KeyWaitPolicy::FirstReleased, "First released"),
+];
+
+/// Debug windows drawn over the game, shown with F1
+pub struct ImguiOverlay {
+    imgui: Context,
+    renderer: AutoRenderer,
+    last_frame: Instant,
+    visible: bool,
+    memory_goto: u16,
+}
+
+impl ImguiOverlay {
+    /// The window renderer must be the OpenGL one, imgui draws with its
+    /// context
+    pub fn new(window: &Window) -> Self {
+        let gl = unsafe {
+            glow::Context::from_loader_function(|s| {
+                window.subsystem().gl_get_proc_address(s) as _
+            })
+        };
+
+        let mut imgui = Context::create();
+        imgui.set_ini_filename(None);
+        imgui.set_log_filename(None);
+
+        let renderer =
+            AutoRenderer::new(gl, &mut imgui).expect("imgui renderer");
+
+        Self {
+            imgui,
+            renderer,
+            last_frame: Instant::now(),
+            visible: false,
+            memory_goto: 0x200,
+        }
+    }
+
+    /// Returns true when the event is for the overlay and must not reach the
+    /// emulator
+    pub fn handle_event(&mut self, event: &Event) -> bool {
+        if let Event::KeyDown {
+            keycode: Some(TOGGLE_KEY),
+            repeat: false,
+            ..
+        } = event
+        {
+            self.visible = !self.visible;
+            return true;
+        }
+
+        if !self.visible {
+            return false;
+        }
+
+        let io = self.imgui.io_mut();
+        match *event {
+            Event::MouseWheel { x, y, .. } => {
+                io.add_mouse_wheel_event([x as f32, y as f32])
+            }
+            Event::MouseButtonDown { mouse_btn, .. } => {
+                mouse_button_event(io, mouse_btn, true)
+            }
+            Event::MouseButtonUp { mouse_btn, .. } => {
+                mouse_button_event(io, mouse_btn, false)
+            }
+            Event::TextInput { ref text, .. } => {
+                text.chars().for_each(|c| io.add_input_character(c))
+            }
+            Event::KeyDown {
+                keycode: Some(keycode),
+                keymod,
+                ..
+            } => key_event(io, keycode, keymod, true),
+            Event::KeyUp {
+                keycode: Some(keycode),
+                keymod,
+                ..
+            } => key_event(io, keycode, keymod, false),
+            _ => {}
+        }
+
+        // releases always go through, a key can't stay stuck in the game
+        match event {
+            Event::KeyDown { .. } | Event::TextInput { .. } => {
+                self.imgui.io().want_capture_keyboard
+            }
+            _ => false,
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        window: &Window,
+        event_pump: &EventPump,
+        machine: &mut Machine,
+    ) {
+        if !self.visible {
+            return;
+        }
+
+        let io = self.imgui.io_mut();
+        let now = Instant::now();
+        io.update_delta_time(now - self.last_frame);
+        self.last_frame = now;
+
+        let (width, height) = window.size();
+        let (drawable_width, drawable_height) = window.drawable_size();
+        io.display_size = [width as f32, height as f32];
+        io.display_framebuffer_scale = [
+            drawable_width as f32 / width.max(1) as f32,
+            drawable_height as f32 / height.max(1) as f32,
+        ];
+
+        let mouse = event_pump.mouse_state();
+        io.add_mouse_pos_event([mouse.x() as f32, mouse.y() as f32]);
+
+        let ui = self.imgui.new_frame();
+
+        registers_window(ui, machine.cpu(), machine.bus());
+        disassembly_window(ui, machine.cpu(), machine.bus());
+        memory_window(ui, machine.bus_mut(), &mut self.memory_goto);
+        quirks_window(ui, machine.cpu_mut());
+
+        let draw_data = self.imgui.render();
+        if let Err(e) = self.renderer.render(draw_data) {
+            error!("imgui render: {}", e);
+        }
+    }
+}
+
+fn mouse_button_event(io: &mut Io, button: SdlMouseButton, down: bool) {
+    let button = match button {
+        SdlMouseButton::Left => MouseButton::Left,
+        SdlMouseButton::Right => MouseButton::Right,
+        SdlMouseButton::Middle => MouseButton::Middle,
+        _ => return,
+    };
+
+    io.add_mouse_button_event(button, down);
+}
+
+/// Only the keys needed to edit the fields are forwarded
+fn key_event(io: &mut Io, keycode: Keycode, keymod: Mod, down: bool) {
+    io.add_key_event(
+        Key::ModCtrl,
+        keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+    );
+    io.add_key_event(
+        Key::ModShift,
+        keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+    );
+
+    let key = match keycode {
+        Keycode::Tab => Key::Tab,
+        Keycode::Left => Key::LeftArrow,
+        Keycode::Right => Key::RightArrow,
+        Keycode::Up => Key::UpArrow,
+        Keycode::Down => Key::DownArrow,
+        Keycode::Home => Key::Home,
+        Keycode::End => Key::End,
+        Keycode::Delete => Key::Delete,
+        Keycode::Backspace => Key::Backspace,
+        Keycode::Return => Key::Enter,
+        Keycode::KpEnter => Key::KeypadEnter,
+        Keycode::Escape => Key::Escape,
+        Keycode::A => Key::A,
+        Keycode::C => Key::C,
+        Keycode::V => Key::V,
+        Keycode::X => Key::X,
+        _ => return,
+    };
+
+    io.add_key_event(key, down);
+}
+
+fn registers_window(ui: &Ui, cpu: &Cpu, bus: &Bus) {
+    ui.window("Registers")
+        .position([4.0, 4.0], Condition::FirstUseEver)
+        .size([130.0, 240.0], Condition::FirstUseEver)
+        .build(|| {
+            let v = cpu.registers();
+
+            ui.text(format!("PC {:03X}  I {:03X}", cpu.pc(), cpu.index()));
+            for x in 0..8 {
+                ui.text(format!(
+                    "V{:X} {:02X}   V{:X} {:02X}",
+                    x,
+                    v[x],
+                    x + 8,
+                    v[x + 8]
+                ));
+            }
+            ui.text(format!("DT {:02X}   ST {:02X}", bus.delay, bus.beep));
+            if let Some(x) = cpu.key_await() {
+                ui.text(format!("waiting key in V{:X}", x));
+            }
+
+            ui.separator();
+            ui.text("Stack");
+            for addr in cpu.call_stack().iter().rev() {
+                ui.text(format!("{:03X}", addr));
+            }
+        });
+}
+
+fn disassembly_window(ui: &Ui, cpu: &Cpu, bus: &Bus) {
+    ui.window("Disassembly")
+        .position([138.0, 4.0], Condition::FirstUseEver)
+        .size([180.0, 240.0], Condition::FirstUseEver)
+        .build(|| {
+            let pc = cpu.pc();
+            let start = pc.saturating_sub(8);
+
+            for addr in (start..start + 40).step_by(2) {
+                let (opcode, text) = disassemble_at(bus.memory(), addr);
+                let line = format!("{:03X}  {:04X}  {}", addr, opcode, text);
+
+                match addr == pc {
+                    true => ui.text_colored([1.0, 0.8, 0.2, 1.0], line),
+                    false => ui.text(line),
+                }
+            }
+        });
+}
+
+fn memory_window(ui: &Ui, bus: &mut Bus, goto: &mut u16) {
+    ui.window("Memory")
+        .position([322.0, 4.0], Condition::FirstUseEver)
+        .size([186.0, 170.0], Condition::FirstUseEver)
+        .build(|| {
+            ui.set_next_item_width(40.0);
+            let jump = ui
+                .input_scalar("Go to", goto)
+                .display_format("%03X")
+                .chars_hexadecimal(true)
+                .enter_returns_true(true)
+                .build();
+            *goto %= MEMORY_SIZE as u16;
+
+            ui.child_window("bytes").build(|| {
+                let _padding =
+                    ui.push_style_var(StyleVar::FramePadding([1.0, 0.0]));
+                let _spacing =
+                    ui.push_style_var(StyleVar::ItemSpacing([2.0, 1.0]));
+
+                if jump {
+                    let row = *goto as usize / MEMORY_ROW;
+                    ui.set_scroll_y(
+                        row as f32 * ui.frame_height_with_spacing(),
+                    );
+                }
+
+                let clipper =
+                    ListClipper::new((MEMORY_SIZE / MEMORY_ROW) as i32)
+                        .begin(ui);
+                for row in clipper.iter() {
+                    let row_addr = row as usize * MEMORY_ROW;
+                    ui.text(format!("{:03X}", row_addr));
+
+                    for addr in row_addr..row_addr + MEMORY_ROW {
+                        let _id = ui.push_id_usize(addr);
+                        let mut byte = bus.read_byte(addr as u16);
+
+                        ui.same_line();
+                        ui.set_next_item_width(16.0);
+                        let changed = ui
+                            .input_scalar("##byte", &mut byte)
+                            .display_format("%02X")
+                            .chars_hexadecimal(true)
+                            .build();
+                        if changed {
+                            bus.write_byte(addr as u16, byte);
+                        }
+                    }
+                }
+            });
+        });
+}
+
+fn quirks_window(ui: &Ui, cpu: &mut Cpu) {
+    ui.window("Quirks")
+        .position([322.0, 178.0], Condition::FirstUseEver)
+        .size([186.0, 94.0], Condition::FirstUseEver)
+        .build(|| {
+            let mut current = KEY_WAIT_POLICIES
+                .iter()
+                .position(|&(policy, _)| policy == cpu.key_wait_policy())
+                .unwrap_or(0);
+            let names = KEY_WAIT_POLICIES.map(|(_, name)| name);
+
+            if ui.combo_simple_string("FX0A key", &mut current, &names) {
+                cpu.set_key_wait_policy(KEY_WAIT_POLICIES[current].0);
+            }
+
+            let mut quirks = cpu.quirks();
+            if ui.checkbox("Display wait", &mut quirks.display_wait) {
+                cpu.set_quirks(quirks);
+            }
+        });
+}
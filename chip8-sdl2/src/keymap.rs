@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use chip8::{bus::KEYPAD_SIZE, keypad::Keypad};
+use chip8_frontend::KEYPAD_LAYOUT;
+use log::warn;
+use sdl2::keyboard::Scancode;
+
+/// Names of the keyboard keys, like `KeyboardEvent.code`, with their
+/// scancode, the position on the keyboard doesn't depend on the layout
+const SCANCODES: [(&str, Scancode); 51] = [
+    ("Digit1", Scancode::Num1),
+    ("Digit2", Scancode::Num2),
+    ("Digit3", Scancode::Num3),
+    ("Digit4", Scancode::Num4),
+    ("Digit5", Scancode::Num5),
+    ("Digit6", Scancode::Num6),
+    ("Digit7", Scancode::Num7),
+    ("Digit8", Scancode::Num8),
+    ("Digit9", Scancode::Num9),
+    ("Digit0", Scancode::Num0),
+    ("KeyQ", Scancode::Q),
+    ("KeyW", Scancode::W),
+    ("KeyE", Scancode::E),
+    ("KeyR", Scancode::R),
+    ("KeyT", Scancode::T),
+    ("KeyY", Scancode::Y),
+    ("KeyU", Scancode::U),
+    ("KeyI", Scancode::I),
+    ("KeyO", Scancode::O),
+    ("KeyP", Scancode::P),
+    ("KeyA", Scancode::A),
+    ("KeyS", Scancode::S),
+    ("KeyD", Scancode::D),
+    ("KeyF", Scancode::F),
+    ("KeyG", Scancode::G),
+    ("KeyH", Scancode::H),
+    ("KeyJ", Scancode::J),
+    ("KeyK", Scancode::K),
+    ("KeyL", Scancode::L),
+    ("KeyZ", Scancode::Z),
+    ("KeyX", Scancode::X),
+    ("KeyC", Scancode::C),
+    ("KeyV", Scancode::V),
+    ("KeyB", Scancode::B),
+    ("KeyN", Scancode::N),
+    ("KeyM", Scancode::M),
+    ("Space", Scancode::Space),
+    ("Numpad7", Scancode::Kp7),
+    ("Numpad8", Scancode::Kp8),
+    ("Numpad9", Scancode::Kp9),
+    ("Numpad4", Scancode::Kp4),
+    ("Numpad5", Scancode::Kp5),
+    ("Numpad6", Scancode::Kp6),
+    ("Numpad1", Scancode::Kp1),
+    ("Numpad2", Scancode::Kp2),
+    ("Numpad3", Scancode::Kp3),
+    ("Numpad0", Scancode::Kp0),
+    ("ArrowUp", Scancode::Up),
+    ("ArrowLeft", Scancode::Left),
+    ("ArrowRight", Scancode::Right),
+    ("ArrowDown", Scancode::Down),
+];
+
+pub fn scancode(name: &str) -> Option<Scancode> {
+    SCANCODES
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|&(_, scancode)| scancode)
+}
+
+/// The keypad key of each keyboard key named in `keymap`, the unknown names
+/// are left out
+pub fn key_map(keymap: &[String; KEYPAD_SIZE]) -> HashMap<Scancode, Keypad> {
+    KEYPAD_LAYOUT
+        .iter()
+        .filter_map(|&key| {
+            let name = &keymap[key as usize];
+            let scancode = scancode(name);
+            if scancode.is_none() {
+                warn!("unknown key {}", name);
+            }
+            scancode.map(|scancode| (scancode, key))
+        })
+        .collect()
+}
@@ -0,0 +1,15 @@
+#[cfg(target_os = "android")]
+mod android;
+pub mod compare;
+mod debug_text;
+mod font;
+#[cfg(feature = "imgui")]
+mod imgui_overlay;
+mod keymap;
+mod osd;
+mod pause_menu;
+#[cfg(feature = "rhai")]
+mod script_hud;
+pub mod sdl2_frontend;
+mod timing_text;
+mod touch_keypad;
@@ -1,11 +1,17 @@
-mod sdl2_frontend;
-
-use chip8::{beep::Beeper, bus::Bus, cpu::Cpu, delay::Delay, rom::Rom};
+use chip8::{cpu::KeyWaitPolicy, machine::Machine, rom::Rom};
+#[cfg(feature = "rhai")]
+use chip8_frontend::rhai_script::RhaiScript;
+use chip8_frontend::{
+    kiosk::{Kiosk, Playlist},
+    slots::Slots,
+};
+use chip8_sdl2::{
+    compare::{self, Comparison},
+    sdl2_frontend::SDL2Frontend,
+};
 use log::debug;
 
-use std::env;
-
-use crate::sdl2_frontend::SDL2Frontend;
+use std::{env, fs, path::Path};
 
 fn main() {
     dotenv::dotenv().ok();
@@ -13,19 +19,69 @@ fn main() {
 
     let args: Vec<String> = env::args().collect();
 
+    debug!("start");
+
+    // chip8-sdl2 --kiosk PLAYLIST
+    if let [_, option, playlist_path] = args.as_slice() {
+        if option == "--kiosk" {
+            let playlist = Playlist::load(Path::new(playlist_path))
+                .expect("Failed to read playlist");
+
+            debug!("playlist: {} roms", playlist.entries().len());
+
+            let kiosk = Kiosk::new(playlist);
+            let mut frontend = SDL2Frontend::new(kiosk.start());
+            frontend.set_kiosk(kiosk);
+            frontend.run();
+
+            return;
+        }
+    }
+
+    // chip8-sdl2 --compare ROM [POLICY POLICY]
+    if let [_, option, rom_path, policies @ ..] = args.as_slice() {
+        if option == "--compare" {
+            let policies = match policies {
+                [] => [KeyWaitPolicy::Lowest, KeyWaitPolicy::FirstReleased],
+                [first, second] => [first, second].map(|name| {
+                    compare::parse_policy(name).expect("Unknown key policy")
+                }),
+                _ => panic!("--compare takes a rom and two key policies"),
+            };
+
+            let data = fs::read(rom_path).expect("Failed to read rom file");
+            compare::run(Comparison::new(Rom::from_bytes(data), policies));
+
+            return;
+        }
+    }
+
     let rom_path_default = String::from("roms/slipperyslope.ch8");
     let rom_path = args.get(1).unwrap_or(&rom_path_default);
 
-    debug!("start");
+    // chip8-sdl2 ROM --rhai SCRIPT
+    #[cfg(feature = "rhai")]
+    let rhai = match args.as_slice() {
+        [_, _, option, path] if option == "--rhai" => {
+            Some(RhaiScript::new_from(path).expect("Failed to load script"))
+        }
+        _ => None,
+    };
 
-    let rom = Rom::new_from(rom_path).expect("Failed to read rom file");
+    let data = fs::read(rom_path).expect("Failed to read rom file");
+    let slots = Slots::for_rom(&data);
+    let rom = Rom::from_bytes(data);
 
     debug!("loaded: {}", rom);
 
-    let cpu = Cpu::new();
-    let delay = Delay::new();
-    let beep = Beeper::new();
-    let bus = Bus::new(rom);
-
-    SDL2Frontend::new(cpu, delay, beep, bus).run();
+    let mut frontend = SDL2Frontend::new(Machine::new(rom));
+    frontend.set_rom_path(Path::new(rom_path));
+    if let Some(slots) = slots {
+        frontend.set_slots(slots);
+    }
+    #[cfg(feature = "rhai")]
+    if let Some(script) = rhai {
+        frontend.set_rhai(script);
+    }
+    frontend.run();
 }
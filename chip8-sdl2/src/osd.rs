@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::Canvas,
+    video::Window,
+};
+
+use crate::font;
+
+/// How long a message stays on the screen
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+
+/// Short messages drawn over the screen for a moment
+pub struct Osd {
+    message: Option<(String, Instant)>,
+}
+
+impl Osd {
+    pub fn new() -> Self {
+        Self { message: None }
+    }
+
+    /// Show `message` in place of the current one
+    pub fn show(&mut self, message: impl Into<String>) {
+        self.message = Some((message.into(), Instant::now()));
+    }
+
+    /// Forget the message once its time is over, returns true when it was
+    /// removed and the screen must be drawn again
+    pub fn update(&mut self) -> bool {
+        let expired = self
+            .message
+            .as_ref()
+            .is_some_and(|(_, shown)| shown.elapsed() >= MESSAGE_DURATION);
+        if expired {
+            self.message = None;
+        }
+
+        expired
+    }
+
+    /// Draw the message with its top left corner `at`, on a box of
+    /// `background`
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        at: Point,
+        pixel: u32,
+        foreground: Color,
+        background: Color,
+    ) -> Result<(), String> {
+        let Some((message, _)) = &self.message else {
+            return Ok(());
+        };
+
+        // the box has a 1 pixel margin
+        let width = font::text_width(message, pixel) + 2 * pixel;
+        let height = (font::LINE_HEIGHT + 1) * pixel;
+        canvas.set_draw_color(background);
+        canvas.fill_rect(Rect::new(at.x(), at.y(), width, height))?;
+
+        font::draw_text(
+            canvas,
+            at.offset(pixel as i32, pixel as i32),
+            pixel,
+            message,
+            foreground,
+        )
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::Canvas,
+    video::Window,
+};
+
+use crate::font;
+
+/// Entries of the rom list shown at once
+const VISIBLE_ROMS: usize = 8;
+/// Extensions of the files listed by Open Rom
+const ROM_EXTENSIONS: [&str; 2] = ["ch8", "c8"];
+
+const MAIN_ITEMS: [&str; 5] =
+    ["Resume", "Reset", "Open Rom", "Settings", "Quit"];
+
+/// Moves in the menu, from the keyboard or a controller
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+}
+
+/// What the frontend has to do after an input
+#[derive(Clone, Debug, PartialEq)]
+pub enum MenuAction {
+    Resume,
+    Reset,
+    Open(PathBuf),
+    ToggleFullscreen,
+    ToggleSound,
+    /// Change the cpu speed by a number of steps
+    Speed(i32),
+    Quit,
+}
+
+/// Settings shown in the menu, owned by the frontend
+pub struct MenuSettings {
+    pub fullscreen: bool,
+    pub sound: bool,
+    /// CPU frequency, in Hz
+    pub speed: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Page {
+    Main,
+    Roms,
+    Settings,
+}
+
+/// Menu drawn over the paused game, so that the frontend can be used with
+/// a controller only
+pub struct PauseMenu {
+    page: Page,
+    selected: usize,
+    /// Files of the rom directory, read when the rom list is opened
+    roms: Vec<PathBuf>,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        Self {
+            page: Page::Main,
+            selected: 0,
+            roms: vec![],
+        }
+    }
+
+    /// Handle `input`, the roms are listed from `rom_dir`
+    pub fn input(
+        &mut self,
+        input: MenuInput,
+        rom_dir: &Path,
+    ) -> Option<MenuAction> {
+        let count = match self.page {
+            Page::Main => MAIN_ITEMS.len(),
+            Page::Roms => self.roms.len(),
+            Page::Settings => 3,
+        };
+
+        match (self.page, input) {
+            (_, MenuInput::Up) if count > 0 => {
+                self.selected = (self.selected + count - 1) % count;
+                None
+            }
+            (_, MenuInput::Down) if count > 0 => {
+                self.selected = (self.selected + 1) % count;
+                None
+            }
+
+            (Page::Main, MenuInput::Back) => Some(MenuAction::Resume),
+            (Page::Main, MenuInput::Select) => match self.selected {
+                0 => Some(MenuAction::Resume),
+                1 => Some(MenuAction::Reset),
+                2 => {
+                    self.roms = list_roms(rom_dir);
+                    self.open(Page::Roms);
+                    None
+                }
+                3 => {
+                    self.open(Page::Settings);
+                    None
+                }
+                _ => Some(MenuAction::Quit),
+            },
+
+            (Page::Roms, MenuInput::Select) => {
+                self.roms.get(self.selected).cloned().map(MenuAction::Open)
+            }
+
+            (Page::Settings, MenuInput::Select) => match self.selected {
+                0 => Some(MenuAction::ToggleFullscreen),
+                1 => Some(MenuAction::ToggleSound),
+                _ => None,
+            },
+            (Page::Settings, MenuInput::Left | MenuInput::Right) => {
+                let steps = match input {
+                    MenuInput::Left => -1,
+                    _ => 1,
+                };
+                match self.selected {
+                    0 => Some(MenuAction::ToggleFullscreen),
+                    1 => Some(MenuAction::ToggleSound),
+                    _ => Some(MenuAction::Speed(steps)),
+                }
+            }
+
+            (Page::Roms | Page::Settings, MenuInput::Back) => {
+                // on the item of the page left
+                let item = match self.page {
+                    Page::Roms => 2,
+                    _ => 3,
+                };
+                self.open(Page::Main);
+                self.selected = item;
+                None
+            }
+
+            _ => None,
+        }
+    }
+
+    fn open(&mut self, page: Page) {
+        self.page = page;
+        self.selected = 0;
+    }
+
+    /// Items of the current page, the selected one is at the returned index
+    fn items(&self, settings: &MenuSettings) -> (Vec<String>, usize) {
+        let on_off = |on| match on {
+            true => "On",
+            false => "Off",
+        };
+
+        match self.page {
+            Page::Main => (
+                MAIN_ITEMS.iter().map(|item| item.to_string()).collect(),
+                self.selected,
+            ),
+            Page::Settings => (
+                vec![
+                    format!("Fullscreen: {}", on_off(settings.fullscreen)),
+                    format!("Sound: {}", on_off(settings.sound)),
+                    format!("Speed: {} Hz", settings.speed.round()),
+                ],
+                self.selected,
+            ),
+            Page::Roms if self.roms.is_empty() => {
+                (vec!["No roms".to_string()], usize::MAX)
+            }
+            // a window of the list, around the selected rom
+            Page::Roms => {
+                let first = self
+                    .selected
+                    .saturating_sub(VISIBLE_ROMS / 2)
+                    .min(self.roms.len().saturating_sub(VISIBLE_ROMS));
+                let names = self.roms[first..]
+                    .iter()
+                    .take(VISIBLE_ROMS)
+                    .map(|path| {
+                        path.file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_default()
+                    })
+                    .collect();
+                (names, self.selected - first)
+            }
+        }
+    }
+
+    /// Draw the menu in the middle of `area`, on a box of `background`
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        area: Rect,
+        pixel: u32,
+        settings: &MenuSettings,
+        foreground: Color,
+        background: Color,
+    ) -> Result<(), String> {
+        let (items, selected) = self.items(settings);
+        // the items are indented by the marker of the selected one
+        let lines: Vec<String> = items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| match index == selected {
+                true => format!("> {}", item),
+                false => format!("  {}", item),
+            })
+            .collect();
+
+        let width = lines
+            .iter()
+            .map(|line| font::text_width(line, pixel))
+            .max()
+            .unwrap_or(0)
+            + 4 * pixel;
+        let height = (lines.len() as u32 * font::LINE_HEIGHT + 3) * pixel;
+        let at = area
+            .center()
+            .offset(-(width as i32) / 2, -(height as i32) / 2);
+        canvas.set_draw_color(foreground);
+        canvas.fill_rect(Rect::new(at.x(), at.y(), width, height))?;
+        // a border of 1 pixel
+        canvas.set_draw_color(background);
+        canvas.fill_rect(Rect::new(
+            at.x() + pixel as i32,
+            at.y() + pixel as i32,
+            width - 2 * pixel,
+            height - 2 * pixel,
+        ))?;
+
+        for (index, line) in lines.iter().enumerate() {
+            let y = (index as u32 * font::LINE_HEIGHT + 2) * pixel;
+            font::draw_text(
+                canvas,
+                Point::new(at.x() + 2 * pixel as i32, at.y() + y as i32),
+                pixel,
+                line,
+                foreground,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Roms of `dir` by name, none when it can't be read
+fn list_roms(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut roms: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|extension| {
+                ROM_EXTENSIONS
+                    .iter()
+                    .any(|known| extension.eq_ignore_ascii_case(known))
+            })
+        })
+        .collect();
+    roms.sort();
+
+    roms
+}
@@ -0,0 +1,74 @@
+use std::sync::mpsc::Receiver;
+
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::Canvas,
+    video::Window,
+};
+
+use crate::font;
+
+/// Lines given to `hud` by the rhai script, drawn over the bottom of the
+/// screen
+pub struct ScriptHud {
+    receiver: Receiver<Vec<String>>,
+    lines: Vec<String>,
+}
+
+impl ScriptHud {
+    /// Show the lines sent by the emulation thread after each frame
+    pub fn new(receiver: Receiver<Vec<String>>) -> Self {
+        Self {
+            receiver,
+            lines: vec![],
+        }
+    }
+
+    /// Keep the lines of the last frame run
+    pub fn update(&mut self) {
+        if let Some(lines) = self.receiver.try_iter().last() {
+            self.lines = lines;
+        }
+    }
+
+    /// Draw the lines in the bottom left corner of `area`, on a box of
+    /// `background`
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        area: Rect,
+        pixel: u32,
+        foreground: Color,
+        background: Color,
+    ) -> Result<(), String> {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+
+        let width = self
+            .lines
+            .iter()
+            .map(|line| font::text_width(line, pixel))
+            .max()
+            .unwrap_or(0)
+            + 2 * pixel;
+        let height = (self.lines.len() as u32 * font::LINE_HEIGHT + 1) * pixel;
+        let y = area.bottom() - height as i32;
+        canvas.set_draw_color(background);
+        canvas.fill_rect(Rect::new(area.x(), y, width, height))?;
+
+        for (index, line) in self.lines.iter().enumerate() {
+            let top = (index as u32 * font::LINE_HEIGHT + 1) * pixel;
+            font::draw_text(
+                canvas,
+                Point::new(area.x() + pixel as i32, y + top as i32),
+                pixel,
+                line,
+                foreground,
+            )?;
+        }
+
+        Ok(())
+    }
+}
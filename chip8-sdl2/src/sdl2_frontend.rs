@@ -1,187 +1,987 @@
 use std::{
     collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
     thread::sleep,
     time::{Duration, Instant},
 };
 
 use chip8::{
-    beep::Beeper,
-    bus::{Bus, DISPLAY_HEIGHT, DISPLAY_WIDTH},
-    cpu::Cpu,
-    delay::Delay,
+    bus::{Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH, PLANES},
     keypad::Keypad,
+    machine::{Machine, FRAME_RATE},
+    rom::Rom,
 };
+use chip8_frontend::{
+    config::Config,
+    emulator_thread::{Command, EmulatorThread},
+    kiosk::Kiosk,
+    slots::{SlotError, Slots},
+};
+use log::{debug, info, warn};
 use sdl2::{
     audio::{AudioCallback, AudioDevice, AudioSpecDesired, AudioStatus},
-    event::Event,
-    keyboard::Keycode,
+    controller::{Button, GameController},
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Mod, Scancode},
     pixels::Color,
-    rect::Rect,
+    rect::{Point, Rect},
     render::Canvas,
-    video::Window,
-    EventPump,
+    video::{FullscreenType, Window},
+    AudioSubsystem, EventPump, GameControllerSubsystem,
+};
+
+#[cfg(feature = "rhai")]
+use chip8_frontend::rhai_script::RhaiScript;
+
+#[cfg(feature = "imgui")]
+use crate::imgui_overlay::ImguiOverlay;
+#[cfg(feature = "rhai")]
+use crate::script_hud::ScriptHud;
+use crate::{
+    debug_text::DebugText,
+    keymap,
+    osd::Osd,
+    pause_menu::{MenuAction, MenuInput, MenuSettings, PauseMenu},
+    timing_text::TimingText,
+    touch_keypad::TouchKeypad,
 };
 
 const FOREGROUND: Color = Color::RGB(69, 115, 13);
 const BACKGROUND: Color = Color::RGB(124, 209, 21);
+/// Color of each pixel value, the bit of each XO-CHIP plane set
+const PLANE_COLORS: [Color; 1 << PLANES] = [
+    BACKGROUND,
+    FOREGROUND,
+    Color::RGB(190, 235, 110),
+    Color::RGB(34, 58, 6),
+];
+/// Change of the cpu speed by the speed hotkeys, and its range, in Hz
+const SPEED_STEP: f64 = 100.0;
+const MIN_SPEED: f64 = 100.0;
+const MAX_SPEED: f64 = 2000.0;
+/// Frames run in the time of one with the turbo on
+const TURBO_SPEED: f64 = 4.0;
+/// Period over which the rates of the title are measured
+const STATS_PERIOD: Duration = Duration::from_secs(1);
 
 pub struct SDL2Frontend {
     // chip8
-    cpu: Cpu,
-    delay: Delay,
-    beeper: Beeper,
-    bus: Bus,
+    /// The machine runs on its own thread, the drawing and the events
+    /// don't slow it down
+    emulator: EmulatorThread,
+    config: Config,
+    state_file: Option<PathBuf>,
+    /// Save states of the rom, none in kiosk mode or without a data
+    /// directory
+    slots: Option<Slots>,
+    /// Names of the entries as the kiosk starts them, none out of kiosk
+    /// mode
+    kiosk_titles: Option<Receiver<String>>,
+    /// Name of the rom in the title
+    rom_name: Option<String>,
+    /// Where the pause menu lists the roms to open
+    rom_dir: PathBuf,
     // sdl
     canvas: Canvas<Window>,
+    audio_subsystem: AudioSubsystem,
     audio_device: AudioDevice<SquareWave>,
     event_pump: EventPump,
+    controller_subsystem: GameControllerSubsystem,
+    /// The controllers are opened as they are plugged
+    controllers: Vec<GameController>,
+    /// Keypad key of each keyboard key, by position on the keyboard
+    key_map: HashMap<Scancode, Keypad>,
+    touch_keypad: Option<TouchKeypad>,
+    /// The screen of the machine, kept from frame to frame
+    frame: Framebuffer,
+    osd: Osd,
+    debug_text: DebugText,
+    timing_text: TimingText,
+    /// Shown while the game is paused by Escape or the Start button
+    pause_menu: Option<PauseMenu>,
+    /// None on the software renderer, which has no OpenGL context
+    #[cfg(feature = "imgui")]
+    overlay: Option<ImguiOverlay>,
+    /// Text of the rhai script, none without a script
+    #[cfg(feature = "rhai")]
+    script_hud: Option<ScriptHud>,
     // loop
     running: bool,
+    paused: bool,
+    turbo: bool,
+    // stats
+    stats_time: Instant,
+    /// Frames of the emulator at `stats_time`
+    stats_frames: u64,
+    fps: f64,
+    /// The app is in background on mobile, nothing runs until it's back
+    background: bool,
 }
 
 impl SDL2Frontend {
-    pub fn new(cpu: Cpu, delay: Delay, beeper: Beeper, bus: Bus) -> Self {
+    pub fn new(mut machine: Machine) -> Self {
         let sdl = sdl2::init().expect("SDL2 Init");
 
-        let canvas = SDL2Frontend::create_canvas(&sdl);
-        let audio_device = SDL2Frontend::create_audio(&sdl);
+        // the overlay draws with OpenGL on the renderer context
+        #[cfg(feature = "imgui")]
+        sdl2::hint::set("SDL_RENDER_DRIVER", "opengl");
+
+        let config = Config::load();
+        machine.set_cpu_frequency(config.cpu_frequency);
+        machine.cpu_mut().set_quirks(config.quirks());
+        let (mut canvas, accelerated) =
+            SDL2Frontend::create_canvas(&sdl, config.scale, config.window_size);
+        if config.fullscreen {
+            set_fullscreen(canvas.window_mut(), true);
+        }
+        let audio_subsystem = sdl.audio().expect("SDL2: sound");
+        let audio_device = SDL2Frontend::create_audio(&audio_subsystem)
+            .expect("open playback");
         let event_pump = sdl.event_pump().expect("SDL2: EventPump");
+        let controller_subsystem =
+            sdl.game_controller().expect("SDL2: game controller");
+        #[cfg(feature = "imgui")]
+        let overlay = accelerated.then(|| ImguiOverlay::new(canvas.window()));
+        #[cfg(not(feature = "imgui"))]
+        let _ = accelerated;
+
+        let key_map = keymap::key_map(&config.keymap);
 
         Self {
             // chip8
-            cpu,
-            delay,
-            beeper,
-            bus,
+            emulator: EmulatorThread::spawn(machine),
+            config,
+            state_file: None,
+            slots: None,
+            kiosk_titles: None,
+            rom_name: None,
+            rom_dir: PathBuf::from("roms"),
             // sdl
             canvas,
+            audio_subsystem,
             audio_device,
             event_pump,
+            controller_subsystem,
+            controllers: vec![],
+            key_map,
+            touch_keypad: None,
+            frame: Framebuffer::default(),
+            osd: Osd::new(),
+            debug_text: DebugText::new(),
+            timing_text: TimingText::new(),
+            pause_menu: None,
+            #[cfg(feature = "imgui")]
+            overlay,
+            #[cfg(feature = "rhai")]
+            script_hud: None,
             // loop
             running: true,
+            paused: false,
+            turbo: false,
+            stats_time: Instant::now(),
+            stats_frames: 0,
+            fps: 0.0,
+            background: false,
         }
     }
 
-    pub fn run(&mut self) {
-        let mut loop_time = Instant::now();
-        let mut cpu_cycles = 0.0;
-        let mut video_frames = 0.0;
-        let mut delay_update = 0.0;
-        let mut beep_update = 0.0;
-        let mut delta: f64;
-
-        let mut key_map = HashMap::new();
-        key_map.insert(Keycode::Num1, Keypad::Key1);
-        key_map.insert(Keycode::Num2, Keypad::Key2);
-        key_map.insert(Keycode::Num3, Keypad::Key3);
-        key_map.insert(Keycode::Num4, Keypad::KeyC);
-        key_map.insert(Keycode::A, Keypad::Key4);
-        key_map.insert(Keycode::Z, Keypad::Key5);
-        key_map.insert(Keycode::E, Keypad::Key6);
-        key_map.insert(Keycode::R, Keypad::KeyD);
-        key_map.insert(Keycode::Q, Keypad::Key7);
-        key_map.insert(Keycode::S, Keypad::Key8);
-        key_map.insert(Keycode::D, Keypad::Key9);
-        key_map.insert(Keycode::F, Keypad::KeyE);
-        key_map.insert(Keycode::W, Keypad::KeyA);
-        key_map.insert(Keycode::X, Keypad::Key0);
-        key_map.insert(Keycode::C, Keypad::KeyB);
-        key_map.insert(Keycode::V, Keypad::KeyF);
-
-        'running: loop {
-            self.read_events(&key_map);
-
-            if !self.running {
-                break 'running;
-            }
+    /// Draw a keypad next to the screen for touch screens
+    pub fn set_touch_keypad(&mut self, enabled: bool) {
+        self.touch_keypad = enabled.then(TouchKeypad::new);
+
+        // the layout follows the shape of the window instead
+        let (width, height) = match enabled {
+            true => (0, 0),
+            false => logical_size(self.config.scale),
+        };
+        if let Err(e) = self.canvas.set_logical_size(width, height) {
+            warn!("logical size: {}", e);
+        }
+    }
 
-            delta = loop_time.elapsed().as_secs_f64();
+    /// Restore the state saved in `path`, if any, and save it back when the
+    /// app goes in background or quits
+    pub fn set_state_file(&mut self, path: PathBuf) {
+        match fs::read(&path) {
+            Ok(data) => match self.emulator.machine().load_state(&data) {
+                Ok(()) => info!("state restored from {}", path.display()),
+                Err(e) => warn!("{}: {}", path.display(), e),
+            },
+            Err(e) => info!("no state restored, {}: {}", path.display(), e),
+        }
 
-            cpu_cycles += delta / 0.002; // 500Hz
-            while cpu_cycles >= 1.0 {
-                cpu_cycles -= 1.0;
-                self.cpu.emulate(&mut self.bus);
-            }
+        self.state_file = Some(path);
+    }
 
-            video_frames += delta / 0.02; // 50Hz
-            while video_frames >= 1.0 {
-                video_frames -= 1.0;
-                self.update_canvas();
+    /// Save and load the states in `slots` with F5 and F7, F6 and Shift+F6
+    /// select the slot
+    pub fn set_slots(&mut self, slots: Slots) {
+        self.slots = Some(slots);
+    }
+
+    /// The rom was read from `path`: it is named in the title, and the
+    /// pause menu lists the roms of its directory instead of `roms`
+    pub fn set_rom_path(&mut self, path: &Path) {
+        if let Some(name) = path.file_name() {
+            self.set_rom_name(&name.to_string_lossy());
+        }
+        self.rom_dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+    }
+
+    /// Play the kiosk playlist fullscreen instead of the machine rom
+    pub fn set_kiosk(&mut self, mut kiosk: Kiosk) {
+        self.set_rom_name(&kiosk.current().name);
+
+        let mut machine = kiosk.start();
+        machine.set_cpu_frequency(self.config.cpu_frequency);
+        machine.cpu_mut().set_quirks(self.config.quirks());
+        let (titles, receiver) = mpsc::channel();
+        self.emulator = EmulatorThread::spawn_with(machine, move |machine| {
+            if kiosk.run_frame(machine) {
+                titles.send(kiosk.current().name.clone()).ok();
             }
+        });
+        self.kiosk_titles = Some(receiver);
 
-            delay_update += delta / 0.0166666666667; // 60 Hz
-            while delay_update >= 1.0 {
-                delay_update -= 1.0;
+        set_fullscreen(self.canvas.window_mut(), true);
+    }
 
-                self.delay.update(&mut self.bus);
+    /// Run `script` along the rom, until another rom is opened
+    ///
+    /// The rom goes on without the script after its first error.
+    #[cfg(feature = "rhai")]
+    pub fn set_rhai(&mut self, script: RhaiScript) {
+        let machine = self.emulator.machine().clone();
+        let (lines, receiver) = mpsc::channel();
+        let mut script = Some(script);
+        self.emulator = EmulatorThread::spawn_with(machine, move |machine| {
+            let Some(running) = &mut script else {
+                machine.run_frame();
+                return;
+            };
+            let result = running.run_frame(machine);
+            for line in running.take_output() {
+                info!("rhai: {}", line);
             }
+            match result {
+                Ok(()) => {
+                    lines.send(running.hud().to_vec()).ok();
+                }
+                Err(e) => {
+                    warn!("rhai script stopped, {}", e);
+                    lines.send(vec![]).ok();
+                    script = None;
+                    machine.run_frame();
+                }
+            }
+        });
+        self.script_hud = Some(ScriptHud::new(receiver));
+    }
 
-            beep_update += delta / 0.0166666666667; // 60 Hz
-            while beep_update >= 1.0 {
-                beep_update -= 1.0;
+    pub fn run(&mut self) {
+        let mut frames = self.emulator.frames();
+        let window_size = self.config.window_size;
+
+        while self.running {
+            self.read_events();
 
-                self.beeper.update(&mut self.bus);
+            let title = self
+                .kiosk_titles
+                .as_ref()
+                .and_then(|titles| titles.try_iter().last());
+            if let Some(title) = title {
+                self.set_rom_name(&title);
+            }
+            self.update_stats();
+            #[cfg(feature = "rhai")]
+            if let Some(hud) = &mut self.script_hud {
+                hud.update();
+            }
 
+            // frames run by the emulation thread since the last drawing
+            if self.emulator.frames() != frames && !self.background {
+                frames = self.emulator.frames();
                 self.update_audio();
+                self.update_canvas();
             }
 
-            loop_time = Instant::now();
+            // also while paused
+            if self.osd.update() {
+                self.update_canvas();
+            }
 
-            sleep(Duration::from_millis(10));
+            sleep(Duration::from_millis(5));
         }
+
+        // once, not at each step of a resize
+        if self.config.window_size != window_size {
+            self.save_config();
+        }
+        self.save_state();
     }
 
-    fn read_events(&mut self, keymap: &HashMap<Keycode, Keypad>) {
-        for event in self.event_pump.poll_iter() {
+    fn read_events(&mut self) {
+        let (width, height) = self.canvas.output_size().unwrap_or((1, 1));
+
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+        for event in events {
+            #[cfg(feature = "imgui")]
+            if let Some(overlay) = &mut self.overlay {
+                if overlay.handle_event(&event) {
+                    continue;
+                }
+            }
+
             match event {
                 Event::Quit { .. }
                 | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
+                    keycode: Some(Keycode::AcBack),
                     ..
                 } => self.running = false,
 
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    repeat: false,
+                    ..
+                }
+                | Event::ControllerButtonDown {
+                    button: Button::Start,
+                    ..
+                } => match self.pause_menu {
+                    Some(_) => self.menu_input(MenuInput::Back),
+                    None => self.open_pause_menu(),
+                },
+
+                // the menu takes the inputs while it is open
                 Event::KeyDown {
                     keycode: Some(keycode),
                     ..
+                } if self.pause_menu.is_some() => {
+                    let input = match keycode {
+                        Keycode::Up => Some(MenuInput::Up),
+                        Keycode::Down => Some(MenuInput::Down),
+                        Keycode::Left => Some(MenuInput::Left),
+                        Keycode::Right => Some(MenuInput::Right),
+                        Keycode::Return | Keycode::Space => {
+                            Some(MenuInput::Select)
+                        }
+                        Keycode::Backspace => Some(MenuInput::Back),
+                        _ => None,
+                    };
+                    if let Some(input) = input {
+                        self.menu_input(input);
+                    }
+                }
+                Event::ControllerButtonDown { button, .. }
+                    if self.pause_menu.is_some() =>
+                {
+                    let input = match button {
+                        Button::DPadUp => Some(MenuInput::Up),
+                        Button::DPadDown => Some(MenuInput::Down),
+                        Button::DPadLeft => Some(MenuInput::Left),
+                        Button::DPadRight => Some(MenuInput::Right),
+                        Button::A => Some(MenuInput::Select),
+                        Button::B => Some(MenuInput::Back),
+                        _ => None,
+                    };
+                    if let Some(input) = input {
+                        self.menu_input(input);
+                    }
+                }
+
+                // the default output may have changed, a device opened
+                // without a name follows it only when opened again
+                Event::AudioDeviceAdded {
+                    iscapture: false, ..
+                }
+                | Event::AudioDeviceRemoved {
+                    iscapture: false, ..
+                } => self.reopen_audio(),
+
+                Event::ControllerDeviceAdded { which, .. } => {
+                    match self.controller_subsystem.open(which) {
+                        Ok(controller) => {
+                            info!("controller: {}", controller.name());
+                            self.controllers.push(controller);
+                        }
+                        Err(e) => warn!("controller {}: {}", which, e),
+                    }
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.controllers
+                        .retain(|controller| controller.instance_id() != which);
+                }
+
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    scancode,
+                    keymod,
+                    repeat,
+                    ..
                 } => {
-                    if let Some(key) = keymap.get(&keycode) {
-                        self.bus.keys[(*key as usize)] = true;
+                    if self.hotkey(keycode, keymod, repeat) {
+                        continue;
+                    }
+                    if let Some(&key) =
+                        scancode.and_then(|s| self.key_map.get(&s))
+                    {
+                        self.emulator.machine().set_key(key, true);
                     }
                 }
                 Event::KeyUp {
-                    keycode: Some(keycode),
+                    scancode: Some(scancode),
+                    ..
+                } => {
+                    if let Some(&key) = self.key_map.get(&scancode) {
+                        self.emulator.machine().set_key(key, false);
+                    }
+                }
+
+                // the screen is drawn again in its new place, also while
+                // paused
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
                     ..
+                } => self.update_canvas(),
+                // the size of the window, not of the screen in fullscreen
+                Event::Window {
+                    win_event: WindowEvent::Resized(width, height),
+                    ..
+                } if self.canvas.window().fullscreen_state()
+                    == FullscreenType::Off =>
+                {
+                    self.config.window_size =
+                        Some((width.max(1) as u32, height.max(1) as u32));
+                }
+
+                // touch positions are normalized to the window
+                Event::FingerDown {
+                    finger_id, x, y, ..
                 } => {
-                    if let Some(key) = keymap.get(&keycode) {
-                        self.bus.keys[(*key as usize)] = false;
+                    if let Some(keypad) = &mut self.touch_keypad {
+                        let at = touch_point(x, y, width, height);
+                        let mut machine = self.emulator.machine();
+                        keypad.finger_down(&mut machine, finger_id, at);
                     }
                 }
+                Event::FingerMotion {
+                    finger_id, x, y, ..
+                } => {
+                    if let Some(keypad) = &mut self.touch_keypad {
+                        let at = touch_point(x, y, width, height);
+                        let mut machine = self.emulator.machine();
+                        keypad.finger_motion(&mut machine, finger_id, at);
+                    }
+                }
+                Event::FingerUp { finger_id, .. } => {
+                    if let Some(keypad) = &mut self.touch_keypad {
+                        keypad
+                            .finger_up(&mut self.emulator.machine(), finger_id);
+                    }
+                }
+
+                // mobile lifecycle, the app may be killed while in background
+                Event::AppWillEnterBackground { .. } => {
+                    self.background = true;
+                    self.emulator.send(Command::Pause(true));
+                    self.audio_device.pause();
+                    if let Some(keypad) = &mut self.touch_keypad {
+                        keypad.release_all(&mut self.emulator.machine());
+                    }
+                    self.save_state();
+                }
+                Event::AppDidEnterForeground { .. } => {
+                    self.background = false;
+                    self.emulator.send(Command::Pause(self.paused));
+                }
+                Event::AppTerminating { .. } => self.running = false,
+
                 _ => {}
             }
         }
     }
 
-    fn update_canvas(&mut self) {
-        self.canvas.set_draw_color(BACKGROUND);
-        self.canvas.clear();
-        self.canvas.set_draw_color(FOREGROUND);
+    /// Run the hotkey of `keycode` if it is one: Space pauses, Ctrl+R
+    /// resets, F10 steps an instruction and Shift+F10 a frame while
+    /// paused, PageUp and PageDown change the speed, Tab toggles the turbo,
+    /// as fast as possible with Shift, F5 to F7 use the save states, F11
+    /// and Alt+Enter toggle the fullscreen, F2 shows the debug text and F3
+    /// the frame timing
+    /// Returns true when the key was used
+    fn hotkey(&mut self, keycode: Keycode, keymod: Mod, repeat: bool) -> bool {
+        let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+        let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+        let alt = keymod.intersects(Mod::LALTMOD | Mod::RALTMOD);
 
-        for w in 0..DISPLAY_WIDTH {
-            for h in 0..DISPLAY_HEIGHT {
-                if !self.bus.vram[w][h] {
-                    continue;
+        match keycode {
+            Keycode::Space if !repeat => {
+                self.paused = !self.paused;
+                let message = match self.paused {
+                    true => "Paused",
+                    false => "Resumed",
+                };
+                info!("{}", message);
+                self.osd.show(message);
+                self.emulator.send(Command::Pause(self.paused));
+                if self.paused {
+                    self.audio_device.pause();
                 }
+                self.update_title();
+                self.update_canvas();
+            }
+            Keycode::R if ctrl && !repeat => {
+                info!("reset");
+                self.osd.show("Reset");
+                self.emulator.send(Command::Reset);
+            }
+            // held down to step repeatedly
+            Keycode::F10 if self.paused && shift => {
+                self.emulator.send(Command::AdvanceFrame);
+            }
+            Keycode::F10 if self.paused => self.emulator.send(Command::Step),
+            Keycode::Tab if !repeat => {
+                self.turbo = !self.turbo;
+                let speed = match self.turbo {
+                    true => TURBO_SPEED,
+                    false => 1.0,
+                };
+                let unlimited = self.turbo && shift;
+                self.emulator.send(Command::Speed(speed));
+                self.emulator.send(Command::Turbo(unlimited));
+                self.osd.show(match unlimited {
+                    true => "Turbo".to_string(),
+                    false => format!("Speed {:.0}%", speed * 100.0),
+                });
+                self.update_canvas();
+            }
+            Keycode::PageUp => self.change_speed(1),
+            Keycode::PageDown => self.change_speed(-1),
+            Keycode::F2 if !repeat => {
+                self.debug_text.toggle();
+                self.update_canvas();
+            }
+            Keycode::F3 if !repeat => {
+                self.timing_text.toggle();
+                self.update_canvas();
+            }
+            Keycode::F11 if !repeat => self.toggle_fullscreen(),
+            Keycode::Return if alt && !repeat => self.toggle_fullscreen(),
+            Keycode::F5 if !repeat => self.save_slot(),
+            Keycode::F7 if !repeat => self.load_slot(),
+            Keycode::F6 => {
+                if let Some(slots) = &mut self.slots {
+                    match shift {
+                        true => slots.previous(),
+                        false => slots.next(),
+                    }
+                    let slot = slots.current();
+                    let used = slots.is_used(slot);
+                    self.osd.show(match used {
+                        true => format!("Slot {}", slot),
+                        false => format!("Slot {} empty", slot),
+                    });
+                    self.update_canvas();
+                }
+            }
+            // the hotkeys aren't keypad keys, a repeat or F10 while running
+            // does nothing
+            Keycode::Space
+            | Keycode::Tab
+            | Keycode::F2
+            | Keycode::F3
+            | Keycode::F5
+            | Keycode::F7
+            | Keycode::F10
+            | Keycode::F11 => {}
+            Keycode::R if ctrl => {}
+            Keycode::Return if alt => {}
+            _ => return false,
+        }
 
-                self.canvas
-                    .fill_rect(Rect::new(w as i32, h as i32, 1, 1))
-                    .expect("draw pixel")
+        true
+    }
+
+    /// Change the cpu speed by `steps` of `SPEED_STEP`, it is kept in the
+    /// config
+    fn change_speed(&mut self, steps: i32) {
+        let mut machine = self.emulator.machine();
+        let frequency = (machine.cpu_frequency() + steps as f64 * SPEED_STEP)
+            .clamp(MIN_SPEED, MAX_SPEED);
+        machine.set_cpu_frequency(frequency);
+        drop(machine);
+
+        info!("speed: {} Hz", frequency);
+        self.osd.show(format!("CPU {} Hz", frequency));
+        self.config.cpu_frequency = frequency;
+        self.save_config();
+        self.update_canvas();
+    }
+
+    fn save_config(&self) {
+        if let Err(e) = self.config.save() {
+            warn!("config: {}", e);
+        }
+    }
+
+    /// Pause the game under the menu
+    fn open_pause_menu(&mut self) {
+        self.pause_menu = Some(PauseMenu::new());
+        self.emulator.send(Command::Pause(true));
+        self.audio_device.pause();
+        self.update_title();
+        self.update_canvas();
+    }
+
+    fn close_pause_menu(&mut self) {
+        self.pause_menu = None;
+        self.paused = false;
+        self.emulator.send(Command::Pause(false));
+        self.update_title();
+        self.update_canvas();
+    }
+
+    fn menu_input(&mut self, input: MenuInput) {
+        let Some(menu) = &mut self.pause_menu else {
+            return;
+        };
+
+        match menu.input(input, &self.rom_dir) {
+            Some(MenuAction::Resume) => self.close_pause_menu(),
+            Some(MenuAction::Reset) => {
+                self.emulator.send(Command::Reset);
+                self.close_pause_menu();
+            }
+            Some(MenuAction::Open(path)) => {
+                if let Err(e) = self.open_rom(&path) {
+                    warn!("{}: {}", path.display(), e);
+                    self.osd.show("Open failed");
+                }
+                self.close_pause_menu();
+            }
+            Some(MenuAction::ToggleFullscreen) => self.toggle_fullscreen(),
+            Some(MenuAction::ToggleSound) => {
+                self.config.audio.enabled = !self.config.audio.enabled;
+                self.save_config();
+            }
+            Some(MenuAction::Speed(steps)) => self.change_speed(steps),
+            Some(MenuAction::Quit) => self.running = false,
+            None => {}
+        }
+        self.update_canvas();
+    }
+
+    /// Start the rom at `path` in place of the current one, the speed and
+    /// the quirks are kept
+    fn open_rom(&mut self, path: &Path) -> io::Result<()> {
+        let data = fs::read(path)?;
+        let slots = Slots::for_rom(&data);
+
+        let frequency = self.emulator.machine().cpu_frequency();
+        let quirks = self.emulator.machine().cpu().quirks();
+        let mut machine = Machine::new(Rom::from_bytes(data));
+        machine.set_cpu_frequency(frequency);
+        machine.cpu_mut().set_quirks(quirks);
+        self.emulator = EmulatorThread::spawn(machine);
+        self.slots = slots;
+        self.kiosk_titles = None;
+        #[cfg(feature = "rhai")]
+        self.script_hud = None;
+        // the frames count again from 0
+        self.stats_time = Instant::now();
+        self.stats_frames = 0;
+
+        self.set_rom_path(path);
+
+        Ok(())
+    }
+
+    /// The choice is kept in the config, except in kiosk mode which is
+    /// always fullscreen
+    fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let fullscreen = window.fullscreen_state() == FullscreenType::Off;
+        set_fullscreen(window, fullscreen);
+
+        if self.kiosk_titles.is_none() {
+            self.config.fullscreen = fullscreen;
+            self.save_config();
+        }
+    }
+
+    fn save_slot(&mut self) {
+        let Some(slots) = &self.slots else {
+            return;
+        };
+
+        let slot = slots.current();
+        let result = slots.save_current(&self.emulator.machine());
+        match result {
+            Ok(()) => self.osd.show(format!("State saved to slot {}", slot)),
+            Err(e) => {
+                warn!("save slot {}: {}", slot, e);
+                self.osd.show("Save failed");
             }
         }
+        self.update_canvas();
+    }
+
+    fn load_slot(&mut self) {
+        let Some(slots) = &self.slots else {
+            return;
+        };
+
+        let slot = slots.current();
+        let result = slots.load_current(&mut self.emulator.machine());
+        match result {
+            Ok(()) => self.osd.show(format!("State loaded from slot {}", slot)),
+            Err(SlotError::Empty(_)) => {
+                self.osd.show(format!("Slot {} empty", slot))
+            }
+            Err(e) => {
+                warn!("load slot {}: {}", slot, e);
+                self.osd.show("Load failed");
+            }
+        }
+        self.update_canvas();
+    }
+
+    fn set_rom_name(&mut self, name: &str) {
+        self.rom_name = Some(name.to_string());
+        self.update_title();
+    }
+
+    /// Measure the frame rate once per period
+    fn update_stats(&mut self) {
+        let elapsed = self.stats_time.elapsed();
+        if elapsed < STATS_PERIOD {
+            return;
+        }
+
+        let frames = self.emulator.frames();
+        self.fps = frames.saturating_sub(self.stats_frames) as f64
+            / elapsed.as_secs_f64();
+        self.stats_time = Instant::now();
+        self.stats_frames = frames;
+        self.update_title();
+    }
+
+    /// The rom, then the pause state or the rates
+    /// The instructions per second are those of the cpu speed over the
+    /// frames run
+    fn update_title(&mut self) {
+        let mut title = String::from("chip8");
+        if let Some(name) = &self.rom_name {
+            title.push_str(&format!(" - {}", name));
+        }
+        if self.paused || self.pause_menu.is_some() {
+            title.push_str(" [Paused]");
+        } else {
+            let ips =
+                self.fps * self.emulator.machine().cpu_frequency() / FRAME_RATE;
+            title.push_str(&format!(" - {:.0} FPS {:.0} IPS", self.fps, ips));
+        }
+
+        if let Err(e) = self.canvas.window_mut().set_title(&title) {
+            warn!("title: {}", e);
+        }
+    }
+
+    fn save_state(&self) {
+        if let Some(path) = &self.state_file {
+            if let Err(e) =
+                fs::write(path, self.emulator.machine().save_state())
+            {
+                warn!("{}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Screen and keypad areas, the keypad goes under the screen in
+    /// portrait and on its right in landscape
+    fn layout(&self) -> (Rect, Option<Rect>) {
+        // drawn in the logical size when there is one
+        let (width, height) = match self.canvas.logical_size() {
+            (0, 0) => self.canvas.output_size().unwrap_or((1, 1)),
+            size => size,
+        };
+
+        if self.touch_keypad.is_none() {
+            return (Rect::new(0, 0, width, height), None);
+        }
+
+        if height >= width {
+            let screen_height = width / 2;
+            let keypad_size = (height - screen_height).min(width);
+            (
+                Rect::new(0, 0, width, screen_height),
+                Some(Rect::new(
+                    ((width - keypad_size) / 2) as i32,
+                    screen_height as i32,
+                    keypad_size,
+                    keypad_size,
+                )),
+            )
+        } else {
+            let keypad_size = height.min(width / 2);
+            (
+                Rect::new(0, 0, width - keypad_size, height),
+                Some(Rect::new(
+                    (width - keypad_size) as i32,
+                    0,
+                    keypad_size,
+                    keypad_size,
+                )),
+            )
+        }
+    }
+
+    fn update_canvas(&mut self) {
+        let start = Instant::now();
+        let (screen, keypad_area) = self.layout();
+
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+
+        let (width, height) = {
+            let machine = self.emulator.machine();
+            machine.bus().framebuffer_into(&mut self.frame);
+            machine.bus().resolution()
+        };
+        let (width, height) = (width as u32, height as u32);
+
+        // biggest integer size of the pixels of the resolution fitting the
+        // screen area, centered
+        let size = (screen.width() / width)
+            .min(screen.height() / height)
+            .max(1);
+        let area = Rect::new(
+            screen.x() + (screen.width() as i32 - (width * size) as i32) / 2,
+            screen.y() + (screen.height() as i32 - (height * size) as i32) / 2,
+            width * size,
+            height * size,
+        );
+        let (x, y) = (area.x(), area.y());
+        // the size of a low resolution pixel, for the texts
+        let scale = size * width / DISPLAY_WIDTH as u32;
+
+        self.canvas.set_draw_color(BACKGROUND);
+        self.canvas.fill_rect(area).expect("draw screen");
+
+        let frame = &self.frame;
+        let lit = frame.pixels.iter().enumerate().filter(|(_, &p)| p != 0);
+        for (index, &pixel) in lit {
+            let (w, h) = (index % frame.width, index / frame.width);
+            self.canvas.set_draw_color(PLANE_COLORS[pixel as usize]);
+            self.canvas
+                .fill_rect(Rect::new(
+                    x + (w as u32 * size) as i32,
+                    y + (h as u32 * size) as i32,
+                    size,
+                    size,
+                ))
+                .expect("draw pixel")
+        }
+
+        if let (Some(keypad), Some(area)) =
+            (&mut self.touch_keypad, keypad_area)
+        {
+            keypad.set_area(area);
+            keypad
+                .draw(
+                    &mut self.canvas,
+                    &self.emulator.machine(),
+                    FOREGROUND,
+                    BACKGROUND,
+                )
+                .expect("draw keypad");
+        }
+
+        self.debug_text
+            .draw(
+                &mut self.canvas,
+                area,
+                (scale / 4).max(1),
+                &self.emulator.machine(),
+                FOREGROUND,
+                BACKGROUND,
+            )
+            .expect("draw debug text");
+        self.timing_text
+            .draw(
+                &mut self.canvas,
+                area,
+                (scale / 4).max(1),
+                &self.emulator.timing(),
+                FOREGROUND,
+                BACKGROUND,
+            )
+            .expect("draw timing text");
+
+        #[cfg(feature = "rhai")]
+        if let Some(hud) = &self.script_hud {
+            hud.draw(
+                &mut self.canvas,
+                area,
+                (scale / 4).max(1),
+                BACKGROUND,
+                FOREGROUND,
+            )
+            .expect("draw script hud");
+        }
+
+        if let Some(menu) = &self.pause_menu {
+            let settings = MenuSettings {
+                fullscreen: self.canvas.window().fullscreen_state()
+                    != FullscreenType::Off,
+                sound: self.config.audio.enabled,
+                speed: self.emulator.machine().cpu_frequency(),
+            };
+            menu.draw(
+                &mut self.canvas,
+                area,
+                (scale / 2).max(1),
+                &settings,
+                FOREGROUND,
+                BACKGROUND,
+            )
+            .expect("draw pause menu");
+        }
+
+        // over the chip8 screen, in inverted colors, at half its scale so
+        // that the longer messages fit
+        self.osd
+            .draw(
+                &mut self.canvas,
+                Point::new(x, y),
+                (scale / 2).max(1),
+                BACKGROUND,
+                FOREGROUND,
+            )
+            .expect("draw osd");
+
+        #[cfg(feature = "imgui")]
+        if let Some(overlay) = &mut self.overlay {
+            overlay.draw(
+                self.canvas.window(),
+                &self.event_pump,
+                &mut self.emulator.machine(),
+            );
+        }
+
         self.canvas.present();
+        self.emulator.record_render(start.elapsed());
     }
 
+    /// Nothing plays when the sound is disabled in the config
     fn update_audio(&mut self) {
-        if self.beeper.is_beeping() {
+        let beeping =
+            self.config.audio.enabled && self.emulator.machine().is_beeping();
+        if beeping {
             if self.audio_device.status() != AudioStatus::Playing {
                 self.audio_device.resume();
             }
@@ -190,57 +990,124 @@ impl SDL2Frontend {
         }
     }
 
-    fn create_canvas(sdl: &sdl2::Sdl) -> Canvas<Window> {
-        let pixel_size = 8;
+    /// A window of `window_size`, or of `scale` output pixels per chip8
+    /// pixel, with the software renderer when there is no accelerated one
+    /// Returns true with the accelerated renderer
+    ///
+    /// The canvas has the logical size of `scale`, SDL keeps its aspect
+    /// ratio with black bars whatever the size of the window.
+    fn create_canvas(
+        sdl: &sdl2::Sdl,
+        scale: u32,
+        window_size: Option<(u32, u32)>,
+    ) -> (Canvas<Window>, bool) {
+        let (width, height) = logical_size(scale);
+        let (window_width, window_height) =
+            window_size.unwrap_or((width, height));
         let video_subsystem = sdl.video().expect("SDL2: video");
-        let window = video_subsystem
-            .window(
-                "chip8",
-                DISPLAY_WIDTH as u32 * pixel_size,
-                DISPLAY_HEIGHT as u32 * pixel_size,
-            )
-            .position_centered()
-            .opengl()
-            .build()
-            .expect("SDL2: window");
-        let mut canvas = window
-            .into_canvas()
-            .accelerated()
-            .build()
-            .expect("SDL2: Canvas");
-        canvas
-            .set_scale(pixel_size as f32, pixel_size as f32)
-            .expect("Canvas scale");
+        // the canvas takes the window, even when it fails
+        let window = |opengl: bool| {
+            let mut builder =
+                video_subsystem.window("chip8", window_width, window_height);
+            builder.position_centered().resizable();
+            if opengl {
+                builder.opengl();
+            }
+            builder.build().map_err(|e| e.to_string())
+        };
+
+        // without OpenGL the window itself may fail
+        let accelerated = window(true).and_then(|window| {
+            window
+                .into_canvas()
+                .accelerated()
+                .build()
+                .map_err(|e| e.to_string())
+        });
+        let (mut canvas, accelerated) = match accelerated {
+            Ok(canvas) => (canvas, true),
+            Err(e) => {
+                warn!("no accelerated renderer, using the software one: {}", e);
+                // the driver hint of the overlay would be used again
+                sdl2::hint::set("SDL_RENDER_DRIVER", "software");
+                let canvas = window(false)
+                    .expect("SDL2: window")
+                    .into_canvas()
+                    .software()
+                    .build()
+                    .expect("SDL2: Canvas");
+                (canvas, false)
+            }
+        };
+        if let Err(e) = canvas.set_logical_size(width, height) {
+            warn!("logical size: {}", e);
+        }
         canvas.set_draw_color(Color::RGB(0, 0, 0));
         canvas.clear();
         canvas.present();
-        canvas
+        (canvas, accelerated)
     }
 
-    fn create_audio(sdl: &sdl2::Sdl) -> AudioDevice<SquareWave> {
-        let audio_subsystem = sdl.audio().expect("SDL2: sound");
+    /// Open the default output again, the beep resumes with the next frame
+    fn reopen_audio(&mut self) {
+        match SDL2Frontend::create_audio(&self.audio_subsystem) {
+            Ok(audio_device) => {
+                debug!("audio device reopened");
+                self.audio_device = audio_device;
+            }
+            Err(e) => warn!("open playback: {}", e),
+        }
+    }
+
+    fn create_audio(
+        audio_subsystem: &AudioSubsystem,
+    ) -> Result<AudioDevice<SquareWave>, String> {
         let desired_spec = AudioSpecDesired {
             freq: Some(44_100),
             channels: Some(1),
             samples: None,
         };
-        let audio_device = audio_subsystem
-            .open_playback(None, &desired_spec, |spec| {
-                // Show obtained AudioSpec
-                println!("{:?}", spec);
-
-                // initialize the audio callback
-                SquareWave {
-                    phase_inc: 440.0 / spec.freq as f32,
-                    phase: 0.0,
-                    volume: 0.25,
-                }
-            })
-            .expect("open playback");
-        audio_device
+        audio_subsystem.open_playback(None, &desired_spec, |spec| {
+            // Show obtained AudioSpec
+            println!("{:?}", spec);
+
+            // initialize the audio callback
+            SquareWave {
+                phase_inc: 440.0 / spec.freq as f32,
+                phase: 0.0,
+                volume: 0.25,
+            }
+        })
     }
 }
 
+/// Size of the chip8 screen with `scale` pixels per chip8 pixel, from 2 to
+/// 16 for the high resolution screen to fit
+fn logical_size(scale: u32) -> (u32, u32) {
+    let pixel_size = scale.clamp(2, 16);
+    (
+        DISPLAY_WIDTH as u32 * pixel_size,
+        DISPLAY_HEIGHT as u32 * pixel_size,
+    )
+}
+
+/// Fullscreen on the desktop resolution, the screen is letterboxed, the
+/// cursor is only shown in a window
+fn set_fullscreen(window: &mut Window, fullscreen: bool) {
+    let state = match fullscreen {
+        true => FullscreenType::Desktop,
+        false => FullscreenType::Off,
+    };
+    if let Err(e) = window.set_fullscreen(state) {
+        warn!("fullscreen: {}", e);
+    }
+    window.subsystem().sdl().mouse().show_cursor(!fullscreen);
+}
+
+fn touch_point(x: f32, y: f32, width: u32, height: u32) -> Point {
+    Point::new((x * width as f32) as i32, (y * height as f32) as i32)
+}
+
 struct SquareWave {
     phase_inc: f32,
     phase: f32,
@@ -0,0 +1,67 @@
+use chip8_frontend::timing::FrameTiming;
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::Canvas,
+    video::Window,
+};
+
+use crate::font;
+
+/// Time of the emulation and of the drawing of the frames, and the frames
+/// missed, drawn as text over the screen to diagnose the stutters
+pub struct TimingText {
+    visible: bool,
+}
+
+impl TimingText {
+    pub fn new() -> Self {
+        Self { visible: false }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Draw the text in the bottom right corner of `area`, on a box of
+    /// `background`
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        area: Rect,
+        pixel: u32,
+        timing: &FrameTiming,
+        foreground: Color,
+        background: Color,
+    ) -> Result<(), String> {
+        if !self.visible {
+            return Ok(());
+        }
+
+        let lines = timing.lines();
+        let width = lines
+            .iter()
+            .map(|line| font::text_width(line, pixel))
+            .max()
+            .unwrap_or(0)
+            + 2 * pixel;
+        let height = (lines.len() as u32 * font::LINE_HEIGHT + 1) * pixel;
+        let x = area.right() - width as i32;
+        let y = area.bottom() - height as i32;
+        canvas.set_draw_color(background);
+        canvas.fill_rect(Rect::new(x, y, width, height))?;
+
+        for (index, line) in lines.iter().enumerate() {
+            let top = (index as u32 * font::LINE_HEIGHT + 1) * pixel;
+            font::draw_text(
+                canvas,
+                Point::new(x + pixel as i32, y + top as i32),
+                pixel,
+                line,
+                foreground,
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use chip8::{cpu::SPRITE_ADDR, keypad::Keypad, machine::Machine};
+use chip8_frontend::KEYPAD_LAYOUT;
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::Canvas,
+    video::Window,
+};
+
+// space between the keys, in output pixels
+const KEY_GAP: u32 = 4;
+
+/// On screen keypad for touch screens, a finger sliding to another key
+/// presses that key instead
+pub struct TouchKeypad {
+    area: Rect,
+    fingers: HashMap<i64, Keypad>,
+}
+
+impl TouchKeypad {
+    pub fn new() -> Self {
+        Self {
+            area: Rect::new(0, 0, 1, 1),
+            fingers: HashMap::new(),
+        }
+    }
+
+    /// Part of the output covered by the keypad
+    pub fn set_area(&mut self, area: Rect) {
+        self.area = area;
+    }
+
+    pub fn finger_down(
+        &mut self,
+        machine: &mut Machine,
+        finger: i64,
+        at: Point,
+    ) {
+        self.finger_motion(machine, finger, at);
+    }
+
+    pub fn finger_motion(
+        &mut self,
+        machine: &mut Machine,
+        finger: i64,
+        at: Point,
+    ) {
+        let key = self.key_at(at);
+        if self.fingers.get(&finger).copied() == key {
+            return;
+        }
+
+        self.finger_up(machine, finger);
+        if let Some(key) = key {
+            self.fingers.insert(finger, key);
+            machine.set_key(key, true);
+        }
+    }
+
+    pub fn finger_up(&mut self, machine: &mut Machine, finger: i64) {
+        if let Some(key) = self.fingers.remove(&finger) {
+            // another finger may still hold it
+            if !self.fingers.values().any(|&held| held == key) {
+                machine.set_key(key, false);
+            }
+        }
+    }
+
+    /// Release everything, fingers are lost when the app goes in background
+    pub fn release_all(&mut self, machine: &mut Machine) {
+        for (_, key) in self.fingers.drain() {
+            machine.set_key(key, false);
+        }
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        machine: &Machine,
+        foreground: Color,
+        background: Color,
+    ) -> Result<(), String> {
+        let memory = machine.bus().memory();
+
+        for (index, &key) in KEYPAD_LAYOUT.iter().enumerate() {
+            let rect = self.key_rect(index);
+            let pressed = machine.bus().keys[key as usize];
+            let (fill, glyph) = match pressed {
+                true => (background, foreground),
+                false => (foreground, background),
+            };
+
+            canvas.set_draw_color(fill);
+            canvas.fill_rect(rect)?;
+
+            // the label is the key digit from the chip8 font, 4x5 pixels
+            let pixel = (rect.width() / 8).min(rect.height() / 10).max(1);
+            let x = rect.x() + (rect.width() - pixel * 4) as i32 / 2;
+            let y = rect.y() + (rect.height() - pixel * 5) as i32 / 2;
+            let sprite = SPRITE_ADDR as usize + key as usize * 5;
+
+            canvas.set_draw_color(glyph);
+            for (row, line) in memory[sprite..sprite + 5].iter().enumerate() {
+                for column in 0..4 {
+                    if line & (0x80 >> column) != 0 {
+                        canvas.fill_rect(Rect::new(
+                            x + (column * pixel) as i32,
+                            y + (row as u32 * pixel) as i32,
+                            pixel,
+                            pixel,
+                        ))?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn key_at(&self, at: Point) -> Option<Keypad> {
+        (0..KEYPAD_LAYOUT.len())
+            .find(|&index| self.key_rect(index).contains_point(at))
+            .map(|index| KEYPAD_LAYOUT[index])
+    }
+
+    /// Keys on a 4x4 grid, row by row
+    fn key_rect(&self, index: usize) -> Rect {
+        let width = self.area.width() / 4;
+        let height = self.area.height() / 4;
+        let (column, row) = ((index % 4) as u32, (index / 4) as u32);
+
+        Rect::new(
+            self.area.x() + (column * width + KEY_GAP / 2) as i32,
+            self.area.y() + (row * height + KEY_GAP / 2) as i32,
+            width.saturating_sub(KEY_GAP).max(1),
+            height.saturating_sub(KEY_GAP).max(1),
+        )
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use chip8::{
+    beep::Beeper,
+    bus::{Bus, DISPLAY_HEIGHT, DISPLAY_WIDTH, KEYPAD_SIZE},
+    cpu::Cpu,
+    delay::Delay,
+    keypad::Keypad,
+};
+use log::warn;
+use ratatui::{
+    crossterm::{
+        event::{
+            self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+            KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+            PushKeyboardEnhancementFlags,
+        },
+        execute, terminal,
+    },
+    layout::{Constraint, Layout},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+    DefaultTerminal,
+};
+
+const FOREGROUND: Color = Color::Rgb(69, 115, 13);
+const BACKGROUND: Color = Color::Rgb(124, 209, 21);
+
+// most terminals only report key presses, so a key is held for this long
+// after its last press (or auto-repeat) event
+const KEY_HOLD: Duration = Duration::from_millis(150);
+
+pub struct TuiFrontend {
+    // chip8
+    cpu: Cpu,
+    delay: Delay,
+    beeper: Beeper,
+    bus: Bus,
+    // terminal
+    terminal: DefaultTerminal,
+    key_release_events: bool,
+    key_expiry: [Option<Instant>; KEYPAD_SIZE],
+    beeping: bool,
+    // loop
+    running: bool,
+}
+
+impl TuiFrontend {
+    pub fn new(cpu: Cpu, delay: Delay, beeper: Beeper, bus: Bus) -> Self {
+        let terminal = ratatui::init();
+
+        let key_release_events = terminal::supports_keyboard_enhancement()
+            .unwrap_or(false)
+            && execute!(
+                io::stdout(),
+                PushKeyboardEnhancementFlags(
+                    KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                )
+            )
+            .is_ok();
+
+        Self {
+            // chip8
+            cpu,
+            delay,
+            beeper,
+            bus,
+            // terminal
+            terminal,
+            key_release_events,
+            key_expiry: [None; KEYPAD_SIZE],
+            beeping: false,
+            // loop
+            running: true,
+        }
+    }
+
+    pub fn run(&mut self) {
+        let mut loop_time = Instant::now();
+        let mut cpu_cycles = 0.0;
+        let mut video_frames = 0.0;
+        let mut delay_update = 0.0;
+        let mut beep_update = 0.0;
+        let mut delta: f64;
+
+        let mut key_map = HashMap::new();
+        key_map.insert(KeyCode::Char('1'), Keypad::Key1);
+        key_map.insert(KeyCode::Char('2'), Keypad::Key2);
+        key_map.insert(KeyCode::Char('3'), Keypad::Key3);
+        key_map.insert(KeyCode::Char('4'), Keypad::KeyC);
+        key_map.insert(KeyCode::Char('a'), Keypad::Key4);
+        key_map.insert(KeyCode::Char('z'), Keypad::Key5);
+        key_map.insert(KeyCode::Char('e'), Keypad::Key6);
+        key_map.insert(KeyCode::Char('r'), Keypad::KeyD);
+        key_map.insert(KeyCode::Char('q'), Keypad::Key7);
+        key_map.insert(KeyCode::Char('s'), Keypad::Key8);
+        key_map.insert(KeyCode::Char('d'), Keypad::Key9);
+        key_map.insert(KeyCode::Char('f'), Keypad::KeyE);
+        key_map.insert(KeyCode::Char('w'), Keypad::KeyA);
+        key_map.insert(KeyCode::Char('x'), Keypad::Key0);
+        key_map.insert(KeyCode::Char('c'), Keypad::KeyB);
+        key_map.insert(KeyCode::Char('v'), Keypad::KeyF);
+
+        'running: loop {
+            self.read_events(&key_map);
+
+            if !self.running {
+                break 'running;
+            }
+
+            delta = loop_time.elapsed().as_secs_f64();
+
+            cpu_cycles += delta / 0.002; // 500Hz
+            while cpu_cycles >= 1.0 {
+                cpu_cycles -= 1.0;
+                self.cpu.emulate(&mut self.bus);
+            }
+
+            video_frames += delta / 0.02; // 50Hz
+            while video_frames >= 1.0 {
+                video_frames -= 1.0;
+                self.update_display();
+            }
+
+            delay_update += delta / 0.0166666666667; // 60 Hz
+            while delay_update >= 1.0 {
+                delay_update -= 1.0;
+
+                self.delay.update(&mut self.bus);
+            }
+
+            beep_update += delta / 0.0166666666667; // 60 Hz
+            while beep_update >= 1.0 {
+                beep_update -= 1.0;
+
+                self.beeper.update(&mut self.bus);
+
+                self.update_audio();
+            }
+
+            loop_time = Instant::now();
+
+            sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn read_events(&mut self, keymap: &HashMap<KeyCode, Keypad>) {
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            let event = match event::read() {
+                Ok(event) => event,
+                Err(err) => {
+                    warn!("terminal event: {}", err);
+                    break;
+                }
+            };
+
+            if let Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind,
+                ..
+            }) = event
+            {
+                match code {
+                    KeyCode::Esc => self.running = false,
+                    KeyCode::Char('c')
+                        if modifiers.contains(KeyModifiers::CONTROL) =>
+                    {
+                        self.running = false
+                    }
+                    _ => {
+                        let code = match code {
+                            KeyCode::Char(c) => {
+                                KeyCode::Char(c.to_ascii_lowercase())
+                            }
+                            code => code,
+                        };
+
+                        if let Some(key) = keymap.get(&code) {
+                            self.keyboard_input(*key, kind);
+                        }
+                    }
+                }
+            }
+        }
+
+        // release keys the terminal won't tell us about
+        let now = Instant::now();
+        for key in 0..KEYPAD_SIZE {
+            if self.key_expiry[key].is_some_and(|expiry| expiry <= now) {
+                self.key_expiry[key] = None;
+                self.bus.keys[key] = false;
+            }
+        }
+    }
+
+    fn keyboard_input(&mut self, key: Keypad, kind: KeyEventKind) {
+        let key = key as usize;
+
+        match kind {
+            KeyEventKind::Release => self.bus.keys[key] = false,
+            _ => {
+                self.bus.keys[key] = true;
+                if !self.key_release_events {
+                    self.key_expiry[key] = Some(Instant::now() + KEY_HOLD);
+                }
+            }
+        }
+    }
+
+    fn update_display(&mut self) {
+        let vram = &self.bus.vram();
+
+        // one character cell holds two pixels stacked vertically
+        let lines: Vec<Line> = (0..DISPLAY_HEIGHT)
+            .step_by(2)
+            .map(|h| {
+                (0..DISPLAY_WIDTH)
+                    .map(|w| {
+                        Span::styled(
+                            "▀",
+                            Style::new()
+                                .fg(pixel_color(vram[w][h]))
+                                .bg(pixel_color(vram[w][h + 1])),
+                        )
+                    })
+                    .collect::<Line>()
+            })
+            .collect();
+
+        let registers = self.register_lines();
+
+        let res = self.terminal.draw(|frame| {
+            // the registers in a sidebar on the right of the display
+            let [display, sidebar] = Layout::horizontal([
+                Constraint::Length(DISPLAY_WIDTH as u16 + 2),
+                Constraint::Length(16),
+            ])
+            .areas(frame.area());
+
+            frame.render_widget(
+                Paragraph::new(lines).block(Block::bordered().title("chip8")),
+                display,
+            );
+            frame.render_widget(
+                Paragraph::new(registers)
+                    .block(Block::bordered().title("registers")),
+                sidebar,
+            );
+        });
+
+        if let Err(err) = res {
+            warn!("terminal draw: {}", err);
+        }
+    }
+
+    fn register_lines(&self) -> Vec<Line<'static>> {
+        let v = self.cpu.registers();
+
+        let mut lines = vec![
+            Line::from(format!("PC 0x{:03X}", self.cpu.pc())),
+            Line::from(format!("I  0x{:03X}", self.cpu.index())),
+        ];
+        lines.extend((0..8).map(|x| {
+            Line::from(format!(
+                "V{:X} {:02X}  V{:X} {:02X}",
+                x,
+                v[x],
+                x + 8,
+                v[x + 8]
+            ))
+        }));
+        lines.push(Line::from(format!(
+            "DT {:02X}  ST {:02X}",
+            self.bus.delay, self.bus.beep
+        )));
+        if let Some(x) = self.cpu.key_await() {
+            lines.push(Line::from(format!("wait key V{:X}", x)));
+        }
+
+        lines
+    }
+
+    fn update_audio(&mut self) {
+        let beeping = self.beeper.is_beeping();
+
+        // the terminal bell can't be held, ring it once per beep
+        if beeping && !self.beeping {
+            let mut stdout = io::stdout();
+            stdout
+                .write_all(b"\x07")
+                .and_then(|_| stdout.flush())
+                .unwrap_or_else(|err| warn!("terminal bell: {}", err));
+        }
+
+        self.beeping = beeping;
+    }
+}
+
+impl Drop for TuiFrontend {
+    fn drop(&mut self) {
+        if self.key_release_events {
+            execute!(io::stdout(), PopKeyboardEnhancementFlags).ok();
+        }
+
+        ratatui::restore();
+    }
+}
+
+fn pixel_color(pixel: bool) -> Color {
+    if pixel {
+        FOREGROUND
+    } else {
+        BACKGROUND
+    }
+}
@@ -0,0 +1,222 @@
+use chip8::{
+    bus::{Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH, KEYPAD_SIZE},
+    machine::Machine,
+    rom::Rom,
+};
+use wasm_bindgen::{prelude::*, Clamped};
+use web_sys::{
+    AudioContext, CanvasRenderingContext2d, GainNode, HtmlCanvasElement,
+    ImageData, OscillatorType,
+};
+
+const FOREGROUND: [u8; 4] = [69, 115, 13, 255];
+const BACKGROUND: [u8; 4] = [124, 209, 21, 255];
+
+const BEEP_FREQUENCY: f32 = 440.0;
+const BEEP_VOLUME: f32 = 0.1;
+
+/// `KeyboardEvent.code` of the keys, by physical position so the layout is
+/// the same on every keyboard
+const KEY_CODES: [(&str, u8); KEYPAD_SIZE] = [
+    ("Digit1", 0x1),
+    ("Digit2", 0x2),
+    ("Digit3", 0x3),
+    ("Digit4", 0xC),
+    ("KeyQ", 0x4),
+    ("KeyW", 0x5),
+    ("KeyE", 0x6),
+    ("KeyR", 0xD),
+    ("KeyA", 0x7),
+    ("KeyS", 0x8),
+    ("KeyD", 0x9),
+    ("KeyF", 0xE),
+    ("KeyZ", 0xA),
+    ("KeyX", 0x0),
+    ("KeyC", 0xB),
+    ("KeyV", 0xF),
+];
+
+/// Square wave played while the sound timer is running
+struct Buzzer {
+    context: AudioContext,
+    gain: GainNode,
+}
+
+impl Buzzer {
+    fn new() -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+
+        let oscillator = context.create_oscillator()?;
+        oscillator.set_type(OscillatorType::Square);
+        oscillator.frequency().set_value(BEEP_FREQUENCY);
+
+        let gain = context.create_gain()?;
+        gain.gain().set_value(0.0);
+
+        oscillator.connect_with_audio_node(&gain)?;
+        gain.connect_with_audio_node(&context.destination())?;
+        oscillator.start()?;
+
+        Ok(Self { context, gain })
+    }
+
+    fn set_beeping(&self, beeping: bool) {
+        let volume = if beeping { BEEP_VOLUME } else { 0.0 };
+        self.gain.gain().set_value(volume);
+    }
+}
+
+/// Emulator drawing into a canvas of the size of the screen in use, 64x32
+/// or 128x64, the page is expected to scale it up
+#[wasm_bindgen]
+pub struct Chip8 {
+    machine: Option<Machine>,
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+    /// The screen of the machine, blank until there is one
+    frame: Framebuffer,
+    image: Vec<u8>,
+    buzzer: Option<Buzzer>,
+    cpu_frequency: Option<f64>,
+}
+
+#[wasm_bindgen]
+impl Chip8 {
+    #[wasm_bindgen(constructor)]
+    pub fn new(canvas: HtmlCanvasElement) -> Result<Chip8, JsValue> {
+        canvas.set_width(DISPLAY_WIDTH as u32);
+        canvas.set_height(DISPLAY_HEIGHT as u32);
+
+        let context = canvas
+            .get_context("2d")?
+            .ok_or("canvas has no 2d context")?
+            .dyn_into::<CanvasRenderingContext2d>()?;
+
+        let mut chip8 = Self {
+            machine: None,
+            canvas,
+            context,
+            frame: Framebuffer {
+                width: DISPLAY_WIDTH,
+                height: DISPLAY_HEIGHT,
+                pixels: vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            },
+            image: vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT * 4],
+            // the page still works without sound
+            buzzer: Buzzer::new().ok(),
+            cpu_frequency: None,
+        };
+        chip8.draw()?;
+
+        Ok(chip8)
+    }
+
+    /// Start `data` from a fresh machine, a rom too big for the memory is
+    /// refused
+    pub fn load_rom(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        let mut machine = Machine::try_new(Rom::from_bytes(data.to_vec()))
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        if let Some(frequency) = self.cpu_frequency {
+            machine.set_cpu_frequency(frequency);
+        }
+        self.machine = Some(machine);
+
+        self.draw()
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.machine.is_some()
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(machine) = &mut self.machine {
+            machine.reset();
+        }
+    }
+
+    /// Instructions per second
+    pub fn set_speed(&mut self, frequency: f64) {
+        self.cpu_frequency = Some(frequency);
+        if let Some(machine) = &mut self.machine {
+            machine.set_cpu_frequency(frequency);
+        }
+    }
+
+    /// Emulate one 60Hz frame, then draw the screen and update the sound
+    pub fn run_frame(&mut self) -> Result<(), JsValue> {
+        let machine = match &mut self.machine {
+            Some(machine) => machine,
+            None => return Ok(()),
+        };
+
+        machine.run_frame();
+        if let Some(buzzer) = &self.buzzer {
+            buzzer.set_beeping(machine.is_beeping());
+        }
+
+        self.draw()
+    }
+
+    /// Keep the buzzer quiet, used while the emulation is paused
+    pub fn mute(&self) {
+        if let Some(buzzer) = &self.buzzer {
+            buzzer.set_beeping(false);
+        }
+    }
+
+    /// Browsers only allow the sound to start from a user gesture
+    pub fn resume_audio(&self) {
+        if let Some(buzzer) = &self.buzzer {
+            let _ = buzzer.context.resume();
+        }
+    }
+
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        if let Some(machine) = &mut self.machine {
+            if let Some(state) = machine.bus_mut().keys.get_mut(key as usize) {
+                *state = pressed;
+            }
+        }
+    }
+
+    /// Handle a `KeyboardEvent.code`, returns false for the keys which aren't
+    /// part of the keypad
+    pub fn key_event(&mut self, code: &str, pressed: bool) -> bool {
+        match KEY_CODES.iter().find(|(key_code, _)| *key_code == code) {
+            Some(&(_, key)) => {
+                self.set_key(key, pressed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn draw(&mut self) -> Result<(), JsValue> {
+        if let Some(machine) = &self.machine {
+            machine.bus().framebuffer_into(&mut self.frame);
+        }
+        let frame = &self.frame;
+
+        // the SUPER-CHIP high resolution changes the size
+        let (width, height) = (frame.width as u32, frame.height as u32);
+        if self.canvas.width() != width || self.canvas.height() != height {
+            self.canvas.set_width(width);
+            self.canvas.set_height(height);
+        }
+        self.image.resize(frame.pixels.len() * 4, 0);
+
+        for (pixel, &lit) in self.image.chunks_exact_mut(4).zip(&frame.pixels) {
+            pixel.copy_from_slice(match lit & 1 != 0 {
+                true => &FOREGROUND,
+                false => &BACKGROUND,
+            });
+        }
+
+        let image = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&self.image),
+            width,
+            height,
+        )?;
+        self.context.put_image_data(&image, 0.0, 0.0)
+    }
+}
@@ -0,0 +1,110 @@
+import init, { Chip8 } from "./pkg/chip8_wasm.js";
+
+const FRAME_TIME = 1000 / 60;
+
+// same layout as the physical keyboard mapping
+const KEYPAD = [0x1, 0x2, 0x3, 0xc, 0x4, 0x5, 0x6, 0xd, 0x7, 0x8, 0x9, 0xe, 0xa, 0x0, 0xb, 0xf];
+
+await init();
+
+const screen = document.getElementById("screen");
+const status = document.getElementById("status");
+const pauseButton = document.getElementById("pause");
+const chip8 = new Chip8(screen);
+
+let running = true;
+let lastTime = performance.now();
+let frames = 0;
+
+function loop(time) {
+    frames += (time - lastTime) / FRAME_TIME;
+    lastTime = time;
+
+    // don't try to catch up after the tab was in background
+    frames = Math.min(frames, 4);
+
+    while (frames >= 1) {
+        frames -= 1;
+        if (running) {
+            chip8.run_frame();
+        }
+    }
+
+    requestAnimationFrame(loop);
+}
+requestAnimationFrame(loop);
+
+async function loadRom(file) {
+    const data = new Uint8Array(await file.arrayBuffer());
+    chip8.load_rom(data);
+    chip8.resume_audio();
+    status.textContent = `${file.name} (${data.length} bytes)`;
+}
+
+// rom loading
+document.getElementById("rom").addEventListener("change", (event) => {
+    const file = event.target.files[0];
+    if (file) {
+        loadRom(file);
+    }
+});
+
+screen.addEventListener("dragover", (event) => {
+    event.preventDefault();
+    screen.classList.add("dragging");
+});
+screen.addEventListener("dragleave", () => screen.classList.remove("dragging"));
+screen.addEventListener("drop", (event) => {
+    event.preventDefault();
+    screen.classList.remove("dragging");
+
+    const file = event.dataTransfer.files[0];
+    if (file) {
+        loadRom(file);
+    }
+});
+
+// controls
+pauseButton.addEventListener("click", () => {
+    running = !running;
+    pauseButton.textContent = running ? "Pause" : "Continue";
+    if (!running) {
+        chip8.mute();
+    }
+});
+document.getElementById("reset").addEventListener("click", () => chip8.reset());
+
+// keyboard
+function onKey(event, pressed) {
+    if (event.repeat || event.target instanceof HTMLInputElement) {
+        return;
+    }
+    if (chip8.key_event(event.code, pressed)) {
+        event.preventDefault();
+        chip8.resume_audio();
+    }
+}
+document.addEventListener("keydown", (event) => onKey(event, true));
+document.addEventListener("keyup", (event) => onKey(event, false));
+
+// touch keypad, pointer capture keeps the key pressed while the finger slides
+const keypad = document.getElementById("keypad");
+for (const key of KEYPAD) {
+    const button = document.createElement("button");
+    button.textContent = key.toString(16).toUpperCase();
+
+    const press = (pressed) => (event) => {
+        event.preventDefault();
+        button.classList.toggle("pressed", pressed);
+        chip8.set_key(key, pressed);
+        if (pressed) {
+            button.setPointerCapture(event.pointerId);
+            chip8.resume_audio();
+        }
+    };
+    button.addEventListener("pointerdown", press(true));
+    button.addEventListener("pointerup", press(false));
+    button.addEventListener("pointercancel", press(false));
+
+    keypad.appendChild(button);
+}
@@ -0,0 +1,57 @@
+use std::hint::black_box;
+
+use chip8::{
+    decode::{self, Instruction},
+    machine::Machine,
+    rom::Rom,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+/// Instructions per second of a turbo speed, 10000 per frame
+const TURBO: f64 = 600_000.0;
+
+/// Arithmetic, skips, draws and a jump back, like the loop of a game
+const ROM: [u8; 20] = [
+    0x60, 0x05, // V0 = 5
+    0x71, 0x01, // V1 += 1
+    0x82, 0x14, // V2 += V1
+    0x83, 0x26, // V3 = V2 >> 1
+    0x31, 0x00, // skip if V1 == 0
+    0xF2, 0x1E, // I += V2
+    0xA0, 0x00, // I = font 0
+    0xD0, 0x15, // draw it at V0, V1
+    0xE0, 0xA1, // skip if key V0 is not pressed
+    0x12, 0x02, // loop
+];
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.bench_function("match", |b| {
+        b.iter(|| {
+            (0..=u16::MAX)
+                .map(|opcode| Instruction::from_opcode(black_box(opcode)))
+                .filter(|&instruction| instruction == Instruction::Unknown)
+                .count()
+        })
+    });
+    group.bench_function("table", |b| {
+        let table = decode::table();
+        b.iter(|| {
+            (0..=u16::MAX)
+                .map(|opcode| table[black_box(opcode) as usize])
+                .filter(|&instruction| instruction == Instruction::Unknown)
+                .count()
+        })
+    });
+    group.finish();
+}
+
+fn run_frame(c: &mut Criterion) {
+    let mut machine = Machine::new(Rom::from_bytes(ROM.to_vec()));
+    machine.set_cpu_frequency(TURBO);
+
+    c.bench_function("run_frame turbo", |b| b.iter(|| machine.run_frame()));
+}
+
+criterion_group!(benches, decode, run_frame);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+//! Run arbitrary bytes as a rom, the emulator must not panic
+//!
+//! The first two bytes are the keys held, one bit per key.
+
+#![no_main]
+
+use chip8::{machine::Machine, rom::Rom};
+use libfuzzer_sys::fuzz_target;
+
+/// Instructions run for each input
+const STEPS: usize = 10_000;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((keys, rom)) = data.split_first_chunk::<2>() else {
+        return;
+    };
+    let keys = u16::from_le_bytes(*keys);
+
+    let mut machine = Machine::new(Rom::from_bytes(rom.to_vec()));
+    machine.cpu_mut().set_seed(1);
+    for (key, held) in machine.bus_mut().keys.iter_mut().enumerate() {
+        *held = keys & (1 << key) != 0;
+    }
+
+    for _ in 0..STEPS {
+        let pc = machine.cpu().pc() as usize;
+        let memory = machine.bus().memory();
+        let opcode = u16::from_be_bytes([
+            memory[pc % memory.len()],
+            memory[(pc + 1) % memory.len()],
+        ]);
+        let waiting = machine.cpu().key_await().is_some();
+
+        machine.step();
+
+        let cpu = machine.cpu();
+        assert!(cpu.pc() <= 0x0FFF, "pc {:04X}", cpu.pc());
+
+        // the flag is written last, also when VF is the destination
+        let flag = matches!(opcode & 0xF00F, 0x8004..=0x8007 | 0x800E);
+        if flag && !waiting {
+            let vf = cpu.registers()[0xF];
+            assert!(vf <= 1, "VF {:02X} after {:04X}", vf, opcode);
+        }
+    }
+});
@@ -0,0 +1,25 @@
+//! Disassemble arbitrary bytes at every offset, each instruction must
+//! assemble back to its bytes
+
+#![no_main]
+
+use chip8::{asm::assemble, disasm::disassemble_at};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(len) = u16::try_from(data.len()) else {
+        return;
+    };
+
+    for addr in 0..len {
+        let (opcode, text) = disassemble_at(data, addr);
+        assert_eq!(
+            assemble(&text),
+            Ok(opcode.to_be_bytes().to_vec()),
+            "{:04X} at {:03X}: {}",
+            opcode,
+            addr,
+            text
+        );
+    }
+});
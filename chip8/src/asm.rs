@@ -0,0 +1,409 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+    ops::Range,
+};
+
+/// Address of the first byte of a program
+const PROGRAM_START: u16 = 0x200;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AsmError {
+    /// Line of the source, from 1
+    pub line: usize,
+    /// Bytes of the source the error is about, a label, an operand or a
+    /// whole instruction
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+impl AsmError {
+    /// The line of `source` with the error, the span underlined below it
+    pub fn annotate(&self, source: &str) -> String {
+        let start = source[..self.span.start].rfind('\n').map_or(0, |n| n + 1);
+        let end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |n| start + n);
+        let column = source[start..self.span.start].chars().count();
+        let len = source[self.span.clone()].chars().count().max(1);
+
+        format!(
+            "{}\n{}{}",
+            &source[start..end],
+            " ".repeat(column),
+            "^".repeat(len)
+        )
+    }
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    V(u8),
+    I,
+    /// `[I]`, the memory at I
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    /// `HF`, the SUPER-CHIP big font
+    Hf,
+    /// The RPL flags of the SUPER-CHIP
+    R,
+    /// `VX-VY`, the registers from VX to VY of the XO-CHIP
+    Range(u8, u8),
+    /// `LONG`, the XO-CHIP address in the next word
+    Long,
+    /// The XO-CHIP audio pitch
+    Pitch,
+    Value(u16),
+}
+
+/// Assemble a program in the syntax of `disassemble`, one instruction per
+/// line
+///
+/// A line may start with a `label:` defined as the address of what follows
+/// it, text after `;` is ignored. Numbers are decimal or hex with `0x`,
+/// labels can be used in place of any of them. `DB` and `DW` write their
+/// operands as bytes and words.
+///
+/// ```text
+/// loop:  LD V0, K      ; wait for a key
+///        JP loop
+/// ```
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    // labels are known before the instructions that use them are encoded
+    let mut labels = HashMap::new();
+    let mut lines = vec![];
+    let mut addr = PROGRAM_START as usize;
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |part: &str, message: String| AsmError {
+            line: number + 1,
+            span: span(source, part),
+            message,
+        };
+
+        let mut line = line.split(';').next().unwrap_or_default().trim();
+        if let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(error(label, format!("invalid label {}", label)));
+            }
+            if labels.insert(label, addr).is_some() {
+                let message = format!("label {} defined twice", label);
+                return Err(error(label, message));
+            }
+            line = rest.trim();
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = match line.split_once(char::is_whitespace) {
+            Some((mnemonic, operands)) => (mnemonic, operands.trim()),
+            None => (line, ""),
+        };
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let operands: Vec<&str> = match operands.is_empty() {
+            true => vec![],
+            false => operands.split(',').map(str::trim).collect(),
+        };
+
+        addr += match mnemonic.as_str() {
+            "DB" => operands.len(),
+            "DW" => operands.len() * 2,
+            _ => 2,
+        };
+        lines.push((number + 1, line, mnemonic, operands));
+    }
+
+    let mut program = vec![];
+    for (line, instruction, mnemonic, texts) in lines {
+        let error = |part: &str, message: String| AsmError {
+            line,
+            span: span(source, part),
+            message,
+        };
+
+        let operands = texts
+            .iter()
+            .map(|text| {
+                parse_operand(text, &labels).map_err(|e| error(text, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match mnemonic.as_str() {
+            "DB" => {
+                for (text, operand) in texts.iter().zip(operands) {
+                    program.push(byte(operand).map_err(|e| error(text, e))?);
+                }
+            }
+            "DW" => {
+                for (text, operand) in texts.iter().zip(operands) {
+                    let word = match operand {
+                        Operand::Value(word) => word,
+                        _ => {
+                            return Err(error(text, "invalid word".to_string()))
+                        }
+                    };
+                    program.extend(word.to_be_bytes());
+                }
+            }
+            _ => {
+                let opcode = encode(&mnemonic, &operands)
+                    .map_err(|e| error(instruction, e))?;
+                program.extend(opcode.to_be_bytes());
+            }
+        }
+    }
+
+    Ok(program)
+}
+
+/// Where `part`, a slice of `source`, is in it
+fn span(source: &str, part: &str) -> Range<usize> {
+    let start = part.as_ptr() as usize - source.as_ptr() as usize;
+    start..start + part.len()
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_operand(
+    operand: &str,
+    labels: &HashMap<&str, usize>,
+) -> Result<Operand, String> {
+    let upper = operand.to_ascii_uppercase();
+    let operand = match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::Dt,
+        "ST" => Operand::St,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "B" => Operand::B,
+        "HF" => Operand::Hf,
+        "R" => Operand::R,
+        "LONG" => Operand::Long,
+        "PITCH" => Operand::Pitch,
+        _ if upper.len() == 2 && upper.starts_with('V') => {
+            match u8::from_str_radix(&upper[1..], 16) {
+                Ok(x) => Operand::V(x),
+                Err(_) => return Err(format!("invalid register {}", operand)),
+            }
+        }
+        _ if upper.len() == 5 && upper.starts_with('V') => {
+            let register = |name: &str| {
+                name.strip_prefix('V')
+                    .and_then(|x| u8::from_str_radix(x, 16).ok())
+            };
+            match upper.split_once('-') {
+                Some((x, y)) => match (register(x), register(y)) {
+                    (Some(x), Some(y)) => Operand::Range(x, y),
+                    _ => return Err(format!("invalid range {}", operand)),
+                },
+                None => return Err(format!("invalid range {}", operand)),
+            }
+        }
+        _ => {
+            let value = match upper.strip_prefix("0X") {
+                Some(hex) => usize::from_str_radix(hex, 16).ok(),
+                None if upper.starts_with(|c: char| c.is_ascii_digit()) => {
+                    operand.parse().ok()
+                }
+                None => labels.get(operand).copied(),
+            };
+            match value.and_then(|value| u16::try_from(value).ok()) {
+                Some(value) => Operand::Value(value),
+                None => return Err(format!("invalid value {}", operand)),
+            }
+        }
+    };
+
+    Ok(operand)
+}
+
+fn byte(operand: Operand) -> Result<u8, String> {
+    match operand {
+        Operand::Value(value) => u8::try_from(value)
+            .map_err(|_| format!("{:#X} doesn't fit in a byte", value)),
+        _ => Err("invalid byte".to_string()),
+    }
+}
+
+fn encode(mnemonic: &str, operands: &[Operand]) -> Result<u16, String> {
+    use Operand::*;
+
+    let addr = |nnn: u16| match nnn {
+        0..=0xFFF => Ok(nnn),
+        _ => Err(format!("{:#X} is out of memory", nnn)),
+    };
+    let xy = |x: u8, y: u8| ((x as u16) << 8) | ((y as u16) << 4);
+    let xnn = |x: u8, nn: u16| {
+        byte(Value(nn)).map(|nn| ((x as u16) << 8) | nn as u16)
+    };
+
+    let opcode = match (mnemonic, operands) {
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SCD", &[Value(n @ 1..=0xF)]) => 0x00C0 | n,
+        ("SCD", &[Value(n)]) => {
+            return Err(format!("scrolls are 1 to 15 rows, not {}", n))
+        }
+        ("SCR", []) => 0x00FB,
+        ("SCL", []) => 0x00FC,
+        ("EXIT", []) => 0x00FD,
+        ("LOW", []) => 0x00FE,
+        ("HIGH", []) => 0x00FF,
+        ("SCU", &[Value(n @ 1..=0xF)]) => 0x00D0 | n,
+        ("SCU", &[Value(n)]) => {
+            return Err(format!("scrolls are 1 to 15 rows, not {}", n))
+        }
+        ("PLANE", &[Value(n @ 0..=3)]) => 0xF001 | n << 8,
+        ("PLANE", &[Value(n)]) => {
+            return Err(format!("the planes are 0 to 3, not {}", n))
+        }
+        ("AUDIO", []) => 0xF002,
+        ("SYS", &[Value(nnn)]) => addr(nnn)?,
+        ("JP", &[Value(nnn)]) => 0x1000 | addr(nnn)?,
+        ("JP", &[V(0), Value(nnn)]) => 0xB000 | addr(nnn)?,
+        ("CALL", &[Value(nnn)]) => 0x2000 | addr(nnn)?,
+        ("SE", &[V(x), Value(nn)]) => 0x3000 | xnn(x, nn)?,
+        ("SE", &[V(x), V(y)]) => 0x5000 | xy(x, y),
+        ("SNE", &[V(x), Value(nn)]) => 0x4000 | xnn(x, nn)?,
+        ("SNE", &[V(x), V(y)]) => 0x9000 | xy(x, y),
+        ("LD", &[V(x), Value(nn)]) => 0x6000 | xnn(x, nn)?,
+        ("LD", &[V(x), V(y)]) => 0x8000 | xy(x, y),
+        ("LD", &[I, Value(nnn)]) => 0xA000 | addr(nnn)?,
+        ("LD", &[I, Long]) => 0xF000,
+        ("LD", &[IndirectI, Range(x, y)]) => 0x5002 | xy(x, y),
+        ("LD", &[Range(x, y), IndirectI]) => 0x5003 | xy(x, y),
+        ("LD", &[Pitch, V(x)]) => 0xF03A | xy(x, 0),
+        ("LD", &[V(x), Dt]) => 0xF007 | xy(x, 0),
+        ("LD", &[V(x), K]) => 0xF00A | xy(x, 0),
+        ("LD", &[Dt, V(x)]) => 0xF015 | xy(x, 0),
+        ("LD", &[St, V(x)]) => 0xF018 | xy(x, 0),
+        ("LD", &[F, V(x)]) => 0xF029 | xy(x, 0),
+        ("LD", &[B, V(x)]) => 0xF033 | xy(x, 0),
+        ("LD", &[IndirectI, V(x)]) => 0xF055 | xy(x, 0),
+        ("LD", &[V(x), IndirectI]) => 0xF065 | xy(x, 0),
+        ("LD", &[Hf, V(x)]) => 0xF030 | xy(x, 0),
+        ("LD", &[R, V(x @ 0..=7)]) => 0xF075 | xy(x, 0),
+        ("LD", &[V(x @ 0..=7), R]) => 0xF085 | xy(x, 0),
+        ("LD", &[R, V(_)] | &[V(_), R]) => {
+            return Err("the flags are V0 to V7".to_string())
+        }
+        ("ADD", &[V(x), Value(nn)]) => 0x7000 | xnn(x, nn)?,
+        ("ADD", &[V(x), V(y)]) => 0x8004 | xy(x, y),
+        ("ADD", &[I, V(x)]) => 0xF01E | xy(x, 0),
+        ("OR", &[V(x), V(y)]) => 0x8001 | xy(x, y),
+        ("AND", &[V(x), V(y)]) => 0x8002 | xy(x, y),
+        ("XOR", &[V(x), V(y)]) => 0x8003 | xy(x, y),
+        ("SUB", &[V(x), V(y)]) => 0x8005 | xy(x, y),
+        ("SHR", &[V(x), V(y)]) => 0x8006 | xy(x, y),
+        ("SUBN", &[V(x), V(y)]) => 0x8007 | xy(x, y),
+        ("SHL", &[V(x), V(y)]) => 0x800E | xy(x, y),
+        ("RND", &[V(x), Value(nn)]) => 0xC000 | xnn(x, nn)?,
+        ("DRW", &[V(x), V(y), Value(n @ 0..=0xF)]) => 0xD000 | xy(x, y) | n,
+        ("DRW", &[V(_), V(_), Value(n)]) => {
+            return Err(format!("sprites are at most 15 rows, not {}", n))
+        }
+        ("SKP", &[V(x)]) => 0xE09E | xy(x, 0),
+        ("SKNP", &[V(x)]) => 0xE0A1 | xy(x, 0),
+        _ => return Err(format!("invalid instruction {}", mnemonic)),
+    };
+
+    Ok(opcode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::disassemble;
+
+    #[test]
+    fn test_assemble_disassembly() {
+        for opcode in 0..=u16::MAX {
+            let text = disassemble(opcode);
+            assert_eq!(
+                assemble(&text),
+                Ok(opcode.to_be_bytes().to_vec()),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            start:  LD V0, 0          ; counter
+            loop:   add v0, 1
+                    SE V0, 10
+                    JP loop
+                    LD I, sprite
+                    DRW V0, V1, 2
+            end:    JP end
+            sprite: DB 0xF0, 0x90
+                    DW 0x1234
+        ";
+
+        assert_eq!(
+            assemble(source),
+            Ok(vec![
+                0x60, 0x00, 0x70, 0x01, 0x30, 0x0A, 0x12, 0x02, 0xA2, 0x0E,
+                0xD0, 0x12, 0x12, 0x0C, 0xF0, 0x90, 0x12, 0x34,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        let line = |source| assemble(source).unwrap_err().line;
+
+        assert_eq!(line("CLS\nJP nowhere"), 2);
+        assert_eq!(line("a: CLS\na: RET"), 2);
+        assert_eq!(line("LD V0, 256"), 1);
+        assert_eq!(line("DRW V0, V1, 16"), 1);
+        assert_eq!(line("LD VG, 1"), 1);
+        assert_eq!(line("NOP"), 1);
+        assert_eq!(line("SCD 0"), 1);
+        assert_eq!(line("LD R, V8"), 1);
+        assert_eq!(line("PLANE 4"), 1);
+        assert_eq!(line("LD [I], V1-VX"), 1);
+    }
+
+    #[test]
+    fn test_error_span() {
+        let source = "start: CLS\n  DB 1, 0x100 ; too big\n";
+        let error = assemble(source).unwrap_err();
+        assert_eq!(&source[error.span.clone()], "0x100");
+        assert_eq!(
+            error.annotate(source),
+            "  DB 1, 0x100 ; too big\n        ^^^^^"
+        );
+
+        let source = "CLS\nstart: DRW V0, V1, 16";
+        let error = assemble(source).unwrap_err();
+        assert_eq!(&source[error.span], "DRW V0, V1, 16");
+
+        let source = "a: CLS\na: RET";
+        assert_eq!(assemble(source).unwrap_err().span, 7..8);
+    }
+}
@@ -1,9 +1,19 @@
-use crate::bus::Bus;
+use crate::{
+    bus::Bus,
+    state::{Snapshot, StateError, StateReader, StateWriter},
+};
 
+#[derive(Clone)]
 pub struct Beeper {
     beep: bool,
 }
 
+impl Default for Beeper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Beeper {
     pub fn new() -> Self {
         Self { beep: false }
@@ -38,3 +48,15 @@ impl BeeperBus for Bus {
         self.beep = value;
     }
 }
+
+impl Snapshot for Beeper {
+    fn save(&self, state: &mut StateWriter) {
+        state.bool(self.beep);
+    }
+
+    fn load(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.beep = state.bool()?;
+
+        Ok(())
+    }
+}
@@ -1,47 +1,502 @@
 use crate::{
-    cpu::{CpuBus, SPRITE_ADDR},
+    cpu::{CpuBus, BIG_SPRITE_ADDR, SPRITE_ADDR},
+    error::Error,
     rom::Rom,
+    state::{Snapshot, StateError, StateReader, StateWriter},
 };
 
 pub const DISPLAY_WIDTH: usize = 64;
 pub const DISPLAY_HEIGHT: usize = 32;
+/// Size of the SUPER-CHIP high resolution screen
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
 pub const KEYPAD_SIZE: usize = 16;
+pub const MEMORY_SIZE: usize = 0x1000;
+/// Memory of the XO-CHIP, all the 16 bits addresses
+pub const XO_MEMORY_SIZE: usize = 0x10000;
+/// Bit planes of the XO-CHIP screen, the first one is the chip8 screen
+pub const PLANES: usize = 2;
+/// Bytes of the XO-CHIP audio pattern, a sample per bit
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+/// XO-CHIP pitch of the patterns played at 4000 samples per second
+pub const DEFAULT_PITCH: u8 = 64;
 
+/// The screen pixel by pixel, by column then row
+pub type Vram = [[bool; DISPLAY_HEIGHT]; DISPLAY_WIDTH];
+
+/// The screen packed row by row, the leftmost pixel in the high bit
+pub type Rows = [u64; DISPLAY_HEIGHT];
+
+/// The high resolution screen packed row by row, like `Rows`
+pub type HiresRows = [u128; HIRES_HEIGHT];
+
+/// The screen at the resolution in use, a byte per pixel row after row,
+/// ready for the textures
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    /// Bit N of a pixel is set when it is lit on the plane N
+    pub pixels: Vec<u8>,
+}
+
+impl Framebuffer {
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// A bit plane of the screen
+#[derive(Clone, Default)]
+struct Plane {
+    rows: Rows,
+    /// The 128x64 screen while it is on, `rows` then shows it at half size
+    /// to the frontends made for 64x32
+    hires: Option<Box<HiresRows>>,
+}
+
+#[derive(Clone)]
 pub struct Bus {
-    memory: [u8; 0x1000],
-    pub vram: [[bool; DISPLAY_HEIGHT]; DISPLAY_WIDTH],
+    memory: Box<[u8]>,
+    planes: [Plane; PLANES],
+    /// Mask of the planes drawn on, the first one unless XO-CHIP FN01
+    /// selects others
+    selected: u8,
     pub keys: [bool; KEYPAD_SIZE],
     pub delay: u8,
     pub beep: u8,
+    /// XO-CHIP sound played while the sound timer runs, none until F002
+    /// loads one and the frontends beep as usual
+    pub audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    pub pitch: u8,
 }
 
 impl Bus {
+    /// The end of a rom too big for the memory is cut, see `try_new`
     pub fn new(rom: Rom) -> Self {
-        let mut memory = [0; 0x1000];
+        Self::with_memory_size(rom, MEMORY_SIZE)
+    }
+
+    /// A bus with the 64KB of the XO-CHIP
+    pub fn new_xo_chip(rom: Rom) -> Self {
+        Self::with_memory_size(rom, XO_MEMORY_SIZE)
+    }
+
+    /// Like `new`, but a rom too big for the memory is an error
+    pub fn try_new(rom: Rom) -> Result<Self, Error> {
+        check_size(&rom, MEMORY_SIZE)?;
+
+        Ok(Self::new(rom))
+    }
+
+    pub fn try_new_xo_chip(rom: Rom) -> Result<Self, Error> {
+        check_size(&rom, XO_MEMORY_SIZE)?;
+
+        Ok(Self::new_xo_chip(rom))
+    }
+
+    fn with_memory_size(rom: Rom, size: usize) -> Self {
+        let mut memory = vec![0; size].into_boxed_slice();
 
         Bus::load_font4x5(&mut memory);
+        Bus::load_font8x10(&mut memory);
 
-        for addr in 0..rom.size() {
-            memory[0x200 + addr] = rom.read(addr as u16);
-        }
+        // the end of a rom too big for the memory is cut
+        let size = rom.size().min(memory.len() - 0x200);
+        memory[0x200..0x200 + size].copy_from_slice(&rom.data()[..size]);
 
-        let vram = [[false; DISPLAY_HEIGHT]; DISPLAY_WIDTH];
         let keys = [false; KEYPAD_SIZE];
 
         Self {
             memory,
-            vram,
+            planes: Default::default(),
+            selected: 1,
             keys,
             delay: 0,
             beep: 0,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
         }
     }
 
+    pub fn is_xo_chip(&self) -> bool {
+        self.memory.len() == XO_MEMORY_SIZE
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    /// The screen, its first plane
+    pub fn rows(&self) -> &Rows {
+        &self.planes[0].rows
+    }
+
+    /// A plane of the XO-CHIP screen, `rows` for the first one
+    pub fn plane_rows(&self, plane: usize) -> &Rows {
+        &self.planes[plane].rows
+    }
+
+    /// The screen unpacked, copied
+    pub fn vram(&self) -> Vram {
+        let mut vram = [[false; DISPLAY_HEIGHT]; DISPLAY_WIDTH];
+        for (x, column) in vram.iter_mut().enumerate() {
+            for (pixel, row) in column.iter_mut().zip(self.rows()) {
+                *pixel = row & pixel_bit(x) != 0;
+            }
+        }
+
+        vram
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.rows()[y] & pixel_bit(x) != 0
+    }
+
+    /// Width and height of the screen, 128x64 while the SUPER-CHIP high
+    /// resolution is on, `CpuBus::resolution` without importing the trait
+    pub fn resolution(&self) -> (usize, usize) {
+        CpuBus::resolution(self)
+    }
+
+    /// The screen copied in a linear buffer, at the resolution
+    pub fn framebuffer(&self) -> Framebuffer {
+        let mut framebuffer = Framebuffer::default();
+        self.framebuffer_into(&mut framebuffer);
+
+        framebuffer
+    }
+
+    /// Like `framebuffer`, reusing the buffer of the previous frame
+    pub fn framebuffer_into(&self, framebuffer: &mut Framebuffer) {
+        let (width, height) = self.resolution();
+        framebuffer.width = width;
+        framebuffer.height = height;
+        framebuffer.pixels.clear();
+        framebuffer.pixels.resize(width * height, 0);
+
+        for (bit, plane) in self.planes.iter().enumerate() {
+            let rows = framebuffer.pixels.chunks_exact_mut(width);
+            for (y, pixels) in rows.enumerate() {
+                plane.fill_row(y, bit, pixels);
+            }
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        let rows = &mut self.planes[0].rows;
+        if on {
+            rows[y] |= pixel_bit(x);
+        } else {
+            rows[y] &= !pixel_bit(x);
+        }
+    }
+
+    /// The SUPER-CHIP 128x64 screen, while it is on
+    pub fn hires_rows(&self) -> Option<&HiresRows> {
+        self.planes[0].hires.as_deref()
+    }
+
+    /// A plane of the XO-CHIP 128x64 screen, like `hires_rows`
+    pub fn plane_hires_rows(&self, plane: usize) -> Option<&HiresRows> {
+        self.planes[plane].hires.as_deref()
+    }
+
+    /// `frame_hash` of the screen, the 128x64 one while it is on, then of
+    /// the second plane too in the XO-CHIP mode
+    pub fn frame_hash(&self) -> u64 {
+        let planes = match self.is_xo_chip() {
+            true => &self.planes[..],
+            false => &self.planes[..1],
+        };
+        let (width, height) = self.screen_size();
+
+        hash_screen(
+            width as usize,
+            height as usize,
+            planes.iter().flat_map(Plane::bytes),
+        )
+    }
+
+    /// Playback rate of the audio pattern in samples per second, set by the
+    /// XO-CHIP pitch
+    pub fn sample_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
+
+    /// The planes selected by FN01
+    fn selected_planes(&mut self) -> impl Iterator<Item = &mut Plane> {
+        let selected = self.selected;
+        self.planes
+            .iter_mut()
+            .enumerate()
+            .filter(move |(index, _)| selected & 1 << index != 0)
+            .map(|(_, plane)| plane)
+    }
+
     fn load_font4x5(memory: &mut [u8]) {
         for i in 0..FONT4X5.len() {
             memory[i + SPRITE_ADDR as usize] = FONT4X5[i];
         }
     }
+
+    fn load_font8x10(memory: &mut [u8]) {
+        let start = BIG_SPRITE_ADDR as usize;
+        memory[start..start + FONT8X10.len()].copy_from_slice(&FONT8X10);
+    }
+}
+
+/// Hash of a screen, the one definition of the same frame for the replays,
+/// the golden files and the comparisons
+///
+/// FNV-1a over the width and the height as little endian u16, then the
+/// rows packed in bytes, the leftmost pixel in the high bit.
+pub fn frame_hash(vram: &Vram) -> u64 {
+    let mut rows = [0; DISPLAY_HEIGHT];
+    for (x, column) in vram.iter().enumerate() {
+        for (row, &pixel) in rows.iter_mut().zip(column) {
+            if pixel {
+                *row |= pixel_bit(x);
+            }
+        }
+    }
+
+    hash_rows(&rows)
+}
+
+fn hash_rows(rows: &Rows) -> u64 {
+    hash_screen(
+        DISPLAY_WIDTH,
+        DISPLAY_HEIGHT,
+        rows.iter().flat_map(|row| row.to_be_bytes()),
+    )
+}
+
+impl Plane {
+    /// The rows packed in bytes, the 128x64 ones while they are on
+    fn bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        let hires = self.hires.iter().flat_map(|hires| hires.iter());
+        let rows = self.rows.iter().filter(|_| self.hires.is_none());
+
+        hires
+            .flat_map(|row| row.to_be_bytes())
+            .chain(rows.flat_map(|row| row.to_be_bytes()))
+    }
+
+    fn clear(&mut self) {
+        self.rows = [0; DISPLAY_HEIGHT];
+        if let Some(hires) = &mut self.hires {
+            hires.fill(0);
+        }
+    }
+
+    /// Set `bit` of the lit pixels of row `y`, at the resolution in use
+    fn fill_row(&self, y: usize, bit: usize, pixels: &mut [u8]) {
+        let (row, width) = match &self.hires {
+            Some(hires) => (hires[y], HIRES_WIDTH),
+            None => (self.rows[y] as u128, DISPLAY_WIDTH),
+        };
+
+        for (x, pixel) in pixels.iter_mut().enumerate() {
+            *pixel |= ((row >> (width - 1 - x)) as u8 & 1) << bit;
+        }
+    }
+
+    fn read(&self, x: u8, y: u8) -> bool {
+        match &self.hires {
+            Some(hires) => {
+                let (x, y) =
+                    (x as usize % HIRES_WIDTH, y as usize % HIRES_HEIGHT);
+                hires[y] & hires_pixel_bit(x) != 0
+            }
+            None => {
+                let (x, y) =
+                    (x as usize % DISPLAY_WIDTH, y as usize % DISPLAY_HEIGHT);
+                self.rows[y] & pixel_bit(x) != 0
+            }
+        }
+    }
+
+    fn write(&mut self, x: u8, y: u8, pixel: bool) {
+        let Some(hires) = &mut self.hires else {
+            let (x, y) =
+                (x as usize % DISPLAY_WIDTH, y as usize % DISPLAY_HEIGHT);
+            match pixel {
+                true => self.rows[y] |= pixel_bit(x),
+                false => self.rows[y] &= !pixel_bit(x),
+            }
+            return;
+        };
+
+        let (x, y) = (x as usize % HIRES_WIDTH, y as usize % HIRES_HEIGHT);
+        match pixel {
+            true => hires[y] |= hires_pixel_bit(x),
+            false => hires[y] &= !hires_pixel_bit(x),
+        }
+        self.squeeze_row(y);
+    }
+
+    /// The whole row at once, the sprite rotated to its place
+    fn draw_row(&mut self, x: u8, y: u8, line: u8) -> bool {
+        let Some(hires) = &mut self.hires else {
+            let mask = ((line as u64) << (DISPLAY_WIDTH - 8))
+                .rotate_right(x as u32 % DISPLAY_WIDTH as u32);
+            let row = &mut self.rows[y as usize % DISPLAY_HEIGHT];
+            let collision = *row & mask != 0;
+            *row ^= mask;
+
+            return collision;
+        };
+
+        let mask = ((line as u128) << (HIRES_WIDTH - 8))
+            .rotate_right(x as u32 % HIRES_WIDTH as u32);
+        let y = y as usize % HIRES_HEIGHT;
+        let collision = hires[y] & mask != 0;
+        hires[y] ^= mask;
+        self.squeeze_row(y);
+
+        collision
+    }
+
+    /// The picture is kept, at the size of the new screen
+    fn set_hires(&mut self, hires: bool) {
+        match (hires, &self.hires) {
+            (true, None) => {
+                let mut screen = Box::new([0; HIRES_HEIGHT]);
+                for (y, row) in screen.iter_mut().enumerate() {
+                    *row = stretch(self.rows[y / 2]);
+                }
+                self.hires = Some(screen);
+            }
+            (false, Some(_)) => self.hires = None,
+            _ => {}
+        }
+    }
+
+    fn scroll_down(&mut self, n: u8) {
+        match &mut self.hires {
+            Some(hires) => {
+                shift_down(&mut hires[..], n as usize);
+                self.squeeze_rows();
+            }
+            None => shift_down(&mut self.rows, n as usize),
+        }
+    }
+
+    fn scroll_up(&mut self, n: u8) {
+        match &mut self.hires {
+            Some(hires) => {
+                shift_up(&mut hires[..], n as usize);
+                self.squeeze_rows();
+            }
+            None => shift_up(&mut self.rows, n as usize),
+        }
+    }
+
+    fn scroll_left(&mut self) {
+        match &mut self.hires {
+            Some(hires) => {
+                hires.iter_mut().for_each(|row| *row <<= 4);
+                self.squeeze_rows();
+            }
+            None => self.rows.iter_mut().for_each(|row| *row <<= 4),
+        }
+    }
+
+    fn scroll_right(&mut self) {
+        match &mut self.hires {
+            Some(hires) => {
+                hires.iter_mut().for_each(|row| *row >>= 4);
+                self.squeeze_rows();
+            }
+            None => self.rows.iter_mut().for_each(|row| *row >>= 4),
+        }
+    }
+
+    /// Show the high resolution row `y` in the half size `rows`, a pixel is
+    /// lit when one of the 4 it stands for is
+    fn squeeze_row(&mut self, y: usize) {
+        if let Some(hires) = &self.hires {
+            let top = y & !1;
+            self.rows[y / 2] = squeeze(hires[top] | hires[top + 1]);
+        }
+    }
+
+    fn squeeze_rows(&mut self) {
+        for y in (0..HIRES_HEIGHT).step_by(2) {
+            self.squeeze_row(y);
+        }
+    }
+}
+
+fn hash_screen(
+    width: usize,
+    height: usize,
+    rows: impl Iterator<Item = u8>,
+) -> u64 {
+    let sizes = [width as u16, height as u16];
+    sizes
+        .iter()
+        .flat_map(|size| size.to_le_bytes())
+        .chain(rows)
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// The rom fits in a memory of `size` bytes after 0x200
+fn check_size(rom: &Rom, size: usize) -> Result<(), Error> {
+    let max = size - 0x200;
+    match rom.size() > max {
+        true => Err(Error::RomTooBig {
+            size: rom.size(),
+            max,
+        }),
+        false => Ok(()),
+    }
+}
+
+/// Bit of the pixel at column `x` in a row
+fn pixel_bit(x: usize) -> u64 {
+    1 << (DISPLAY_WIDTH - 1 - x)
+}
+
+fn hires_pixel_bit(x: usize) -> u128 {
+    1 << (HIRES_WIDTH - 1 - x)
+}
+
+/// A high resolution row at half its width, a pixel for each pair
+fn squeeze(row: u128) -> u64 {
+    let pairs = row | row >> 1;
+    (0..DISPLAY_WIDTH).fold(0, |squeezed, x| {
+        squeezed | ((pairs >> (2 * x)) as u64 & 1) << x
+    })
+}
+
+/// A row at twice its width, each pixel twice
+fn stretch(row: u64) -> u128 {
+    (0..DISPLAY_WIDTH).fold(0, |stretched, x| {
+        stretched | (((row >> x) as u128 & 1) * 0b11) << (2 * x)
+    })
+}
+
+/// Move `rows` down by `n`, the top ones are cleared
+fn shift_down<T: Copy + Default>(rows: &mut [T], n: usize) {
+    let n = n.min(rows.len());
+    rows.copy_within(..rows.len() - n, n);
+    rows[..n].fill(T::default());
+}
+
+/// Move `rows` up by `n`, the bottom ones are cleared
+fn shift_up<T: Copy + Default>(rows: &mut [T], n: usize) {
+    let n = n.min(rows.len());
+    rows.copy_within(n.., 0);
+    let len = rows.len();
+    rows[len - n..].fill(T::default());
 }
 
 const FONT4X5: [u8; 80] = [
@@ -62,13 +517,36 @@ const FONT4X5: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
+
+/// The SUPER-CHIP big digits, A to F drawn in the same style as they
+/// aren't in the original font
+const FONT8X10: [u8; 160] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x3C, 0x7E, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFE, 0xC3, 0xC3, 0xFE, 0xFE, 0xC3, 0xC3, 0xFE, 0xFC, // B
+    0x3C, 0x7E, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0x7E, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
 impl CpuBus for Bus {
+    /// The addresses past the memory wrap around, like I + N
     fn read_byte(&self, addr: u16) -> u8 {
-        self.memory[addr as usize]
+        self.memory[addr as usize % self.memory.len()]
     }
 
     fn write_byte(&mut self, addr: u16, byte: u8) {
-        self.memory[addr as usize] = byte;
+        let len = self.memory.len();
+        self.memory[addr as usize % len] = byte;
     }
 
     fn read_keypad(&self, key: u8) -> bool {
@@ -76,20 +554,74 @@ impl CpuBus for Bus {
     }
 
     fn clear_screen(&mut self) {
-        for w in 0..DISPLAY_WIDTH {
-            for h in 0..DISPLAY_HEIGHT {
-                self.vram[w][h] = false;
-            }
-        }
+        self.selected_planes().for_each(Plane::clear);
     }
 
+    /// Set when it is on one of the selected planes
     fn read_screen(&self, x: u8, y: u8) -> bool {
-        self.vram[x as usize % DISPLAY_WIDTH][y as usize % DISPLAY_HEIGHT]
+        self.planes
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.selected & 1 << index != 0)
+            .any(|(_, plane)| plane.read(x, y))
     }
 
     fn write_screen(&mut self, x: u8, y: u8, pixel: bool) {
-        self.vram[x as usize % DISPLAY_WIDTH][y as usize % DISPLAY_HEIGHT] =
-            pixel;
+        self.selected_planes()
+            .for_each(|plane| plane.write(x, y, pixel));
+    }
+
+    fn draw_sprite_row(&mut self, x: u8, y: u8, line: u8) -> bool {
+        self.selected_planes().fold(false, |collision, plane| {
+            plane.draw_row(x, y, line) | collision
+        })
+    }
+
+    fn screen_size(&self) -> (u8, u8) {
+        match self.planes[0].hires {
+            Some(_) => (HIRES_WIDTH as u8, HIRES_HEIGHT as u8),
+            None => (DISPLAY_WIDTH as u8, DISPLAY_HEIGHT as u8),
+        }
+    }
+
+    /// On all the planes
+    fn set_hires(&mut self, hires: bool) {
+        for plane in &mut self.planes {
+            plane.set_hires(hires);
+        }
+    }
+
+    fn scroll_down(&mut self, n: u8) {
+        self.selected_planes()
+            .for_each(|plane| plane.scroll_down(n));
+    }
+
+    fn scroll_left(&mut self) {
+        self.selected_planes().for_each(Plane::scroll_left);
+    }
+
+    fn scroll_right(&mut self) {
+        self.selected_planes().for_each(Plane::scroll_right);
+    }
+
+    fn planes(&self) -> u8 {
+        self.selected
+    }
+
+    fn select_planes(&mut self, planes: u8) {
+        self.selected = planes & ((1 << PLANES) - 1);
+    }
+
+    fn scroll_up(&mut self, n: u8) {
+        self.selected_planes().for_each(|plane| plane.scroll_up(n));
+    }
+
+    fn write_audio_pattern(&mut self, pattern: [u8; AUDIO_PATTERN_SIZE]) {
+        self.audio_pattern = Some(pattern);
+    }
+
+    fn write_pitch(&mut self, pitch: u8) {
+        self.pitch = pitch;
     }
 
     fn read_timer(&self) -> u8 {
@@ -104,3 +636,260 @@ impl CpuBus for Bus {
         self.beep = value;
     }
 }
+
+impl Snapshot for Bus {
+    fn save(&self, state: &mut StateWriter) {
+        state.bool(self.is_xo_chip());
+        state.bytes(&self.memory);
+        state.bits(self.vram().iter().flatten().copied());
+        state.bits(self.keys);
+        state.u8(self.delay);
+        state.u8(self.beep);
+        save_hires(state, self.hires_rows());
+
+        // the XO-CHIP second plane and sound
+        state.u8(self.selected);
+        for &row in self.plane_rows(1) {
+            state.u64(row);
+        }
+        save_hires(state, self.plane_hires_rows(1));
+        state.bool(self.audio_pattern.is_some());
+        state.bytes(&self.audio_pattern.unwrap_or_default());
+        state.u8(self.pitch);
+    }
+
+    fn load(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let len = match state.bool()? {
+            true => XO_MEMORY_SIZE,
+            false => MEMORY_SIZE,
+        };
+        self.memory = state.bytes(len)?.into();
+
+        // by column then row, as before the screen was packed
+        let vram = state.bits(DISPLAY_WIDTH * DISPLAY_HEIGHT)?;
+        for (index, bit) in vram.into_iter().enumerate() {
+            self.set_pixel(index / DISPLAY_HEIGHT, index % DISPLAY_HEIGHT, bit);
+        }
+        for (key, bit) in self.keys.iter_mut().zip(state.bits(KEYPAD_SIZE)?) {
+            *key = bit;
+        }
+        self.delay = state.u8()?;
+        self.beep = state.u8()?;
+        self.planes[0].hires = load_hires(state)?;
+
+        self.selected = match state.u8()? {
+            selected if selected < 1 << PLANES => selected,
+            _ => return Err(StateError::InvalidValue("planes")),
+        };
+        for row in &mut self.planes[1].rows {
+            *row = state.u64()?;
+        }
+        self.planes[1].hires = load_hires(state)?;
+        if self.planes[1].hires.is_some() != self.planes[0].hires.is_some() {
+            return Err(StateError::InvalidValue("planes"));
+        }
+        let loaded = state.bool()?;
+        let mut pattern = [0; AUDIO_PATTERN_SIZE];
+        pattern.copy_from_slice(state.bytes(AUDIO_PATTERN_SIZE)?);
+        self.audio_pattern = loaded.then_some(pattern);
+        self.pitch = state.u8()?;
+
+        Ok(())
+    }
+}
+
+/// A 128x64 screen pixel by pixel, row by row, after whether it is on
+/// It is blank while off, the states of a machine all have the same size
+fn save_hires(state: &mut StateWriter, hires: Option<&HiresRows>) {
+    let rows = hires.copied().unwrap_or([0; HIRES_HEIGHT]);
+    state.bool(hires.is_some());
+    state.bits(rows.into_iter().flat_map(|row| {
+        (0..HIRES_WIDTH).map(move |x| row & hires_pixel_bit(x) != 0)
+    }));
+}
+
+fn load_hires(
+    state: &mut StateReader,
+) -> Result<Option<Box<HiresRows>>, StateError> {
+    let on = state.bool()?;
+    let bits = state.bits(HIRES_WIDTH * HIRES_HEIGHT)?;
+    let mut hires = Box::new([0; HIRES_HEIGHT]);
+    for (index, bit) in bits.into_iter().enumerate() {
+        if bit {
+            hires[index / HIRES_WIDTH] |= hires_pixel_bit(index % HIRES_WIDTH);
+        }
+    }
+
+    Ok(on.then_some(hires))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fnv1a(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    #[test]
+    fn test_frame_hash() {
+        let mut bus = Bus::new(Rom::from_bytes(vec![]));
+        // 64 and 32, then 8 bytes per row
+        let mut packed = vec![64, 0, 32, 0];
+        packed.resize(4 + 8 * DISPLAY_HEIGHT, 0);
+        assert_eq!(bus.frame_hash(), fnv1a(&packed));
+
+        // the second row, its ninth pixel
+        bus.set_pixel(8, 1, true);
+        packed[4 + 8 + 1] = 0x80;
+        assert_eq!(bus.frame_hash(), fnv1a(&packed));
+        assert_eq!(frame_hash(&bus.vram()), bus.frame_hash());
+    }
+
+    #[test]
+    fn test_try_new() {
+        let rom = Rom::from_bytes(vec![0xAB; MEMORY_SIZE - 0x1FF]);
+        assert!(matches!(
+            Bus::try_new(rom.clone()),
+            Err(Error::RomTooBig {
+                size: 0xE01,
+                max: 0xE00
+            })
+        ));
+        assert_eq!(Bus::new(rom.clone()).memory()[MEMORY_SIZE - 1], 0xAB);
+
+        let bus = Bus::try_new_xo_chip(rom).unwrap();
+        assert_eq!(bus.memory()[MEMORY_SIZE], 0xAB);
+        assert_eq!(bus.memory()[MEMORY_SIZE + 1], 0);
+    }
+
+    #[test]
+    fn test_framebuffer() {
+        let mut bus = Bus::new_xo_chip(Rom::from_bytes(vec![]));
+        bus.set_pixel(3, 1, true);
+        let frame = bus.framebuffer();
+        assert_eq!((frame.width, frame.height), (64, 32));
+        assert_eq!(frame.pixels[64 + 3], 1);
+        assert_eq!(frame.pixels.iter().filter(|&&pixel| pixel != 0).count(), 1);
+
+        bus.select_planes(2);
+        bus.write_screen(3, 1, true);
+        assert_eq!(bus.framebuffer().pixel(3, 1), 3);
+
+        bus.set_hires(true);
+        bus.write_screen(127, 63, true);
+        assert_eq!(bus.resolution(), (128, 64));
+        let frame = bus.framebuffer();
+        assert_eq!((frame.width, frame.height), (128, 64));
+        assert_eq!(frame.pixel(127, 63), 2);
+
+        // the buffer of a bigger frame is reused
+        let mut reused = frame.clone();
+        let capacity = reused.pixels.capacity();
+        bus.set_hires(false);
+        bus.framebuffer_into(&mut reused);
+        assert_eq!(reused, bus.framebuffer());
+        assert_eq!(reused.pixels.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_pixels() {
+        let mut bus = Bus::new(Rom::from_bytes(vec![]));
+        bus.set_pixel(0, 2, true);
+        bus.set_pixel(63, 2, true);
+        assert_eq!(bus.rows()[2], 0x8000_0000_0000_0001);
+
+        let vram = bus.vram();
+        assert!(vram[0][2] && vram[63][2] && !vram[1][2]);
+        assert!(bus.pixel(63, 2));
+
+        bus.set_pixel(0, 2, false);
+        assert!(!bus.pixel(0, 2));
+    }
+
+    #[test]
+    fn test_draw_sprite_row() {
+        let mut bus = Bus::new(Rom::from_bytes(vec![]));
+
+        // wraps around the right edge and the bottom
+        assert!(!bus.draw_sprite_row(60, 33, 0b1100_0011));
+        assert_eq!(bus.rows()[1], 0x3000_0000_0000_000C);
+
+        assert!(bus.draw_sprite_row(0, 1, 0b0010_0000));
+        assert_eq!(bus.rows()[1], 0x1000_0000_0000_000C);
+    }
+
+    #[test]
+    fn test_hires() {
+        let mut bus = Bus::new(Rom::from_bytes(vec![]));
+        bus.set_pixel(1, 0, true);
+        let lores = bus.frame_hash();
+
+        // the picture is kept at twice the size
+        bus.set_hires(true);
+        assert_eq!(bus.screen_size(), (128, 64));
+        assert_eq!(bus.resolution(), (128, 64));
+        assert_eq!(bus.hires_rows().unwrap()[1], 0x3 << 124);
+        assert_ne!(bus.frame_hash(), lores);
+
+        // wraps around the right edge, the rows show it at half size
+        assert!(!bus.draw_sprite_row(124, 5, 0b1100_0011));
+        assert_eq!(bus.hires_rows().unwrap()[5], 0x3 << 124 | 0xC);
+        assert_eq!(bus.rows()[2], 0x4000_0000_0000_0002);
+        assert!(bus.read_screen(124, 5) && !bus.read_screen(120, 5));
+
+        bus.scroll_right();
+        assert_eq!(bus.hires_rows().unwrap()[5], 0x3 << 120);
+        bus.scroll_down(3);
+        assert_eq!(bus.hires_rows().unwrap()[8], 0x3 << 120);
+        assert_eq!(bus.rows()[4], 0x1000_0000_0000_0000);
+
+        bus.clear_screen();
+        assert_eq!(bus.hires_rows(), Some(&[0; HIRES_HEIGHT]));
+        bus.set_hires(false);
+        assert_eq!(bus.hires_rows(), None);
+        assert_eq!(bus.screen_size(), (64, 32));
+    }
+
+    #[test]
+    fn test_planes() {
+        let mut bus = Bus::new_xo_chip(Rom::from_bytes(vec![0xAB; 0x2000]));
+        assert_eq!(bus.memory()[0x21FF], 0xAB);
+        let first = bus.frame_hash();
+
+        bus.select_planes(2);
+        assert!(!bus.draw_sprite_row(0, 31, 0b1000_0001));
+        assert_eq!(bus.rows()[31], 0);
+        assert_eq!(bus.plane_rows(1)[31], 1 << 63 | 1 << 56);
+        assert_ne!(bus.frame_hash(), first);
+
+        // a pixel of either plane collides
+        bus.select_planes(3);
+        assert!(bus.draw_sprite_row(0, 31, 0b1000_0000));
+        assert!(bus.read_screen(7, 31));
+        bus.scroll_up(30);
+        assert_eq!(bus.rows()[1], 1 << 63);
+        assert_eq!(bus.plane_rows(1)[1], 1 << 56);
+
+        bus.select_planes(1);
+        bus.clear_screen();
+        assert_eq!(bus.rows()[1], 0);
+        assert_eq!(bus.plane_rows(1)[1], 1 << 56);
+
+        assert_eq!(bus.sample_rate(), 4000.0);
+        bus.write_pitch(112);
+        assert_eq!(bus.sample_rate(), 8000.0);
+    }
+
+    #[test]
+    fn test_big_font() {
+        let bus = Bus::new(Rom::from_bytes(vec![]));
+        let start = BIG_SPRITE_ADDR as usize;
+        // the 0, and the F at the end
+        assert_eq!(bus.memory()[start], 0x3C);
+        assert_eq!(bus.memory()[start + 159], 0xC0);
+        assert!(start + FONT8X10.len() <= 0x200);
+    }
+}
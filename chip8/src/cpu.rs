@@ -1,19 +1,147 @@
+use std::sync::Arc;
+
 use log::{trace, warn};
 use rand::random;
 
-use crate::bus::KEYPAD_SIZE;
+use crate::{
+    bus::{AUDIO_PATTERN_SIZE, DISPLAY_HEIGHT, DISPLAY_WIDTH, KEYPAD_SIZE},
+    decode::{self, Instruction},
+    state::{Snapshot, StateError, StateReader, StateWriter},
+};
 
-const V_SIZE: usize = 16;
+pub const V_SIZE: usize = 16;
 const STACK_SIZE: usize = 16;
 pub const SPRITE_ADDR: u16 = 0x000;
+/// The SUPER-CHIP 8x10 digits, after the 4x5 ones
+pub const BIG_SPRITE_ADDR: u16 = 0x050;
+/// Flags saved by FX75, the HP48 has 8
+pub const RPL_SIZE: usize = 8;
 const PC_INIT: u16 = 0x0200;
 
+/// The instruction run by `Cpu::step`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StepInfo {
+    /// Address of the instruction
+    pub pc: u16,
+    pub opcode: u16,
+    pub instruction: Instruction,
+    /// Bit N is set when VN changed
+    pub registers: u16,
+    /// I changed
+    pub index: bool,
+}
+
+impl StepInfo {
+    /// VN changed
+    pub fn changed(&self, n: usize) -> bool {
+        self.registers & 1 << n != 0
+    }
+}
+
+/// An instruction run, given to the tracer of `Cpu::set_tracer`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// Address of the instruction
+    pub pc: u16,
+    pub opcode: u16,
+    /// V0..VF once the instruction ran
+    pub registers: [u8; V_SIZE],
+    /// I once the instruction ran
+    pub index: u16,
+}
+
+type Tracer = Arc<dyn Fn(TraceEvent) + Send + Sync>;
+
+/// Selects which key is stored by FX0A when several keys are involved
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum KeyWaitPolicy {
+    /// Store the lowest pressed key as soon as any key is down
+    #[default]
+    Lowest,
+    /// Store the held key that was pressed last
+    MostRecentlyPressed,
+    /// Store the first key released after being pressed, like the COSMAC VIP
+    FirstReleased,
+}
+
+/// Behaviors the chip8 variants disagree on, each rom expects the ones of
+/// the interpreter it was written for
+/// The default is what this emulator always did, close to the XO-CHIP
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VX in place, VY is ignored
+    pub shift_vx: bool,
+    /// FX55 and FX65 leave I unchanged
+    pub keep_i: bool,
+    /// BNNN jumps to NNN plus VX, X being the high nibble of NNN
+    pub jump_vx: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF
+    pub vf_reset: bool,
+    /// Sprites are cut at the edges of the screen instead of wrapping
+    /// around
+    pub clip_sprites: bool,
+    /// DXYN waits for the display to refresh, at most one per frame
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// The original interpreter of the COSMAC VIP
+    pub const VIP: Self = Self {
+        shift_vx: false,
+        keep_i: false,
+        jump_vx: false,
+        vf_reset: true,
+        clip_sprites: true,
+        display_wait: true,
+    };
+
+    /// The SUPER-CHIP 1.1 of the HP48
+    pub const SUPER_CHIP: Self = Self {
+        shift_vx: true,
+        keep_i: true,
+        jump_vx: true,
+        vf_reset: false,
+        clip_sprites: true,
+        display_wait: false,
+    };
+
+    pub const XO_CHIP: Self = Self {
+        shift_vx: false,
+        keep_i: false,
+        jump_vx: false,
+        vf_reset: false,
+        clip_sprites: false,
+        display_wait: false,
+    };
+}
+
+#[derive(Clone)]
 pub struct Cpu {
-    pc: u16,
-    i: u16,
-    v: [u8; V_SIZE], // v0..vf registers
-    stack: Vec<u16>,
+    // written back by the jit after a block
+    pub(crate) pc: u16,
+    pub(crate) i: u16,
+    pub(crate) v: [u8; V_SIZE], // v0..vf registers
+    stack: [u16; STACK_SIZE],
+    depth: usize, // return addresses in the stack
     key_await: Option<u8>,
+    key_wait_policy: KeyWaitPolicy,
+    keys_held: [bool; KEYPAD_SIZE],
+    key_stamps: [u64; KEYPAD_SIZE], // press order, used by MostRecentlyPressed
+    key_stamp: u64,
+    rng: u64,            // xorshift state, saved so runs can be replayed
+    rpl: [u8; RPL_SIZE], // kept by reset, like the HP48 did
+    xo_chip: bool,
+    quirks: Quirks,
+    vblank_wait: bool, // a DXYN waits for the display, with display_wait
+    // by opcode, looked up instead of decoding each instruction
+    decoded: &'static [Instruction],
+    tracer: Option<Tracer>, // shared by the clones
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Cpu {
@@ -22,14 +150,145 @@ impl Cpu {
             pc: PC_INIT,
             i: 0,
             v: [0; V_SIZE],
-            stack: Vec::with_capacity(STACK_SIZE),
+            stack: [0; STACK_SIZE],
+            depth: 0,
             key_await: None,
+            key_wait_policy: KeyWaitPolicy::default(),
+            keys_held: [false; KEYPAD_SIZE],
+            key_stamps: [0; KEYPAD_SIZE],
+            key_stamp: 0,
+            rng: random::<u64>() | 1,
+            rpl: [0; RPL_SIZE],
+            xo_chip: false,
+            quirks: Quirks::default(),
+            vblank_wait: false,
+            decoded: decode::table(),
+            tracer: None,
+        }
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn index(&self) -> u16 {
+        self.i
+    }
+
+    pub fn registers(&self) -> &[u8; V_SIZE] {
+        &self.v
+    }
+
+    /// Poke the registers, with the `debug` feature
+    #[cfg(feature = "debug")]
+    pub fn registers_mut(&mut self) -> &mut [u8; V_SIZE] {
+        &mut self.v
+    }
+
+    #[cfg(feature = "debug")]
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    #[cfg(feature = "debug")]
+    pub fn set_index(&mut self, index: u16) {
+        self.i = index;
+    }
+
+    /// Return addresses of the running subroutines, innermost last
+    pub fn call_stack(&self) -> &[u16] {
+        &self.stack[..self.depth]
+    }
+
+    /// Register waiting for a key press (FX0A), if any
+    pub fn key_await(&self) -> Option<u8> {
+        self.key_await
+    }
+
+    /// A DXYN waits for the next frame, with the display wait quirk
+    pub fn vblank_wait(&self) -> bool {
+        self.vblank_wait
+    }
+
+    pub fn key_wait_policy(&self) -> KeyWaitPolicy {
+        self.key_wait_policy
+    }
+
+    pub fn set_key_wait_policy(&mut self, policy: KeyWaitPolicy) {
+        self.key_wait_policy = policy;
+    }
+
+    /// Flags of FX75 and FX85, where the SUPER-CHIP games keep their scores
+    pub fn rpl_flags(&self) -> &[u8; RPL_SIZE] {
+        &self.rpl
+    }
+
+    /// Whether the XO-CHIP instructions run, the others ignore them
+    pub fn is_xo_chip(&self) -> bool {
+        self.xo_chip
+    }
+
+    /// The XO-CHIP addresses the 64KB of `Bus::new_xo_chip`, use
+    /// `Machine::set_xo_chip` to switch both
+    pub fn set_xo_chip(&mut self, xo_chip: bool) {
+        self.xo_chip = xo_chip;
+        self.pc &= self.address_mask();
+        self.i &= self.address_mask();
+    }
+
+    /// The pc and I wrap around the memory
+    fn address_mask(&self) -> u16 {
+        match self.xo_chip {
+            true => 0xFFFF,
+            false => 0x0FFF,
         }
     }
 
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Call `tracer` after each instruction, the blocks run by the jit
+    /// aren't traced
+    pub fn set_tracer(
+        &mut self,
+        tracer: impl Fn(TraceEvent) + Send + Sync + 'static,
+    ) {
+        self.tracer = Some(Arc::new(tracer));
+    }
+
+    pub fn clear_tracer(&mut self) {
+        self.tracer = None;
+    }
+
+    /// The display refreshed, called at the end of each frame; a DXYN
+    /// waiting for it goes on
+    pub fn vblank(&mut self) {
+        self.vblank_wait = false;
+    }
+
+    /// Seed the CXNN random numbers, two cpus with the same seed and inputs
+    /// run the same way
+    pub fn set_seed(&mut self, seed: u64) {
+        // xorshift never leaves a zero state
+        self.rng = seed | 1;
+    }
+
+    fn next_random(&mut self) -> u8 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+
+        (self.rng >> 56) as u8
+    }
+
     fn pc_read_byte(&mut self, bus: &impl CpuBus) -> u8 {
         let byte = bus.read_byte(self.pc);
-        self.pc = (self.pc + 1) & 0x0FFF;
+        self.pc = self.pc.wrapping_add(1) & self.address_mask();
 
         byte
     }
@@ -42,23 +301,98 @@ impl Cpu {
     }
 
     pub fn emulate(&mut self, bus: &mut impl CpuBus) {
-        if self.key_await.is_some() {
-            let x = self.key_await.unwrap() as usize;
-
-            for key in 0..KEYPAD_SIZE as u8 {
-                if bus.read_keypad(key) {
-                    self.v[x] = key;
-                    self.key_await = None;
-                    break;
-                }
+        // press order must be known before FX0A starts waiting
+        if self.key_wait_policy == KeyWaitPolicy::MostRecentlyPressed {
+            self.update_keys_held(bus);
+        }
+
+        if self.vblank_wait {
+            return;
+        }
+
+        if let Some(x) = self.key_await {
+            if let Some(key) = self.poll_key_await(bus) {
+                self.v[x as usize] = key;
+                self.key_await = None;
             }
 
             return;
         }
 
+        let pc = self.pc;
         let opcode = self.pc_read_word(bus);
 
         self.execute(bus, opcode);
+
+        if let Some(tracer) = &self.tracer {
+            tracer(TraceEvent {
+                pc,
+                opcode,
+                registers: self.v,
+                index: self.i,
+            });
+        }
+    }
+
+    /// Like `emulate`, and tell what was run
+    /// None when no instruction ran: FX0A or a DXYN with the display wait
+    /// quirk are still waiting
+    pub fn step(&mut self, bus: &mut impl CpuBus) -> Option<StepInfo> {
+        let waiting = self.vblank_wait || self.key_await.is_some();
+        let pc = self.pc;
+        let opcode = u16::from_be_bytes([
+            bus.read_byte(pc),
+            bus.read_byte(pc.wrapping_add(1) & self.address_mask()),
+        ]);
+        let (v, i) = (self.v, self.i);
+
+        self.emulate(bus);
+        if waiting {
+            return None;
+        }
+
+        Some(StepInfo {
+            pc,
+            opcode,
+            instruction: self.decoded[opcode as usize],
+            registers: (0..V_SIZE)
+                .filter(|&n| v[n] != self.v[n])
+                .fold(0, |bits, n| bits | 1 << n),
+            index: i != self.i,
+        })
+    }
+
+    /// Return the key to store for FX0A, if any, according to the policy
+    fn poll_key_await(&mut self, bus: &impl CpuBus) -> Option<u8> {
+        match self.key_wait_policy {
+            KeyWaitPolicy::Lowest => {
+                (0..KEYPAD_SIZE as u8).find(|&key| bus.read_keypad(key))
+            }
+            KeyWaitPolicy::MostRecentlyPressed => (0..KEYPAD_SIZE as u8)
+                .filter(|&key| self.keys_held[key as usize])
+                .max_by_key(|&key| self.key_stamps[key as usize]),
+            KeyWaitPolicy::FirstReleased => {
+                let held = self.keys_held;
+                self.update_keys_held(bus);
+
+                (0..KEYPAD_SIZE as u8).find(|&key| {
+                    held[key as usize] && !self.keys_held[key as usize]
+                })
+            }
+        }
+    }
+
+    fn update_keys_held(&mut self, bus: &impl CpuBus) {
+        for key in 0..KEYPAD_SIZE {
+            let pressed = bus.read_keypad(key as u8);
+
+            if pressed && !self.keys_held[key] {
+                self.key_stamp += 1;
+                self.key_stamps[key] = self.key_stamp;
+            }
+
+            self.keys_held[key] = pressed;
+        }
     }
 
     pub fn reset(&mut self) {
@@ -67,62 +401,90 @@ impl Cpu {
         for x in 0..V_SIZE {
             self.v[x] = 0;
         }
-        self.stack.clear();
+        self.depth = 0;
         self.key_await = None;
+        self.vblank_wait = false;
+        self.keys_held = [false; KEYPAD_SIZE];
+        self.key_stamps = [0; KEYPAD_SIZE];
+        self.key_stamp = 0;
     }
 
     fn execute(&mut self, bus: &mut impl CpuBus, opcode: u16) {
-        let nibbles = (
-            ((opcode & 0xF000) >> 12) as u8,
-            ((opcode & 0x0F00) >> 8) as u8,
-            ((opcode & 0x00F0) >> 4) as u8,
-            (opcode & 0x000F) as u8,
-        );
-        let nnn = (opcode & 0x0FFF) as u16;
-        let nn = (opcode & 0x00FF) as u8;
-
-        trace!("${:04x} : {:04x}", self.pc - 2, opcode);
-
-        match nibbles {
-            (0x0, 0x0, 0xe, 0x0) => self.opcode_00e0(bus),
-            (0x0, 0x0, 0xe, 0xe) => self.opcode_00ee(),
-            (0x0, _, _, _) => self.opcode_0nnn(nnn),
-            (0x1, _, _, _) => self.opcode_1nnn(nnn),
-            (0x2, _, _, _) => self.opcode_2nnn(nnn),
-            (0x3, x, _, _) => self.opcode_3xnn(x, nn),
-            (0x4, x, _, _) => self.opcode_4xnn(x, nn),
-            (0x5, x, y, 0) => self.opcode_5xy0(x, y),
-            (0x6, x, _, _) => self.opcode_6xnn(x, nn),
-            (0x7, x, _, _) => self.opcode_7xnn(x, nn),
-            (0x8, x, y, 0x0) => self.opcode_8xy0(x, y),
-            (0x8, x, y, 0x1) => self.opcode_8xy1(x, y),
-            (0x8, x, y, 0x2) => self.opcode_8xy2(x, y),
-            (0x8, x, y, 0x3) => self.opcode_8xy3(x, y),
-            (0x8, x, y, 0x4) => self.opcode_8xy4(x, y),
-            (0x8, x, y, 0x5) => self.opcode_8xy5(x, y),
-            (0x8, x, y, 0x6) => self.opcode_8xy6(x, y),
-            (0x8, x, y, 0x7) => self.opcode_8xy7(x, y),
-            (0x8, x, y, 0xe) => self.opcode_8xye(x, y),
-            (0x9, x, y, 0x0) => self.opcode_9xy0(x, y),
-            (0xa, _, _, _) => self.opcode_annn(nnn),
-            (0xb, _, _, _) => self.opcode_bnnn(nnn),
-            (0xc, x, _, _) => self.opcode_cxnn(x, nn),
-            (0xd, x, y, n) => self.opcode_dxyn(x, y, n, bus),
-            (0xe, x, 0x9, 0xe) => self.opcode_ex9e(x, bus),
-            (0xe, x, 0xa, 0x1) => self.opcode_exa1(x, bus),
-            (0xf, x, 0x0, 0x7) => self.opcode_fx07(x, bus),
-            (0xf, x, 0x0, 0xa) => self.opcode_fx0a(x),
-            (0xf, x, 0x1, 0x5) => self.opcode_fx15(x, bus),
-            (0xf, x, 0x1, 0x8) => self.opcode_fx18(x, bus),
-            (0xf, x, 0x1, 0xe) => self.opcode_fx1e(x),
-            (0xf, x, 0x2, 0x9) => self.opcode_fx29(x),
-            (0xf, x, 0x3, 0x3) => self.opcode_fx33(x, bus),
-            (0xf, x, 0x5, 0x5) => self.opcode_fx55(x, bus),
-            (0xf, x, 0x6, 0x5) => self.opcode_fx65(x, bus),
-            _ => {}
+        let pc = self.pc.wrapping_sub(2) & self.address_mask();
+        trace!("${:04x} : {:04x}", pc, opcode);
+
+        let instruction = self.decoded[opcode as usize];
+        match instruction {
+            _ if instruction.is_xo_chip() && !self.xo_chip => {}
+            Instruction::Cls => self.opcode_00e0(bus),
+            Instruction::Ret => self.opcode_00ee(),
+            Instruction::Sys(nnn) => self.opcode_0nnn(nnn),
+            Instruction::Jp(nnn) => self.opcode_1nnn(nnn),
+            Instruction::Call(nnn) => self.opcode_2nnn(nnn),
+            Instruction::SeByte(x, nn) => self.opcode_3xnn(x, nn, bus),
+            Instruction::SneByte(x, nn) => self.opcode_4xnn(x, nn, bus),
+            Instruction::SeReg(x, y) => self.opcode_5xy0(x, y, bus),
+            Instruction::LdByte(x, nn) => self.opcode_6xnn(x, nn),
+            Instruction::AddByte(x, nn) => self.opcode_7xnn(x, nn),
+            Instruction::LdReg(x, y) => self.opcode_8xy0(x, y),
+            Instruction::Or(x, y) => self.opcode_8xy1(x, y),
+            Instruction::And(x, y) => self.opcode_8xy2(x, y),
+            Instruction::Xor(x, y) => self.opcode_8xy3(x, y),
+            Instruction::AddReg(x, y) => self.opcode_8xy4(x, y),
+            Instruction::Sub(x, y) => self.opcode_8xy5(x, y),
+            Instruction::Shr(x, y) => self.opcode_8xy6(x, y),
+            Instruction::Subn(x, y) => self.opcode_8xy7(x, y),
+            Instruction::Shl(x, y) => self.opcode_8xye(x, y),
+            Instruction::SneReg(x, y) => self.opcode_9xy0(x, y, bus),
+            Instruction::LdI(nnn) => self.opcode_annn(nnn),
+            Instruction::JpV0(nnn) => self.opcode_bnnn(nnn),
+            Instruction::Rnd(x, nn) => self.opcode_cxnn(x, nn),
+            Instruction::Drw(x, y, n) => self.opcode_dxyn(x, y, n, bus),
+            Instruction::Skp(x) => self.opcode_ex9e(x, bus),
+            Instruction::Sknp(x) => self.opcode_exa1(x, bus),
+            Instruction::LdVxDt(x) => self.opcode_fx07(x, bus),
+            Instruction::LdVxK(x) => self.opcode_fx0a(x),
+            Instruction::LdDtVx(x) => self.opcode_fx15(x, bus),
+            Instruction::LdStVx(x) => self.opcode_fx18(x, bus),
+            Instruction::AddIVx(x) => self.opcode_fx1e(x),
+            Instruction::LdFVx(x) => self.opcode_fx29(x),
+            Instruction::LdBVx(x) => self.opcode_fx33(x, bus),
+            Instruction::LdIVx(x) => self.opcode_fx55(x, bus),
+            Instruction::LdVxI(x) => self.opcode_fx65(x, bus),
+            Instruction::Scd(n) => self.opcode_00cn(n, bus),
+            Instruction::Scr => self.opcode_00fb(bus),
+            Instruction::Scl => self.opcode_00fc(bus),
+            Instruction::Exit => self.opcode_00fd(),
+            Instruction::Low => self.opcode_00fe(bus),
+            Instruction::High => self.opcode_00ff(bus),
+            Instruction::LdHfVx(x) => self.opcode_fx30(x),
+            Instruction::LdRVx(x) => self.opcode_fx75(x),
+            Instruction::LdVxR(x) => self.opcode_fx85(x),
+            Instruction::Scu(n) => self.opcode_00dn(n, bus),
+            Instruction::LdIRange(x, y) => self.opcode_5xy2(x, y, bus),
+            Instruction::LdRangeI(x, y) => self.opcode_5xy3(x, y, bus),
+            Instruction::LdILong => self.opcode_f000(bus),
+            Instruction::Plane(n) => self.opcode_fn01(n, bus),
+            Instruction::Audio => self.opcode_f002(bus),
+            Instruction::LdPitchVx(x) => self.opcode_fx3a(x, bus),
+            Instruction::Unknown => {}
         }
     }
 
+    /// Skip the next instruction, the pc wraps around the memory
+    /// The XO-CHIP F000 NNNN is skipped whole
+    fn skip(&mut self, bus: &impl CpuBus) {
+        let next = u16::from_be_bytes([
+            bus.read_byte(self.pc),
+            bus.read_byte(self.pc.wrapping_add(1)),
+        ]);
+        let len = match next {
+            0xF000 if self.xo_chip => 4,
+            _ => 2,
+        };
+        self.pc = self.pc.wrapping_add(len) & self.address_mask();
+    }
+
     /// Execute machine language subroutine at address NNN
     fn opcode_0nnn(&mut self, nnn: u16) {
         trace!("not implemented, call {}", nnn);
@@ -135,43 +497,50 @@ impl Cpu {
 
     /// Return from a subroutine
     fn opcode_00ee(&mut self) {
-        match self.stack.pop() {
-            Some(addr) => self.pc = addr,
-            None => warn!("unable to return from subroutine"),
+        if self.depth == 0 {
+            warn!("unable to return from subroutine");
+            return;
         }
+        self.depth -= 1;
+        self.pc = self.stack[self.depth];
     }
 
     /// Jump to address NNN
     fn opcode_1nnn(&mut self, nnn: u16) {
-        self.pc = nnn & 0x0FFF;
+        self.pc = nnn;
     }
 
     /// Execute subroutine starting at address NNN
     fn opcode_2nnn(&mut self, nnn: u16) {
-        self.stack.push(self.pc);
+        if self.depth >= STACK_SIZE {
+            warn!("stack overflow, call {:03X} ignored", nnn);
+            return;
+        }
+        self.stack[self.depth] = self.pc;
+        self.depth += 1;
         self.pc = nnn;
     }
 
     /// Skip the following instruction if the value of register VX equals NN
-    fn opcode_3xnn(&mut self, x: u8, nn: u8) {
+    fn opcode_3xnn(&mut self, x: u8, nn: u8, bus: &impl CpuBus) {
         if self.v[x as usize] == nn {
-            self.pc = self.pc.wrapping_add(2);
+            self.skip(bus);
         }
     }
 
     /// Skip the following instruction if the value of register VX is not equal
     /// to NN
-    fn opcode_4xnn(&mut self, x: u8, nn: u8) {
+    fn opcode_4xnn(&mut self, x: u8, nn: u8, bus: &impl CpuBus) {
         if self.v[x as usize] != nn {
-            self.pc = self.pc.wrapping_add(2);
+            self.skip(bus);
         }
     }
 
     /// Skip the following instruction if the value of register VX is equal to
     /// the value of register VY
-    fn opcode_5xy0(&mut self, x: u8, y: u8) {
+    fn opcode_5xy0(&mut self, x: u8, y: u8, bus: &impl CpuBus) {
         if self.v[x as usize] == self.v[y as usize] {
-            self.pc = self.pc.wrapping_add(2);
+            self.skip(bus);
         }
     }
 
@@ -194,16 +563,26 @@ impl Cpu {
     /// Set VX to VX OR VY
     fn opcode_8xy1(&mut self, x: u8, y: u8) {
         self.v[x as usize] |= self.v[y as usize];
+        self.logic_vf_reset();
     }
 
     /// Set VX to VX AND VY
     fn opcode_8xy2(&mut self, x: u8, y: u8) {
         self.v[x as usize] &= self.v[y as usize];
+        self.logic_vf_reset();
     }
 
     /// Set VX to VX XOR VY
     fn opcode_8xy3(&mut self, x: u8, y: u8) {
         self.v[x as usize] ^= self.v[y as usize];
+        self.logic_vf_reset();
+    }
+
+    /// VF is reset by the logic operations of the COSMAC VIP
+    fn logic_vf_reset(&mut self) {
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
     }
 
     /// Add the value of register VY to register VX
@@ -233,12 +612,14 @@ impl Cpu {
     // Store the value of register VY shifted right one bit in register VX
     // Set register VF to the least significant bit prior to the shift
     // VY is unchanged
+    // VX itself is shifted with the shift_vx quirk
     fn opcode_8xy6(&mut self, x: u8, y: u8) {
         let x = x as usize;
-        let y = y as usize;
+        let y = if self.quirks.shift_vx { x } else { y as usize };
 
-        self.v[x] = self.v[y] >> 1;
-        self.v[0xF] = self.v[y] & 0x01;
+        let vy = self.v[y];
+        self.v[x] = vy >> 1;
+        self.v[0xF] = vy & 0x01;
     }
 
     /// Set register VX to the value of VY minus VX
@@ -256,19 +637,21 @@ impl Cpu {
     /// Store the value of register VY shifted left one bit in register VX
     /// Set register VF to the most significant bit prior to the shift
     /// VY is unchanged
+    /// VX itself is shifted with the shift_vx quirk
     fn opcode_8xye(&mut self, x: u8, y: u8) {
         let x = x as usize;
-        let y = y as usize;
+        let y = if self.quirks.shift_vx { x } else { y as usize };
 
-        self.v[x] = self.v[y] << 1;
-        self.v[0xF] = (self.v[y] & 0x80) >> 7;
+        let vy = self.v[y];
+        self.v[x] = vy << 1;
+        self.v[0xF] = (vy & 0x80) >> 7;
     }
 
     /// Skip the following instruction if the value of register VX is not
     /// equal to the value of register VY
-    fn opcode_9xy0(&mut self, x: u8, y: u8) {
+    fn opcode_9xy0(&mut self, x: u8, y: u8, bus: &impl CpuBus) {
         if self.v[x as usize] != self.v[y as usize] {
-            self.pc = self.pc.wrapping_add(2);
+            self.skip(bus);
         }
     }
 
@@ -278,39 +661,101 @@ impl Cpu {
     }
 
     /// Jump to address NNN + V0
+    /// NNN + VX with the jump_vx quirk, X being the high nibble of NNN
     fn opcode_bnnn(&mut self, nnn: u16) {
-        self.pc = nnn.wrapping_add(self.v[0] as u16);
+        let x = match self.quirks.jump_vx {
+            true => (nnn >> 8) as usize,
+            false => 0,
+        };
+        self.pc = nnn.wrapping_add(self.v[x] as u16) & self.address_mask();
     }
 
     /// Set VX to a random number with a mask of NN
     fn opcode_cxnn(&mut self, x: u8, nn: u8) {
-        self.v[x as usize] = random::<u8>() & nn;
+        self.v[x as usize] = self.next_random() & nn;
     }
 
     /// Draw a sprite at position VX, VY with N bytes of sprite data starting
     /// at the address stored in I
+    /// N = 0 draws a 16x16 sprite of 32 bytes, two per row (SUPER-CHIP)
+    /// Each selected plane is drawn with the sprite after the one of the
+    /// previous plane (XO-CHIP)
     /// Set VF to 01 if any set pixels are changed to unset, and 00 otherwise
+    /// The cpu then waits for the display with the display_wait quirk
     fn opcode_dxyn(&mut self, x: u8, y: u8, n: u8, bus: &mut impl CpuBus) {
+        // read before VF is cleared, VF can hold a coordinate
+        let (vx, vy) = (self.v[x as usize], self.v[y as usize]);
         self.v[0xF] = 0x0;
+        self.vblank_wait = self.quirks.display_wait;
 
-        for h in 0..n as u8 {
-            let sprite_line = bus.read_byte(self.i.wrapping_add(h as u16));
-            let y = self.v[y as usize].wrapping_add(h);
-
-            for w in 0..8_u8 {
-                let x = self.v[x as usize].wrapping_add(w);
-
-                let toggle = (sprite_line << w) & 0x80 > 0;
+        let planes = bus.planes();
+        if planes.count_ones() <= 1 {
+            self.draw_sprite(vx, vy, n, self.i, bus);
+            return;
+        }
 
-                if toggle {
-                    let pixel = bus.read_screen(x, y);
+        let len = match n {
+            0 => 32,
+            n => n as u16,
+        };
+        let mut addr = self.i;
+        for plane in (0..8).map(|p| 1 << p).filter(|p| planes & p != 0) {
+            bus.select_planes(plane);
+            self.draw_sprite(vx, vy, n, addr, bus);
+            addr = addr.wrapping_add(len);
+        }
+        bus.select_planes(planes);
+    }
+
+    /// The sprite of DXYN at `addr`, on the selected planes
+    fn draw_sprite(
+        &mut self,
+        vx: u8,
+        vy: u8,
+        n: u8,
+        addr: u16,
+        bus: &mut impl CpuBus,
+    ) {
+        let (width, height) = bus.screen_size();
+        let clip = self.quirks.clip_sprites;
+        // a clipped sprite starts on the screen, and is cut at its edges
+        let (vx, vy) = match clip {
+            true => (vx % width, vy % height),
+            false => (vx, vy),
+        };
+        let rows = match n {
+            0 => 16,
+            n => n,
+        };
+
+        for h in 0..rows {
+            let y = vy.wrapping_add(h);
+            if clip && y >= height {
+                break;
+            }
 
-                    if pixel {
-                        self.v[0xF] = 0x1;
-                    }
+            let collision = if n == 0 {
+                let addr = addr.wrapping_add(h as u16 * 2);
+                let mut sprite_line = u16::from_be_bytes([
+                    bus.read_byte(addr),
+                    bus.read_byte(addr.wrapping_add(1)),
+                ]);
+                if clip {
+                    sprite_line &= clip_mask(vx, width, 16);
+                }
 
-                    bus.write_screen(x, y, pixel ^ true);
+                bus.draw_sprite_row16(vx, y, sprite_line)
+            } else {
+                let mut sprite_line =
+                    bus.read_byte(addr.wrapping_add(h as u16));
+                if clip {
+                    sprite_line &= clip_mask(vx, width, 8) as u8;
                 }
+
+                bus.draw_sprite_row(vx, y, sprite_line)
+            };
+            if collision {
+                self.v[0xF] = 0x1;
             }
         }
     }
@@ -318,16 +763,17 @@ impl Cpu {
     /// Skip the following instruction if the key corresponding to the hex
     /// value currently stored in register VX is pressed
     fn opcode_ex9e(&mut self, x: u8, bus: &impl CpuBus) {
-        if bus.read_keypad(self.v[x as usize]) {
-            self.pc = self.pc.wrapping_add(2);
+        // only the low nibble names a key
+        if bus.read_keypad(self.v[x as usize] & 0x0F) {
+            self.skip(bus);
         }
     }
 
     /// Skip the following instruction if the key corresponding to the hex
     /// value currently stored in register VX is not pressed
     fn opcode_exa1(&mut self, x: u8, bus: &impl CpuBus) {
-        if !bus.read_keypad(self.v[x as usize]) {
-            self.pc = self.pc.wrapping_add(2);
+        if !bus.read_keypad(self.v[x as usize] & 0x0F) {
+            self.skip(bus);
         }
     }
 
@@ -337,8 +783,13 @@ impl Cpu {
     }
 
     /// Wait for a keypress and store the result in register VX
+    /// The stored key depends on the key wait policy
     fn opcode_fx0a(&mut self, x: u8) {
         self.key_await = Some(x);
+
+        if self.key_wait_policy == KeyWaitPolicy::FirstReleased {
+            self.keys_held = [false; KEYPAD_SIZE];
+        }
     }
 
     /// Set the delay timer to the value of register VX
@@ -353,15 +804,14 @@ impl Cpu {
 
     /// Add the value stored in register VX to register I
     fn opcode_fx1e(&mut self, x: u8) {
-        self.i += self.v[x as usize] as u16;
-        self.i &= 0x0FFF
+        self.i = self.i.wrapping_add(self.v[x as usize] as u16);
+        self.i &= self.address_mask();
     }
 
     /// Set I to the memory address of the sprite data corresponding to the
     /// hexadecimal digit stored in register VX
     fn opcode_fx29(&mut self, x: u8) {
         self.i = SPRITE_ADDR + self.v[x as usize] as u16 * 5;
-        self.i &= 0x0FFF
     }
 
     /// Store the binary-coded decimal equivalent of the value stored in
@@ -369,33 +819,220 @@ impl Cpu {
     fn opcode_fx33(&mut self, x: u8, bus: &mut impl CpuBus) {
         let value = self.v[x as usize];
 
-        bus.write_byte(self.i + 2, value % 10);
-        bus.write_byte(self.i + 1, (value / 10) % 10);
+        bus.write_byte(self.i.wrapping_add(2), value % 10);
+        bus.write_byte(self.i.wrapping_add(1), (value / 10) % 10);
         bus.write_byte(self.i, value / 100);
     }
 
     /// Store the values of registers V0 to VX inclusive in memory starting
     /// at address I
-    /// I is set to I + X + 1 after operation
+    /// I is set to I + X + 1 after operation, unless the keep_i quirk
     fn opcode_fx55(&mut self, x: u8, bus: &mut impl CpuBus) {
         for addr in 0..=x as u16 {
             bus.write_byte(self.i.wrapping_add(addr), self.v[addr as usize]);
         }
 
-        self.i += x as u16 + 1;
-        self.i &= 0x0FFF;
+        if !self.quirks.keep_i {
+            self.i = self.i.wrapping_add(x as u16 + 1) & self.address_mask();
+        }
     }
 
     /// Fill registers V0 to VX inclusive with the values stored in memory
     /// starting at address I
-    /// I is set to I + X + 1 after operation
+    /// I is set to I + X + 1 after operation, unless the keep_i quirk
     fn opcode_fx65(&mut self, x: u8, bus: &mut impl CpuBus) {
         for addr in 0..=x as u16 {
             self.v[addr as usize] = bus.read_byte(self.i.wrapping_add(addr));
         }
 
-        self.i += x as u16 + 1;
-        self.i &= 0x0FFF;
+        if !self.quirks.keep_i {
+            self.i = self.i.wrapping_add(x as u16 + 1) & self.address_mask();
+        }
+    }
+
+    /// Scroll the screen down N rows (SUPER-CHIP)
+    fn opcode_00cn(&mut self, n: u8, bus: &mut impl CpuBus) {
+        bus.scroll_down(n);
+    }
+
+    /// Scroll the screen right 4 pixels (SUPER-CHIP)
+    fn opcode_00fb(&mut self, bus: &mut impl CpuBus) {
+        bus.scroll_right();
+    }
+
+    /// Scroll the screen left 4 pixels (SUPER-CHIP)
+    fn opcode_00fc(&mut self, bus: &mut impl CpuBus) {
+        bus.scroll_left();
+    }
+
+    /// Exit the interpreter (SUPER-CHIP)
+    /// The pc stays on the instruction, the machine is halted
+    fn opcode_00fd(&mut self) {
+        self.pc = self.pc.wrapping_sub(2) & self.address_mask();
+    }
+
+    /// Switch to the 64x32 screen (SUPER-CHIP)
+    fn opcode_00fe(&mut self, bus: &mut impl CpuBus) {
+        bus.set_hires(false);
+    }
+
+    /// Switch to the 128x64 screen (SUPER-CHIP)
+    fn opcode_00ff(&mut self, bus: &mut impl CpuBus) {
+        bus.set_hires(true);
+    }
+
+    /// Set I to the memory address of the 8x10 sprite data corresponding to
+    /// the hexadecimal digit stored in register VX (SUPER-CHIP)
+    fn opcode_fx30(&mut self, x: u8) {
+        self.i = BIG_SPRITE_ADDR + (self.v[x as usize] & 0x0F) as u16 * 10;
+    }
+
+    /// Store the values of registers V0 to VX inclusive in the RPL flags
+    /// (SUPER-CHIP)
+    fn opcode_fx75(&mut self, x: u8) {
+        let len = x as usize + 1;
+        self.rpl[..len].copy_from_slice(&self.v[..len]);
+    }
+
+    /// Fill registers V0 to VX inclusive with the RPL flags (SUPER-CHIP)
+    fn opcode_fx85(&mut self, x: u8) {
+        let len = x as usize + 1;
+        self.v[..len].copy_from_slice(&self.rpl[..len]);
+    }
+
+    /// Scroll the selected planes up N rows (XO-CHIP)
+    fn opcode_00dn(&mut self, n: u8, bus: &mut impl CpuBus) {
+        bus.scroll_up(n);
+    }
+
+    /// The registers from VX to VY, in this order, either way
+    fn range(x: u8, y: u8) -> impl Iterator<Item = u8> {
+        let len = x.abs_diff(y) + 1;
+        (0..len).map(move |offset| match x <= y {
+            true => x + offset,
+            false => x - offset,
+        })
+    }
+
+    /// Store the values of registers VX to VY inclusive in memory starting
+    /// at address I, I is left unchanged (XO-CHIP)
+    fn opcode_5xy2(&mut self, x: u8, y: u8, bus: &mut impl CpuBus) {
+        for (addr, r) in Self::range(x, y).enumerate() {
+            let addr = self.i.wrapping_add(addr as u16);
+            bus.write_byte(addr, self.v[r as usize]);
+        }
+    }
+
+    /// Fill registers VX to VY inclusive with the values stored in memory
+    /// starting at address I, I is left unchanged (XO-CHIP)
+    fn opcode_5xy3(&mut self, x: u8, y: u8, bus: &impl CpuBus) {
+        for (addr, r) in Self::range(x, y).enumerate() {
+            let addr = self.i.wrapping_add(addr as u16);
+            self.v[r as usize] = bus.read_byte(addr);
+        }
+    }
+
+    /// Set I to the 16 bits address NNNN of the next word (XO-CHIP)
+    fn opcode_f000(&mut self, bus: &impl CpuBus) {
+        self.i = self.pc_read_word(bus);
+    }
+
+    /// Select the planes drawn on by the mask N (XO-CHIP)
+    fn opcode_fn01(&mut self, n: u8, bus: &mut impl CpuBus) {
+        bus.select_planes(n);
+    }
+
+    /// Load the audio pattern from the 16 bytes at address I (XO-CHIP)
+    fn opcode_f002(&mut self, bus: &mut impl CpuBus) {
+        let mut pattern = [0; AUDIO_PATTERN_SIZE];
+        for (addr, byte) in pattern.iter_mut().enumerate() {
+            *byte = bus.read_byte(self.i.wrapping_add(addr as u16));
+        }
+        bus.write_audio_pattern(pattern);
+    }
+
+    /// Set the audio pitch to the value of register VX (XO-CHIP)
+    fn opcode_fx3a(&mut self, x: u8, bus: &mut impl CpuBus) {
+        bus.write_pitch(self.v[x as usize]);
+    }
+}
+
+/// Mask of a `bits` wide sprite row at `x`, without the columns past the
+/// right edge of the screen
+fn clip_mask(x: u8, width: u8, bits: u32) -> u16 {
+    let cut = (x as u32 + bits).saturating_sub(width as u32);
+    (u16::MAX >> (16 - bits)).checked_shl(cut).unwrap_or(0)
+}
+
+impl Snapshot for Cpu {
+    fn save(&self, state: &mut StateWriter) {
+        state.bool(self.xo_chip);
+        state.u16(self.pc);
+        state.u16(self.i);
+        state.bytes(&self.v);
+        state.u16(self.depth as u16);
+        // the whole stack, the states of a machine all have the same size
+        for &addr in self.call_stack() {
+            state.u16(addr);
+        }
+        for _ in self.depth..STACK_SIZE {
+            state.u16(0);
+        }
+        state.u8(self.key_await.unwrap_or(0xFF));
+        state.bits(self.keys_held);
+        for stamp in self.key_stamps {
+            state.u64(stamp);
+        }
+        state.u64(self.key_stamp);
+        state.u64(self.rng);
+        state.bytes(&self.rpl);
+        state.bool(self.vblank_wait);
+    }
+
+    fn load(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.xo_chip = state.bool()?;
+        self.pc = state.u16()?;
+        if self.pc > self.address_mask() {
+            return Err(StateError::InvalidValue("pc"));
+        }
+        self.i = state.u16()?;
+        self.v.copy_from_slice(state.bytes(V_SIZE)?);
+
+        self.depth = state.u16()? as usize;
+        if self.depth > STACK_SIZE {
+            return Err(StateError::InvalidValue("stack"));
+        }
+        let mask = self.address_mask();
+        for addr in &mut self.stack[..self.depth] {
+            *addr = state.u16()?;
+            if *addr > mask {
+                return Err(StateError::InvalidValue("stack"));
+            }
+        }
+        state.bytes((STACK_SIZE - self.depth) * 2)?;
+
+        self.key_await = match state.u8()? {
+            0xFF => None,
+            x if (x as usize) < V_SIZE => Some(x),
+            _ => return Err(StateError::InvalidValue("key_await")),
+        };
+        for (held, bit) in
+            self.keys_held.iter_mut().zip(state.bits(KEYPAD_SIZE)?)
+        {
+            *held = bit;
+        }
+        for stamp in self.key_stamps.iter_mut() {
+            *stamp = state.u64()?;
+        }
+        self.key_stamp = state.u64()?;
+        self.rng = match state.u64()? {
+            0 => return Err(StateError::InvalidValue("rng")),
+            rng => rng,
+        };
+        self.rpl.copy_from_slice(state.bytes(RPL_SIZE)?);
+        self.vblank_wait = state.bool()?;
+
+        Ok(())
     }
 }
 
@@ -412,6 +1049,105 @@ pub trait CpuBus {
     fn read_screen(&self, x: u8, y: u8) -> bool;
     fn write_screen(&mut self, x: u8, y: u8, pixel: bool);
 
+    /// Toggle the pixels of the sprite `line` at `x`, `y`, wrapping around
+    /// the screen, returns true when a set pixel was unset
+    fn draw_sprite_row(&mut self, x: u8, y: u8, line: u8) -> bool {
+        let mut collision = false;
+        for w in 0..8_u8 {
+            if (line << w) & 0x80 > 0 {
+                let x = x.wrapping_add(w);
+                let pixel = self.read_screen(x, y);
+                collision |= pixel;
+                self.write_screen(x, y, !pixel);
+            }
+        }
+
+        collision
+    }
+
+    // super-chip screen
+    /// Width and height of the screen, 64x32 until `set_hires`
+    fn screen_size(&self) -> (u8, u8) {
+        (DISPLAY_WIDTH as u8, DISPLAY_HEIGHT as u8)
+    }
+
+    /// `screen_size` as sizes, for the frontends to scale the frames
+    fn resolution(&self) -> (usize, usize) {
+        let (width, height) = self.screen_size();
+
+        (width as usize, height as usize)
+    }
+
+    /// Switch to the 128x64 screen or back, ignored by the buses without one
+    fn set_hires(&mut self, _hires: bool) {}
+
+    /// Toggle the pixels of a 16 pixels wide sprite `line`, like
+    /// `draw_sprite_row`
+    fn draw_sprite_row16(&mut self, x: u8, y: u8, line: u16) -> bool {
+        let left = self.draw_sprite_row(x, y, (line >> 8) as u8);
+        let right = self.draw_sprite_row(x.wrapping_add(8), y, line as u8);
+
+        left || right
+    }
+
+    /// Move the screen down `n` rows, the top ones are cleared
+    fn scroll_down(&mut self, n: u8) {
+        let (width, height) = self.screen_size();
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let pixel = y >= n && self.read_screen(x, y - n);
+                self.write_screen(x, y, pixel);
+            }
+        }
+    }
+
+    /// Move the screen 4 pixels left, the right ones are cleared
+    fn scroll_left(&mut self) {
+        let (width, height) = self.screen_size();
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = x + 4 < width && self.read_screen(x + 4, y);
+                self.write_screen(x, y, pixel);
+            }
+        }
+    }
+
+    /// Move the screen 4 pixels right, the left ones are cleared
+    fn scroll_right(&mut self) {
+        let (width, height) = self.screen_size();
+        for y in 0..height {
+            for x in (0..width).rev() {
+                let pixel = x >= 4 && self.read_screen(x - 4, y);
+                self.write_screen(x, y, pixel);
+            }
+        }
+    }
+
+    // xo-chip
+    /// Mask of the planes the screen functions work on, the buses with a
+    /// single plane have only the first one
+    fn planes(&self) -> u8 {
+        1
+    }
+
+    fn select_planes(&mut self, _planes: u8) {}
+
+    /// Move the screen up `n` rows, the bottom ones are cleared
+    fn scroll_up(&mut self, n: u8) {
+        let (width, height) = self.screen_size();
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = y + n < height && self.read_screen(x, y + n);
+                self.write_screen(x, y, pixel);
+            }
+        }
+    }
+
+    /// Sound played while the sound timer runs, ignored by the buses
+    /// without one
+    fn write_audio_pattern(&mut self, _pattern: [u8; AUDIO_PATTERN_SIZE]) {}
+    fn write_pitch(&mut self, _pitch: u8) {}
+
     // timer
     fn read_timer(&self) -> u8;
     fn write_timer(&mut self, value: u8);
@@ -507,6 +1243,74 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_accessors() {
+        let mut cpu = create_cpu();
+        cpu.pc = 0x0345;
+        cpu.i = 0x0123;
+        cpu.stack[0] = 0x0202;
+        cpu.depth = 1;
+        cpu.key_await = Some(0x4);
+
+        assert_eq!(cpu.pc(), 0x0345);
+        assert_eq!(cpu.index(), 0x0123);
+        assert_eq!(cpu.registers(), &cpu.v);
+        assert_eq!(cpu.call_stack(), &[0x0202]);
+        assert_eq!(cpu.key_await(), Some(0x4));
+    }
+
+    #[test]
+    fn test_step() {
+        let (mut cpu, mut bus) = create_cpu_with_bus();
+        // 6120: V1 = 0x20, 8124: V1 += V2, A300: I = 0x300, F00A: wait a key
+        bus.memory[0x200..0x208]
+            .copy_from_slice(&[0x61, 0x20, 0x81, 0x24, 0xA3, 0x00, 0xF0, 0x0A]);
+
+        let info = cpu.step(&mut bus).unwrap();
+        assert_eq!(info.pc, 0x200);
+        assert_eq!(info.opcode, 0x6120);
+        assert_eq!(info.instruction, Instruction::LdByte(1, 0x20));
+        assert_eq!(info.registers, 1 << 1);
+        assert!(!info.index);
+
+        // VF is the carry, V2 is 233
+        let info = cpu.step(&mut bus).unwrap();
+        assert!(info.changed(1) && info.changed(0xF) && !info.changed(2));
+
+        let info = cpu.step(&mut bus).unwrap();
+        assert_eq!((info.registers, info.index), (0, true));
+
+        assert_eq!(cpu.step(&mut bus).unwrap().opcode, 0xF00A);
+        assert_eq!(cpu.step(&mut bus), None);
+        assert_eq!(cpu.pc(), 0x208);
+    }
+
+    #[test]
+    fn test_tracer() {
+        let (mut cpu, mut bus) = create_cpu_with_bus();
+        // 6120: V1 = 0x20, A300: I = 0x300, F00A: wait a key
+        bus.memory[0x200..0x206]
+            .copy_from_slice(&[0x61, 0x20, 0xA3, 0x00, 0xF0, 0x0A]);
+
+        let events = Arc::new(std::sync::Mutex::new(vec![]));
+        let traced = events.clone();
+        cpu.set_tracer(move |event| traced.lock().unwrap().push(event));
+        for _ in 0..4 {
+            cpu.emulate(&mut bus);
+        }
+
+        let events = events.lock().unwrap();
+        let trace: Vec<_> = events.iter().map(|e| (e.pc, e.opcode)).collect();
+        assert_eq!(trace, [(0x200, 0x6120), (0x202, 0xA300), (0x204, 0xF00A)]);
+        assert_eq!(events[0].registers[1], 0x20);
+        assert_eq!((events[0].index, events[1].index), (0, 0x300));
+
+        cpu.clear_tracer();
+        bus.keypad[0] = true;
+        cpu.emulate(&mut bus);
+        assert_eq!(events.len(), 3);
+    }
+
     #[test]
     fn test_opcode_0nnn() {
         let mut cpu = create_cpu();
@@ -527,7 +1331,8 @@ mod tests {
         let mut cpu = create_cpu();
 
         cpu.pc = 0x100;
-        cpu.stack.push(0x0200);
+        cpu.stack[0] = 0x0200;
+        cpu.depth = 1;
 
         cpu.opcode_00ee();
 
@@ -568,25 +1373,33 @@ mod tests {
         assert_eq!(0x0100, cpu.stack[0]);
         assert_eq!(0x0200, cpu.stack[1]);
         assert_eq!(0x0FFF, cpu.stack[2]);
+        assert_eq!(cpu.call_stack(), &[0x0100, 0x0200, 0x0FFF]);
+        assert_eq!(0x0555, cpu.pc);
+
+        // a full stack ignores the call
+        cpu.stack = [0x0200; STACK_SIZE];
+        cpu.depth = STACK_SIZE;
+        cpu.opcode_2nnn(0x0300);
+        assert_eq!(cpu.depth, STACK_SIZE);
         assert_eq!(0x0555, cpu.pc);
     }
 
     #[test]
     fn test_opcode_3xnn() {
-        let mut cpu = create_cpu();
+        let (mut cpu, bus) = create_cpu_with_bus();
 
         for x in 0..=0xE {
             for nn in 0..=0xFF {
                 cpu.pc = 0x100;
                 cpu.v[x as usize] = nn;
 
-                cpu.opcode_3xnn(x, nn);
+                cpu.opcode_3xnn(x, nn, &bus);
                 assert_eq!(0x102, cpu.pc); // jump
 
-                cpu.opcode_3xnn(x, nn.wrapping_add(0x55));
+                cpu.opcode_3xnn(x, nn.wrapping_add(0x55), &bus);
                 assert_eq!(0x102, cpu.pc); // no jump
 
-                cpu.opcode_3xnn(x, nn);
+                cpu.opcode_3xnn(x, nn, &bus);
                 assert_eq!(0x104, cpu.pc); // jump
             }
         }
@@ -594,20 +1407,20 @@ mod tests {
 
     #[test]
     fn test_opcode_4xnn() {
-        let mut cpu = create_cpu();
+        let (mut cpu, bus) = create_cpu_with_bus();
 
         for x in 0..=0xE {
             for nn in 0..=0xFF {
                 cpu.pc = 0x100;
                 cpu.v[x as usize] = nn;
 
-                cpu.opcode_4xnn(x, nn);
+                cpu.opcode_4xnn(x, nn, &bus);
                 assert_eq!(0x100, cpu.pc); // no jump
 
-                cpu.opcode_4xnn(x, nn.wrapping_add(0x55));
+                cpu.opcode_4xnn(x, nn.wrapping_add(0x55), &bus);
                 assert_eq!(0x102, cpu.pc); // jump
 
-                cpu.opcode_4xnn(x, nn);
+                cpu.opcode_4xnn(x, nn, &bus);
                 assert_eq!(0x102, cpu.pc); // no jump
             }
         }
@@ -615,7 +1428,7 @@ mod tests {
 
     #[test]
     fn test_opcode_5xy0() {
-        let mut cpu = create_cpu();
+        let (mut cpu, bus) = create_cpu_with_bus();
         cpu.pc = 0x100;
 
         let x = 0u8;
@@ -623,11 +1436,11 @@ mod tests {
         cpu.v[x as usize] = 0xFF;
         cpu.v[y as usize] = 0x55;
 
-        cpu.opcode_5xy0(x, y);
+        cpu.opcode_5xy0(x, y, &bus);
         assert_eq!(0x100, cpu.pc); // no jump
 
         cpu.v[x as usize] = 0x55;
-        cpu.opcode_5xy0(x, y);
+        cpu.opcode_5xy0(x, y, &bus);
         assert_eq!(0x102, cpu.pc); // jump
     }
 
@@ -810,6 +1623,12 @@ mod tests {
         cpu.opcode_8xy6(0xf, 1);
         assert_eq!(cpu.v[1], 0x02);
         assert_eq!(cpu.v[0xF], 0x00);
+
+        // the flag is the bit shifted out of VY, also when X is Y
+        cpu.v[1] = 0x05;
+        cpu.opcode_8xy6(1, 1);
+        assert_eq!(cpu.v[1], 0x02);
+        assert_eq!(cpu.v[0xF], 0x01);
     }
 
     #[test]
@@ -902,21 +1721,26 @@ mod tests {
         cpu.opcode_8xye(0xF, 1);
         assert_eq!(cpu.v[1], 0x01);
         assert_eq!(cpu.v[0xF], 0x00);
+
+        cpu.v[1] = 0x81;
+        cpu.opcode_8xye(1, 1);
+        assert_eq!(cpu.v[1], 0x02);
+        assert_eq!(cpu.v[0xF], 0x01);
     }
 
     #[test]
     fn test_opcode_9xy0() {
-        let mut cpu = create_cpu();
+        let (mut cpu, bus) = create_cpu_with_bus();
         cpu.pc = 0x0200;
         cpu.v[0] = 0xFF;
         cpu.v[1] = 0x50;
 
-        cpu.opcode_9xy0(0, 1);
+        cpu.opcode_9xy0(0, 1, &bus);
         assert_eq!(cpu.pc, 0x0202);
 
         cpu.v[0] = 0x22;
         cpu.v[1] = 0x22;
-        cpu.opcode_9xy0(0, 1);
+        cpu.opcode_9xy0(0, 1, &bus);
         assert_eq!(cpu.pc, 0x0202);
     }
 
@@ -937,6 +1761,10 @@ mod tests {
 
         cpu.opcode_bnnn(0x0123);
         assert_eq!(cpu.pc, 0x0134);
+
+        cpu.v[0] = 0xFF;
+        cpu.opcode_bnnn(0x0FFF);
+        assert_eq!(cpu.pc, 0x00FE);
     }
 
     #[test]
@@ -954,6 +1782,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_opcode_cxnn_seed() {
+        let mut cpu = create_cpu();
+        let mut other = create_cpu();
+        cpu.set_seed(42);
+        other.set_seed(42);
+
+        let numbers: Vec<u8> = (0..16)
+            .map(|_| {
+                cpu.opcode_cxnn(0, 0xFF);
+                cpu.v[0]
+            })
+            .collect();
+        for &number in &numbers {
+            other.opcode_cxnn(0, 0xFF);
+            assert_eq!(other.v[0], number);
+        }
+        assert!(numbers.iter().any(|&number| number != numbers[0]));
+    }
+
     #[test]
     fn test_opcode_dxyn_draw() {
         let (mut cpu, mut bus) = create_cpu_with_bus();
@@ -968,7 +1816,8 @@ mod tests {
         bus.memory[0x506] = 0b0000_0011;
         bus.memory[0x507] = 0b0000_0001;
 
-        for n in 0..=0xF {
+        // 0 draws a 16x16 sprite
+        for n in 1..=0xF {
             clear_screen(&mut bus);
 
             cpu.i = 0x500;
@@ -991,6 +1840,152 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_opcode_dxy0() {
+        let (mut cpu, mut bus) = create_cpu_with_bus();
+        clear_screen(&mut bus);
+        // a 16 pixels wide diagonal, two bytes per row
+        for h in 0..16 {
+            let line = 0x8000_u16 >> h;
+            bus.memory[0x500 + h * 2..0x502 + h * 2]
+                .copy_from_slice(&line.to_be_bytes());
+        }
+        cpu.i = 0x500;
+        cpu.v[0] = 60;
+        cpu.v[1] = 20;
+
+        cpu.opcode_dxyn(0, 1, 0, &mut bus);
+        assert_eq!(cpu.v[0xF], 0x00);
+        for h in 0..16_u8 {
+            // wraps around the right edge and the bottom
+            let (x, y) = ((60 + h) % 64, (20 + h) % 32);
+            assert!(bus.read_screen(x, y), "{}", h);
+            assert!(!bus.read_screen(x + 1, y));
+        }
+
+        cpu.opcode_dxyn(0, 1, 0, &mut bus);
+        assert_eq!(cpu.v[0xF], 0x01);
+    }
+
+    #[test]
+    fn test_opcode_scroll() {
+        let (mut cpu, mut bus) = create_cpu_with_bus();
+        clear_screen(&mut bus);
+        bus.write_screen(10, 3, true);
+
+        cpu.opcode_00cn(2, &mut bus);
+        assert!(bus.read_screen(10, 5) && !bus.read_screen(10, 3));
+        cpu.opcode_00fb(&mut bus);
+        assert!(bus.read_screen(14, 5) && !bus.read_screen(10, 5));
+        cpu.opcode_00fc(&mut bus);
+        cpu.opcode_00fc(&mut bus);
+        assert!(bus.read_screen(6, 5) && !bus.read_screen(14, 5));
+
+        // the pixels pushed out are lost
+        cpu.opcode_00cn(0xF, &mut bus);
+        cpu.opcode_00cn(0xF, &mut bus);
+        assert!(bus.screen.iter().flatten().all(|&pixel| !pixel));
+    }
+
+    #[test]
+    fn test_opcode_fx30() {
+        let mut cpu = create_cpu();
+        cpu.v[3] = 0x7;
+        cpu.opcode_fx30(3);
+        assert_eq!(cpu.i, BIG_SPRITE_ADDR + 70);
+
+        // only the low nibble names a digit
+        cpu.v[3] = 0x1A;
+        cpu.opcode_fx30(3);
+        assert_eq!(cpu.i, BIG_SPRITE_ADDR + 100);
+    }
+
+    #[test]
+    fn test_opcode_fx75_fx85() {
+        let mut cpu = create_cpu();
+        let registers = cpu.v;
+
+        cpu.opcode_fx75(3);
+        assert_eq!(cpu.rpl_flags()[..4], registers[..4]);
+        assert_eq!(cpu.rpl_flags()[4..], [0; 4]);
+
+        cpu.v = [0; V_SIZE];
+        cpu.reset();
+        cpu.opcode_fx85(7);
+        assert_eq!(cpu.v[..4], registers[..4]);
+        assert_eq!(cpu.v[4..], [0; 12]);
+    }
+
+    #[test]
+    fn test_opcode_00fd() {
+        let (mut cpu, mut bus) = create_cpu_with_bus();
+        bus.memory[0x200] = 0x00;
+        bus.memory[0x201] = 0xFD;
+
+        cpu.emulate(&mut bus);
+        cpu.emulate(&mut bus);
+        assert_eq!(cpu.pc, 0x200);
+    }
+
+    #[test]
+    fn test_opcode_5xy2_5xy3() {
+        let (mut cpu, mut bus) = create_cpu_with_bus();
+        cpu.i = 0x300;
+        cpu.v[1..4].copy_from_slice(&[1, 2, 3]);
+
+        cpu.opcode_5xy2(1, 3, &mut bus);
+        assert_eq!(bus.memory[0x300..0x303], [1, 2, 3]);
+        // backwards
+        cpu.opcode_5xy2(3, 1, &mut bus);
+        assert_eq!(bus.memory[0x300..0x303], [3, 2, 1]);
+        assert_eq!(cpu.i, 0x300);
+
+        cpu.opcode_5xy3(5, 7, &bus);
+        assert_eq!(cpu.v[5..8], [3, 2, 1]);
+        assert_eq!(cpu.i, 0x300);
+    }
+
+    #[test]
+    fn test_opcode_f000() {
+        let (mut cpu, mut bus) = create_cpu_with_bus();
+        cpu.set_xo_chip(true);
+        bus.memory[0x200..0x208]
+            .copy_from_slice(&[0x30, 0x00, 0xF0, 0x00, 0xAB, 0xCD, 0xF0, 0x00]);
+
+        // the skip jumps over the 4 bytes
+        cpu.v[0] = 0;
+        cpu.emulate(&mut bus);
+        assert_eq!(cpu.pc, 0x206);
+
+        cpu.pc = 0x202;
+        cpu.emulate(&mut bus);
+        assert_eq!(cpu.i, 0xABCD);
+        assert_eq!(cpu.pc, 0x206);
+
+        // the 4KB of the chip8 don't know it
+        cpu.set_xo_chip(false);
+        cpu.pc = 0x200;
+        cpu.emulate(&mut bus);
+        assert_eq!(cpu.pc, 0x204);
+        cpu.emulate(&mut bus);
+        assert_eq!(cpu.i, 0xABCD & 0x0FFF);
+    }
+
+    #[test]
+    fn test_opcode_dxyn_vf_coordinate() {
+        let (mut cpu, mut bus) = create_cpu_with_bus();
+        clear_screen(&mut bus);
+        bus.memory[0x500] = 0b1000_0000;
+        cpu.i = 0x500;
+        cpu.v[0xF] = 10;
+        cpu.v[1] = 5;
+
+        cpu.opcode_dxyn(0xF, 1, 1, &mut bus);
+        assert!(bus.read_screen(10, 5));
+        assert!(!bus.read_screen(0, 5));
+        assert_eq!(cpu.v[0xF], 0x00);
+    }
+
     #[test]
     fn test_opcode_dxyn_collision() {
         let (mut cpu, mut bus) = create_cpu_with_bus();
@@ -1004,11 +1999,11 @@ mod tests {
 
         cpu.opcode_dxyn(0, 1, 1, &mut bus);
         assert_eq!(cpu.v[0xF], 0x00);
-        assert_eq!(bus.screen[0][0], true);
+        assert!(bus.screen[0][0]);
 
         cpu.opcode_dxyn(0, 1, 1, &mut bus);
         assert_eq!(cpu.v[0xF], 0x01);
-        assert_eq!(bus.screen[0][0], false);
+        assert!(!bus.screen[0][0]);
     }
 
     #[test]
@@ -1024,15 +2019,15 @@ mod tests {
 
         cpu.opcode_dxyn(0, 1, 1, &mut bus);
         assert_eq!(cpu.v[0xF], 0x00);
-        assert_eq!(bus.screen[0][0], true);
-        assert_eq!(bus.screen[SCREEN_W - 1][0], true);
+        assert!(bus.screen[0][0]);
+        assert!(bus.screen[SCREEN_W - 1][0]);
 
         // clear one pixel
         bus.memory[0x500] = 0b0000_0001;
         cpu.opcode_dxyn(0, 1, 1, &mut bus);
         assert_eq!(cpu.v[0xF], 0x01);
-        assert_eq!(bus.screen[0][0], false);
-        assert_eq!(bus.screen[SCREEN_W - 1][0], true);
+        assert!(!bus.screen[0][0]);
+        assert!(bus.screen[SCREEN_W - 1][0]);
     }
 
     #[test]
@@ -1050,9 +2045,9 @@ mod tests {
 
         cpu.opcode_dxyn(0, 1, 3, &mut bus);
         assert_eq!(cpu.v[0xF], 0x00);
-        assert_eq!(bus.screen[0][0], true);
-        assert_eq!(bus.screen[0][SCREEN_H - 1], true);
-        assert_eq!(bus.screen[0][SCREEN_H - 2], true);
+        assert!(bus.screen[0][0]);
+        assert!(bus.screen[0][SCREEN_H - 1]);
+        assert!(bus.screen[0][SCREEN_H - 2]);
 
         // clear one pixel
         bus.memory[0x500] = 0b0000_0000;
@@ -1060,9 +2055,71 @@ mod tests {
         bus.memory[0x502] = 0b1000_0000;
         cpu.opcode_dxyn(0, 1, 3, &mut bus);
         assert_eq!(cpu.v[0xF], 0x01);
-        assert_eq!(bus.screen[0][0], false);
-        assert_eq!(bus.screen[0][SCREEN_H - 1], true);
-        assert_eq!(bus.screen[0][SCREEN_H - 2], true);
+        assert!(!bus.screen[0][0]);
+        assert!(bus.screen[0][SCREEN_H - 1]);
+        assert!(bus.screen[0][SCREEN_H - 2]);
+    }
+
+    #[test]
+    fn test_opcode_dxyn_clip() {
+        let (mut cpu, mut bus) = create_cpu_with_bus();
+        cpu.set_quirks(Quirks::VIP);
+        bus.memory[0x500] = 0b1000_0001;
+        bus.memory[0x501] = 0b1000_0001;
+        cpu.i = 0x500;
+        // the start wraps, the rest is cut
+        cpu.v[0] = SCREEN_W as u8 * 2 + SCREEN_W as u8 - 4;
+        cpu.v[1] = SCREEN_H as u8 - 1;
+
+        cpu.opcode_dxyn(0, 1, 2, &mut bus);
+        assert!(bus.screen[SCREEN_W - 4][SCREEN_H - 1]);
+        assert_eq!(bus.screen.iter().flatten().filter(|&&p| p).count(), 1);
+    }
+
+    #[test]
+    fn test_quirks() {
+        let mut cpu = create_cpu();
+        cpu.set_quirks(Quirks::SUPER_CHIP);
+        cpu.v[1] = 0b0000_0011;
+        cpu.v[2] = 0b1000_0000;
+
+        // VY is ignored
+        cpu.opcode_8xy6(1, 2);
+        assert_eq!((cpu.v[1], cpu.v[0xF]), (0b0000_0001, 1));
+        cpu.opcode_8xye(1, 2);
+        assert_eq!((cpu.v[1], cpu.v[0xF]), (0b0000_0010, 0));
+
+        cpu.v[3] = 0x10;
+        cpu.opcode_bnnn(0x300);
+        assert_eq!(cpu.pc, 0x310);
+
+        let mut bus = create_bus();
+        cpu.i = 0x400;
+        cpu.opcode_fx55(3, &mut bus);
+        cpu.opcode_fx65(3, &mut bus);
+        assert_eq!(cpu.i, 0x400);
+
+        cpu.set_quirks(Quirks::VIP);
+        cpu.v[0xF] = 1;
+        cpu.opcode_8xy1(1, 2);
+        assert_eq!(cpu.v[0xF], 0);
+    }
+
+    #[test]
+    fn test_quirk_display_wait() {
+        let (mut cpu, mut bus) = create_cpu_with_bus();
+        cpu.set_quirks(Quirks::VIP);
+        // D001, 7001
+        bus.memory[0x200..0x204].copy_from_slice(&[0xD0, 0x01, 0x70, 0x01]);
+        cpu.v[0] = 0;
+
+        cpu.emulate(&mut bus);
+        cpu.emulate(&mut bus);
+        assert_eq!((cpu.pc, cpu.v[0]), (0x202, 0));
+
+        cpu.vblank();
+        cpu.emulate(&mut bus);
+        assert_eq!((cpu.pc, cpu.v[0]), (0x204, 1));
     }
 
     #[test]
@@ -1072,12 +2129,20 @@ mod tests {
         cpu.pc = 0x0300;
         bus.keypad[0x5] = false;
 
-        cpu.opcode_ex9e(1, &mut bus);
+        cpu.opcode_ex9e(1, &bus);
         assert_eq!(cpu.pc, 0x0300);
 
         bus.keypad[0x5] = true;
-        cpu.opcode_ex9e(1, &mut bus);
+        cpu.opcode_ex9e(1, &bus);
         assert_eq!(cpu.pc, 0x0302);
+
+        cpu.v[1] = 0xF5;
+        cpu.opcode_ex9e(1, &bus);
+        assert_eq!(cpu.pc, 0x0304);
+
+        cpu.pc = 0x0FFE;
+        cpu.opcode_ex9e(1, &bus);
+        assert_eq!(cpu.pc, 0x0000);
     }
 
     #[test]
@@ -1087,11 +2152,11 @@ mod tests {
         cpu.pc = 0x0300;
         bus.keypad[0x5] = false;
 
-        cpu.opcode_exa1(1, &mut bus);
+        cpu.opcode_exa1(1, &bus);
         assert_eq!(cpu.pc, 0x0302);
 
         bus.keypad[0x5] = true;
-        cpu.opcode_exa1(1, &mut bus);
+        cpu.opcode_exa1(1, &bus);
         assert_eq!(cpu.pc, 0x0302);
     }
 
@@ -1101,7 +2166,7 @@ mod tests {
         cpu.v[1] = 0x5;
         bus.timer = 0xA0;
 
-        cpu.opcode_fx07(1, &mut bus);
+        cpu.opcode_fx07(1, &bus);
         assert_eq!(cpu.v[1], 0xA0);
     }
 
@@ -1134,6 +2199,51 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_opcode_fx0a_most_recently_pressed() {
+        let (mut cpu, mut bus) = create_cpu_with_bus();
+        cpu.set_key_wait_policy(KeyWaitPolicy::MostRecentlyPressed);
+        cpu.pc = 0x400;
+
+        bus.keypad[0xA] = true;
+        cpu.emulate(&mut bus); // 0x0000: no-op
+        bus.keypad[0x3] = true;
+        cpu.emulate(&mut bus);
+
+        cpu.opcode_fx0a(1);
+        cpu.emulate(&mut bus);
+        assert_eq!(cpu.v[1], 0x3);
+
+        bus.keypad[0x3] = false;
+        cpu.opcode_fx0a(1);
+        cpu.emulate(&mut bus);
+        assert_eq!(cpu.v[1], 0xA); // only key still held
+    }
+
+    #[test]
+    fn test_opcode_fx0a_first_released() {
+        let (mut cpu, mut bus) = create_cpu_with_bus();
+        cpu.set_key_wait_policy(KeyWaitPolicy::FirstReleased);
+        cpu.pc = 0x400;
+        cpu.v[1] = 0x00;
+
+        cpu.opcode_fx0a(1);
+        bus.keypad[0x2] = true;
+        bus.keypad[0x7] = true;
+        for _clk in 0..100 {
+            cpu.emulate(&mut bus);
+        }
+        assert_eq!(cpu.pc, 0x0400); // still waiting while keys are held
+        assert_eq!(cpu.v[1], 0x00);
+
+        bus.keypad[0x7] = false;
+        cpu.emulate(&mut bus);
+        assert_eq!(cpu.v[1], 0x7);
+
+        cpu.emulate(&mut bus); // next intruction
+        assert_eq!(cpu.pc, 0x0402);
+    }
+
     #[test]
     fn test_opcode_fx15() {
         let (mut cpu, mut bus) = create_cpu_with_bus();
@@ -1261,4 +2371,87 @@ mod tests {
             assert_eq!(cpu.i, 0x500 + x as u16 + 1);
         }
     }
+
+    mod properties {
+        use proptest::prelude::*;
+
+        use super::*;
+
+        /// VX and VY, VF holds the flags
+        fn operands() -> impl Strategy<Value = (u8, u8)> {
+            (0..0xF_u8, 0..0xF_u8).prop_filter("x != y", |(x, y)| x != y)
+        }
+
+        proptest! {
+            #[test]
+            fn test_8xy4_8xy5_restore_vx(
+                (x, y) in operands(),
+                vx in any::<u8>(),
+                vy in any::<u8>(),
+            ) {
+                let mut cpu = create_cpu();
+                cpu.v[x as usize] = vx;
+                cpu.v[y as usize] = vy;
+
+                cpu.opcode_8xy4(x, y);
+                let carry = cpu.v[0xF];
+                cpu.opcode_8xy5(x, y);
+
+                prop_assert_eq!(cpu.v[x as usize], vx);
+                prop_assert_eq!(cpu.v[y as usize], vy);
+                // no borrow back exactly when there was no carry
+                prop_assert_eq!(cpu.v[0xF], 1 - carry);
+            }
+
+            #[test]
+            fn test_dxyn_twice_restores_screen(
+                screen in prop::collection::vec(
+                    prop::collection::vec(any::<bool>(), SCREEN_H),
+                    SCREEN_W,
+                ),
+                sprite in prop::collection::vec(any::<u8>(), 32),
+                x in any::<u8>(),
+                y in any::<u8>(),
+                n in 0..=0xF_u8,
+            ) {
+                let (mut cpu, mut bus) = create_cpu_with_bus();
+                bus.screen = screen.clone();
+                bus.memory[0x500..0x520].copy_from_slice(&sprite);
+                cpu.i = 0x500;
+                cpu.v[0] = x;
+                cpu.v[1] = y;
+
+                cpu.opcode_dxyn(0, 1, n, &mut bus);
+                let first = cpu.v[0xF];
+                cpu.opcode_dxyn(0, 1, n, &mut bus);
+                let second = cpu.v[0xF];
+
+                prop_assert_eq!(&bus.screen, &screen);
+                // each pixel set is erased by one of the draws
+                // DXY0 draws 16 rows of 2 bytes
+                let len = match n {
+                    0 => 32,
+                    n => n as usize,
+                };
+                let drawn = sprite[..len].iter().any(|&line| line != 0);
+                prop_assert_eq!(first | second == 1, drawn);
+            }
+
+            #[test]
+            fn test_fx33_digits_recombine(x in 0..=0xF_u8, vx in any::<u8>()) {
+                let (mut cpu, mut bus) = create_cpu_with_bus();
+                cpu.v[x as usize] = vx;
+                cpu.i = 0x500;
+
+                cpu.opcode_fx33(x, &mut bus);
+                let digits = &bus.memory[0x500..0x503];
+
+                prop_assert!(digits.iter().all(|&digit| digit < 10));
+                let value = digits
+                    .iter()
+                    .fold(0_u32, |value, &digit| value * 10 + digit as u32);
+                prop_assert_eq!(value, vx as u32);
+            }
+        }
+    }
 }
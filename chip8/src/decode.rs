@@ -0,0 +1,280 @@
+use std::sync::OnceLock;
+
+/// An opcode split into its operands, named like in the disassembly
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Cls,
+    Ret,
+    Sys(u16),
+    Jp(u16),
+    Call(u16),
+    SeByte(u8, u8),
+    SneByte(u8, u8),
+    SeReg(u8, u8),
+    LdByte(u8, u8),
+    AddByte(u8, u8),
+    LdReg(u8, u8),
+    Or(u8, u8),
+    And(u8, u8),
+    Xor(u8, u8),
+    AddReg(u8, u8),
+    Sub(u8, u8),
+    Shr(u8, u8),
+    Subn(u8, u8),
+    Shl(u8, u8),
+    SneReg(u8, u8),
+    LdI(u16),
+    JpV0(u16),
+    Rnd(u8, u8),
+    Drw(u8, u8, u8),
+    Skp(u8),
+    Sknp(u8),
+    LdVxDt(u8),
+    LdVxK(u8),
+    LdDtVx(u8),
+    LdStVx(u8),
+    AddIVx(u8),
+    LdFVx(u8),
+    LdBVx(u8),
+    LdIVx(u8),
+    LdVxI(u8),
+    // SUPER-CHIP 1.1
+    Scd(u8),
+    Scr,
+    Scl,
+    Exit,
+    Low,
+    High,
+    LdHfVx(u8),
+    LdRVx(u8),
+    LdVxR(u8),
+    // XO-CHIP
+    Scu(u8),
+    /// Store VX to VY at I and after
+    LdIRange(u8, u8),
+    /// Fill VX to VY from I and after
+    LdRangeI(u8, u8),
+    /// Load I with the address in the next word, the instruction is 4 bytes
+    LdILong,
+    Plane(u8),
+    Audio,
+    LdPitchVx(u8),
+    /// Ignored by the cpu
+    Unknown,
+}
+
+impl Instruction {
+    pub fn from_opcode(opcode: u16) -> Self {
+        let nibbles = (
+            ((opcode & 0xF000) >> 12) as u8,
+            ((opcode & 0x0F00) >> 8) as u8,
+            ((opcode & 0x00F0) >> 4) as u8,
+            (opcode & 0x000F) as u8,
+        );
+        let nnn = opcode & 0x0FFF;
+        let nn = (opcode & 0x00FF) as u8;
+
+        match nibbles {
+            (0x0, 0x0, 0xe, 0x0) => Self::Cls,
+            (0x0, 0x0, 0xe, 0xe) => Self::Ret,
+            (0x0, 0x0, 0xc, n) if n > 0 => Self::Scd(n),
+            (0x0, 0x0, 0xf, 0xb) => Self::Scr,
+            (0x0, 0x0, 0xf, 0xc) => Self::Scl,
+            (0x0, 0x0, 0xf, 0xd) => Self::Exit,
+            (0x0, 0x0, 0xf, 0xe) => Self::Low,
+            (0x0, 0x0, 0xf, 0xf) => Self::High,
+            (0x0, 0x0, 0xd, n) if n > 0 => Self::Scu(n),
+            (0x0, _, _, _) => Self::Sys(nnn),
+            (0x1, _, _, _) => Self::Jp(nnn),
+            (0x2, _, _, _) => Self::Call(nnn),
+            (0x3, x, _, _) => Self::SeByte(x, nn),
+            (0x4, x, _, _) => Self::SneByte(x, nn),
+            (0x5, x, y, 0) => Self::SeReg(x, y),
+            (0x5, x, y, 2) => Self::LdIRange(x, y),
+            (0x5, x, y, 3) => Self::LdRangeI(x, y),
+            (0x6, x, _, _) => Self::LdByte(x, nn),
+            (0x7, x, _, _) => Self::AddByte(x, nn),
+            (0x8, x, y, 0x0) => Self::LdReg(x, y),
+            (0x8, x, y, 0x1) => Self::Or(x, y),
+            (0x8, x, y, 0x2) => Self::And(x, y),
+            (0x8, x, y, 0x3) => Self::Xor(x, y),
+            (0x8, x, y, 0x4) => Self::AddReg(x, y),
+            (0x8, x, y, 0x5) => Self::Sub(x, y),
+            (0x8, x, y, 0x6) => Self::Shr(x, y),
+            (0x8, x, y, 0x7) => Self::Subn(x, y),
+            (0x8, x, y, 0xe) => Self::Shl(x, y),
+            (0x9, x, y, 0x0) => Self::SneReg(x, y),
+            (0xa, _, _, _) => Self::LdI(nnn),
+            (0xb, _, _, _) => Self::JpV0(nnn),
+            (0xc, x, _, _) => Self::Rnd(x, nn),
+            (0xd, x, y, n) => Self::Drw(x, y, n),
+            (0xe, x, 0x9, 0xe) => Self::Skp(x),
+            (0xe, x, 0xa, 0x1) => Self::Sknp(x),
+            (0xf, x, 0x0, 0x7) => Self::LdVxDt(x),
+            (0xf, x, 0x0, 0xa) => Self::LdVxK(x),
+            (0xf, x, 0x1, 0x5) => Self::LdDtVx(x),
+            (0xf, x, 0x1, 0x8) => Self::LdStVx(x),
+            (0xf, x, 0x1, 0xe) => Self::AddIVx(x),
+            (0xf, x, 0x2, 0x9) => Self::LdFVx(x),
+            (0xf, x, 0x3, 0x3) => Self::LdBVx(x),
+            (0xf, x, 0x5, 0x5) => Self::LdIVx(x),
+            (0xf, x, 0x6, 0x5) => Self::LdVxI(x),
+            (0xf, x, 0x3, 0x0) => Self::LdHfVx(x),
+            (0xf, x @ 0..=7, 0x7, 0x5) => Self::LdRVx(x),
+            (0xf, x @ 0..=7, 0x8, 0x5) => Self::LdVxR(x),
+            (0xf, 0x0, 0x0, 0x0) => Self::LdILong,
+            (0xf, n @ 0..=3, 0x0, 0x1) => Self::Plane(n),
+            (0xf, 0x0, 0x0, 0x2) => Self::Audio,
+            (0xf, x, 0x3, 0xa) => Self::LdPitchVx(x),
+            _ => Self::Unknown,
+        }
+    }
+
+    /// The opcode decoded as this instruction, none for `Unknown`
+    /// `LdILong` is followed by its address in the next word
+    pub fn opcode(&self) -> Option<u16> {
+        let xy = |high: u16, x: u8, y: u8, low: u16| {
+            high << 12 | (x as u16) << 8 | (y as u16) << 4 | low
+        };
+        let xnn =
+            |high: u16, x: u8, nn: u8| high << 12 | (x as u16) << 8 | nn as u16;
+        let fx = |x: u8, low: u16| 0xF000 | (x as u16) << 8 | low;
+
+        Some(match *self {
+            Self::Cls => 0x00E0,
+            Self::Ret => 0x00EE,
+            Self::Sys(nnn) => nnn & 0x0FFF,
+            Self::Jp(nnn) => 0x1000 | nnn & 0x0FFF,
+            Self::Call(nnn) => 0x2000 | nnn & 0x0FFF,
+            Self::SeByte(x, nn) => xnn(0x3, x, nn),
+            Self::SneByte(x, nn) => xnn(0x4, x, nn),
+            Self::SeReg(x, y) => xy(0x5, x, y, 0x0),
+            Self::LdByte(x, nn) => xnn(0x6, x, nn),
+            Self::AddByte(x, nn) => xnn(0x7, x, nn),
+            Self::LdReg(x, y) => xy(0x8, x, y, 0x0),
+            Self::Or(x, y) => xy(0x8, x, y, 0x1),
+            Self::And(x, y) => xy(0x8, x, y, 0x2),
+            Self::Xor(x, y) => xy(0x8, x, y, 0x3),
+            Self::AddReg(x, y) => xy(0x8, x, y, 0x4),
+            Self::Sub(x, y) => xy(0x8, x, y, 0x5),
+            Self::Shr(x, y) => xy(0x8, x, y, 0x6),
+            Self::Subn(x, y) => xy(0x8, x, y, 0x7),
+            Self::Shl(x, y) => xy(0x8, x, y, 0xE),
+            Self::SneReg(x, y) => xy(0x9, x, y, 0x0),
+            Self::LdI(nnn) => 0xA000 | nnn & 0x0FFF,
+            Self::JpV0(nnn) => 0xB000 | nnn & 0x0FFF,
+            Self::Rnd(x, nn) => xnn(0xC, x, nn),
+            Self::Drw(x, y, n) => xy(0xD, x, y, n as u16),
+            Self::Skp(x) => xnn(0xE, x, 0x9E),
+            Self::Sknp(x) => xnn(0xE, x, 0xA1),
+            Self::LdVxDt(x) => fx(x, 0x07),
+            Self::LdVxK(x) => fx(x, 0x0A),
+            Self::LdDtVx(x) => fx(x, 0x15),
+            Self::LdStVx(x) => fx(x, 0x18),
+            Self::AddIVx(x) => fx(x, 0x1E),
+            Self::LdFVx(x) => fx(x, 0x29),
+            Self::LdBVx(x) => fx(x, 0x33),
+            Self::LdIVx(x) => fx(x, 0x55),
+            Self::LdVxI(x) => fx(x, 0x65),
+            Self::Scd(n) => 0x00C0 | n as u16,
+            Self::Scr => 0x00FB,
+            Self::Scl => 0x00FC,
+            Self::Exit => 0x00FD,
+            Self::Low => 0x00FE,
+            Self::High => 0x00FF,
+            Self::LdHfVx(x) => fx(x, 0x30),
+            Self::LdRVx(x) => fx(x, 0x75),
+            Self::LdVxR(x) => fx(x, 0x85),
+            Self::Scu(n) => 0x00D0 | n as u16,
+            Self::LdIRange(x, y) => xy(0x5, x, y, 0x2),
+            Self::LdRangeI(x, y) => xy(0x5, x, y, 0x3),
+            Self::LdILong => 0xF000,
+            Self::Plane(n) => fx(n, 0x01),
+            Self::Audio => 0xF002,
+            Self::LdPitchVx(x) => fx(x, 0x3A),
+            Self::Unknown => return None,
+        })
+    }
+
+    /// Only run by the cpus in the XO-CHIP mode
+    pub fn is_xo_chip(&self) -> bool {
+        matches!(
+            self,
+            Self::Scu(_)
+                | Self::LdIRange(..)
+                | Self::LdRangeI(..)
+                | Self::LdILong
+                | Self::Plane(_)
+                | Self::Audio
+                | Self::LdPitchVx(_)
+        )
+    }
+}
+
+/// Every opcode decoded once, indexed by the opcode, shared by all the cpus
+pub fn table() -> &'static [Instruction] {
+    static TABLE: OnceLock<Box<[Instruction]>> = OnceLock::new();
+
+    TABLE.get_or_init(|| (0..=u16::MAX).map(Instruction::from_opcode).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::disassemble;
+
+    #[test]
+    fn test_from_opcode() {
+        assert_eq!(Instruction::from_opcode(0x00E0), Instruction::Cls);
+        assert_eq!(Instruction::from_opcode(0x0123), Instruction::Sys(0x123));
+        assert_eq!(
+            Instruction::from_opcode(0x8AB4),
+            Instruction::AddReg(0xA, 0xB)
+        );
+        assert_eq!(Instruction::from_opcode(0xD125), Instruction::Drw(1, 2, 5));
+        assert_eq!(Instruction::from_opcode(0xF365), Instruction::LdVxI(3));
+        assert_eq!(Instruction::from_opcode(0x5121), Instruction::Unknown);
+
+        assert_eq!(Instruction::from_opcode(0x00C4), Instruction::Scd(4));
+        assert_eq!(Instruction::from_opcode(0x00C0), Instruction::Sys(0x0C0));
+        assert_eq!(Instruction::from_opcode(0x00FF), Instruction::High);
+        assert_eq!(Instruction::from_opcode(0xF530), Instruction::LdHfVx(5));
+        assert_eq!(Instruction::from_opcode(0xF775), Instruction::LdRVx(7));
+        // the HP48 has 8 flags
+        assert_eq!(Instruction::from_opcode(0xF885), Instruction::Unknown);
+
+        assert_eq!(Instruction::from_opcode(0x00D2), Instruction::Scu(2));
+        assert_eq!(
+            Instruction::from_opcode(0x5352),
+            Instruction::LdIRange(3, 5)
+        );
+        assert_eq!(Instruction::from_opcode(0xF000), Instruction::LdILong);
+        assert_eq!(Instruction::from_opcode(0xF301), Instruction::Plane(3));
+        assert_eq!(Instruction::from_opcode(0xF401), Instruction::Unknown);
+        assert_eq!(Instruction::from_opcode(0xF23A), Instruction::LdPitchVx(2));
+        assert!(Instruction::from_opcode(0xF002).is_xo_chip());
+        assert!(!Instruction::from_opcode(0x00FF).is_xo_chip());
+    }
+
+    #[test]
+    fn test_table() {
+        let table = table();
+        assert_eq!(table.len(), 0x10000);
+
+        for opcode in 0..=u16::MAX {
+            let instruction = table[opcode as usize];
+            assert_eq!(instruction, Instruction::from_opcode(opcode));
+            assert_eq!(
+                instruction.opcode(),
+                (instruction != Instruction::Unknown).then_some(opcode)
+            );
+            // the same opcodes are unknown to the disassembler
+            assert_eq!(
+                instruction == Instruction::Unknown,
+                disassemble(opcode).starts_with("DW"),
+                "{:04X}",
+                opcode
+            );
+        }
+    }
+}
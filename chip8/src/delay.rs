@@ -1,7 +1,17 @@
-use crate::bus::Bus;
+use crate::{
+    bus::Bus,
+    state::{Snapshot, StateError, StateReader, StateWriter},
+};
 
+#[derive(Clone)]
 pub struct Delay {}
 
+impl Default for Delay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Delay {
     pub fn new() -> Self {
         Self {}
@@ -32,3 +42,12 @@ impl DelayBus for Bus {
         self.delay
     }
 }
+
+/// Nothing to save, the timer is on the bus
+impl Snapshot for Delay {
+    fn save(&self, _state: &mut StateWriter) {}
+
+    fn load(&mut self, _state: &mut StateReader) -> Result<(), StateError> {
+        Ok(())
+    }
+}
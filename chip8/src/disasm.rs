@@ -0,0 +1,141 @@
+use crate::decode::Instruction;
+
+/// Mnemonic of an opcode, in the usual Cowgod syntax
+/// Unknown opcodes are shown as data words
+pub fn disassemble(opcode: u16) -> String {
+    match Instruction::from_opcode(opcode) {
+        Instruction::Unknown => format!("DW 0x{:04X}", opcode),
+        instruction => mnemonic(instruction),
+    }
+}
+
+/// Mnemonic of a decoded instruction, `Unknown` has no opcode to show
+pub fn mnemonic(instruction: Instruction) -> String {
+    use Instruction::*;
+
+    match instruction {
+        Cls => "CLS".to_string(),
+        Ret => "RET".to_string(),
+        Scd(n) => format!("SCD {}", n),
+        Scr => "SCR".to_string(),
+        Scl => "SCL".to_string(),
+        Exit => "EXIT".to_string(),
+        Low => "LOW".to_string(),
+        High => "HIGH".to_string(),
+        Scu(n) => format!("SCU {}", n),
+        Sys(nnn) => format!("SYS 0x{:03X}", nnn),
+        Jp(nnn) => format!("JP 0x{:03X}", nnn),
+        Call(nnn) => format!("CALL 0x{:03X}", nnn),
+        SeByte(x, nn) => format!("SE V{:X}, 0x{:02X}", x, nn),
+        SneByte(x, nn) => format!("SNE V{:X}, 0x{:02X}", x, nn),
+        SeReg(x, y) => format!("SE V{:X}, V{:X}", x, y),
+        LdIRange(x, y) => format!("LD [I], V{:X}-V{:X}", x, y),
+        LdRangeI(x, y) => format!("LD V{:X}-V{:X}, [I]", x, y),
+        LdByte(x, nn) => format!("LD V{:X}, 0x{:02X}", x, nn),
+        AddByte(x, nn) => format!("ADD V{:X}, 0x{:02X}", x, nn),
+        LdReg(x, y) => format!("LD V{:X}, V{:X}", x, y),
+        Or(x, y) => format!("OR V{:X}, V{:X}", x, y),
+        And(x, y) => format!("AND V{:X}, V{:X}", x, y),
+        Xor(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+        AddReg(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+        Sub(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+        Shr(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+        Subn(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+        Shl(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+        SneReg(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+        LdI(nnn) => format!("LD I, 0x{:03X}", nnn),
+        JpV0(nnn) => format!("JP V0, 0x{:03X}", nnn),
+        Rnd(x, nn) => format!("RND V{:X}, 0x{:02X}", x, nn),
+        Drw(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        Skp(x) => format!("SKP V{:X}", x),
+        Sknp(x) => format!("SKNP V{:X}", x),
+        LdVxDt(x) => format!("LD V{:X}, DT", x),
+        LdVxK(x) => format!("LD V{:X}, K", x),
+        LdDtVx(x) => format!("LD DT, V{:X}", x),
+        LdStVx(x) => format!("LD ST, V{:X}", x),
+        AddIVx(x) => format!("ADD I, V{:X}", x),
+        LdFVx(x) => format!("LD F, V{:X}", x),
+        LdBVx(x) => format!("LD B, V{:X}", x),
+        LdIVx(x) => format!("LD [I], V{:X}", x),
+        LdVxI(x) => format!("LD V{:X}, [I]", x),
+        LdHfVx(x) => format!("LD HF, V{:X}", x),
+        LdRVx(x) => format!("LD R, V{:X}", x),
+        LdVxR(x) => format!("LD V{:X}, R", x),
+        LdILong => "LD I, LONG".to_string(),
+        Plane(n) => format!("PLANE {}", n),
+        Audio => "AUDIO".to_string(),
+        LdPitchVx(x) => format!("LD PITCH, V{:X}", x),
+        Unknown => "DW".to_string(),
+    }
+}
+
+/// Disassemble the instruction at `addr`, big endian like the cpu reads it
+pub fn disassemble_at(memory: &[u8], addr: u16) -> (u16, String) {
+    let addr = addr as usize;
+    let high = memory.get(addr).copied().unwrap_or(0);
+    let low = memory.get(addr + 1).copied().unwrap_or(0);
+    let opcode = u16::from_be_bytes([high, low]);
+
+    (opcode, disassemble(opcode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble() {
+        let cases = [
+            (0x00E0, "CLS"),
+            (0x00EE, "RET"),
+            (0x0123, "SYS 0x123"),
+            (0x1208, "JP 0x208"),
+            (0x2ABC, "CALL 0xABC"),
+            (0x3A0F, "SE VA, 0x0F"),
+            (0x5120, "SE V1, V2"),
+            (0x6B42, "LD VB, 0x42"),
+            (0x8AB4, "ADD VA, VB"),
+            (0x812E, "SHL V1, V2"),
+            (0xA300, "LD I, 0x300"),
+            (0xB200, "JP V0, 0x200"),
+            (0xD015, "DRW V0, V1, 5"),
+            (0xE39E, "SKP V3"),
+            (0xF50A, "LD V5, K"),
+            (0xF229, "LD F, V2"),
+            (0xF455, "LD [I], V4"),
+            (0xF465, "LD V4, [I]"),
+            (0x00C3, "SCD 3"),
+            (0x00FB, "SCR"),
+            (0x00FF, "HIGH"),
+            (0xD120, "DRW V1, V2, 0"),
+            (0xF330, "LD HF, V3"),
+            (0xF775, "LD R, V7"),
+            (0xF285, "LD V2, R"),
+            (0xF985, "DW 0xF985"),
+            (0x00D4, "SCU 4"),
+            (0x5132, "LD [I], V1-V3"),
+            (0x5E23, "LD VE-V2, [I]"),
+            (0xF000, "LD I, LONG"),
+            (0xF201, "PLANE 2"),
+            (0xF002, "AUDIO"),
+            (0xF53A, "LD PITCH, V5"),
+            (0x5121, "DW 0x5121"),
+            (0xFFFF, "DW 0xFFFF"),
+        ];
+
+        for (opcode, text) in cases {
+            assert_eq!(disassemble(opcode), text, "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn test_disassemble_at() {
+        let memory = [0x00, 0xE0, 0x12];
+
+        assert_eq!(disassemble_at(&memory, 0), (0x00E0, "CLS".to_string()));
+        assert_eq!(
+            disassemble_at(&memory, 2),
+            (0x1200, "JP 0x200".to_string())
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use std::{
+    fmt::{self, Display},
+    io,
+};
+
+/// Why a rom can't be loaded or run
+#[derive(Debug)]
+pub enum Error {
+    /// The rom file couldn't be read
+    Io(io::Error),
+    /// The rom doesn't fit in the memory after 0x200
+    RomTooBig { size: usize, max: usize },
+    /// The address is past the end of the rom
+    OutOfRange(u16),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::RomTooBig { size, max } => {
+                write!(f, "rom of {} bytes, at most {} fit", size, max)
+            }
+            Error::OutOfRange(addr) => {
+                write!(f, "address 0x{:04X} outside of the rom", addr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
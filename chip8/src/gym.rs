@@ -0,0 +1,172 @@
+//! A gym-like interface for agents playing a rom: they observe the screen,
+//! choose the keys held, and advance frames
+//!
+//! ```
+//! use chip8::{gym::Env, rom::Rom};
+//!
+//! // 7001: V0 += 1, 1200: loop
+//! let rom = Rom::from_bytes(vec![0x70, 0x01, 0x12, 0x00]);
+//! let mut env = Env::new(rom, 1).watch(0x200..0x202);
+//! let observation = env.reset();
+//! assert_eq!(observation.pixels.len(), 64 * 32);
+//! assert_eq!(observation.memory, [0x70, 0x01]);
+//!
+//! env.act(1 << 0x5); // hold key 5
+//! assert!(env.step_frames(10));
+//! assert_eq!(env.frame(), 10);
+//! ```
+
+use std::ops::Range;
+
+use crate::{
+    bus::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    machine::Machine,
+    rom::Rom,
+};
+
+/// What an agent sees of the machine
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Observation {
+    /// 1 for a lit pixel, 0 otherwise, row by row
+    pub pixels: Vec<u8>,
+    /// The watched ranges of the memory, one after the other
+    pub memory: Vec<u8>,
+}
+
+/// A machine stepped by an agent, restarted from the same state by `reset`
+pub struct Env {
+    machine: Machine,
+    initial: Machine,
+    watched: Vec<Range<usize>>,
+    frame: u64,
+}
+
+impl Env {
+    /// Random numbers from `seed`, each episode is the same with the same
+    /// actions
+    pub fn new(rom: Rom, seed: u64) -> Self {
+        let mut machine = Machine::new(rom);
+        machine.cpu_mut().set_seed(seed);
+
+        Self {
+            initial: machine.clone(),
+            machine,
+            watched: vec![],
+            frame: 0,
+        }
+    }
+
+    /// Instructions per second
+    pub fn speed(mut self, frequency: f64) -> Self {
+        self.initial.set_cpu_frequency(frequency);
+        self.machine.set_cpu_frequency(frequency);
+        self
+    }
+
+    /// Add `range` of the memory to the observations, the score of a game
+    /// for instance; clamped to the memory, a reversed range is empty
+    pub fn watch(mut self, range: Range<usize>) -> Self {
+        let end = range.end.min(self.machine.bus().memory().len());
+        self.watched.push(range.start.min(end)..end);
+        self
+    }
+
+    /// Start a new episode from the start of the rom
+    pub fn reset(&mut self) -> Observation {
+        self.machine = self.initial.clone();
+        self.frame = 0;
+        self.observe()
+    }
+
+    pub fn observe(&self) -> Observation {
+        let bus = self.machine.bus();
+        let mut pixels = Vec::with_capacity(DISPLAY_WIDTH * DISPLAY_HEIGHT);
+        for y in 0..DISPLAY_HEIGHT {
+            pixels.extend((0..DISPLAY_WIDTH).map(|x| bus.pixel(x, y) as u8));
+        }
+        let memory = self
+            .watched
+            .iter()
+            .flat_map(|range| &bus.memory()[range.clone()])
+            .copied()
+            .collect();
+
+        Observation { pixels, memory }
+    }
+
+    /// Hold the keys whose bits are set, key 0 in the lowest bit, until the
+    /// next action
+    pub fn act(&mut self, keys: u16) {
+        for (key, held) in self.machine.bus_mut().keys.iter_mut().enumerate() {
+            *held = keys & (1 << key) != 0;
+        }
+    }
+
+    /// Run `frames` frames, stopping early when the rom halts
+    /// Returns false once the episode is over
+    pub fn step_frames(&mut self, frames: u64) -> bool {
+        for _ in 0..frames {
+            if self.machine.is_halted() {
+                break;
+            }
+            self.machine.run_frame();
+            self.frame += 1;
+        }
+
+        !self.machine.is_halted()
+    }
+
+    /// Frames run since the last reset
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// The machine, for what the observations don't show
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_episode() {
+        let rom = Rom::from_bytes(vec![
+            0xE0, 0x9E, // skip while key V0 = 0 is held
+            0x12, 0x00, // loop
+            0xD1, 0x15, // draw the 0 of the font at 0, 0
+            0x12, 0x06, // halt
+        ]);
+        #[allow(clippy::reversed_empty_ranges)]
+        let mut env = Env::new(rom, 1)
+            .watch(0x200..0x202)
+            .watch(0x300..0x200)
+            .watch(0xFFFF..0x1_0000);
+
+        let observation = env.reset();
+        assert!(observation.pixels.iter().all(|&pixel| pixel == 0));
+        assert_eq!(observation.memory, [0xE0, 0x9E]);
+
+        assert!(env.step_frames(5));
+        env.act(0b1);
+        assert!(!env.step_frames(5));
+        // the top row of the 0 is F0
+        let observation = env.observe();
+        assert_eq!(observation.pixels[..8], [1, 1, 1, 1, 0, 0, 0, 0]);
+        assert_eq!(env.frame(), 6);
+
+        env.reset();
+        assert_eq!(env.frame(), 0);
+        assert!(env.observe().pixels.iter().all(|&pixel| pixel == 0));
+    }
+
+    #[test]
+    fn test_halt() {
+        // 1200: jump to itself
+        let mut env = Env::new(Rom::from_bytes(vec![0x12, 0x00]), 1);
+        assert!(!env.step_frames(10));
+        assert_eq!(env.frame(), 0);
+    }
+}
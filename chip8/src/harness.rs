@@ -0,0 +1,57 @@
+//! Test roms run without any frontend, like the ones of the Timendus suite,
+//! to check the screen they end on
+//!
+//! ```
+//! use chip8::{harness::run_headless, rom::Rom};
+//!
+//! // A000: I = font 0, D005: draw it, 1204: halt
+//! let rom = Rom::from_bytes(vec![0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04]);
+//! let snapshot = run_headless(rom, 3);
+//! assert_eq!(snapshot.framebuffer.pixel(0, 0), 1);
+//! ```
+
+use crate::{bus::Framebuffer, machine::Machine, rom::Rom, testing::SEED};
+
+/// The screen a rom ended on, see `run_headless`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameBufferSnapshot {
+    pub framebuffer: Framebuffer,
+    /// `Bus::frame_hash` of the screen, to compare with a known good one
+    pub hash: u64,
+}
+
+/// Run `cycles` instructions of `rom` from its start, the timers updated
+/// at the default speed, then take its screen
+pub fn run_headless(rom: Rom, cycles: u64) -> FrameBufferSnapshot {
+    let mut machine = Machine::new(rom);
+    machine.cpu_mut().set_seed(SEED);
+    for _ in 0..cycles {
+        machine.step();
+    }
+
+    FrameBufferSnapshot {
+        framebuffer: machine.bus().framebuffer(),
+        hash: machine.bus().frame_hash(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bus::DISPLAY_WIDTH, testing::TestRun};
+
+    #[test]
+    fn test_run_headless() {
+        let rom = [0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04];
+        let snapshot = run_headless(Rom::from_bytes(rom.into()), 3);
+        assert_eq!(snapshot.framebuffer.width, DISPLAY_WIDTH);
+        assert_eq!(snapshot.framebuffer.pixel(0, 0), 1);
+        assert_eq!(snapshot.framebuffer.pixel(4, 0), 0);
+
+        // the draw was the second instruction
+        TestRun::rom(rom)
+            .run_frames(1)
+            .expect_frame_hash(snapshot.hash);
+        assert_ne!(run_headless(Rom::from_bytes(rom.into()), 1), snapshot);
+    }
+}
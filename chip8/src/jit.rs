@@ -0,0 +1,437 @@
+use std::mem;
+
+use cranelift_codegen::{
+    ir::{condcodes::IntCC, types, AbiParam, InstBuilder, MemFlags, Value},
+    settings::{self, Configurable},
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Module};
+use log::{debug, warn};
+
+use crate::{
+    bus::Bus,
+    cpu::{Cpu, KeyWaitPolicy, Quirks, V_SIZE},
+};
+
+const MEMORY_SIZE: usize = 0x1000;
+
+/// Instructions translated at most in a block
+const MAX_BLOCK: usize = 64;
+
+/// The registers seen by the native code
+#[repr(C)]
+struct State {
+    v: [u8; V_SIZE],
+    i: u16,
+    pc: u16,
+}
+
+const V_OFFSET: i32 = 0;
+const I_OFFSET: i32 = V_SIZE as i32;
+const PC_OFFSET: i32 = I_OFFSET + 2;
+
+type BlockFn = unsafe extern "C" fn(*mut State);
+
+/// Code translated from the instructions at an address
+struct Block {
+    /// The bytes it was translated from, a block whose bytes changed in
+    /// memory is translated again
+    bytes: Vec<u8>,
+    /// Instructions run by the code, none when the first one can't be
+    /// translated
+    len: u32,
+    code: Option<BlockFn>,
+}
+
+/// Experimental backend translating the basic blocks of a rom to native
+/// code with cranelift, run by `Machine::run_frame_jit`
+///
+/// Only the instructions working on the registers are translated, the
+/// others are left to the interpreter. The code of the blocks dropped
+/// after a self-modification is kept until the `Jit` is dropped.
+pub struct Jit {
+    module: Option<JITModule>,
+    context: FunctionBuilderContext,
+    blocks: Vec<Option<Block>>,
+}
+
+impl Jit {
+    /// Fails when cranelift doesn't support the host
+    pub fn new() -> Result<Self, String> {
+        let mut flags = settings::builder();
+        flags
+            .set("use_colocated_libcalls", "false")
+            .and_then(|_| flags.set("is_pic", "false"))
+            .and_then(|_| flags.set("opt_level", "speed"))
+            .map_err(|e| e.to_string())?;
+        let isa = cranelift_native::builder()
+            .map_err(|e| e.to_string())?
+            .finish(settings::Flags::new(flags))
+            .map_err(|e| e.to_string())?;
+        let module =
+            JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+
+        Ok(Self {
+            module: Some(module),
+            context: FunctionBuilderContext::new(),
+            blocks: (0..MEMORY_SIZE).map(|_| None).collect(),
+        })
+    }
+
+    /// Run the block at the pc if it is no longer than `budget`
+    /// instructions, returns the instructions run, 0 when the interpreter
+    /// has to run the next one
+    pub(crate) fn run(&mut self, cpu: &mut Cpu, bus: &Bus, budget: u32) -> u32 {
+        // the interpreter tracks the keys before each instruction, and the
+        // blocks are translated for the 4KB of the chip8 and the default
+        // quirks
+        if cpu.key_await().is_some()
+            || cpu.key_wait_policy() == KeyWaitPolicy::MostRecentlyPressed
+            || cpu.is_xo_chip()
+            || cpu.quirks() != Quirks::default()
+        {
+            return 0;
+        }
+
+        let pc = cpu.pc() as usize;
+        let memory = bus.memory();
+        let stale = match &self.blocks[pc] {
+            Some(block) => {
+                memory.get(pc..pc + block.bytes.len())
+                    != Some(block.bytes.as_slice())
+            }
+            None => true,
+        };
+        if stale {
+            let block = self.translate(memory, pc);
+            self.blocks[pc] = Some(block);
+        }
+
+        let block = self.blocks[pc].as_ref().expect("translated block");
+        let Some(code) = block.code.filter(|_| block.len <= budget) else {
+            return 0;
+        };
+
+        let mut state = State {
+            v: *cpu.registers(),
+            i: cpu.index(),
+            pc: cpu.pc(),
+        };
+        // SAFETY: the code was compiled for this signature, and only
+        // accesses the fields of the state
+        unsafe { code(&mut state) };
+
+        cpu.v = state.v;
+        cpu.i = state.i;
+        cpu.pc = state.pc;
+
+        block.len
+    }
+
+    fn translate(&mut self, memory: &[u8], start: usize) -> Block {
+        let opcodes: Vec<u16> = (start..memory.len() - 1)
+            .step_by(2)
+            .map(|addr| u16::from_be_bytes([memory[addr], memory[addr + 1]]))
+            .take(MAX_BLOCK)
+            .collect();
+        let len = block_len(&opcodes);
+        let end = (start + 2 * len.max(1)).min(memory.len());
+        let bytes = memory[start..end].to_vec();
+
+        let code = match len {
+            0 => None,
+            len => match self.compile(start as u16, &opcodes[..len]) {
+                Ok(code) => Some(code),
+                Err(e) => {
+                    warn!("block {:03X} not compiled: {}", start, e);
+                    None
+                }
+            },
+        };
+        debug!("block {:03X}: {} instructions", start, len);
+
+        Block {
+            bytes,
+            len: if code.is_some() { len as u32 } else { 0 },
+            code,
+        }
+    }
+
+    fn compile(
+        &mut self,
+        start: u16,
+        opcodes: &[u16],
+    ) -> Result<BlockFn, String> {
+        let module = self.module.as_mut().expect("jit module");
+        let mut ctx = module.make_context();
+        let pointer = module.target_config().pointer_type();
+        ctx.func.signature.params.push(AbiParam::new(pointer));
+
+        let mut builder =
+            FunctionBuilder::new(&mut ctx.func, &mut self.context);
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+        let state = builder.block_params(entry)[0];
+        let flags = MemFlags::trusted();
+
+        // a single basic block, the registers are plain values
+        let mut v: Vec<Value> = (0..V_SIZE as i32)
+            .map(|x| builder.ins().load(types::I8, flags, state, V_OFFSET + x))
+            .collect();
+        let mut i = builder.ins().load(types::I16, flags, state, I_OFFSET);
+        let mut pc = None;
+
+        for (index, &opcode) in opcodes.iter().enumerate() {
+            let addr = start as usize + 2 * index;
+            let next = ((addr + 2) % MEMORY_SIZE) as i64;
+            let skip = ((addr + 4) % MEMORY_SIZE) as i64;
+            let x = (opcode >> 8 & 0xF) as usize;
+            let y = (opcode >> 4 & 0xF) as usize;
+            let nn = (opcode & 0xFF) as i64;
+            let nnn = (opcode & 0xFFF) as i64;
+            let (vx, vy) = (v[x], v[y]);
+
+            match opcode >> 12 {
+                0x1 => pc = Some(builder.ins().iconst(types::I16, nnn)),
+                0x3 | 0x4 | 0x5 | 0x9 => {
+                    let (cc, other) = match opcode >> 12 {
+                        0x3 => {
+                            (IntCC::Equal, builder.ins().iconst(types::I8, nn))
+                        }
+                        0x4 => (
+                            IntCC::NotEqual,
+                            builder.ins().iconst(types::I8, nn),
+                        ),
+                        0x5 => (IntCC::Equal, vy),
+                        _ => (IntCC::NotEqual, vy),
+                    };
+                    let condition = builder.ins().icmp(cc, vx, other);
+                    let skip = builder.ins().iconst(types::I16, skip);
+                    let next = builder.ins().iconst(types::I16, next);
+                    pc = Some(builder.ins().select(condition, skip, next));
+                }
+                0x6 => v[x] = builder.ins().iconst(types::I8, nn),
+                0x7 => v[x] = builder.ins().iadd_imm(vx, nn),
+                0x8 => {
+                    let (value, flag) = match opcode & 0xF {
+                        0x0 => (vy, None),
+                        0x1 => (builder.ins().bor(vx, vy), None),
+                        0x2 => (builder.ins().band(vx, vy), None),
+                        0x3 => (builder.ins().bxor(vx, vy), None),
+                        0x4 => {
+                            let sum = builder.ins().iadd(vx, vy);
+                            let carry = builder.ins().icmp(
+                                IntCC::UnsignedLessThan,
+                                sum,
+                                vx,
+                            );
+                            (sum, Some(carry))
+                        }
+                        0x5 => {
+                            let difference = builder.ins().isub(vx, vy);
+                            let no_borrow = builder.ins().icmp(
+                                IntCC::UnsignedGreaterThanOrEqual,
+                                vx,
+                                vy,
+                            );
+                            (difference, Some(no_borrow))
+                        }
+                        0x6 => {
+                            let shifted = builder.ins().ushr_imm(vy, 1);
+                            (shifted, Some(builder.ins().band_imm(vy, 1)))
+                        }
+                        0x7 => {
+                            let difference = builder.ins().isub(vy, vx);
+                            let no_borrow = builder.ins().icmp(
+                                IntCC::UnsignedGreaterThanOrEqual,
+                                vy,
+                                vx,
+                            );
+                            (difference, Some(no_borrow))
+                        }
+                        _ => {
+                            let shifted = builder.ins().ishl_imm(vy, 1);
+                            (shifted, Some(builder.ins().ushr_imm(vy, 7)))
+                        }
+                    };
+                    // the flag is written last, over VX when X is F
+                    v[x] = value;
+                    if let Some(flag) = flag {
+                        v[0xF] = flag;
+                    }
+                }
+                0xA => i = builder.ins().iconst(types::I16, nnn),
+                0xB => {
+                    let v0 = builder.ins().uextend(types::I16, v[0]);
+                    let target = builder.ins().iadd_imm(v0, nnn);
+                    pc = Some(builder.ins().band_imm(target, 0x0FFF));
+                }
+                _ => {
+                    // FX1E, the only other instruction of `block_len`
+                    let vx = builder.ins().uextend(types::I16, vx);
+                    let sum = builder.ins().iadd(i, vx);
+                    i = builder.ins().band_imm(sum, 0x0FFF);
+                }
+            }
+        }
+
+        let end = start as usize + 2 * opcodes.len();
+        let pc = match pc {
+            Some(pc) => pc,
+            None => {
+                builder.ins().iconst(types::I16, (end % MEMORY_SIZE) as i64)
+            }
+        };
+        for (x, &value) in v.iter().enumerate() {
+            builder
+                .ins()
+                .store(flags, value, state, V_OFFSET + x as i32);
+        }
+        builder.ins().store(flags, i, state, I_OFFSET);
+        builder.ins().store(flags, pc, state, PC_OFFSET);
+        builder.ins().return_(&[]);
+        builder.finalize();
+
+        let id = module
+            .declare_anonymous_function(&ctx.func.signature)
+            .map_err(|e| e.to_string())?;
+        module
+            .define_function(id, &mut ctx)
+            .map_err(|e| e.to_string())?;
+        module.clear_context(&mut ctx);
+        module.finalize_definitions().map_err(|e| e.to_string())?;
+
+        let code = module.get_finalized_function(id);
+        // SAFETY: the function was built with a single pointer parameter
+        // and no result
+        Ok(unsafe { mem::transmute::<*const u8, BlockFn>(code) })
+    }
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        self.blocks.clear();
+        if let Some(module) = self.module.take() {
+            // SAFETY: the blocks, the only references to the code, are gone
+            unsafe { module.free_memory() };
+        }
+    }
+}
+
+/// Instructions from the start of `opcodes` that can be translated, a jump
+/// or a skip ends the block
+fn block_len(opcodes: &[u16]) -> usize {
+    let mut len = 0;
+    for &opcode in opcodes {
+        let translated = matches!(
+            (opcode >> 12, opcode & 0xF, opcode & 0xFF),
+            (0x1 | 0x3 | 0x4 | 0x6 | 0x7 | 0xA | 0xB, _, _)
+                | (0x5 | 0x9, 0x0, _)
+                | (0x8, 0x0..=0x7 | 0xE, _)
+                | (0xF, _, 0x1E)
+        );
+        if !translated {
+            break;
+        }
+
+        len += 1;
+        if matches!(opcode >> 12, 0x1 | 0x3 | 0x4 | 0x5 | 0x9 | 0xB) {
+            break;
+        }
+    }
+
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{machine::Machine, rom::Rom};
+
+    fn create_machines(rom: Vec<u8>) -> (Machine, Machine) {
+        let mut machine = Machine::new(Rom::from_bytes(rom));
+        machine.cpu_mut().set_seed(1);
+        machine.set_cpu_frequency(6_000.0);
+
+        (machine.clone(), machine)
+    }
+
+    /// Run `frames` frames with and without the jit, the states must be the
+    /// same after each
+    fn check(rom: Vec<u8>, frames: usize) {
+        let (mut interpreted, mut compiled) = create_machines(rom);
+        let mut jit = Jit::new().expect("jit");
+
+        for frame in 0..frames {
+            interpreted.run_frame();
+            compiled.run_frame_jit(&mut jit);
+            assert!(
+                interpreted.save_state() == compiled.save_state(),
+                "frame {}, pc {:03X} and {:03X}",
+                frame,
+                interpreted.cpu().pc(),
+                compiled.cpu().pc()
+            );
+        }
+    }
+
+    #[test]
+    fn test_block_len() {
+        assert_eq!(block_len(&[0x6005, 0x7101, 0x8014, 0x1200]), 4);
+        assert_eq!(block_len(&[0x6005, 0x3005, 0x6006]), 2);
+        assert_eq!(block_len(&[0x6005, 0xD015, 0x1200]), 1);
+        assert_eq!(block_len(&[0xD015]), 0);
+        assert_eq!(block_len(&[0x5121]), 0);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        check(
+            vec![
+                0x60, 0xF0, // V0 = F0
+                0x61, 0x20, // V1 = 20
+                0x80, 0x14, // V0 += V1
+                0x82, 0x06, // V2 = V0 >> 1
+                0x83, 0x0E, // V3 = V0 << 1
+                0x8F, 0x15, // VF -= V1
+                0xF0, 0x1E, // I += V0
+                0x30, 0x10, // skip if V0 == 10
+                0x12, 0x00, // loop
+                0x12, 0x12, // halt
+            ],
+            10,
+        );
+    }
+
+    #[test]
+    fn test_self_modification() {
+        check(
+            vec![
+                0x70, 0x01, // V0 += 1, its immediate rewritten each loop
+                0xA2, 0x01, // I = the immediate
+                0xF0, 0x55, // write V0 there
+                0x12, 0x00, // loop
+            ],
+            30,
+        );
+    }
+
+    #[test]
+    fn test_random_programs() {
+        let mut seed = 0x2545F4914F6CDD1D_u64;
+        for _ in 0..200 {
+            let rom = (0..0x200)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    seed as u8
+                })
+                .collect();
+            check(rom, 20);
+        }
+    }
+}
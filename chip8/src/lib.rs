@@ -1,6 +1,20 @@
+pub mod asm;
 pub mod beep;
 pub mod bus;
 pub mod cpu;
+pub mod decode;
 pub mod delay;
+pub mod disasm;
+pub mod error;
+pub mod gym;
+pub mod harness;
+#[cfg(feature = "jit")]
+pub mod jit;
 pub mod keypad;
+pub mod machine;
+pub mod replay;
 pub mod rom;
+pub mod state;
+pub mod testing;
+
+pub use error::Error;
@@ -0,0 +1,858 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    beep::Beeper,
+    bus::Bus,
+    cpu::{Cpu, CpuBus},
+    decode::{self, Instruction},
+    delay::Delay,
+    error::Error,
+    keypad::Keypad,
+    rom::Rom,
+    state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+#[cfg(feature = "jit")]
+use crate::jit::Jit;
+
+/// Timers are updated once per frame
+pub const FRAME_RATE: f64 = 60.0;
+pub const CPU_FREQUENCY: f64 = 500.0;
+/// Most frames run at once to catch up, at the normal speed
+const CATCH_UP_FRAMES: f64 = 4.0;
+
+/// Why `run_until_event` returned
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A 00E0 or a DXYN ran
+    Draw,
+    /// The beeper started or stopped, at the end of a frame
+    Sound(bool),
+    /// A FX0A started waiting for a key
+    KeyWait,
+    /// The delay timer reached 0, at the end of a frame
+    TimerExpired,
+    /// The pc is on a breakpoint, the instruction isn't run yet
+    Breakpoint(u16),
+    /// Nothing happened in the frames allowed
+    Timeout,
+}
+
+/// Frames counted by `Machine::frames_due`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FramesDue {
+    /// To run now
+    pub run: u32,
+    /// Dropped to not catch up after a hitch
+    pub missed: u64,
+}
+
+/// The chip8 components scheduled frame by frame
+#[derive(Clone)]
+pub struct Machine {
+    rom: Rom,
+    cpu: Cpu,
+    delay: Delay,
+    beeper: Beeper,
+    bus: Bus,
+    cpu_frequency: f64,
+    cpu_cycles: f64,
+    frame_interrupted: bool,
+    speed: f64,
+    turbo: bool,
+    paused: bool,
+    /// Frames of real time not run yet
+    frames_due: f64,
+}
+
+impl Machine {
+    /// The end of a rom too big for the memory is cut, see `try_new`
+    pub fn new(rom: Rom) -> Self {
+        let bus = Bus::new(rom.clone());
+
+        Self::with_bus(rom, bus)
+    }
+
+    /// Like `new`, but a rom too big for the memory is an error
+    pub fn try_new(rom: Rom) -> Result<Self, Error> {
+        let bus = Bus::try_new(rom.clone())?;
+
+        Ok(Self::with_bus(rom, bus))
+    }
+
+    /// A machine in the XO-CHIP mode, with its 64KB of memory
+    pub fn try_new_xo_chip(rom: Rom) -> Result<Self, Error> {
+        let bus = Bus::try_new_xo_chip(rom.clone())?;
+        let mut machine = Self::with_bus(rom, bus);
+        machine.cpu.set_xo_chip(true);
+
+        Ok(machine)
+    }
+
+    fn with_bus(rom: Rom, bus: Bus) -> Self {
+        Self {
+            rom,
+            cpu: Cpu::new(),
+            delay: Delay::new(),
+            beeper: Beeper::new(),
+            bus,
+            cpu_frequency: CPU_FREQUENCY,
+            cpu_cycles: 0.0,
+            frame_interrupted: false,
+            speed: 1.0,
+            turbo: false,
+            paused: false,
+            frames_due: 0.0,
+        }
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut Cpu {
+        &mut self.cpu
+    }
+
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut Bus {
+        &mut self.bus
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        self.beeper.is_beeping()
+    }
+
+    /// Instructions executed per second
+    pub fn cpu_frequency(&self) -> f64 {
+        self.cpu_frequency
+    }
+
+    /// A frequency that isn't finite is ignored, frames would never end
+    pub fn set_cpu_frequency(&mut self, frequency: f64) {
+        if frequency.is_finite() {
+            self.cpu_frequency = frequency.max(0.0);
+        }
+    }
+
+    /// Frames run in the time of one by `tick`
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Run the frames faster or slower than their rate, the speed of the cpu
+    /// within a frame is kept
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn is_turbo(&self) -> bool {
+        self.turbo
+    }
+
+    /// Run as many frames as the host can in place of the speed, `tick`
+    /// spends half the time it is given on them
+    pub fn set_turbo(&mut self, turbo: bool) {
+        self.turbo = turbo;
+        self.frames_due = 0.0;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// No frame is due while paused, the time isn't made up for after
+    pub fn pause(&mut self, paused: bool) {
+        self.paused = paused;
+        self.frames_due = 0.0;
+    }
+
+    pub fn is_xo_chip(&self) -> bool {
+        self.cpu.is_xo_chip()
+    }
+
+    /// Run the rom as an XO-CHIP one with 64KB of memory, or back, the rom
+    /// restarts
+    pub fn set_xo_chip(&mut self, xo_chip: bool) {
+        self.cpu.set_xo_chip(xo_chip);
+        self.reset();
+    }
+
+    /// The rom jumps to itself, the usual way to stop a chip8 program, or
+    /// exits with the SUPER-CHIP 00FD
+    pub fn is_halted(&self) -> bool {
+        let pc = self.cpu.pc() as usize;
+        let memory = self.bus.memory();
+
+        match memory.get(pc..pc + 2) {
+            Some(&[high, low]) => {
+                let opcode = u16::from_be_bytes([high, low]);
+                (opcode & 0xF000 == 0x1000 && opcode & 0x0FFF == pc as u16)
+                    || opcode == 0x00FD
+            }
+            _ => false,
+        }
+    }
+
+    pub fn set_key(&mut self, key: Keypad, pressed: bool) {
+        self.bus.keys[key as usize] = pressed;
+    }
+
+    /// Count the frames due after `delta` of real time at the speed, for
+    /// the frontends running them one by one
+    /// After a hitch, the frames past a few behind are missed
+    pub fn frames_due(&mut self, delta: Duration) -> FramesDue {
+        if self.paused {
+            return FramesDue::default();
+        }
+
+        self.frames_due += delta.as_secs_f64() * FRAME_RATE * self.speed;
+        let most = CATCH_UP_FRAMES * self.speed.max(1.0);
+        let mut missed = 0;
+        if self.frames_due > most {
+            missed = (self.frames_due - most) as u64;
+            self.frames_due = most;
+        }
+        let run = self.frames_due as u32;
+        self.frames_due -= run as f64;
+
+        FramesDue { run, missed }
+    }
+
+    /// Run the frames due after `delta` of real time, returns how many ran
+    pub fn tick(&mut self, delta: Duration) -> u32 {
+        if self.turbo && !self.paused {
+            return self.run_for(delta / 2);
+        }
+
+        let frames = self.frames_due(delta).run;
+        for _ in 0..frames {
+            self.run_frame();
+        }
+
+        frames
+    }
+
+    /// Execute one frame worth of instructions, then update the timers
+    pub fn run_frame(&mut self) {
+        self.run_frame_until(|_| false);
+    }
+
+    /// Pause and run a single frame, to go through the rom frame by frame;
+    /// a frame interrupted by `step` is finished
+    pub fn advance_frame(&mut self) {
+        self.pause(true);
+        self.run_frame();
+    }
+
+    /// Run frames back to back until `budget` of wall time is spent, at
+    /// least one, whatever the speed; returns how many ran
+    pub fn run_for(&mut self, budget: Duration) -> u32 {
+        let start = Instant::now();
+        let mut frames = 0;
+        loop {
+            self.run_frame();
+            frames += 1;
+
+            if start.elapsed() >= budget {
+                return frames;
+            }
+        }
+    }
+
+    /// Like `run_frame`, but stop before an instruction when `stop` returns
+    /// true; the next call finishes the interrupted frame
+    /// Returns true when the frame is complete
+    pub fn run_frame_until(
+        &mut self,
+        mut stop: impl FnMut(&Cpu) -> bool,
+    ) -> bool {
+        if !self.frame_interrupted {
+            self.cpu_cycles += self.cpu_frequency / FRAME_RATE;
+        }
+        self.frame_interrupted = false;
+
+        while self.cpu_cycles >= 1.0 {
+            if stop(&self.cpu) {
+                self.frame_interrupted = true;
+                return false;
+            }
+
+            self.cpu_cycles -= 1.0;
+            self.cpu.emulate(&mut self.bus);
+        }
+
+        self.delay.update(&mut self.bus);
+        self.beeper.update(&mut self.bus);
+        self.cpu.vblank();
+
+        true
+    }
+
+    /// Like `run_frame`, with the blocks `jit` can translate run as native
+    /// code, the result is the same
+    #[cfg(feature = "jit")]
+    pub fn run_frame_jit(&mut self, jit: &mut Jit) {
+        if !self.frame_interrupted {
+            self.cpu_cycles += self.cpu_frequency / FRAME_RATE;
+        }
+        self.frame_interrupted = false;
+
+        while self.cpu_cycles >= 1.0 {
+            let budget = self.cpu_cycles as u32;
+            let run = match jit.run(&mut self.cpu, &self.bus, budget) {
+                0 => {
+                    self.cpu.emulate(&mut self.bus);
+                    1
+                }
+                run => run,
+            };
+            self.cpu_cycles -= run as f64;
+        }
+
+        self.delay.update(&mut self.bus);
+        self.beeper.update(&mut self.bus);
+        self.cpu.vblank();
+    }
+
+    /// Run frames until something a frontend has to react to happens, for
+    /// the frontends without a display to refresh at 60Hz; the next call
+    /// goes on from the instruction after the event
+    /// Returns the event and the frames completed, at most `max_frames`
+    pub fn run_until_event(
+        &mut self,
+        breakpoints: &[u16],
+        max_frames: u64,
+    ) -> (Event, u64) {
+        // the breakpoint we may have stopped on is run this time
+        let mut first = true;
+
+        for frames in 0..max_frames {
+            if !self.frame_interrupted {
+                self.cpu_cycles += self.cpu_frequency / FRAME_RATE;
+            }
+            // set until the frame is complete, any return below is in it
+            self.frame_interrupted = true;
+
+            while self.cpu_cycles >= 1.0 {
+                let pc = self.cpu.pc();
+                if !std::mem::replace(&mut first, false)
+                    && breakpoints.contains(&pc)
+                {
+                    return (Event::Breakpoint(pc), frames);
+                }
+
+                let waiting =
+                    self.cpu.vblank_wait() || self.cpu.key_await().is_some();
+                let opcode = u16::from_be_bytes([
+                    self.bus.read_byte(pc),
+                    self.bus.read_byte(pc.wrapping_add(1)),
+                ]);
+                let draws = !waiting
+                    && matches!(
+                        decode::table()[opcode as usize],
+                        Instruction::Cls
+                            | Instruction::Drw(..)
+                            | Instruction::Scd(_)
+                            | Instruction::Scr
+                            | Instruction::Scl
+                            | Instruction::Low
+                            | Instruction::High
+                            | Instruction::Scu(_)
+                    );
+
+                self.cpu_cycles -= 1.0;
+                self.cpu.emulate(&mut self.bus);
+
+                if draws {
+                    return (Event::Draw, frames);
+                }
+                if !waiting && self.cpu.key_await().is_some() {
+                    return (Event::KeyWait, frames);
+                }
+            }
+
+            self.frame_interrupted = false;
+            let beeping = self.beeper.is_beeping();
+            let delay = self.bus.delay;
+            self.delay.update(&mut self.bus);
+            self.beeper.update(&mut self.bus);
+            self.cpu.vblank();
+
+            if self.beeper.is_beeping() != beeping {
+                return (Event::Sound(!beeping), frames + 1);
+            }
+            if delay > 0 && self.bus.delay == 0 {
+                return (Event::TimerExpired, frames + 1);
+            }
+        }
+
+        (Event::Timeout, max_frames)
+    }
+
+    /// Execute a single instruction, the timers are updated when it ends a
+    /// frame
+    pub fn step(&mut self) {
+        if self.cpu_frequency == 0.0 {
+            return;
+        }
+
+        let mut executed = false;
+        while !executed {
+            let mut first = true;
+            self.run_frame_until(|_| !std::mem::replace(&mut first, false));
+            executed = !first;
+        }
+    }
+
+    /// Execute a single instruction, or a whole subroutine for a CALL until
+    /// it returns, in at most `frames` frames
+    /// Returns false when the subroutine is still running
+    pub fn step_over(&mut self, frames: u32) -> bool {
+        let pc = self.cpu.pc();
+        let depth = self.cpu.call_stack().len();
+        let opcode = u16::from_be_bytes([
+            self.bus.read_byte(pc),
+            self.bus.read_byte(pc.wrapping_add(1)),
+        ]);
+
+        self.step();
+        if !matches!(Instruction::from_opcode(opcode), Instruction::Call(_)) {
+            return true;
+        }
+
+        let next = pc.wrapping_add(2);
+        let returned =
+            |cpu: &Cpu| cpu.pc() == next && cpu.call_stack().len() == depth;
+        for _ in 0..frames {
+            if returned(&self.cpu) || !self.run_frame_until(returned) {
+                return true;
+            }
+        }
+
+        returned(&self.cpu)
+    }
+
+    /// Restart the rom with a fresh memory, settings are kept
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+        self.delay = Delay::new();
+        self.beeper = Beeper::new();
+        self.bus = match self.cpu.is_xo_chip() {
+            true => Bus::new_xo_chip(self.rom.clone()),
+            false => Bus::new(self.rom.clone()),
+        };
+        self.cpu_cycles = 0.0;
+        self.frame_interrupted = false;
+        self.frames_due = 0.0;
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+
+        self.cpu.save(&mut state);
+        self.bus.save(&mut state);
+        self.beeper.save(&mut state);
+        state.f64(self.cpu_cycles);
+        state.bool(self.frame_interrupted);
+
+        state.finish()
+    }
+
+    /// Restore a state from `save_state`, the machine is left untouched if
+    /// the state is invalid
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut state = StateReader::new(data)?;
+        let mut cpu = self.cpu.clone();
+        let mut bus = self.bus.clone();
+        let mut beeper = self.beeper.clone();
+
+        cpu.load(&mut state)?;
+        bus.load(&mut state)?;
+        beeper.load(&mut state)?;
+        let cpu_cycles = state.f64()?;
+        let frame_interrupted = state.bool()?;
+        if !cpu_cycles.is_finite() || cpu_cycles < 0.0 {
+            return Err(StateError::InvalidValue("cpu_cycles"));
+        }
+
+        self.cpu = cpu;
+        self.bus = bus;
+        self.beeper = beeper;
+        // left over from a frame, the speed may have been lowered since
+        self.cpu_cycles = cpu_cycles.min(1.0 + self.cpu_frequency / FRAME_RATE);
+        self.frame_interrupted = frame_interrupted;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Quirks;
+
+    fn create_machine(program: &[u8]) -> Machine {
+        Machine::new(Rom::from_bytes(program.to_vec()))
+    }
+
+    #[test]
+    fn test_run_frame() {
+        // 7001: add 1 to V0, 1202: jump back
+        let mut machine = create_machine(&[0x70, 0x01, 0x12, 0x00]);
+        machine.set_cpu_frequency(600.0);
+
+        machine.run_frame();
+        assert_eq!(machine.cpu().registers()[0], 5);
+
+        machine.set_cpu_frequency(90.0); // 1.5 instruction per frame
+        machine.run_frame();
+        assert_eq!(machine.cpu().pc(), 0x202);
+        machine.run_frame();
+        assert_eq!(machine.cpu().pc(), 0x202);
+        assert_eq!(machine.cpu().registers()[0], 7);
+
+        for frequency in [f64::INFINITY, f64::NAN] {
+            machine.set_cpu_frequency(frequency);
+            assert_eq!(machine.cpu_frequency(), 90.0);
+        }
+    }
+
+    #[test]
+    fn test_run_frame_until() {
+        // 7001: add 1 to V0, 1200: jump back
+        let mut machine = create_machine(&[0x70, 0x01, 0x12, 0x00]);
+        machine.set_cpu_frequency(600.0);
+        machine.bus_mut().delay = 5;
+
+        assert!(!machine.run_frame_until(|cpu| cpu.registers()[0] == 2));
+        assert_eq!(machine.cpu().pc(), 0x202);
+        assert_eq!(machine.bus().delay, 5);
+
+        assert!(machine.run_frame_until(|_| false));
+        assert_eq!(machine.cpu().registers()[0], 5);
+        assert_eq!(machine.bus().delay, 4);
+    }
+
+    #[test]
+    fn test_tick() {
+        // 7001: add 1 to V0, 1200: jump back
+        let mut machine = create_machine(&[0x70, 0x01, 0x12, 0x00]);
+        machine.set_cpu_frequency(120.0);
+
+        assert_eq!(machine.tick(Duration::from_millis(50)), 3);
+        assert_eq!(machine.cpu().registers()[0], 3);
+
+        // a hitch isn't caught up
+        let due = machine.frames_due(Duration::from_millis(500));
+        assert_eq!(due, FramesDue { run: 4, missed: 26 });
+
+        machine.set_speed(2.0);
+        assert_eq!(machine.frames_due(Duration::from_millis(50)).run, 6);
+
+        machine.pause(true);
+        assert_eq!(machine.tick(Duration::from_millis(100)), 0);
+        machine.pause(false);
+        assert_eq!(machine.tick(Duration::ZERO), 0);
+        assert_eq!(machine.cpu().registers()[0], 3);
+
+        machine.set_turbo(true);
+        assert_eq!(machine.tick(Duration::ZERO), 1);
+        assert_eq!(machine.cpu().registers()[0], 4);
+        assert!(machine.tick(Duration::from_millis(20)) >= 1);
+    }
+
+    #[test]
+    fn test_advance_frame() {
+        let mut machine = create_machine(&[0x70, 0x01, 0x12, 0x00]);
+        machine.set_cpu_frequency(120.0);
+
+        // the second half of a frame interrupted in its middle
+        machine.step();
+        machine.advance_frame();
+        assert!(machine.is_paused());
+        assert_eq!(machine.cpu().registers()[0], 1);
+        assert_eq!(machine.tick(Duration::from_millis(100)), 0);
+
+        machine.advance_frame();
+        assert_eq!(machine.cpu().registers()[0], 2);
+    }
+
+    #[test]
+    fn test_run_until_event() {
+        let mut machine = create_machine(&[
+            0x60, 0x03, // V0 = 3
+            0x61, 0x06, // V1 = 6
+            0xF1, 0x15, // delay = V1
+            0xF0, 0x18, // sound = V0
+            0x00, 0xE0, // clear the screen
+            0x12, 0x0A, // wait in place
+        ]);
+        machine.set_cpu_frequency(600.0);
+
+        assert_eq!(machine.run_until_event(&[], 10), (Event::Draw, 0));
+        assert_eq!(machine.cpu().pc(), 0x20A);
+        assert_eq!(machine.run_until_event(&[], 10), (Event::Sound(true), 1));
+        assert_eq!(machine.run_until_event(&[], 10), (Event::Sound(false), 3));
+        assert_eq!(machine.run_until_event(&[], 10), (Event::TimerExpired, 2));
+        assert_eq!(machine.run_until_event(&[], 10), (Event::Timeout, 10));
+    }
+
+    #[test]
+    fn test_run_until_event_display_wait() {
+        // D011: draw, twice, 1204: wait in place
+        let mut machine = create_machine(&[0xD0, 0x11, 0xD0, 0x11, 0x12, 0x04]);
+        machine.cpu_mut().set_quirks(Quirks {
+            display_wait: true,
+            ..Quirks::default()
+        });
+
+        assert_eq!(machine.run_until_event(&[], 10), (Event::Draw, 0));
+        // the second draw waits for the next frame
+        assert_eq!(machine.run_until_event(&[], 10), (Event::Draw, 1));
+        assert_eq!(machine.cpu().pc(), 0x204);
+    }
+
+    #[test]
+    fn test_run_until_event_breakpoint() {
+        // 7001: add 1 to V0, F00A: wait for a key in V0, 1200: loop
+        let mut machine = create_machine(&[0x70, 0x01, 0xF0, 0x0A, 0x12, 0x00]);
+
+        let breakpoints = [0x202];
+        assert_eq!(
+            machine.run_until_event(&breakpoints, 10),
+            (Event::Breakpoint(0x202), 0)
+        );
+        assert_eq!(
+            machine.run_until_event(&breakpoints, 10),
+            (Event::KeyWait, 0)
+        );
+        assert_eq!(machine.run_until_event(&breakpoints, 2).0, Event::Timeout);
+
+        machine.set_key(Keypad::Key7, true);
+        assert_eq!(
+            machine.run_until_event(&breakpoints, 10),
+            (Event::Breakpoint(0x202), 0)
+        );
+        assert_eq!(machine.cpu().registers()[0], 8);
+    }
+
+    #[test]
+    fn test_step() {
+        // 7001: add 1 to V0, 1200: jump back
+        let mut machine = create_machine(&[0x70, 0x01, 0x12, 0x00]);
+        machine.set_cpu_frequency(120.0);
+        machine.bus_mut().delay = 5;
+
+        machine.step();
+        assert_eq!(machine.cpu().pc(), 0x202);
+        assert_eq!(machine.bus().delay, 5);
+
+        machine.step();
+        assert_eq!(machine.cpu().pc(), 0x200);
+        assert_eq!(machine.bus().delay, 4);
+
+        // some frames execute nothing below the frame rate
+        machine.set_cpu_frequency(30.0);
+        machine.step();
+        assert_eq!(machine.cpu().pc(), 0x202);
+        assert_eq!(machine.bus().delay, 2);
+    }
+
+    #[test]
+    fn test_step_over() {
+        let mut machine = create_machine(&[
+            0x22, 0x06, // call 0x206
+            0x60, 0x01, // V0 = 1
+            0x12, 0x04, // loop
+            0x71, 0x01, // V1 += 1
+            0x31, 0x10, // skip if V1 == 16
+            0x12, 0x06, // jump back
+            0x00, 0xEE, // return
+        ]);
+        machine.set_cpu_frequency(600.0);
+
+        assert!(machine.step_over(5));
+        assert_eq!(machine.cpu().pc(), 0x202);
+        assert_eq!(machine.cpu().registers()[1], 16);
+
+        assert!(machine.step_over(0));
+        assert_eq!(machine.cpu().pc(), 0x204);
+
+        machine.reset();
+        machine.set_cpu_frequency(60.0);
+        assert!(!machine.step_over(2));
+        assert_eq!(machine.cpu().call_stack(), &[0x202]);
+    }
+
+    #[test]
+    fn test_is_halted() {
+        // 6001: V0 = 1, 1202: jump to itself
+        let mut machine = create_machine(&[0x60, 0x01, 0x12, 0x02]);
+        assert!(!machine.is_halted());
+
+        machine.run_frame();
+        assert!(machine.is_halted());
+        assert_eq!(machine.cpu().pc(), 0x202);
+    }
+
+    #[test]
+    fn test_timers() {
+        // 6005: V0 = 5, F015: delay = V0, F018: sound = V0
+        let mut machine = create_machine(&[0x60, 0x05, 0xF0, 0x15, 0xF0, 0x18]);
+        machine.set_cpu_frequency(180.0);
+
+        machine.run_frame();
+        assert_eq!(machine.bus().delay, 4);
+        assert_eq!(machine.bus().beep, 4);
+        assert!(machine.is_beeping());
+    }
+
+    #[test]
+    fn test_reset() {
+        // 6142: V1 = 0x42, A300: I = 0x300, F155: store V0..V1
+        let mut machine = create_machine(&[0x61, 0x42, 0xA3, 0x00, 0xF1, 0x55]);
+        machine.set_cpu_frequency(180.0);
+        machine.run_frame();
+        assert_eq!(machine.bus().memory()[0x301], 0x42);
+
+        machine.reset();
+        assert_eq!(machine.cpu().pc(), 0x200);
+        assert_eq!(machine.bus().memory()[0x301], 0x00);
+        assert_eq!(machine.cpu_frequency(), 180.0);
+    }
+
+    #[test]
+    fn test_state() {
+        // 2206: call, 00EE: return, 6142: V1 = 0x42, F10A: wait key
+        let program =
+            [0x22, 0x06, 0xF1, 0x0A, 0x00, 0x00, 0x61, 0x42, 0x00, 0xEE];
+        let mut machine = create_machine(&program);
+        machine.set_cpu_frequency(120.0);
+        machine.run_frame();
+        machine.bus_mut().set_pixel(3, 7, true);
+        machine.bus_mut().delay = 9;
+
+        let state = machine.save_state();
+        let mut restored = create_machine(&program);
+        restored.set_cpu_frequency(120.0);
+        restored.load_state(&state).expect("load state");
+
+        assert_eq!(restored.cpu().pc(), 0x208);
+        assert_eq!(restored.cpu().call_stack(), &[0x202]);
+        assert_eq!(restored.cpu().registers(), machine.cpu().registers());
+        assert!(restored.bus().pixel(3, 7));
+        assert_eq!(restored.bus().delay, 9);
+        assert_eq!(restored.save_state(), state);
+
+        restored.run_frame();
+        machine.run_frame();
+        assert_eq!(restored.save_state(), machine.save_state());
+    }
+
+    #[test]
+    fn test_hires_state() {
+        // 00FF: hires, D010: 16x16 sprite of the memory at 0, 00FD: exit
+        let program = [0x00, 0xFF, 0xD0, 0x10, 0x00, 0xFD];
+        let mut machine = create_machine(&program);
+        machine.run_frame();
+        assert!(machine.is_halted());
+        assert!(machine.bus().hires_rows().is_some());
+
+        let state = machine.save_state();
+        let mut restored = create_machine(&program);
+        restored.load_state(&state).expect("load state");
+        assert_eq!(restored.bus().hires_rows(), machine.bus().hires_rows());
+        assert_eq!(restored.bus().frame_hash(), machine.bus().frame_hash());
+        assert_eq!(restored.save_state(), state);
+
+        // the same size as before the hires screen
+        assert_eq!(create_machine(&program).save_state().len(), state.len());
+    }
+
+    #[test]
+    fn test_xo_chip() {
+        // F301: both planes, F000 1200: I = 1200, D001: a row of each plane
+        let mut program = vec![0xF3, 0x01, 0xF0, 0x00, 0x12, 0x00, 0xD0, 0x01];
+        // 1208: loop
+        program.extend([0x12, 0x08]);
+        // past the 4KB of the chip8
+        program.resize(0x1000, 0);
+        program.extend([0x80, 0x40]);
+
+        let mut machine = create_machine(&program);
+        machine.set_xo_chip(true);
+        assert_eq!(machine.bus().memory().len(), 0x10000);
+        machine.run_frame();
+        assert!(machine.is_halted());
+        assert_eq!(machine.cpu().index(), 0x1200);
+        assert_eq!(machine.bus().rows()[0], 1 << 63);
+        assert_eq!(machine.bus().plane_rows(1)[0], 1 << 62);
+
+        // the state has the mode
+        let state = machine.save_state();
+        let mut restored = create_machine(&program);
+        restored.load_state(&state).expect("load state");
+        assert!(restored.is_xo_chip());
+        assert_eq!(restored.bus().frame_hash(), machine.bus().frame_hash());
+        assert_eq!(restored.save_state(), state);
+
+        // the other cpus ignore the XO-CHIP instructions
+        machine.set_xo_chip(false);
+        assert_eq!(machine.bus().memory().len(), 0x1000);
+        machine.run_frame();
+        assert_eq!(machine.bus().rows()[0], 0);
+    }
+
+    #[test]
+    fn test_state_invalid() {
+        let mut machine = create_machine(&[0x12, 0x00]);
+        let state = machine.save_state();
+
+        assert_eq!(
+            machine.load_state(b"nope"),
+            Err(StateError::InvalidSignature)
+        );
+        assert_eq!(
+            machine.load_state(&state[..state.len() - 1]),
+            Err(StateError::UnexpectedEnd)
+        );
+
+        let mut version = state.clone();
+        version[4] = 0xFF;
+        assert_eq!(
+            machine.load_state(&version),
+            Err(StateError::UnsupportedVersion(0xFF))
+        );
+        // after the header, the mode, PC, I and the registers
+        let mut depth = state.clone();
+        depth[5 + 1 + 2 + 2 + 16] = 17;
+        assert_eq!(
+            machine.load_state(&depth),
+            Err(StateError::InvalidValue("stack"))
+        );
+
+        // the cycles are last but for the interrupted frame flag
+        let with_cycles = |cycles: f64| {
+            let mut state = state.clone();
+            let at = state.len() - 9;
+            state[at..at + 8].copy_from_slice(&cycles.to_le_bytes());
+            state
+        };
+        for cycles in [f64::INFINITY, f64::NAN, -1.0] {
+            assert_eq!(
+                machine.load_state(&with_cycles(cycles)),
+                Err(StateError::InvalidValue("cpu_cycles"))
+            );
+        }
+        // more than a frame at the speed, clamped to it
+        machine.set_cpu_frequency(600.0);
+        assert_eq!(machine.load_state(&with_cycles(1e12)), Ok(()));
+        machine.run_frame();
+        assert_eq!(machine.cpu().pc(), 0x200);
+        assert_eq!(machine.cpu_cycles, 0.0);
+    }
+}
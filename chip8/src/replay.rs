@@ -0,0 +1,297 @@
+//! Movies of a run: the seed of the random numbers and the keys held each
+//! frame, played back to run the rom the same way again
+//!
+//! ```
+//! use chip8::{machine::Machine, replay::Movie, rom::Rom};
+//!
+//! // C0FF: V0 = random, 1202: loop
+//! let rom = Rom::from_bytes(vec![0xC0, 0xFF, 0x12, 0x02]);
+//! let mut machine = Machine::new(rom.clone());
+//! let mut movie = Movie::new(42);
+//! movie.start(&mut machine);
+//! machine.bus_mut().keys[5] = true;
+//! movie.record_frame(&mut machine);
+//!
+//! let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+//! let mut replay = Machine::new(rom);
+//! let mut playback = movie.play(&mut replay);
+//! assert!(playback.run_frame(&mut replay));
+//! assert!(!playback.run_frame(&mut replay));
+//! assert_eq!(replay.cpu().registers(), machine.cpu().registers());
+//! ```
+
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    iter,
+};
+
+use crate::{bus::KEYPAD_SIZE, machine::Machine};
+
+const SIGNATURE: &[u8; 4] = b"CH8M";
+const VERSION: u8 = 1;
+/// Frames of a movie at most, a day at 60 frames per second
+pub const MAX_FRAMES: usize = 60 * 60 * 60 * 24;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MovieError {
+    /// Data doesn't start with the movie signature
+    InvalidSignature,
+    /// Movie written by an unknown format version
+    UnsupportedVersion(u8),
+    /// Data ends in the middle of a field
+    UnexpectedEnd,
+    /// Movie longer than `MAX_FRAMES`
+    TooLong,
+}
+
+impl Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::InvalidSignature => write!(f, "not a chip8 movie"),
+            MovieError::UnsupportedVersion(version) => {
+                write!(f, "unsupported movie version {}", version)
+            }
+            MovieError::UnexpectedEnd => write!(f, "truncated movie"),
+            MovieError::TooLong => write!(f, "movie too long"),
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+/// The keys held each frame from the start of a rom, with the seed of its
+/// random numbers
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Movie {
+    seed: u64,
+    /// Bit N is set when key N is held
+    frames: Vec<u16>,
+}
+
+impl Movie {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            frames: vec![],
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Number of frames
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Keys held during `frame`
+    pub fn keys(&self, frame: usize) -> Option<[bool; KEYPAD_SIZE]> {
+        self.frames.get(frame).map(|&bits| unpack(bits))
+    }
+
+    pub fn push(&mut self, keys: &[bool; KEYPAD_SIZE]) {
+        self.frames.push(pack(keys));
+    }
+
+    /// Restart `machine` with the seed of the movie, its settings are kept
+    pub fn start(&self, machine: &mut Machine) {
+        machine.reset();
+        machine.cpu_mut().set_seed(self.seed);
+    }
+
+    /// Run a frame of `machine` with the keys it holds, adding them to the
+    /// movie
+    pub fn record_frame(&mut self, machine: &mut Machine) {
+        self.push(&machine.bus().keys);
+        machine.run_frame();
+    }
+
+    /// Restart `machine` to play the movie back frame by frame
+    pub fn play(&self, machine: &mut Machine) -> Playback<'_> {
+        self.start(machine);
+        Playback {
+            movie: self,
+            frame: 0,
+        }
+    }
+
+    /// The signature and version, the seed, then the frames as runs of the
+    /// same keys: the keys and the length of the run, all little endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = SIGNATURE.to_vec();
+        data.push(VERSION);
+        data.extend_from_slice(&self.seed.to_le_bytes());
+
+        for run in self.frames.chunk_by(|a, b| a == b) {
+            data.extend_from_slice(&run[0].to_le_bytes());
+            data.extend_from_slice(&(run.len() as u32).to_le_bytes());
+        }
+
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, MovieError> {
+        let (signature, data) = split(data, SIGNATURE.len())?;
+        if signature != SIGNATURE {
+            return Err(MovieError::InvalidSignature);
+        }
+        let (version, data) = split(data, 1)?;
+        if version[0] != VERSION {
+            return Err(MovieError::UnsupportedVersion(version[0]));
+        }
+
+        let (seed, mut data) = split(data, 8)?;
+        let mut seed_bytes = [0; 8];
+        seed_bytes.copy_from_slice(seed);
+        let mut movie = Self::new(u64::from_le_bytes(seed_bytes));
+
+        while !data.is_empty() {
+            let (run, rest) = split(data, 6)?;
+            let keys = u16::from_le_bytes([run[0], run[1]]);
+            let len = u32::from_le_bytes([run[2], run[3], run[4], run[5]]);
+            if len as usize > MAX_FRAMES - movie.frames.len() {
+                return Err(MovieError::TooLong);
+            }
+            movie.frames.extend(iter::repeat_n(keys, len as usize));
+            data = rest;
+        }
+
+        Ok(movie)
+    }
+}
+
+/// A movie played back, see `Movie::play`
+pub struct Playback<'a> {
+    movie: &'a Movie,
+    frame: usize,
+}
+
+impl Playback<'_> {
+    /// Frames played so far
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.len()
+    }
+
+    /// Run the next frame of `machine` with the keys of the movie
+    /// Returns false once the movie is over, nothing runs then
+    pub fn run_frame(&mut self, machine: &mut Machine) -> bool {
+        match self.movie.keys(self.frame) {
+            Some(keys) => {
+                machine.bus_mut().keys = keys;
+                machine.run_frame();
+                self.frame += 1;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn split(data: &[u8], len: usize) -> Result<(&[u8], &[u8]), MovieError> {
+    data.split_at_checked(len).ok_or(MovieError::UnexpectedEnd)
+}
+
+fn pack(keys: &[bool; KEYPAD_SIZE]) -> u16 {
+    (0..KEYPAD_SIZE)
+        .filter(|&key| keys[key])
+        .fold(0, |bits, key| bits | 1 << key)
+}
+
+fn unpack(bits: u16) -> [bool; KEYPAD_SIZE] {
+    let mut keys = [false; KEYPAD_SIZE];
+    for (key, held) in keys.iter_mut().enumerate() {
+        *held = bits & 1 << key != 0;
+    }
+
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom::Rom;
+
+    // C0FF: V0 = random, F10A: V1 = key, 8014: V0 += V1, 1200: loop
+    const PROGRAM: [u8; 8] = [0xC0, 0xFF, 0xF1, 0x0A, 0x80, 0x14, 0x12, 0x00];
+
+    fn create_machine() -> Machine {
+        Machine::new(Rom::from_bytes(PROGRAM.into()))
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut machine = create_machine();
+        let mut movie = Movie::new(7);
+        movie.start(&mut machine);
+        for frame in 0..30 {
+            machine.bus_mut().keys[frame % 3] = frame % 4 == 0;
+            movie.record_frame(&mut machine);
+        }
+        assert_eq!(movie.len(), 30);
+
+        let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+        assert_eq!(movie.seed(), 7);
+
+        // the replay runs from wherever the machine was
+        let mut replay = create_machine();
+        replay.run_frame();
+        let mut playback = movie.play(&mut replay);
+        while playback.run_frame(&mut replay) {}
+        assert!(playback.is_finished());
+        assert_eq!(playback.frame(), 30);
+        assert_eq!(replay.save_state(), machine.save_state());
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut movie = Movie::new(1);
+        let mut keys = [false; KEYPAD_SIZE];
+        for _ in 0..100 {
+            movie.push(&keys);
+        }
+        keys[0xF] = true;
+        movie.push(&keys);
+
+        // 2 runs of 6 bytes after the header
+        let data = movie.to_bytes();
+        assert_eq!(data.len(), 13 + 12);
+        assert_eq!(Movie::from_bytes(&data), Ok(movie.clone()));
+        assert_eq!(movie.keys(100), Some(keys));
+        assert_eq!(movie.keys(101), None);
+
+        assert_eq!(
+            Movie::from_bytes(&data[..data.len() - 1]),
+            Err(MovieError::UnexpectedEnd)
+        );
+        assert_eq!(
+            Movie::from_bytes(b"CH8S\x05"),
+            Err(MovieError::InvalidSignature)
+        );
+        assert_eq!(
+            Movie::from_bytes(b"CH8M\x09"),
+            Err(MovieError::UnsupportedVersion(9))
+        );
+
+        // a run of u32::MAX frames, then runs adding up past the limit
+        let mut long = data[..13].to_vec();
+        long.extend_from_slice(&[0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(Movie::from_bytes(&long), Err(MovieError::TooLong));
+        let mut long = data[..13].to_vec();
+        let half = (MAX_FRAMES / 2 + 1) as u32;
+        for _ in 0..2 {
+            long.extend_from_slice(&[0, 0]);
+            long.extend_from_slice(&half.to_le_bytes());
+        }
+        assert_eq!(Movie::from_bytes(&long), Err(MovieError::TooLong));
+    }
+}
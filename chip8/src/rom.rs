@@ -1,9 +1,15 @@
 use std::{
     fmt::{self, Display},
     fs::File,
-    io::{Error, Read},
+    io::Read,
 };
 
+use crate::{bus::XO_MEMORY_SIZE, error::Error};
+
+/// Largest rom, filling the XO-CHIP memory after 0x200
+pub const MAX_ROM_SIZE: usize = XO_MEMORY_SIZE - 0x200;
+
+#[derive(Clone)]
 pub struct Rom {
     data: Vec<u8>,
     size: usize,
@@ -11,23 +17,54 @@ pub struct Rom {
 
 impl Rom {
     pub fn new_from(path: &str) -> Result<Self, Error> {
-        let mut file = File::open(&path)?;
+        let mut file = File::open(path)?;
         let mut data = vec![];
 
         file.read_to_end(&mut data)?;
 
-        Ok(Self {
+        Self::new(data)
+    }
+
+    /// A rom checked to fit in a memory, the largest one
+    pub fn new(data: Vec<u8>) -> Result<Self, Error> {
+        if data.len() > MAX_ROM_SIZE {
+            return Err(Error::RomTooBig {
+                size: data.len(),
+                max: MAX_ROM_SIZE,
+            });
+        }
+
+        Ok(Self::from_bytes(data))
+    }
+
+    /// A rom from memory, like a download or an embedded file; the bus
+    /// cuts what doesn't fit
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        Self {
             size: data.len(),
             data,
-        })
+        }
     }
 
-    pub fn read(&self, addr: u16) -> u8 {
-        self.data[addr as usize]
+    pub fn read(&self, addr: u16) -> Result<u8, Error> {
+        self.data
+            .get(addr as usize)
+            .copied()
+            .ok_or(Error::OutOfRange(addr))
     }
 
-    pub fn write(&mut self, addr: u16, data: u8) {
-        self.data[addr as usize] = data;
+    pub fn write(&mut self, addr: u16, data: u8) -> Result<(), Error> {
+        let byte = self
+            .data
+            .get_mut(addr as usize)
+            .ok_or(Error::OutOfRange(addr))?;
+        *byte = data;
+
+        Ok(())
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
     }
 
     pub fn size(&self) -> usize {
@@ -35,6 +72,23 @@ impl Rom {
     }
 }
 
+/// Checked like `Rom::new`
+impl TryFrom<&[u8]> for Rom {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> Result<Self, Error> {
+        Self::new(data.to_vec())
+    }
+}
+
+impl TryFrom<Vec<u8>> for Rom {
+    type Error = Error;
+
+    fn try_from(data: Vec<u8>) -> Result<Self, Error> {
+        Self::new(data)
+    }
+}
+
 impl Display for Rom {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Rom {{")?;
@@ -42,3 +96,41 @@ impl Display for Rom {
         write!(f, "}}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_write() {
+        let mut rom = Rom::from_bytes(vec![0x12, 0x00]);
+        assert_eq!(rom.read(1).unwrap(), 0x00);
+        rom.write(1, 0x34).unwrap();
+        assert_eq!(rom.data(), &[0x12, 0x34]);
+
+        assert!(matches!(rom.read(2), Err(Error::OutOfRange(2))));
+        assert!(rom.write(2, 0).is_err());
+    }
+
+    #[test]
+    fn test_new() {
+        assert!(Rom::new(vec![0; MAX_ROM_SIZE]).is_ok());
+        assert!(matches!(
+            Rom::new(vec![0; MAX_ROM_SIZE + 1]),
+            Err(Error::RomTooBig { .. })
+        ));
+        assert!(matches!(
+            Rom::new_from("/nonexistent.ch8"),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_try_from() {
+        let rom = Rom::try_from(&[0x12, 0x00][..]).unwrap();
+        assert_eq!(rom.data(), &[0x12, 0x00]);
+        assert_eq!(rom.size(), 2);
+
+        assert!(Rom::try_from(vec![0; MAX_ROM_SIZE + 1]).is_err());
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+#[cfg(feature = "serde")]
+use crate::{beep::Beeper, bus::Bus, cpu::Cpu, delay::Delay, rom::Rom};
+
+const SIGNATURE: &[u8; 4] = b"CH8S";
+const VERSION: u8 = 6;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StateError {
+    /// Data doesn't start with the state signature
+    InvalidSignature,
+    /// State written by an unknown format version
+    UnsupportedVersion(u8),
+    /// Data ends before the state is complete
+    UnexpectedEnd,
+    /// A field holds a value the emulator can't be in
+    InvalidValue(&'static str),
+}
+
+impl Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::InvalidSignature => write!(f, "not a chip8 state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported state version {}", version)
+            }
+            StateError::UnexpectedEnd => write!(f, "truncated state"),
+            StateError::InvalidValue(field) => {
+                write!(f, "invalid value for {}", field)
+            }
+        }
+    }
+}
+
+impl Error for StateError {}
+
+/// Components able to write and restore their state
+pub(crate) trait Snapshot {
+    fn save(&self, state: &mut StateWriter);
+    fn load(&mut self, state: &mut StateReader) -> Result<(), StateError>;
+}
+
+/// Serde support of the components, their state in the format of
+/// `Machine::save_state`, checked the same way when deserialized
+/// The settings, like the quirks of the cpu, are the default ones
+#[cfg(feature = "serde")]
+macro_rules! serde_snapshot {
+    ($($component:ty => $new:expr),* $(,)?) => {$(
+        impl serde::Serialize for $component {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                let mut state = StateWriter::new();
+                self.save(&mut state);
+                serde::Serialize::serialize(&state.finish(), serializer)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $component {
+            fn deserialize<D: serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<Self, D::Error> {
+                use serde::de::Error;
+
+                let data: Vec<u8> =
+                    serde::Deserialize::deserialize(deserializer)?;
+                let mut component: $component = $new;
+                StateReader::new(&data)
+                    .and_then(|mut state| component.load(&mut state))
+                    .map_err(D::Error::custom)?;
+
+                Ok(component)
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "serde")]
+serde_snapshot! {
+    Cpu => Cpu::new(),
+    Bus => Bus::new(Rom::from_bytes(vec![])),
+    Delay => Delay::new(),
+    Beeper => Beeper::new(),
+}
+
+/// Little endian writer, the signature and version are written first
+pub(crate) struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        let mut state = Self { data: vec![] };
+        state.bytes(SIGNATURE);
+        state.u8(VERSION);
+
+        state
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn f64(&mut self, value: f64) {
+        self.u64(value.to_bits());
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Pack `bits` 8 per byte, most significant bit first
+    pub fn bits(&mut self, bits: impl IntoIterator<Item = bool>) {
+        let mut byte = 0;
+        let mut count = 0;
+
+        for bit in bits {
+            byte = byte << 1 | bit as u8;
+            count += 1;
+
+            if count == 8 {
+                self.u8(byte);
+                byte = 0;
+                count = 0;
+            }
+        }
+
+        if count > 0 {
+            self.u8(byte << (8 - count));
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+pub(crate) struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, StateError> {
+        let mut state = Self { data };
+
+        if state.bytes(SIGNATURE.len())? != SIGNATURE {
+            return Err(StateError::InvalidSignature);
+        }
+
+        match state.u8()? {
+            VERSION => Ok(state),
+            version => Err(StateError::UnsupportedVersion(version)),
+        }
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.bytes(2)?;
+
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn f64(&mut self) -> Result<f64, StateError> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::InvalidValue("bool")),
+        }
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < len {
+            return Err(StateError::UnexpectedEnd);
+        }
+
+        let (bytes, data) = self.data.split_at(len);
+        self.data = data;
+
+        Ok(bytes)
+    }
+
+    /// Unpack `len` bits written by `StateWriter::bits`
+    pub fn bits(&mut self, len: usize) -> Result<Vec<bool>, StateError> {
+        let bytes = self.bytes(len.div_ceil(8))?;
+
+        Ok((0..len)
+            .map(|bit| bytes[bit / 8] & (0x80 >> (bit % 8)) != 0)
+            .collect())
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use serde::{de::DeserializeOwned, Serialize};
+
+    use super::*;
+    use crate::{bus::XO_MEMORY_SIZE, cpu::CpuBus};
+
+    /// `component` through json, serialized again the same
+    fn round_trip<T: Serialize + DeserializeOwned>(component: &T) -> T {
+        let json = serde_json::to_string(component).unwrap();
+        let back: T = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&back).unwrap(), json);
+        back
+    }
+
+    #[test]
+    fn test_serde_cpu() {
+        let mut bus = Bus::new(Rom::from_bytes(vec![
+            0x60, 0x12, // V0 = 0x12
+            0xA3, 0x45, // I = 0x345
+            0x22, 0x06, // call 0x206
+            0x12, 0x06, // loop
+        ]));
+        let mut cpu = Cpu::new();
+        for _ in 0..4 {
+            cpu.step(&mut bus);
+        }
+
+        let back = round_trip(&cpu);
+        assert_eq!(back.pc(), cpu.pc());
+        assert_eq!(back.index(), 0x345);
+        assert_eq!(back.registers()[0], 0x12);
+        assert_eq!(back.call_stack(), [0x206]);
+    }
+
+    #[test]
+    fn test_serde_bus() {
+        let mut bus = Bus::new_xo_chip(Rom::from_bytes(vec![0x12, 0x00]));
+        bus.memory_mut()[XO_MEMORY_SIZE - 1] = 0xAB;
+        bus.set_hires(true);
+        bus.write_screen(100, 50, true);
+
+        let back = round_trip(&bus);
+        assert!(back.is_xo_chip());
+        assert_eq!(back.memory()[XO_MEMORY_SIZE - 1], 0xAB);
+        assert_eq!(back.resolution(), (128, 64));
+        assert!(back.read_screen(100, 50));
+    }
+
+    #[test]
+    fn test_serde_timers() {
+        round_trip(&Delay::new());
+
+        let mut bus = Bus::new(Rom::from_bytes(vec![]));
+        bus.write_sound(5);
+        let mut beeper = Beeper::new();
+        beeper.update(&mut bus);
+        assert!(round_trip(&beeper).is_beeping());
+    }
+
+    #[test]
+    fn test_serde_corrupt() {
+        // the cpu isn't Debug, for unwrap_err
+        let error = serde_json::from_str::<Cpu>("[1, 2, 3, 4, 5]")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "not a chip8 state");
+
+        let mut data = serde_json::to_value(Cpu::new()).unwrap();
+        data.as_array_mut().unwrap().truncate(20);
+        let error = serde_json::from_value::<Cpu>(data).err().unwrap();
+        assert_eq!(error.to_string(), "truncated state");
+    }
+}
@@ -0,0 +1,173 @@
+//! Runs of a rom written as a chain of inputs and expectations, for the
+//! tests of input driven behaviors without any frontend
+//!
+//! ```
+//! use chip8::{keypad::Keypad::Key5, testing::{frames, TestRun}};
+//!
+//! // F00A: wait for a key in V0, 1202: halt
+//! TestRun::rom([0xF0, 0x0A, 0x12, 0x02])
+//!     .run_frames(2)
+//!     .expect_waiting(true)
+//!     .press(Key5, frames(3))
+//!     .expect_register(0, 5);
+//! ```
+//!
+//! `harness::run_headless` runs a test rom to check the screen it ends on.
+
+use crate::{
+    bus::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    keypad::Keypad,
+    machine::Machine,
+    rom::Rom,
+};
+
+/// Seed of the random numbers, the runs are the same each time
+pub(crate) const SEED: u64 = 1;
+
+/// A number of 60Hz frames
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Frames(pub u64);
+
+pub fn frames(count: u64) -> Frames {
+    Frames(count)
+}
+
+/// A machine run step by step, the expectations panic with the frame
+/// where they failed
+pub struct TestRun {
+    machine: Machine,
+    frame: u64,
+}
+
+impl TestRun {
+    pub fn rom(bytes: impl Into<Vec<u8>>) -> Self {
+        let mut machine = Machine::new(Rom::from_bytes(bytes.into()));
+        machine.cpu_mut().set_seed(SEED);
+
+        Self { machine, frame: 0 }
+    }
+
+    /// Instructions per second
+    pub fn speed(mut self, frequency: f64) -> Self {
+        self.machine.set_cpu_frequency(frequency);
+        self
+    }
+
+    pub fn hold(mut self, key: Keypad) -> Self {
+        self.machine.set_key(key, true);
+        self
+    }
+
+    pub fn release(mut self, key: Keypad) -> Self {
+        self.machine.set_key(key, false);
+        self
+    }
+
+    /// Hold `key` during `duration`, then release it
+    pub fn press(self, key: Keypad, duration: Frames) -> Self {
+        self.hold(key).run_frames(duration.0).release(key)
+    }
+
+    pub fn run_frames(mut self, count: u64) -> Self {
+        for _ in 0..count {
+            self.machine.run_frame();
+        }
+        self.frame += count;
+        self
+    }
+
+    #[track_caller]
+    pub fn expect_pixel(self, x: usize, y: usize, on: bool) -> Self {
+        assert!(
+            x < DISPLAY_WIDTH && y < DISPLAY_HEIGHT,
+            "pixel {},{} out of the screen",
+            x,
+            y
+        );
+        assert_eq!(
+            self.machine.bus().pixel(x, y),
+            on,
+            "pixel {},{} at frame {}",
+            x,
+            y,
+            self.frame
+        );
+        self
+    }
+
+    #[track_caller]
+    pub fn expect_register(self, x: usize, value: u8) -> Self {
+        assert_eq!(
+            self.machine.cpu().registers()[x],
+            value,
+            "V{:X} at frame {}",
+            x,
+            self.frame
+        );
+        self
+    }
+
+    /// Waiting for a key with FX0A
+    #[track_caller]
+    pub fn expect_waiting(self, waiting: bool) -> Self {
+        assert_eq!(
+            self.machine.cpu().key_await().is_some(),
+            waiting,
+            "key wait at frame {}",
+            self.frame
+        );
+        self
+    }
+
+    /// The screen has the `Bus::frame_hash` `hash`
+    #[track_caller]
+    pub fn expect_frame_hash(self, hash: u64) -> Self {
+        assert_eq!(
+            self.machine.bus().frame_hash(),
+            hash,
+            "frame hash at frame {}",
+            self.frame
+        );
+        self
+    }
+
+    #[track_caller]
+    pub fn expect_halted(self) -> Self {
+        assert!(
+            self.machine.is_halted(),
+            "not halted at frame {}, pc {:03X}",
+            self.frame,
+            self.machine.cpu().pc()
+        );
+        self
+    }
+
+    /// The machine, for the checks without an `expect_` method
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        // A000: I = font 0, D005: draw it, 1204: halt
+        let run = TestRun::rom([0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04])
+            .run_frames(1)
+            .expect_pixel(0, 0, true)
+            .expect_pixel(4, 0, false)
+            .expect_halted();
+        assert_eq!(run.frame, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "pixel 4,0 at frame 1")]
+    fn test_expect_failure() {
+        TestRun::rom([0xA0, 0x00, 0xD0, 0x05, 0x12, 0x04])
+            .run_frames(1)
+            .expect_pixel(4, 0, true);
+    }
+}
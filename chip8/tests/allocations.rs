@@ -0,0 +1,70 @@
+//! The frames of a running machine allocate nothing, so that thousands of
+//! them can run side by side
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use chip8::{machine::Machine, rom::Rom};
+
+thread_local! {
+    // per thread, the test harness allocates on its own threads
+    static COUNT: Cell<u64> = const { Cell::new(0) };
+}
+
+/// The system allocator, counting the allocations of each thread
+struct Counter;
+
+// SAFETY: every call is forwarded to the system allocator as is
+unsafe impl GlobalAlloc for Counter {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        COUNT.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        COUNT.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counter = Counter;
+
+#[test]
+fn test_run_frame_allocations() {
+    let rom = [
+        0x22, 0x0A, // call the subroutine
+        0xC0, 0xFF, // V0 = random
+        0xF0, 0x33, // digits of V0 at I
+        0xD0, 0x15, // draw at V0, V1
+        0x12, 0x00, // loop
+        0xA3, 0x00, // I = 0x300
+        0x81, 0x04, // V1 += V0
+        0x00, 0xEE, // return
+    ];
+    let mut machine = Machine::new(Rom::from_bytes(rom.to_vec()));
+    machine.set_cpu_frequency(60_000.0);
+
+    // the frontends keep the buffer of the screen from frame to frame
+    let mut framebuffer = machine.bus().framebuffer();
+
+    let before = COUNT.with(Cell::get);
+    for _ in 0..60 {
+        machine.run_frame();
+        machine.bus().framebuffer_into(&mut framebuffer);
+    }
+    machine.step();
+
+    assert_eq!(COUNT.with(Cell::get) - before, 0);
+}
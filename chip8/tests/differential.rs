@@ -0,0 +1,442 @@
+//! Run random programs on the cpu and on a simple reference interpreter in
+//! lockstep, the first state that differs fails the test
+//!
+//! `cargo test -p chip8 --test differential -- --ignored` runs many more
+//! programs.
+
+use chip8::{
+    bus::{
+        DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_HEIGHT, HIRES_WIDTH, KEYPAD_SIZE,
+    },
+    cpu::{BIG_SPRITE_ADDR, RPL_SIZE, V_SIZE},
+    machine::Machine,
+    rom::Rom,
+};
+
+const MEMORY_SIZE: usize = 0x1000;
+const STACK_SIZE: usize = 16;
+const STEPS: usize = 2_000;
+
+/// The instructions written again from their description, as plainly as
+/// possible, with the quirks of `Cpu`
+struct Reference {
+    memory: [u8; MEMORY_SIZE],
+    vram: [[bool; DISPLAY_HEIGHT]; DISPLAY_WIDTH],
+    /// The SUPER-CHIP screen while it is on, `vram` then shows it at half
+    /// size
+    hires: Option<Vec<[bool; HIRES_HEIGHT]>>,
+    keys: [bool; KEYPAD_SIZE],
+    delay: u8,
+    v: [u8; V_SIZE],
+    i: u16,
+    pc: u16,
+    stack: Vec<u16>,
+    key_await: Option<u8>,
+    rng: u64,
+    rpl: [u8; RPL_SIZE],
+}
+
+impl Reference {
+    /// Start from the memory loaded by `machine`, before its first step
+    fn new(machine: &Machine, seed: u64) -> Self {
+        let mut memory = [0; MEMORY_SIZE];
+        memory.copy_from_slice(machine.bus().memory());
+
+        Self {
+            memory,
+            vram: [[false; DISPLAY_HEIGHT]; DISPLAY_WIDTH],
+            hires: None,
+            keys: [false; KEYPAD_SIZE],
+            delay: 0,
+            v: [0; V_SIZE],
+            i: 0,
+            pc: 0x200,
+            stack: vec![],
+            key_await: None,
+            rng: seed | 1,
+            rpl: [0; RPL_SIZE],
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        self.memory[addr as usize % MEMORY_SIZE]
+    }
+
+    fn write(&mut self, addr: u16, byte: u8) {
+        self.memory[addr as usize % MEMORY_SIZE] = byte;
+    }
+
+    fn random(&mut self) -> u8 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+
+        (self.rng >> 56) as u8
+    }
+
+    fn screen_size(&self) -> (usize, usize) {
+        match self.hires {
+            Some(_) => (HIRES_WIDTH, HIRES_HEIGHT),
+            None => (DISPLAY_WIDTH, DISPLAY_HEIGHT),
+        }
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> bool {
+        match &self.hires {
+            Some(hires) => hires[x][y],
+            None => self.vram[x][y],
+        }
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        match &mut self.hires {
+            Some(hires) => {
+                hires[x][y] = on;
+                let (x, y) = (x & !1, y & !1);
+                self.vram[x / 2][y / 2] = hires[x][y]
+                    || hires[x + 1][y]
+                    || hires[x][y + 1]
+                    || hires[x + 1][y + 1];
+            }
+            None => self.vram[x][y] = on,
+        }
+    }
+
+    /// Toggle a pixel, wrapping around the screen, returns true when it was
+    /// set
+    fn toggle(&mut self, x: usize, y: usize) -> bool {
+        let (width, height) = self.screen_size();
+        let (x, y) = (x % width, y % height);
+        let pixel = self.pixel(x, y);
+        self.set_pixel(x, y, !pixel);
+
+        pixel
+    }
+
+    /// Move the screen by `dx`, `dy`, what comes in is cleared
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = self.screen_size();
+        let mut pixels = vec![];
+        for x in 0..width as isize {
+            for y in 0..height as isize {
+                let (from_x, from_y) = (x - dx, y - dy);
+                let inside = (0..width as isize).contains(&from_x)
+                    && (0..height as isize).contains(&from_y);
+                let pixel =
+                    inside && self.pixel(from_x as usize, from_y as usize);
+                pixels.push((x as usize, y as usize, pixel));
+            }
+        }
+        for (x, y, pixel) in pixels {
+            self.set_pixel(x, y, pixel);
+        }
+    }
+
+    fn skip_if(&mut self, condition: bool) {
+        if condition {
+            self.pc = (self.pc + 2) % MEMORY_SIZE as u16;
+        }
+    }
+
+    fn step(&mut self) {
+        if let Some(x) = self.key_await {
+            if let Some(key) = self.keys.iter().position(|&held| held) {
+                self.v[x as usize] = key as u8;
+                self.key_await = None;
+            }
+            return;
+        }
+
+        let opcode =
+            u16::from_be_bytes([self.read(self.pc), self.read(self.pc + 1)]);
+        self.pc = (self.pc + 2) % MEMORY_SIZE as u16;
+
+        let x = (opcode >> 8 & 0xF) as usize;
+        let y = (opcode >> 4 & 0xF) as usize;
+        let n = opcode & 0xF;
+        let nn = opcode as u8;
+        let nnn = opcode & 0xFFF;
+        let (vx, vy) = (self.v[x], self.v[y]);
+
+        match opcode >> 12 {
+            0x0 if opcode == 0x00E0 => {
+                self.vram = [[false; DISPLAY_HEIGHT]; DISPLAY_WIDTH];
+                if let Some(hires) = &mut self.hires {
+                    hires.fill([false; HIRES_HEIGHT]);
+                }
+            }
+            0x0 if opcode == 0x00EE => {
+                if let Some(addr) = self.stack.pop() {
+                    self.pc = addr;
+                }
+            }
+            0x0 if opcode & 0xFFF0 == 0x00C0 => self.scroll(0, n as isize),
+            0x0 if opcode == 0x00FB => self.scroll(4, 0),
+            0x0 if opcode == 0x00FC => self.scroll(-4, 0),
+            0x0 if opcode == 0x00FD => self.pc -= 2,
+            0x0 if opcode == 0x00FE => self.hires = None,
+            0x0 if opcode == 0x00FF && self.hires.is_none() => {
+                let mut hires = vec![[false; HIRES_HEIGHT]; HIRES_WIDTH];
+                for (x, column) in hires.iter_mut().enumerate() {
+                    for (y, pixel) in column.iter_mut().enumerate() {
+                        *pixel = self.vram[x / 2][y / 2];
+                    }
+                }
+                self.hires = Some(hires);
+            }
+            0x1 => self.pc = nnn,
+            0x2 if self.stack.len() < STACK_SIZE => {
+                self.stack.push(self.pc);
+                self.pc = nnn;
+            }
+            0x3 => self.skip_if(vx == nn),
+            0x4 => self.skip_if(vx != nn),
+            0x5 if n == 0 => self.skip_if(vx == vy),
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = vx.wrapping_add(nn),
+            0x8 => {
+                let (value, flag) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, None),
+                    0x2 => (vx & vy, None),
+                    0x3 => (vx ^ vy, None),
+                    0x4 => (
+                        vx.wrapping_add(vy),
+                        Some(vx as u16 + vy as u16 > 0xFF),
+                    ),
+                    0x5 => (vx.wrapping_sub(vy), Some(vx >= vy)),
+                    0x6 => (vy >> 1, Some(vy & 1 == 1)),
+                    0x7 => (vy.wrapping_sub(vx), Some(vy >= vx)),
+                    0xE => (vy << 1, Some(vy & 0x80 != 0)),
+                    _ => return,
+                };
+                self.v[x] = value;
+                if let Some(flag) = flag {
+                    self.v[0xF] = flag as u8;
+                }
+            }
+            0x9 if n == 0 => self.skip_if(vx != vy),
+            0xA => self.i = nnn,
+            0xB => self.pc = (nnn + self.v[0] as u16) % MEMORY_SIZE as u16,
+            0xC => self.v[x] = self.random() & nn,
+            0xD => {
+                self.v[0xF] = 0;
+                // 16x16 for N = 0, two bytes per row
+                let (rows, width) = match n {
+                    0 => (16, 16),
+                    n => (n, 8),
+                };
+                for row in 0..rows {
+                    let line = match width {
+                        16 => u16::from_be_bytes([
+                            self.read(self.i + row * 2),
+                            self.read(self.i + row * 2 + 1),
+                        ]),
+                        _ => (self.read(self.i + row) as u16) << 8,
+                    };
+                    for column in 0..width {
+                        if line & (0x8000 >> column) == 0 {
+                            continue;
+                        }
+                        // the coordinates wrap as bytes first
+                        let px = (vx as usize + column) % 256;
+                        let py = (vy as usize + row as usize) % 256;
+                        if self.toggle(px, py) {
+                            self.v[0xF] = 1;
+                        }
+                    }
+                }
+            }
+            0xE if nn == 0x9E => self.skip_if(self.keys[vx as usize & 0xF]),
+            0xE if nn == 0xA1 => self.skip_if(!self.keys[vx as usize & 0xF]),
+            0xF => match nn {
+                0x07 => self.v[x] = self.delay,
+                0x0A => self.key_await = Some(x as u8),
+                0x15 => self.delay = vx,
+                0x1E => self.i = (self.i + vx as u16) % MEMORY_SIZE as u16,
+                0x29 => self.i = (vx as u16 * 5) % MEMORY_SIZE as u16,
+                0x30 => self.i = BIG_SPRITE_ADDR + (vx as u16 & 0xF) * 10,
+                0x33 => {
+                    self.write(self.i, vx / 100);
+                    self.write(self.i + 1, vx / 10 % 10);
+                    self.write(self.i + 2, vx % 10);
+                }
+                0x55 => {
+                    for r in 0..=x {
+                        self.write(self.i + r as u16, self.v[r]);
+                    }
+                    self.i = (self.i + x as u16 + 1) % MEMORY_SIZE as u16;
+                }
+                0x65 => {
+                    for r in 0..=x {
+                        self.v[r] = self.read(self.i + r as u16);
+                    }
+                    self.i = (self.i + x as u16 + 1) % MEMORY_SIZE as u16;
+                }
+                0x75 if x < RPL_SIZE => {
+                    self.rpl[..=x].copy_from_slice(&self.v[..=x]);
+                }
+                0x85 if x < RPL_SIZE => {
+                    self.v[..=x].copy_from_slice(&self.rpl[..=x]);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// The first part of the state that differs from `machine`
+    fn divergence(&self, machine: &Machine) -> Option<String> {
+        let cpu = machine.cpu();
+        let bus = machine.bus();
+
+        if cpu.pc() != self.pc {
+            return Some(format!(
+                "PC {:03X}, expected {:03X}",
+                cpu.pc(),
+                self.pc
+            ));
+        }
+        if cpu.index() != self.i {
+            return Some(format!(
+                "I {:03X}, expected {:03X}",
+                cpu.index(),
+                self.i
+            ));
+        }
+        for (r, (&actual, &expected)) in
+            cpu.registers().iter().zip(&self.v).enumerate()
+        {
+            if actual != expected {
+                return Some(format!(
+                    "V{:X} {:02X}, expected {:02X}",
+                    r, actual, expected
+                ));
+            }
+        }
+        if cpu.call_stack() != self.stack {
+            return Some(format!(
+                "stack {:03X?}, expected {:03X?}",
+                cpu.call_stack(),
+                self.stack
+            ));
+        }
+        if cpu.rpl_flags() != &self.rpl {
+            return Some(format!(
+                "RPL {:02X?}, expected {:02X?}",
+                cpu.rpl_flags(),
+                self.rpl
+            ));
+        }
+        if cpu.key_await() != self.key_await {
+            return Some(format!(
+                "key wait {:?}, expected {:?}",
+                cpu.key_await(),
+                self.key_await
+            ));
+        }
+        let mut memory = bus.memory().iter().zip(&self.memory);
+        if let Some(addr) =
+            memory.position(|(actual, expected)| actual != expected)
+        {
+            return Some(format!(
+                "memory at {:03X} {:02X}, expected {:02X}",
+                addr,
+                bus.memory()[addr],
+                self.memory[addr]
+            ));
+        }
+        let pixels = (0..DISPLAY_WIDTH)
+            .flat_map(|x| (0..DISPLAY_HEIGHT).map(move |y| (x, y)))
+            .map(|(x, y)| (x, y, bus.pixel(x, y), self.vram[x][y]));
+        for (x, y, actual, expected) in pixels {
+            if actual != expected {
+                return Some(format!(
+                    "pixel {},{} {}, expected {}",
+                    x, y, actual, expected
+                ));
+            }
+        }
+        match (bus.hires_rows(), &self.hires) {
+            (None, None) => {}
+            (Some(rows), Some(hires)) => {
+                for (x, column) in hires.iter().enumerate() {
+                    for (y, &expected) in column.iter().enumerate() {
+                        let actual = rows[y] & 1 << (HIRES_WIDTH - 1 - x) != 0;
+                        if actual != expected {
+                            return Some(format!(
+                                "hires pixel {},{} {}, expected {}",
+                                x, y, actual, expected
+                            ));
+                        }
+                    }
+                }
+            }
+            (actual, _) => {
+                return Some(format!("hires {}", actual.is_some()));
+            }
+        }
+
+        None
+    }
+}
+
+/// Xorshift, the programs are the same at each run
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Run the program `seed` in both interpreters, with keys held at random
+fn run(seed: u64) {
+    let mut random = Random(seed.wrapping_mul(0x9E3779B97F4A7C15) | 1);
+    let rom: Vec<u8> = (0..MEMORY_SIZE - 0x200)
+        .map(|_| random.next() as u8)
+        .collect();
+
+    let mut machine = Machine::new(Rom::from_bytes(rom.clone()));
+    machine.cpu_mut().set_seed(seed);
+    let mut reference = Reference::new(&machine, seed);
+
+    for step in 0..STEPS {
+        if random.next() & 0x3F == 0 {
+            let keys = random.next();
+            for (key, held) in machine.bus_mut().keys.iter_mut().enumerate() {
+                *held = keys & (1 << key) != 0;
+            }
+        }
+        reference.keys = machine.bus().keys;
+        // the timers count down with the frames, only the machine has them
+        reference.delay = machine.bus().delay;
+
+        let pc = reference.pc;
+        let opcode =
+            u16::from_be_bytes([reference.read(pc), reference.read(pc + 1)]);
+        machine.step();
+        reference.step();
+
+        if let Some(divergence) = reference.divergence(&machine) {
+            panic!(
+                "program {}, step {}, {:04X} at {:03X}: {}",
+                seed, step, opcode, pc, divergence
+            );
+        }
+    }
+}
+
+#[test]
+fn test_differential() {
+    (1..=50).for_each(run);
+}
+
+#[test]
+#[ignore]
+fn test_differential_long() {
+    (1..=5_000).for_each(run);
+}
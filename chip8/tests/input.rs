@@ -0,0 +1,67 @@
+use chip8::{
+    keypad::Keypad::*,
+    testing::{frames, TestRun},
+};
+
+#[test]
+fn test_fx0a_waits_for_a_key() {
+    let rom = [
+        0xF0, 0x0A, // wait for a key in V0
+        0xF0, 0x29, // I = sprite of V0
+        0xD1, 0x15, // draw it at V1, V1 = 0, 0
+        0x12, 0x06, // halt
+    ];
+
+    TestRun::rom(rom)
+        .run_frames(10)
+        .expect_waiting(true)
+        .expect_pixel(0, 0, false)
+        .press(KeyA, frames(2))
+        .expect_waiting(false)
+        .expect_register(0, 0xA)
+        .expect_halted()
+        // A: F0 90 F0 90 90
+        .expect_pixel(3, 0, true)
+        .expect_pixel(4, 0, false)
+        .expect_pixel(0, 4, true)
+        .expect_pixel(1, 4, false);
+}
+
+#[test]
+fn test_ex9e_skips_while_pressed() {
+    let rom = [
+        0x60, 0x05, // V0 = 5
+        0xE0, 0x9E, // skip if key V0 is pressed
+        0x12, 0x02, // else loop
+        0xD1, 0x15, // draw the 0 of the font at 0, 0
+        0x12, 0x08, // halt
+    ];
+
+    TestRun::rom(rom)
+        .run_frames(30)
+        .press(Key4, frames(5))
+        .expect_pixel(0, 0, false)
+        .press(Key5, frames(1))
+        .expect_pixel(0, 0, true)
+        .expect_halted();
+}
+
+#[test]
+fn test_exa1_skips_while_released() {
+    let rom = [
+        0x60, 0x05, // V0 = 5
+        0xE0, 0xA1, // skip if key V0 is not pressed
+        0x12, 0x08, // pressed, draw
+        0x12, 0x02, // loop
+        0xD1, 0x15, // draw the 0 of the font at 0, 0
+        0x12, 0x0A, // halt
+    ];
+
+    TestRun::rom(rom)
+        .run_frames(30)
+        .expect_pixel(0, 0, false)
+        .hold(Key5)
+        .run_frames(1)
+        .expect_pixel(0, 0, true)
+        .expect_halted();
+}